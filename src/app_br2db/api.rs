@@ -1,32 +1,77 @@
-use crate::lib_http::response::{build_http_response, respond_http_plain_text, send_response};
+use crate::lib_http::auth::ApiTokens;
+use crate::lib_http::response::{build_http_response, respond_http_plain_text};
+use crate::lib_http::router::{
+    AuthPolicy, BodyParser, Handler, Method, Route, RouteRequest, RouteResult, Router, Visibility,
+};
 use crate::lib_http::types::{HttpRequest, HttpResponse};
+use crate::lib_metrics::{MetricsText, CONTENT_TYPE, REGISTRY};
 use crate::GlobalVars;
+use futures::future::BoxFuture;
 use regex::Regex;
-use std::sync::Arc;
+use std::convert::Infallible;
+use std::sync::{Arc, OnceLock};
 use tokio::net::TcpStream;
 
+static ROUTER: OnceLock<Router<Infallible>> = OnceLock::new();
+
+fn build_router() -> Router<Infallible> {
+    use AuthPolicy::None as NoAuth;
+    use BodyParser::Raw;
+    use Method::Any;
+    use Visibility::Public;
+
+    #[rustfmt::skip]
+    let mut routes = vec![
+        Route::new(Any, "/health_check",     Public, NoAuth, Raw, Handler::Sync(health_check)),
+        Route::new(Any, "/status-charts-v1", Public, NoAuth, Raw, Handler::Async(status_charts_v1)),
+        Route::new(Any, "/metrics",          Public, NoAuth, Raw, Handler::Sync(metrics)),
+    ];
+    routes.extend(crate::app_br2db::dead_letters::routes());
+
+    Router::new(ApiTokens::default(), routes)
+}
+
 pub async fn on_http_req(
     req: HttpRequest,
-    _is_internal: bool,
-    mut socket: TcpStream,
-    _globs: Arc<GlobalVars>,
+    is_internal: bool,
+    socket: TcpStream,
+    globs: Arc<GlobalVars>,
 ) {
-    // pub async fn on_http_req(req: &HttpRequest) -> Result<HttpResponse, String> {
-    let response = match &req.path[..] {
-        "/health_check" => respond_http_plain_text(200, "Alive"),
-        "/status-charts-v1" => build_status_charts_v1(&req)
+    let router = ROUTER.get_or_init(build_router);
+    router.handle(req, is_internal, socket, &globs).await;
+}
+
+fn health_check(
+    _rreq: &RouteRequest,
+    _globs: &Arc<GlobalVars>,
+) -> Result<RouteResult<Infallible>, HttpResponse> {
+    Ok(RouteResult::Respond(respond_http_plain_text(200, "Alive")))
+}
+
+fn metrics(
+    _rreq: &RouteRequest,
+    globs: &Arc<GlobalVars>,
+) -> Result<RouteResult<Infallible>, HttpResponse> {
+    let mut out = MetricsText::new();
+    crate::statistics::write_metrics(globs, &mut out);
+    REGISTRY.render(&mut out);
+    Ok(RouteResult::Respond(build_http_response(
+        200,
+        out.into_bytes(),
+        CONTENT_TYPE,
+    )))
+}
+
+fn status_charts_v1<'a>(
+    rreq: &'a RouteRequest,
+    _globs: &'a Arc<GlobalVars>,
+) -> BoxFuture<'a, Result<RouteResult<Infallible>, HttpResponse>> {
+    Box::pin(async move {
+        let response = build_status_charts_v1(&rreq.req)
             .await
-            .unwrap_or_else(|err| respond_http_plain_text(400, &err)),
-        _ => respond_http_plain_text(404, "Not found"),
-    };
-    // let response = match response {
-    //     Ok(v) => v,
-    //     Err(err) => respond_http_plain_text(500, &err),
-    // };
-    // if let Err(err) = send_response(&mut socket_reader.stream, &response).await {
-    if let Err(err) = send_response(&mut socket, &response).await {
-        crate::LOG.append_log_tag_msg("ERROR", &format!("ERR80: {}", err));
-    }
+            .unwrap_or_else(|err| respond_http_plain_text(400, &err));
+        Ok(RouteResult::Respond(response))
+    })
 }
 
 async fn build_status_charts_v1(req: &HttpRequest) -> Result<HttpResponse, String> {
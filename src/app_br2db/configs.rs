@@ -1,7 +1,10 @@
 use crate::diel_hist_tables::{BigQueryHistoryTable, CustomTableRule};
 use crate::envvars_loader;
 use crate::lib_bigquery::client::GCPConfig;
+use crate::lib_bigquery::saver::{self, BqSaverConfig};
+use crate::lib_dead_letter::{self, DeadLetterConfig};
 use crate::lib_dynamodb::client::AWSConfig;
+use crate::lib_local_store::layout::LocalStoreConfig;
 use crate::lib_rumqtt::BrokerConfig;
 
 pub struct ConfigFile {
@@ -12,11 +15,15 @@ pub struct ConfigFile {
     pub topics: Vec<String>,
 
     pub gcp_config: Option<GCPConfig>,
+    pub bq_saver_config: BqSaverConfig,
     pub gcp_dest_table: BigQueryHistoryTable,
 
     pub aws_config: Option<AWSConfig>,
     pub default_aws_table_name: Option<String>,
     pub custom_aws_table_rules: Option<Vec<CustomTableRule>>,
+
+    pub local_store_config: Option<LocalStoreConfig>,
+    pub dead_letter_config: Option<DeadLetterConfig>,
 }
 
 impl ConfigFile {
@@ -48,6 +55,9 @@ impl ConfigFile {
             envvars_loader::get_var_string_optional("awsConfig_default_table_name");
         let awsConfig_custom_table_rules: Option<Vec<CustomTableRule>> =
             envvars_loader::get_var_structure_optional("awsConfig_custom_table_rules")?;
+        let LOCAL_TELEMETRY_DIR = envvars_loader::get_var_string_optional("LOCAL_TELEMETRY_DIR");
+        let LOCAL_TELEMETRY_COMPRESS =
+            envvars_loader::get_var_bool_optional("LOCAL_TELEMETRY_COMPRESS")?;
 
         let use_tls = CA_PATH.is_some();
 
@@ -96,6 +106,14 @@ impl ConfigFile {
             None
         };
 
+        let dead_letter_config = lib_dead_letter::load_config()?;
+        let bq_saver_config = saver::load_config(dead_letter_config.is_some())?;
+
+        let local_store_config = LOCAL_TELEMETRY_DIR.map(|root_dir| LocalStoreConfig {
+            root_dir,
+            compress: LOCAL_TELEMETRY_COMPRESS == Some(true),
+        });
+
         let apiserver_internal_api = if STATS_SERVER_HTTP.contains("://") {
             STATS_SERVER_HTTP
         } else {
@@ -111,6 +129,9 @@ impl ConfigFile {
             aws_config,
             default_aws_table_name: awsConfig_default_table_name,
             custom_aws_table_rules: awsConfig_custom_table_rules,
+            local_store_config,
+            dead_letter_config,
+            bq_saver_config,
             gcp_dest_table,
         })
     }
@@ -0,0 +1,189 @@
+use crate::lib_bigquery::client::RowBQ;
+use crate::lib_dead_letter::{now_millis, DeadLetter, DeadLetterQueue};
+use crate::lib_http::response::{respond_http_json_serializable, respond_http_plain_text};
+use crate::lib_http::router::{
+    AuthPolicy, BodyParser, Handler, Method, Route, RouteRequest, RouteResult, Visibility,
+};
+use crate::lib_http::types::HttpResponse;
+use crate::GlobalVars;
+use serde::Deserialize;
+use std::sync::Arc;
+use std::time::Duration;
+
+// Itens tentados por ciclo em cada fila, para não competir demais com as gravações normais
+const RETRY_BATCH: usize = 200;
+
+/** Tenta gravar de novo os itens das filas de dead-letter que já podem ser tentados */
+pub async fn run_retry_task(globs: Arc<GlobalVars>) {
+    loop {
+        tokio::time::sleep(Duration::from_secs(10)).await;
+        if let Some(queue) = &globs.dead_letters.dynamodb {
+            for entry in queue.due(now_millis(), RETRY_BATCH) {
+                match retry_dynamodb(&globs, &entry).await {
+                    Ok(()) => queue.on_retry_success(entry.id),
+                    Err(err) => queue.on_retry_failed(entry, &err),
+                }
+            }
+        }
+        if let Some(queue) = &globs.dead_letters.bigquery {
+            for entry in queue.due(now_millis(), RETRY_BATCH) {
+                match retry_bigquery(&globs, &entry).await {
+                    Ok(()) => queue.on_retry_success(entry.id),
+                    Err(err) => queue.on_retry_failed(entry, &err),
+                }
+            }
+        }
+    }
+}
+
+async fn retry_dynamodb(globs: &Arc<GlobalVars>, entry: &DeadLetter) -> Result<(), String> {
+    let client = globs
+        .client_dynamo
+        .as_ref()
+        .ok_or_else(|| "DynamoDB client is null".to_owned())?;
+    for payload in &entry.items {
+        client
+            .insert_telemetry(&entry.table, payload, globs)
+            .await?;
+    }
+    Ok(())
+}
+
+async fn retry_bigquery(globs: &Arc<GlobalVars>, entry: &DeadLetter) -> Result<(), String> {
+    let mut client = globs
+        .client_bigquery
+        .as_ref()
+        .ok_or_else(|| "BigQuery client is null".to_owned())?
+        .clone();
+    let rows: Vec<RowBQ> = entry
+        .items
+        .iter()
+        .map(|item| RowBQ {
+            timestamp: item["timestamp"].as_str().unwrap_or_default().to_owned(),
+            dev_id: item["dev_id"].as_str().unwrap_or_default().to_owned(),
+            payload: item["payload"].as_str().unwrap_or_default().to_owned(),
+        })
+        .collect();
+    let _permit = globs.bq_saver.acquire().await;
+    client
+        .insert_telemetry_list_by_storage(&rows, &entry.table, globs)
+        .await
+}
+
+pub fn routes<T>() -> Vec<Route<T>> {
+    use AuthPolicy::None as NoAuth;
+    use BodyParser::Json;
+    use Method::Post;
+    use Visibility::Internal;
+
+    #[rustfmt::skip]
+    let routes = vec![
+        Route::new(Post, "/dead-letters/list",   Internal, NoAuth, Json, Handler::Sync(dead_letters_list)),
+        Route::new(Post, "/dead-letters/get",    Internal, NoAuth, Json, Handler::Sync(dead_letters_get)),
+        Route::new(Post, "/dead-letters/replay", Internal, NoAuth, Json, Handler::Sync(dead_letters_replay)),
+        Route::new(Post, "/dead-letters/purge",  Internal, NoAuth, Json, Handler::Sync(dead_letters_purge)),
+    ];
+    routes
+}
+
+/*
+  ['/dead-letters/list']: (reqParams: {
+    queue: 'dynamodb'|'bigquery'
+    offset?: number
+    limit?: number // Padrão 100
+  }) => {
+    total: number
+    bytes: number
+    entries: { id, table, itemsCount, bytes, error, tsFailed, attempts, nextRetryTs }[]
+  }
+
+  ['/dead-letters/get']: (reqParams: { queue: string, id: number }) => DeadLetter // Com os itens
+
+  // Tenta de novo na próxima rodada (até 10s). Sem "ids", a fila toda.
+  ['/dead-letters/replay']: (reqParams: { queue: string, ids?: number[] }) => { scheduled: number }
+
+  // Descarta os itens sem gravar. Para esvaziar a fila é preciso informar "all": true.
+  ['/dead-letters/purge']: (reqParams: { queue: string, ids?: number[], all?: boolean }) => { purged: number }
+*/
+#[derive(Deserialize)]
+struct ParamsDeadLetters {
+    queue: String,
+    id: Option<u64>,
+    ids: Option<Vec<u64>>,
+    all: Option<bool>,
+    offset: Option<usize>,
+    limit: Option<usize>,
+}
+
+fn parse_params<'a>(
+    rreq: &RouteRequest,
+    globs: &'a Arc<GlobalVars>,
+) -> Result<(ParamsDeadLetters, &'a DeadLetterQueue), HttpResponse> {
+    let params = serde_json::from_value::<ParamsDeadLetters>(rreq.json().clone())
+        .map_err(|e| respond_http_plain_text(400, &e.to_string()))?;
+    let queue = globs.dead_letters.get(&params.queue).ok_or_else(|| {
+        respond_http_plain_text(
+            404,
+            &format!("Fila desativada ou inválida: {}", params.queue),
+        )
+    })?;
+    Ok((params, queue))
+}
+
+fn dead_letters_list<T>(
+    rreq: &RouteRequest,
+    globs: &Arc<GlobalVars>,
+) -> Result<RouteResult<T>, HttpResponse> {
+    let (params, queue) = parse_params(rreq, globs)?;
+    let (total, entries) = queue.list(params.offset.unwrap_or(0), params.limit.unwrap_or(100));
+    Ok(RouteResult::Respond(respond_http_json_serializable(
+        200,
+        serde_json::json!({ "total": total, "bytes": queue.total_bytes(), "entries": entries }),
+    )))
+}
+
+fn dead_letters_get<T>(
+    rreq: &RouteRequest,
+    globs: &Arc<GlobalVars>,
+) -> Result<RouteResult<T>, HttpResponse> {
+    let (params, queue) = parse_params(rreq, globs)?;
+    let id = params
+        .id
+        .ok_or_else(|| respond_http_plain_text(400, "Faltou parâmetro 'id'"))?;
+    let entry = queue
+        .get(id)
+        .ok_or_else(|| respond_http_plain_text(404, "Item não encontrado"))?;
+    Ok(RouteResult::Respond(respond_http_json_serializable(
+        200, entry,
+    )))
+}
+
+fn dead_letters_replay<T>(
+    rreq: &RouteRequest,
+    globs: &Arc<GlobalVars>,
+) -> Result<RouteResult<T>, HttpResponse> {
+    let (params, queue) = parse_params(rreq, globs)?;
+    let scheduled = queue.replay_now(params.ids.as_deref());
+    Ok(RouteResult::Respond(respond_http_json_serializable(
+        200,
+        serde_json::json!({ "scheduled": scheduled }),
+    )))
+}
+
+fn dead_letters_purge<T>(
+    rreq: &RouteRequest,
+    globs: &Arc<GlobalVars>,
+) -> Result<RouteResult<T>, HttpResponse> {
+    let (params, queue) = parse_params(rreq, globs)?;
+    if params.ids.is_none() && params.all != Some(true) {
+        return Err(respond_http_plain_text(
+            400,
+            "Informar 'ids' ou 'all': true",
+        ));
+    }
+    let purged = queue.purge(params.ids.as_deref());
+    Ok(RouteResult::Respond(respond_http_json_serializable(
+        200,
+        serde_json::json!({ "purged": purged }),
+    )))
+}
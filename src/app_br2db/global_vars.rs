@@ -3,8 +3,10 @@ use super::statistics::StatisticsCounters;
 use crate::configs::{self, ConfigFile};
 use crate::diel_hist_tables::{self, TablesConfig};
 use crate::lib_bigquery::client::BigQueryClient;
-use crate::lib_bigquery::saver::SaveToBqEvent;
+use crate::lib_bigquery::saver::{BqSaverHealth, SaveToBqEvent};
+use crate::lib_dead_letter::DeadLetters;
 use crate::lib_dynamodb::client::DynamoDBClientDiel;
+use crate::lib_local_store::saver::SaveToLocalEvent;
 use crate::log::LogInfo;
 use regex::Regex;
 use std::collections::HashMap;
@@ -15,10 +17,13 @@ pub struct GlobalVars {
     pub configfile: configs::ConfigFile,
     pub stats: StatisticsCounters,
     pub last_table_create_command_aws: Mutex<Option<std::time::Instant>>,
-    pub last_table_create_command_bq: Mutex<Option<std::time::Instant>>,
+    pub last_table_create_command_bq: Mutex<HashMap<String, std::time::Instant>>, // Por tabela
     pub client_dynamo: Option<DynamoDBClientDiel>,
     pub client_bigquery: Option<BigQueryClient>,
+    pub dead_letters: DeadLetters,
     pub to_bigquery: mpsc::Sender<SaveToBqEvent>,
+    pub bq_saver: BqSaverHealth,
+    pub to_local_store: mpsc::Sender<SaveToLocalEvent>,
     pub log_info: Mutex<LogInfo>,
     pub tables: TablesConfig,
     pub valid_dev_id_checker: Regex,
@@ -26,14 +31,28 @@ pub struct GlobalVars {
 }
 
 impl GlobalVars {
-    pub async fn new(configfile: ConfigFile) -> (GlobalVars, mpsc::Receiver<SaveToBqEvent>) {
+    pub async fn new(
+        configfile: ConfigFile,
+    ) -> (
+        GlobalVars,
+        mpsc::Receiver<SaveToBqEvent>,
+        mpsc::Receiver<SaveToLocalEvent>,
+    ) {
         create_globs(configfile).await
     }
 }
 
-pub async fn create_globs(configfile: ConfigFile) -> (GlobalVars, mpsc::Receiver<SaveToBqEvent>) {
+pub async fn create_globs(
+    configfile: ConfigFile,
+) -> (
+    GlobalVars,
+    mpsc::Receiver<SaveToBqEvent>,
+    mpsc::Receiver<SaveToLocalEvent>,
+) {
     let stats = StatisticsCounters::new();
     let (sender_bigquery, receiver_bigquery) = crate::lib_bigquery::saver::create_channel();
+    let (sender_local_store, receiver_local_store) =
+        crate::lib_local_store::saver::create_channel();
 
     // Cria o objeto de conexão com o DynamoDB
     let client_dynamo = if let Some(aws_config) = &configfile.aws_config {
@@ -72,14 +91,25 @@ pub async fn create_globs(configfile: ConfigFile) -> (GlobalVars, mpsc::Receiver
         topicError_c: HashMap::new(),
     };
 
+    let dead_letters = DeadLetters::open(
+        &configfile.dead_letter_config,
+        client_dynamo.is_some(),
+        client_bigquery.is_some(),
+    );
+
+    let bq_saver = BqSaverHealth::new(&configfile.bq_saver_config);
+
     let globs = GlobalVars {
         configfile,
         stats,
         last_table_create_command_aws: Mutex::new(None),
-        last_table_create_command_bq: Mutex::new(None),
+        last_table_create_command_bq: Mutex::new(HashMap::new()),
         client_dynamo,
         client_bigquery,
+        dead_letters,
         to_bigquery: sender_bigquery,
+        bq_saver,
+        to_local_store: sender_local_store,
         log_info: Mutex::new(log_info),
         // globs2: Mutex::new(globs2),
         tables,
@@ -87,5 +117,5 @@ pub async fn create_globs(configfile: ConfigFile) -> (GlobalVars, mpsc::Receiver
         valid_dev_type_checker: Regex::new(r"^D[A-Z0-9]{2}\d").expect("ERRO 24"),
     };
 
-    (globs, receiver_bigquery)
+    (globs, receiver_bigquery, receiver_local_store)
 }
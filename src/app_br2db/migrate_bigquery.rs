@@ -0,0 +1,119 @@
+use crate::configs::ConfigFile;
+use crate::diel_hist_tables::BigQueryHistoryTable;
+use crate::lib_bigquery::client::BigQueryClient;
+use crate::lib_bigquery::schema::{self, ColumnDef, TableFamily};
+
+/**
+ * Cria ou atualiza de uma vez as tabelas de telemetria do dataset configurado (gcp_*), com as colunas tipadas de
+ * cada família, particionamento por dia e clustering por dev_id. As colunas que surgirem depois continuam sendo
+ * acrescentadas automaticamente durante a gravação.
+ * Uso: broker2db --migrate-bigquery-schema [--dry-run]
+ */
+pub async fn run(args: &[String]) -> Result<(), String> {
+    let dry_run = args.iter().any(|arg| arg == "--dry-run");
+    let configfile = ConfigFile::from_env()?;
+    let gcp_config = configfile
+        .gcp_config
+        .as_ref()
+        .ok_or_else(|| "[73] BigQuery não configurado".to_owned())?;
+    let client = BigQueryClient::new(gcp_config, None).await?;
+
+    // As tabelas padrão da configuração, mesmo que ainda não existam
+    let mut targets: Vec<(String, Vec<ColumnDef>)> = Vec::new();
+    match &configfile.gcp_dest_table {
+        BigQueryHistoryTable::None => {
+            return Err("[73] gcp_default_table_id=@none, nada a migrar".to_owned());
+        }
+        BigQueryHistoryTable::SingleTable(table_id) => {
+            targets.push((table_id.to_owned(), schema::all_family_columns()));
+        }
+        BigQueryHistoryTable::DevType => {
+            for dev_type in ["dac", "dut", "dri"] {
+                let table_name = format!("{}_telemetry", dev_type);
+                let columns = schema::columns_for_table(&table_name);
+                targets.push((table_name, columns));
+            }
+        }
+        BigQueryHistoryTable::DevGeneration | BigQueryHistoryTable::DevId => {}
+    }
+
+    // E as que já existem no dataset, de famílias conhecidas
+    for table_name in client.list_tables().await? {
+        if targets.iter().any(|(name, _)| *name == table_name) {
+            continue;
+        }
+        if schema::families_for_table(&table_name) == [TableFamily::Other] {
+            println!("{}: ignorada (família desconhecida)", table_name);
+            continue;
+        }
+        let columns = schema::columns_for_table(&table_name);
+        targets.push((table_name, columns));
+    }
+
+    let mut failed = 0;
+    for (table_name, columns) in &targets {
+        match migrate_table(&client, table_name, columns, dry_run).await {
+            Ok(result) => println!("{}: {}", table_name, result),
+            Err(err) => {
+                failed += 1;
+                println!("{}: ERRO {}", table_name, err);
+            }
+        }
+    }
+
+    println!(
+        "{} tabelas, {} com erro{}",
+        targets.len(),
+        failed,
+        if dry_run { " (dry-run)" } else { "" }
+    );
+    if failed > 0 {
+        return Err(format!("[74] {} tabelas com erro", failed));
+    }
+    Ok(())
+}
+
+async fn migrate_table(
+    client: &BigQueryClient,
+    table_name: &str,
+    columns: &[ColumnDef],
+    dry_run: bool,
+) -> Result<String, String> {
+    let Some(table) = client.get_table(table_name).await? else {
+        if !dry_run {
+            client
+                .create_table_with_columns(table_name, columns)
+                .await?;
+        }
+        return Ok(format!(
+            "{} com {} colunas tipadas",
+            if dry_run { "seria criada" } else { "criada" },
+            columns.len()
+        ));
+    };
+
+    let fields = table.schema.fields.unwrap_or_default();
+    let existing: Vec<&str> = fields.iter().map(|field| field.name.as_str()).collect();
+    let missing = schema::missing_columns(&existing, columns);
+    let mut result = if missing.is_empty() {
+        "em dia".to_owned()
+    } else {
+        let names: Vec<&str> = missing.iter().map(|c| c.name.as_str()).collect();
+        if !dry_run {
+            client.add_columns(table_name, &missing).await?;
+        }
+        format!(
+            "{} {} colunas: {:?}",
+            if dry_run { "faltam" } else { "acrescentadas" },
+            missing.len(),
+            names
+        )
+    };
+    // Particionamento e clustering não dá para mudar numa tabela que já existe
+    if table.time_partitioning.is_none() || table.clustering.is_none() {
+        result.push_str(
+            " (sem particionamento por dia ou clustering por dev_id: é preciso recriar a tabela)",
+        );
+    }
+    Ok(result)
+}
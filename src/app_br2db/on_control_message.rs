@@ -67,6 +67,9 @@ pub async fn process_control_message(
                     &format!("{};{}", table_name, payload.to_string()),
                 );
                 globs.stats.dynamodb_error.fetch_add(1, Ordering::Relaxed);
+                if let Some(queue) = &globs.dead_letters.dynamodb {
+                    queue.push(table_name, vec![payload], &err);
+                }
             }
         };
     });
@@ -1,4 +1,4 @@
-use crate::{save_to_bigquery, save_to_dynamodb, GlobalVars};
+use crate::{save_to_bigquery, save_to_dynamodb, save_to_local_files, GlobalVars};
 use chrono::NaiveDateTime;
 use std::sync::Arc;
 
@@ -63,6 +63,7 @@ pub async fn process_telemetry_message(payload_str: &str, topic: &str, globs: &A
 
     let enable_dynamodb = globs.configfile.aws_config.is_some();
     let enable_bigquery = globs.configfile.gcp_config.is_some();
+    let enable_local_store = globs.configfile.local_store_config.is_some();
 
     if enable_dynamodb {
         let topic = topic.to_owned();
@@ -73,6 +74,10 @@ pub async fn process_telemetry_message(payload_str: &str, topic: &str, globs: &A
         });
     }
 
+    if enable_local_store {
+        save_to_local_files::save_telemetry_to_local_files(topic, &payload, globs).await;
+    }
+
     if enable_bigquery {
         save_to_bigquery::save_telemetry_to_bigquery(topic, payload, dev_id, pack_ts, gmt, globs)
             .await;
@@ -10,15 +10,14 @@ pub fn on_table_not_found_bigquery(
     let globs = globs.to_owned();
     tokio::spawn(async move {
         {
-            let last_create: &mut Option<std::time::Instant> =
-                &mut *globs.last_table_create_command_bq.lock().await;
-            if let Some(last_create) = &last_create {
-                if last_create.elapsed() < std::time::Duration::from_secs(1) {
-                    // Se fizer menos de 1 segundo que enviamos um comando de criar tabela, não continua.
+            let last_create = &mut *globs.last_table_create_command_bq.lock().await;
+            if let Some(last_create) = last_create.get(&proposed_table_name) {
+                if last_create.elapsed() < std::time::Duration::from_secs(60) {
+                    // Se fizer menos de 1 minuto que enviamos um comando de criar esta tabela, não continua.
                     return;
                 }
             }
-            *last_create = Some(std::time::Instant::now());
+            last_create.insert(proposed_table_name.clone(), std::time::Instant::now());
         }
 
         let resp = globs
@@ -0,0 +1,309 @@
+use crate::configs::ConfigFile;
+use crate::GlobalVars;
+use futures::StreamExt;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::io::Write;
+use std::sync::Arc;
+
+// Quantas gravações ficam em andamento ao mesmo tempo
+const CONCURRENCY: usize = 16;
+
+struct ErrDynDbLine {
+    line: String,
+    table: String,
+    payload: Value,
+}
+
+/**
+ * Lê arquivos de log do broker2db e grava de novo no DynamoDB as telemetrias das linhas com tag ERRDYNDB.
+ * Uso: broker2db --reingest-errdyndb <arquivos de log...> [--dry-run]
+ * As linhas que continuarem falhando (ou que não der para interpretar) ficam em "<arquivo>.failed".
+ */
+pub async fn run(args: &[String]) -> Result<(), String> {
+    let dry_run = args.iter().any(|arg| arg == "--dry-run");
+    let files: Vec<&String> = args.iter().filter(|arg| !arg.starts_with("--")).collect();
+    if files.is_empty() {
+        return Err("[47] Uso: --reingest-errdyndb <arquivos de log...> [--dry-run]".to_owned());
+    }
+
+    let globs = if dry_run {
+        None
+    } else {
+        let configfile = ConfigFile::from_env()?;
+        let (globs, _receiver_bigquery, _receiver_local_store) = GlobalVars::new(configfile).await;
+        if globs.client_dynamo.is_none() {
+            return Err("[48] DynamoDB não configurado".to_owned());
+        }
+        Some(Arc::new(globs))
+    };
+
+    for file in files {
+        let content =
+            std::fs::read_to_string(file).map_err(|err| format!("[49] {}: {}", file, err))?;
+        let mut entries = Vec::new();
+        let mut failed = Vec::new();
+        for line in content.lines() {
+            match parse_log_line(line) {
+                Some(Ok(entry)) => entries.push(entry),
+                Some(Err(err)) => {
+                    println!("Linha inválida ({}): {}", err, line);
+                    failed.push(line.to_owned());
+                }
+                None => {}
+            }
+        }
+
+        let mut per_table: HashMap<&str, usize> = HashMap::new();
+        for entry in &entries {
+            *per_table.entry(&entry.table).or_default() += 1;
+        }
+        println!(
+            "{}: {} linhas ERRDYNDB, por tabela: {:?}",
+            file,
+            entries.len(),
+            per_table
+        );
+
+        let mut inserted = 0;
+        if let Some(globs) = &globs {
+            let results: Vec<(&ErrDynDbLine, Result<(), String>)> =
+                futures::stream::iter(entries.iter())
+                    .map(|entry| async move {
+                        let client = globs.client_dynamo.as_ref().expect("DynamoDB client");
+                        let result = client
+                            .insert_telemetry(&entry.table, &entry.payload, globs)
+                            .await
+                            .map(|_| ());
+                        (entry, result)
+                    })
+                    .buffer_unordered(CONCURRENCY)
+                    .collect()
+                    .await;
+            for (entry, result) in results {
+                match result {
+                    Ok(()) => inserted += 1,
+                    Err(err) => {
+                        println!("Falhou de novo ({}): {}", err, entry.table);
+                        failed.push(entry.line.clone());
+                    }
+                }
+            }
+        }
+
+        if !failed.is_empty() {
+            let failed_path = format!("{}.failed", file);
+            let mut out = std::fs::File::create(&failed_path)
+                .map_err(|err| format!("[50] {}: {}", failed_path, err))?;
+            for line in &failed {
+                writeln!(out, "{}", line)
+                    .map_err(|err| format!("[50] {}: {}", failed_path, err))?;
+            }
+        }
+        println!(
+            "{}: {} gravadas, {} falharam{}",
+            file,
+            inserted,
+            failed.len(),
+            if dry_run { " (dry-run)" } else { "" }
+        );
+    }
+
+    Ok(())
+}
+
+/** Retorna None para linhas que não são ERRDYNDB */
+fn parse_log_line(line: &str) -> Option<Result<ErrDynDbLine, String>> {
+    let entry: Value = serde_json::from_str(line).ok()?;
+    if entry["tag"].as_str() != Some("ERRDYNDB") {
+        return None;
+    }
+    let msg = entry["msg"].as_str().unwrap_or_default();
+    let Some((table, payload)) = msg.split_once(';') else {
+        return Some(Err("sem tabela".to_owned()));
+    };
+    // As linhas antigas eram gravadas com "{:?}" em vez de JSON
+    let payload = serde_json::from_str(payload).or_else(|_| parse_debug_value(payload));
+    Some(payload.map(|payload| ErrDynDbLine {
+        line: line.to_owned(),
+        table: table.to_owned(),
+        payload,
+    }))
+}
+
+/** Interpreta o formato de Debug do serde_json::Value, ex.: Object {"a": Number(1), "b": Array [Null]} */
+fn parse_debug_value(text: &str) -> Result<Value, String> {
+    let mut parser = DebugParser {
+        chars: text.chars().collect(),
+        pos: 0,
+    };
+    let value = parser.value()?;
+    parser.skip_spaces();
+    if parser.pos != parser.chars.len() {
+        return Err(format!("conteúdo extra na posição {}", parser.pos));
+    }
+    Ok(value)
+}
+
+struct DebugParser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl DebugParser {
+    fn skip_spaces(&mut self) {
+        while self.chars.get(self.pos).is_some_and(|c| c.is_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_spaces();
+        let matches = token
+            .chars()
+            .enumerate()
+            .all(|(i, c)| self.chars.get(self.pos + i) == Some(&c));
+        if matches {
+            self.pos += token.chars().count();
+        }
+        matches
+    }
+
+    fn expect(&mut self, token: &str) -> Result<(), String> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            Err(format!("esperava '{}' na posição {}", token, self.pos))
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        if self.eat("Null") {
+            Ok(Value::Null)
+        } else if self.eat("Bool(") {
+            let value = if self.eat("true") {
+                true
+            } else {
+                self.expect("false")?;
+                false
+            };
+            self.expect(")")?;
+            Ok(Value::Bool(value))
+        } else if self.eat("Number(") {
+            let start = self.pos;
+            while self.chars.get(self.pos).is_some_and(|c| *c != ')') {
+                self.pos += 1;
+            }
+            let number: String = self.chars[start..self.pos].iter().collect();
+            self.expect(")")?;
+            serde_json::from_str::<serde_json::Number>(&number)
+                .map(Value::Number)
+                .map_err(|err| format!("número inválido '{}': {}", number, err))
+        } else if self.eat("String(") {
+            let value = self.string()?;
+            self.expect(")")?;
+            Ok(Value::String(value))
+        } else if self.eat("Array [") {
+            let mut items = Vec::new();
+            while !self.eat("]") {
+                if !items.is_empty() {
+                    self.expect(",")?;
+                }
+                items.push(self.value()?);
+            }
+            Ok(Value::Array(items))
+        } else if self.eat("Object {") {
+            let mut map = serde_json::Map::new();
+            while !self.eat("}") {
+                if !map.is_empty() {
+                    self.expect(",")?;
+                }
+                self.skip_spaces();
+                let key = self.string()?;
+                self.expect(":")?;
+                map.insert(key, self.value()?);
+            }
+            Ok(Value::Object(map))
+        } else {
+            Err(format!("valor inesperado na posição {}", self.pos))
+        }
+    }
+
+    /** String no formato de Debug do Rust, com aspas e escapes */
+    fn string(&mut self) -> Result<String, String> {
+        self.expect("\"")?;
+        let mut out = String::new();
+        loop {
+            let c = *self.chars.get(self.pos).ok_or("string sem fim")?;
+            self.pos += 1;
+            match c {
+                '"' => return Ok(out),
+                '\\' => {
+                    let esc = *self.chars.get(self.pos).ok_or("string sem fim")?;
+                    self.pos += 1;
+                    match esc {
+                        'n' => out.push('\n'),
+                        'r' => out.push('\r'),
+                        't' => out.push('\t'),
+                        '0' => out.push('\0'),
+                        'u' => {
+                            self.expect("{")?;
+                            let start = self.pos;
+                            while self.chars.get(self.pos).is_some_and(|c| *c != '}') {
+                                self.pos += 1;
+                            }
+                            let hex: String = self.chars[start..self.pos].iter().collect();
+                            self.expect("}")?;
+                            let code = u32::from_str_radix(&hex, 16)
+                                .ok()
+                                .and_then(char::from_u32)
+                                .ok_or_else(|| format!("escape inválido \\u{{{}}}", hex))?;
+                            out.push(code);
+                        }
+                        other => out.push(other),
+                    }
+                }
+                other => out.push(other),
+            }
+        }
+    }
+}
+
+#[test]
+fn test_parse_errdyndb_lines() {
+    let payload = serde_json::json!({
+        "dev_id": "DAC210191234",
+        "timestamp": "2024-05-02T10:11:12",
+        "L1": [1, 0, null],
+        "T0": [25.5, -3.25, 1e-7],
+        "ok": true,
+        "msg": "aspas \" barra \\ tab\t acento ç ∆ \u{1}",
+        "nested": { "empty_arr": [], "empty_obj": {} },
+    });
+
+    // Formato antigo ({:?}) e formato novo (JSON)
+    let old_line = serde_json::json!({
+        "tslog": "2024-05-02T10:11:13.000-0300",
+        "tag": "ERRDYNDB",
+        "msg": format!("DAC2101_RAW;{:?}", payload),
+    })
+    .to_string();
+    let new_line = serde_json::json!({
+        "tslog": "2024-05-02T10:11:13.000-0300",
+        "tag": "ERRDYNDB",
+        "msg": format!("DAC2101_RAW;{}", payload),
+    })
+    .to_string();
+
+    for line in [old_line, new_line] {
+        let entry = parse_log_line(&line).unwrap().unwrap();
+        assert_eq!(entry.table, "DAC2101_RAW");
+        assert_eq!(entry.payload, payload);
+    }
+
+    assert!(parse_log_line(r#"{"tslog":"x","tag":"INFO","msg":"a;b"}"#).is_none());
+    assert!(parse_log_line("não é json").is_none());
+    assert!(parse_log_line(r#"{"tag":"ERRDYNDB","msg":"T;Object {"}"#)
+        .unwrap()
+        .is_err());
+}
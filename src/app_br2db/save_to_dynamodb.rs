@@ -8,14 +8,11 @@ pub async fn save_telemetry_to_dynamodb(
     payload: serde_json::Value,
     globs: &Arc<GlobalVars>,
 ) {
-    let (table_name, dev_id) = match find_dynamodb_table_name(&globs, &topic, &payload).await {
+    let (table_name, dev_id) = match resolve_table_name(&globs, &topic, &payload).await {
         Some(x) => x,
-        None => match propose_table_name(&globs, &topic, &payload) {
-            Some(x) => x,
-            None => {
-                return;
-            }
-        },
+        None => {
+            return;
+        }
     };
 
     let result = globs
@@ -43,12 +40,27 @@ pub async fn save_telemetry_to_dynamodb(
                 &payload,
                 &format!("{}", err),
             );
-            crate::LOG.append_log_tag_msg("ERRDYNDB", &format!("{};{:?}", table_name, payload));
+            crate::LOG.append_log_tag_msg("ERRDYNDB", &format!("{};{}", table_name, payload));
             globs.stats.dynamodb_error.fetch_add(1, Ordering::Relaxed);
+            if let Some(queue) = &globs.dead_letters.dynamodb {
+                queue.push(&table_name, vec![payload], &err);
+            }
         }
     };
 }
 
+// Também usada pelo armazenamento local, para que os arquivos fiquem separados pelas mesmas tabelas do DynamoDB
+pub async fn resolve_table_name(
+    globs: &Arc<GlobalVars>,
+    topic: &str,
+    payload: &serde_json::Value,
+) -> Option<(String, String)> {
+    match find_dynamodb_table_name(globs, topic, payload).await {
+        Some(x) => Some(x),
+        None => propose_table_name(globs, topic, payload),
+    }
+}
+
 async fn find_dynamodb_table_name(
     globs: &Arc<GlobalVars>,
     topic: &str,
@@ -0,0 +1,50 @@
+use crate::lib_local_store::saver::push_line_to_storage;
+use crate::save_to_dynamodb::resolve_table_name;
+use crate::GlobalVars;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+pub async fn save_telemetry_to_local_files(
+    topic: &str,
+    payload: &serde_json::Value,
+    globs: &Arc<GlobalVars>,
+) {
+    let (table_name, dev_id) = match resolve_table_name(&globs, &topic, &payload).await {
+        Some(x) => x,
+        None => {
+            return;
+        }
+    };
+
+    // O arquivo do dia é escolhido pelo timestamp do payload, que já foi validado antes de chegar aqui
+    let day = match payload["timestamp"].as_str() {
+        Some(timestamp) if timestamp.len() >= 10 => timestamp[0..10].to_owned(),
+        _ => {
+            return;
+        }
+    };
+
+    let result = push_line_to_storage(
+        &globs.to_local_store,
+        table_name,
+        dev_id,
+        day,
+        payload.to_string(),
+    )
+    .await;
+    match result {
+        Ok(()) => {
+            globs
+                .stats
+                .local_saved_telemetry
+                .fetch_add(1, Ordering::Relaxed);
+        }
+        Err(err) => {
+            crate::LOG.append_log_tag_msg("ERROR", &format!("[41] {}", err));
+            globs
+                .stats
+                .local_store_error
+                .fetch_add(1, Ordering::Relaxed);
+        }
+    };
+}
@@ -1,3 +1,4 @@
+use crate::lib_metrics::{queue_depth, CounterDeltas, MetricsText};
 use crate::GlobalVars;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
@@ -15,6 +16,8 @@ pub struct StatisticsCounters {
     pub payloads_discarded: AtomicUsize,
     pub payloads_with_insert_error: AtomicUsize,
     pub bq_rows_inserted: AtomicUsize,
+    pub local_saved_telemetry: AtomicUsize,
+    pub local_store_error: AtomicUsize,
 }
 
 impl StatisticsCounters {
@@ -32,6 +35,8 @@ impl StatisticsCounters {
             payloads_discarded: AtomicUsize::new(0),
             payloads_with_insert_error: AtomicUsize::new(0),
             bq_rows_inserted: AtomicUsize::new(0),
+            local_saved_telemetry: AtomicUsize::new(0),
+            local_store_error: AtomicUsize::new(0),
         }
     }
 }
@@ -40,23 +45,25 @@ pub async fn run_service(globs: Arc<GlobalVars>) {
     // "127.0.0.1:8080"
     const INTERVAL: u64 = 120; // em segundos
     let mut ts_start = std::time::Instant::now();
+    let mut deltas = CounterDeltas::default();
 
     loop {
         let elapsed = ts_start.elapsed().as_secs();
-        montar_e_enviar_estatisticas(elapsed, &globs);
+        montar_e_enviar_estatisticas(elapsed, &globs, &mut deltas);
         ts_start = std::time::Instant::now();
         tokio::time::sleep(std::time::Duration::from_secs(INTERVAL)).await;
     }
 }
 
-fn montar_e_enviar_estatisticas(elapsed: u64, globs: &Arc<GlobalVars>) {
+fn montar_e_enviar_estatisticas(elapsed: u64, globs: &Arc<GlobalVars>, deltas: &mut CounterDeltas) {
     let stats = &globs.stats;
-    let saved_telemetry = get_reset_atomic_usize(&stats.saved_telemetry);
-    let bigquery_insertions = get_reset_atomic_usize(&stats.bigquery_insertions);
+    let saved_telemetry = deltas.delta("saved_telemetry", &stats.saved_telemetry);
+    let bigquery_insertions = deltas.delta("bigquery_insertions", &stats.bigquery_insertions);
+    let local_saved_telemetry = deltas.delta("local_saved_telemetry", &stats.local_saved_telemetry);
 
     {
         let mut time_nosave_s = stats.time_nosave_s.load(Ordering::Relaxed);
-        if saved_telemetry == 0 && bigquery_insertions == 0 {
+        if saved_telemetry == 0 && bigquery_insertions == 0 && local_saved_telemetry == 0 {
             if time_nosave_s < 100_000 {
                 time_nosave_s += elapsed as usize;
             }
@@ -74,17 +81,20 @@ fn montar_e_enviar_estatisticas(elapsed: u64, globs: &Arc<GlobalVars>) {
         "origin": "broker2dynamo-v2",
         "interval": elapsed,
 
-        "unknown_topic": get_reset_atomic_usize(&stats.unknown_topic),
-        "dev_id_missing": get_reset_atomic_usize(&stats.dev_id_missing),
-        "dynamodb_error": get_reset_atomic_usize(&stats.dynamodb_error),
+        "unknown_topic": deltas.delta("unknown_topic", &stats.unknown_topic),
+        "dev_id_missing": deltas.delta("dev_id_missing", &stats.dev_id_missing),
+        "dynamodb_error": deltas.delta("dynamodb_error", &stats.dynamodb_error),
         "aws_saved_telemetry": saved_telemetry,
-        "aws_saved_control": get_reset_atomic_usize(&stats.saved_control),
-        "aws_saved_command": get_reset_atomic_usize(&stats.saved_command),
+        "aws_saved_control": deltas.delta("saved_control", &stats.saved_control),
+        "aws_saved_command": deltas.delta("saved_command", &stats.saved_command),
         "bigquery_insertions": bigquery_insertions,
-        "payloads_received": get_reset_atomic_usize(&stats.payloads_received),
-        "payloads_discarded": get_reset_atomic_usize(&stats.payloads_discarded),
-        "payloads_with_insert_error": get_reset_atomic_usize(&stats.payloads_with_insert_error),
-        "bq_rows_inserted": get_reset_atomic_usize(&stats.bq_rows_inserted),
+        "payloads_received": deltas.delta("payloads_received", &stats.payloads_received),
+        "payloads_discarded": deltas.delta("payloads_discarded", &stats.payloads_discarded),
+        "payloads_with_insert_error": deltas.delta("payloads_with_insert_error", &stats.payloads_with_insert_error),
+        "bq_rows_inserted": deltas.delta("bq_rows_inserted", &stats.bq_rows_inserted),
+        "local_saved_telemetry": local_saved_telemetry,
+        "local_store_error": deltas.delta("local_store_error", &stats.local_store_error),
+        "bq_saver": globs.bq_saver.stats_json(deltas),
     });
 
     let payload = message.to_string();
@@ -92,8 +102,83 @@ fn montar_e_enviar_estatisticas(elapsed: u64, globs: &Arc<GlobalVars>) {
     crate::LOG.append_statistics(&payload);
 }
 
-fn get_reset_atomic_usize(atomic_value: &AtomicUsize) -> usize {
-    let value = atomic_value.load(Ordering::Relaxed);
-    atomic_value.fetch_sub(value, Ordering::Relaxed);
-    value
+pub fn write_metrics(globs: &Arc<GlobalVars>, out: &mut MetricsText) {
+    let stats = &globs.stats;
+    let counter = |x: &AtomicUsize| x.load(Ordering::Relaxed);
+    out.counter(
+        "unknown_topic_total",
+        "Payloads com tópico desconhecido",
+        counter(&stats.unknown_topic),
+    );
+    out.counter(
+        "dev_id_missing_total",
+        "Payloads sem dev_id",
+        counter(&stats.dev_id_missing),
+    );
+    out.counter(
+        "dynamodb_error_total",
+        "Erros ao salvar no DynamoDB",
+        counter(&stats.dynamodb_error),
+    );
+    out.counter(
+        "aws_saved_telemetry_total",
+        "Telemetrias salvas no DynamoDB",
+        counter(&stats.saved_telemetry),
+    );
+    out.counter(
+        "aws_saved_control_total",
+        "Mensagens de controle salvas no DynamoDB",
+        counter(&stats.saved_control),
+    );
+    out.counter(
+        "aws_saved_command_total",
+        "Comandos salvos no DynamoDB",
+        counter(&stats.saved_command),
+    );
+    out.counter(
+        "bigquery_insertions_total",
+        "Inserções feitas no BigQuery",
+        counter(&stats.bigquery_insertions),
+    );
+    out.counter(
+        "payloads_received_total",
+        "Payloads recebidos",
+        counter(&stats.payloads_received),
+    );
+    out.counter(
+        "payloads_discarded_total",
+        "Payloads descartados",
+        counter(&stats.payloads_discarded),
+    );
+    out.counter(
+        "payloads_with_insert_error_total",
+        "Payloads com erro de inserção no BigQuery",
+        counter(&stats.payloads_with_insert_error),
+    );
+    out.counter(
+        "bq_rows_inserted_total",
+        "Linhas inseridas no BigQuery",
+        counter(&stats.bq_rows_inserted),
+    );
+    out.counter(
+        "local_saved_telemetry_total",
+        "Telemetrias salvas em arquivos locais",
+        counter(&stats.local_saved_telemetry),
+    );
+    out.counter(
+        "local_store_error_total",
+        "Erros ao salvar em arquivos locais",
+        counter(&stats.local_store_error),
+    );
+    out.gauge(
+        "to_bigquery_queue_depth",
+        "Eventos aguardando o saver do BigQuery",
+        queue_depth(&globs.to_bigquery),
+    );
+    out.gauge(
+        "to_local_store_queue_depth",
+        "Linhas aguardando gravação em arquivos locais",
+        queue_depth(&globs.to_local_store),
+    );
+    globs.bq_saver.write_metrics(out);
 }
@@ -1,36 +1,87 @@
-use crate::lib_http::response::{build_http_response, respond_http_plain_text, send_response};
+use crate::lib_http::auth::ApiTokens;
+use crate::lib_http::response::{build_http_response, respond_http_plain_text};
+use crate::lib_http::router::{
+    AuthPolicy, BodyParser, Handler, Method, Route, RouteRequest, RouteResult, Router, Visibility,
+};
 use crate::lib_http::types::{HttpRequest, HttpResponse};
+use crate::lib_metrics::{MetricsText, CONTENT_TYPE, REGISTRY};
 use crate::GlobalVars;
-use std::sync::Arc;
+use futures::future::BoxFuture;
+use std::convert::Infallible;
+use std::sync::{Arc, OnceLock};
 use tokio::net::TcpStream;
 
+static ROUTER: OnceLock<Router<Infallible>> = OnceLock::new();
+
+fn build_router() -> Router<Infallible> {
+    use AuthPolicy::None as NoAuth;
+    use BodyParser::Raw;
+    use Method::Any;
+    use Visibility::Public;
+
+    #[rustfmt::skip]
+    let routes = vec![
+        Route::new(Any, "/service-getmac/health_check",  Public, NoAuth, Raw, Handler::Sync(health_check)),
+        Route::new(Any, "/service-getmac/get_devs_macs", Public, NoAuth, Raw, Handler::Async(devs_macs)),
+        Route::new(Any, "/service-getmac/get_dev_mac",   Public, NoAuth, Raw, Handler::Async(dev_mac)),
+        Route::new(Any, "/service-getmac/metrics",       Public, NoAuth, Raw, Handler::Sync(metrics)),
+    ];
+
+    Router::new(ApiTokens::default(), routes)
+}
+
 pub async fn on_http_req(
     req: HttpRequest,
-    _is_internal: bool,
-    mut socket: TcpStream,
+    is_internal: bool,
+    socket: TcpStream,
     globs: Arc<GlobalVars>,
 ) {
-    let response = match &req.path[..] {
-        "/service-getmac/health_check" => respond_http_plain_text(200, "Alive"),
-        "/service-getmac/get_devs_macs" => get_devs_macs(&req, &globs)
+    let router = ROUTER.get_or_init(build_router);
+    router.handle(req, is_internal, socket, &globs).await;
+}
+
+fn health_check(
+    _rreq: &RouteRequest,
+    _globs: &Arc<GlobalVars>,
+) -> Result<RouteResult<Infallible>, HttpResponse> {
+    Ok(RouteResult::Respond(respond_http_plain_text(200, "Alive")))
+}
+
+fn metrics(
+    _rreq: &RouteRequest,
+    _globs: &Arc<GlobalVars>,
+) -> Result<RouteResult<Infallible>, HttpResponse> {
+    let mut out = MetricsText::new();
+    REGISTRY.render(&mut out);
+    Ok(RouteResult::Respond(build_http_response(
+        200,
+        out.into_bytes(),
+        CONTENT_TYPE,
+    )))
+}
+
+fn devs_macs<'a>(
+    rreq: &'a RouteRequest,
+    globs: &'a Arc<GlobalVars>,
+) -> BoxFuture<'a, Result<RouteResult<Infallible>, HttpResponse>> {
+    Box::pin(async move {
+        let response = get_devs_macs(&rreq.req, globs)
             .await
-            .unwrap_or_else(|err| respond_http_plain_text(400, &err)),
-        "/service-getmac/get_dev_mac" => get_dev_mac(&req, &globs)
+            .unwrap_or_else(|err| respond_http_plain_text(400, &err));
+        Ok(RouteResult::Respond(response))
+    })
+}
+
+fn dev_mac<'a>(
+    rreq: &'a RouteRequest,
+    globs: &'a Arc<GlobalVars>,
+) -> BoxFuture<'a, Result<RouteResult<Infallible>, HttpResponse>> {
+    Box::pin(async move {
+        let response = get_dev_mac(&rreq.req, globs)
             .await
-            .unwrap_or_else(|err| respond_http_plain_text(400, &err)),
-        _ => {
-            crate::LOG.append_log_tag_msg(
-                "ERROR",
-                &format!("Invalid request: {} {}", req.method, req.path),
-            );
-            respond_http_plain_text(404, &format!("Not found: {}", &req.path))
-        }
-    };
-    if let Err(err) = {
-        send_response(&mut socket, &response).await // socket_write
-    } {
-        crate::LOG.append_log_tag_msg("ERROR[33]", &err.to_string());
-    }
+            .unwrap_or_else(|err| respond_http_plain_text(400, &err));
+        Ok(RouteResult::Respond(response))
+    })
 }
 
 async fn get_devs_macs(req: &HttpRequest, globs: &Arc<GlobalVars>) -> Result<HttpResponse, String> {
@@ -1,81 +1,475 @@
-use std::path::Path;
+use crate::lib_http::{response::respond_http_json, types::HttpResponse};
+use crate::lib_local_store::layout::is_valid_path_part;
+use crate::lib_metrics::{Counter, Gauge, REGISTRY};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use crate::lib_http::{
-    response::{respond_http_json, respond_http_plain_text},
-    types::HttpResponse,
-};
+/*
+Cache das compilações parciais (arquivos .part) usado por todos os *_hist.
+Nome do arquivo: {ts_ini}.{dev_id}.{timezone}.{hash da requisição}.part
+O hash faz com que requisições com configurações diferentes tenham arquivos diferentes, em vez de uma
+sobrescrever a outra. O espaço em disco é limitado e os arquivos menos usados são apagados primeiro.
+Arquivos de períodos que ainda não terminaram (dia atual) expiram depois de `partial_ttl`.
+*/
+
+pub const PARTS_DIR: &str = "./parts";
 
 pub fn create_parts_dir() -> std::io::Result<()> {
-    std::fs::create_dir_all("./parts")
+    std::fs::create_dir_all(PARTS_DIR)
 }
 
-pub fn build_part_file_name(dev_id: &str, ts_ini: &str, offset: &str) -> String {
-    format!("./parts/{}.{}.{}.part", ts_ini, dev_id, offset)
+#[derive(Clone)]
+pub struct PartCacheConfig {
+    pub max_bytes: u64,
+    pub partial_ttl: Duration,
 }
 
-pub fn process_clear_cache(parsed: serde_json::Value) -> Result<HttpResponse, String> {
-    let before = parsed["before"].as_str().unwrap_or("");
-    let mut folder_path = match std::env::current_dir() {
-        Ok(v) => v,
-        Err(err) => {
-            return Ok(respond_http_plain_text(
-                500,
-                &format!("Could not get current directory: {}", err),
-            ))
+// Identifica uma entrada do cache
+pub struct PartEntry {
+    file_name: String,
+    dev_id: String,
+    // Fim do período em UTC, para saber se o arquivo foi salvo com o período já fechado
+    period_end: SystemTime,
+}
+
+impl PartEntry {
+    pub fn new(
+        dev_id: &str,
+        ts_ini: &str,
+        timezone_offset: Option<i64>,
+        i_ts_end: i64,
+        rpars_serialized: &str,
+    ) -> PartEntry {
+        let offset = timezone_offset
+            .map(|v| v.to_string())
+            .unwrap_or("None".to_string());
+        // Sem timezoneOffset considera o horário de Brasília.
+        // Margem de 10 minutos para as telemetrias que chegam atrasadas.
+        let end_utc = i_ts_end - timezone_offset.unwrap_or(-3) * 3600 + 10 * 60;
+        PartEntry {
+            file_name: format!(
+                "{}.{}.{}.{}.part",
+                ts_ini,
+                dev_id,
+                offset,
+                request_hash(rpars_serialized)
+            ),
+            dev_id: dev_id.to_owned(),
+            period_end: UNIX_EPOCH + Duration::from_secs(u64::try_from(end_utc).unwrap_or(0)),
         }
-    };
-    folder_path.push(Path::new("./parts/"));
-    crate::LOG.append_log_tag_msg(
-        "INFO",
-        &format!(
-            "Deleting .part files from the current directory: {}",
-            folder_path.as_path().to_str().unwrap() // .to_str() falha se Path não é UTF-8 válido. Não trabalhamos com caminhos que não sejam UTF-8.
-        ),
-    );
+    }
+
+    fn is_valid(&self) -> bool {
+        is_valid_path_part(&self.dev_id) && !self.dev_id.contains('.')
+    }
+}
+
+pub struct CachedPart<T> {
+    pub data: T,
+    // Salvo depois do fim do período, não precisa consultar de novo
+    pub complete: bool,
+}
+
+struct IndexEntry {
+    dev_id: String,
+    size: u64,
+    last_access: SystemTime,
+}
+
+pub struct PartCache {
+    config: PartCacheConfig,
+    dir: PathBuf,
+    index: Mutex<HashMap<String, IndexEntry>>,
+    hits: Arc<Counter>,
+    misses: Arc<Counter>,
+    expired: Arc<Counter>,
+    evicted: Arc<Counter>,
+    used_bytes: Arc<Gauge>,
+    files: Arc<Gauge>,
+}
+
+#[derive(Serialize)]
+pub struct CacheStatus {
+    pub max_bytes: u64,
+    pub used_bytes: u64,
+    pub files: usize,
+    pub partial_ttl_s: u64,
+    pub hits: u64,
+    pub misses: u64,
+    pub expired: u64,
+    pub evicted: u64,
+    // Quantidade de arquivos por dispositivo, só os que mais ocupam
+    pub top_devices: Vec<(String, usize)>,
+}
+
+impl PartCache {
+    pub fn new(config: PartCacheConfig) -> PartCache {
+        PartCache::open(config, PathBuf::from(PARTS_DIR))
+    }
+
+    // Carrega o índice a partir dos arquivos que já estão na pasta
+    pub(super) fn open(config: PartCacheConfig, dir: PathBuf) -> PartCache {
+        let requests = |result: &str| {
+            REGISTRY.counter(
+                "part_cache_requests_total",
+                "Consultas ao cache de compilações (.part)",
+                &[("result", result)],
+            )
+        };
+        let cache = PartCache {
+            config,
+            dir,
+            index: Mutex::new(HashMap::new()),
+            hits: requests("hit"),
+            misses: requests("miss"),
+            expired: requests("expired"),
+            evicted: REGISTRY.counter(
+                "part_cache_evictions_total",
+                "Arquivos .part apagados por falta de espaço",
+                &[],
+            ),
+            used_bytes: REGISTRY.gauge("part_cache_bytes", "Espaço ocupado pelos .part", &[]),
+            files: REGISTRY.gauge("part_cache_files", "Quantidade de arquivos .part", &[]),
+        };
 
-    // 2021-03-12T00:00:00.DAC210191053.part
-    let rgx_filename = regex::Regex::new(r"^(.+/)?(\d\d\d\d-\d\d-\d\d.*\.\w+\.part)$").unwrap();
-    // let root_path = std::path::Path::new(folder_path);
-    let paths = match std::fs::read_dir(&folder_path) {
-        Ok(v) => v,
-        Err(err) => {
-            return Ok(respond_http_plain_text(
-                500,
-                &format!("Could not get read directory: {}", err),
-            ))
+        let rgx_filename =
+            regex::Regex::new(r"^\d\d\d\d-\d\d-\d\d[^.]*\.([^.]+)\..*\.part$").unwrap();
+        let mut index = cache.index.lock().unwrap();
+        if let Ok(paths) = std::fs::read_dir(&cache.dir) {
+            for entry in paths.flatten() {
+                let file_name = entry.file_name().to_string_lossy().into_owned();
+                // 2021-03-12T00:00:00.DAC210191053.-3.0123456789abcdef.part (ou sem o hash, no formato antigo)
+                let Some(dev_id) = rgx_filename
+                    .captures(&file_name)
+                    .map(|cap| cap[1].to_owned())
+                else {
+                    continue;
+                };
+                let Ok(metadata) = entry.metadata() else {
+                    continue;
+                };
+                index.insert(
+                    file_name,
+                    IndexEntry {
+                        dev_id,
+                        size: metadata.len(),
+                        last_access: metadata.modified().unwrap_or(UNIX_EPOCH),
+                    },
+                );
+            }
         }
-    };
-    let mut n = 0;
-    for entry in paths {
-        let path = match entry {
-            Ok(v) => v.path(),
-            Err(err) => {
-                return Err(format!("Error gettng file info: {}", err));
+        crate::LOG.append_log_tag_msg("INFO", &format!("Part files in cache: {}", index.len()));
+        cache.evict(&mut index, None);
+        drop(index);
+        cache
+    }
+
+    pub fn load<T: DeserializeOwned>(&self, entry: &PartEntry) -> Option<CachedPart<T>> {
+        if !entry.is_valid() {
+            return None;
+        }
+        let path = self.dir.join(&entry.file_name);
+        let written_at = match std::fs::metadata(&path).and_then(|m| m.modified()) {
+            Ok(v) => v,
+            Err(_) => {
+                self.misses.inc();
+                return None;
             }
         };
-        let filename = match rgx_filename.captures(path.to_str().unwrap()) {
-            Some(cap) => cap[2].to_owned(),
-            None => continue,
-        };
-        if (!before.is_empty()) && (&filename[..] > before) {
-            continue;
+        let complete = written_at >= entry.period_end;
+        let age = SystemTime::now()
+            .duration_since(written_at)
+            .unwrap_or_default();
+        if (!complete) && age > self.config.partial_ttl {
+            // Período ainda aberto e arquivo antigo: as telemetrias novas não estão nele
+            self.expired.inc();
+            self.remove_files(|file_name, _| file_name == entry.file_name);
+            return None;
         }
-        match std::fs::remove_file(path) {
-            Ok(_) => {}
+        let data = std::fs::read_to_string(&path)
+            .map_err(|err| err.to_string())
+            .and_then(|serialized| {
+                serde_json::from_str::<T>(&serialized).map_err(|err| err.to_string())
+            });
+        let data = match data {
+            Ok(v) => v,
             Err(err) => {
-                return Ok(respond_http_plain_text(
-                    500,
-                    &format!("Could not get remove file: {}", err),
-                ))
+                crate::LOG.append_log_tag_msg(
+                    "WARN",
+                    &format!("Invalid part file {}: {}", entry.file_name, err),
+                );
+                self.misses.inc();
+                self.remove_files(|file_name, _| file_name == entry.file_name);
+                return None;
             }
+        };
+        self.hits.inc();
+        if let Some(x) = self.index.lock().unwrap().get_mut(&entry.file_name) {
+            x.last_access = SystemTime::now();
         }
-        n += 1;
+        Some(CachedPart { data, complete })
     }
 
+    pub fn save<T: Serialize>(&self, entry: &PartEntry, data: &T) {
+        if !entry.is_valid() {
+            return;
+        }
+        let serialized = serde_json::to_string(data).unwrap();
+        if let Err(err) = std::fs::write(self.dir.join(&entry.file_name), &serialized) {
+            crate::LOG.append_log_tag_msg("ERROR", &format!("write failed: {}", err));
+            return;
+        }
+        let mut index = self.index.lock().unwrap();
+        index.insert(
+            entry.file_name.clone(),
+            IndexEntry {
+                dev_id: entry.dev_id.clone(),
+                size: serialized.len() as u64,
+                last_access: SystemTime::now(),
+            },
+        );
+        self.evict(&mut index, Some(&entry.file_name));
+    }
+
+    pub fn invalidate_dev(&self, dev_id: &str) -> usize {
+        self.remove_files(|_, entry| entry.dev_id == dev_id)
+    }
+
+    // Apaga os arquivos cujo nome é anterior a `before` (ex.: "2021-03-12"), ou todos se vazio
+    pub fn clear_before(&self, before: &str) -> usize {
+        self.remove_files(|file_name, _| before.is_empty() || file_name <= before)
+    }
+
+    pub fn status(&self) -> CacheStatus {
+        let index = self.index.lock().unwrap();
+        let mut per_dev: HashMap<&str, usize> = HashMap::new();
+        for entry in index.values() {
+            *per_dev.entry(&entry.dev_id).or_default() += 1;
+        }
+        let mut top_devices: Vec<(String, usize)> = per_dev
+            .into_iter()
+            .map(|(dev_id, n)| (dev_id.to_owned(), n))
+            .collect();
+        top_devices.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        top_devices.truncate(20);
+        CacheStatus {
+            max_bytes: self.config.max_bytes,
+            used_bytes: index.values().map(|x| x.size).sum(),
+            files: index.len(),
+            partial_ttl_s: self.config.partial_ttl.as_secs(),
+            hits: self.hits.get(),
+            misses: self.misses.get(),
+            expired: self.expired.get(),
+            evicted: self.evicted.get(),
+            top_devices,
+        }
+    }
+
+    fn remove_files(&self, filter: impl Fn(&str, &IndexEntry) -> bool) -> usize {
+        let mut index = self.index.lock().unwrap();
+        let to_remove: Vec<String> = index
+            .iter()
+            .filter(|(file_name, entry)| filter(file_name, entry))
+            .map(|(file_name, _)| file_name.to_owned())
+            .collect();
+        for file_name in &to_remove {
+            self.remove_file(&mut index, file_name);
+        }
+        self.update_gauges(&index);
+        to_remove.len()
+    }
+
+    // Apaga os arquivos usados há mais tempo até caber no limite. O arquivo recém salvo não é apagado.
+    fn evict(&self, index: &mut HashMap<String, IndexEntry>, keep: Option<&str>) {
+        let mut used_bytes: u64 = index.values().map(|x| x.size).sum();
+        if used_bytes > self.config.max_bytes {
+            let mut by_access: Vec<(SystemTime, String)> = index
+                .iter()
+                .filter(|(file_name, _)| Some(file_name.as_str()) != keep)
+                .map(|(file_name, entry)| (entry.last_access, file_name.to_owned()))
+                .collect();
+            by_access.sort();
+            for (_, file_name) in by_access {
+                if used_bytes <= self.config.max_bytes {
+                    break;
+                }
+                used_bytes -= self.remove_file(index, &file_name);
+                self.evicted.inc();
+            }
+        }
+        self.update_gauges(index);
+    }
+
+    fn remove_file(&self, index: &mut HashMap<String, IndexEntry>, file_name: &str) -> u64 {
+        if let Err(err) = std::fs::remove_file(self.dir.join(file_name)) {
+            if err.kind() != std::io::ErrorKind::NotFound {
+                crate::LOG.append_log_tag_msg(
+                    "ERROR",
+                    &format!("Could not remove part file {}: {}", file_name, err),
+                );
+            }
+        }
+        index.remove(file_name).map_or(0, |x| x.size)
+    }
+
+    fn update_gauges(&self, index: &HashMap<String, IndexEntry>) {
+        self.used_bytes
+            .set(index.values().map(|x| x.size).sum::<u64>() as f64);
+        self.files.set(index.len() as f64);
+    }
+}
+
+// FNV-1a, estável entre versões do compilador para os nomes continuarem valendo depois de atualizar
+fn request_hash(rpars_serialized: &str) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for b in rpars_serialized.bytes() {
+        hash ^= u64::from(b);
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("{:016x}", hash)
+}
+
+pub fn process_clear_cache(
+    parsed: serde_json::Value,
+    cache: &PartCache,
+) -> Result<HttpResponse, String> {
+    let before = parsed["before"].as_str().unwrap_or("");
+    let dev_id = parsed["dev_id"].as_str().unwrap_or("");
+    crate::LOG.append_log_tag_msg(
+        "INFO",
+        &format!(
+            "Deleting .part files from: {}",
+            Path::new(PARTS_DIR).display()
+        ),
+    );
+
+    let n = if dev_id.is_empty() {
+        cache.clear_before(before)
+    } else {
+        cache.invalidate_dev(dev_id)
+    };
+
     crate::LOG.append_log_tag_msg("INFO", &format!("Part files removed: {}", n));
 
     let resp = serde_json::json!({
       "success": true,
+      "removed": n,
     });
     return Ok(respond_http_json(200, &resp.to_string()));
 }
+
+#[cfg(test)]
+pub(super) fn test_dir(name: &str) -> PathBuf {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis();
+    let dir = std::env::temp_dir().join(format!("{}-{}", name, millis));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn test_part_cache_eviction() {
+    let dir = test_dir("part-cache-eviction");
+    let config = PartCacheConfig {
+        max_bytes: 250,
+        partial_ttl: Duration::from_secs(3600),
+    };
+    let cache = PartCache::open(config.clone(), dir.clone());
+    // Períodos já fechados, 100 bytes cada
+    let entry = |dev_id: &str, ts_ini: &str| PartEntry::new(dev_id, ts_ini, Some(-3), 0, "{}");
+    let data = "x".repeat(98);
+    let (a, b, c) = (
+        entry("DAC000000001", "2024-01-01T00:00:00"),
+        entry("DAC000000001", "2024-01-02T00:00:00"),
+        entry("DUT000000001", "2024-01-01T00:00:00"),
+    );
+    let before = cache.status();
+    cache.save(&a, &data);
+    std::thread::sleep(Duration::from_millis(10));
+    cache.save(&b, &data);
+    std::thread::sleep(Duration::from_millis(10));
+    // O acesso faz o primeiro arquivo deixar de ser o mais antigo
+    let loaded = cache.load::<String>(&a).unwrap();
+    assert!(loaded.complete);
+    assert_eq!(loaded.data, data);
+    assert!(cache
+        .load::<String>(&entry("DAC000000002", "2024-01-01T00:00:00"))
+        .is_none());
+    std::thread::sleep(Duration::from_millis(10));
+    cache.save(&c, &data);
+
+    assert!(dir.join(&a.file_name).exists());
+    assert!(!dir.join(&b.file_name).exists());
+    assert!(dir.join(&c.file_name).exists());
+    let status = cache.status();
+    assert_eq!(
+        (status.files, status.used_bytes, status.max_bytes),
+        (2, 200, 250)
+    );
+    // Os contadores são globais: hits também são feitos pelo outro teste
+    assert!(status.hits > before.hits);
+    assert_eq!(status.misses - before.misses, 1);
+    assert_eq!(status.evicted - before.evicted, 1);
+    assert_eq!(
+        status.top_devices,
+        vec![
+            ("DAC000000001".to_owned(), 1),
+            ("DUT000000001".to_owned(), 1)
+        ]
+    );
+
+    // O índice é refeito a partir da pasta
+    let cache = PartCache::open(config, dir.clone());
+    assert_eq!((cache.status().files, cache.status().used_bytes), (2, 200));
+    assert_eq!(cache.invalidate_dev("DUT000000001"), 1);
+    assert_eq!(
+        cache.status().top_devices,
+        vec![("DAC000000001".to_owned(), 1)]
+    );
+    assert_eq!(cache.clear_before(""), 1);
+    assert_eq!((cache.status().files, cache.status().used_bytes), (0, 0));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_part_cache_partial_ttl() {
+    let dir = test_dir("part-cache-ttl");
+    let config = |partial_ttl: Duration| PartCacheConfig {
+        max_bytes: 1024 * 1024,
+        partial_ttl,
+    };
+    let tomorrow = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64
+        + 24 * 3600;
+    let open = PartEntry::new("DUT000000001", "2024-01-02T00:00:00", None, tomorrow, "{}");
+    let closed = PartEntry::new("DUT000000001", "2024-01-01T00:00:00", None, 0, "{}");
+
+    let cache = PartCache::open(config(Duration::from_secs(3600)), dir.clone());
+    cache.save(&open, &1);
+    cache.save(&closed, &2);
+    let loaded = cache.load::<i32>(&open).unwrap();
+    assert_eq!((loaded.data, loaded.complete), (1, false));
+
+    // Período aberto com o arquivo mais velho que partial_ttl: expira e é apagado
+    std::thread::sleep(Duration::from_millis(10));
+    let cache = PartCache::open(config(Duration::from_millis(1)), dir.clone());
+    let before = cache.status();
+    assert!(cache.load::<i32>(&open).is_none());
+    assert!(!dir.join(&open.file_name).exists());
+    let loaded = cache.load::<i32>(&closed).unwrap();
+    assert_eq!((loaded.data, loaded.complete), (2, true));
+    let status = cache.status();
+    assert_eq!(status.expired - before.expired, 1);
+    assert_eq!(status.files, 1);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
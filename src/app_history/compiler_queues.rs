@@ -1,21 +1,26 @@
-use std::collections::HashSet;
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use crate::app_history::{
-    dac_hist, dal_hist, dam_hist, dev_export, dma_hist, dmt_hist, dri_hist, dut_hist, energy_hist,
-    energy_stats,
+    dac_hist, dal_hist, dam_hist, dev_export, dma_hist, dmt_hist, dri_hist, dut_hist,
+    energy_consumption, energy_hist, energy_stats, range_hist,
 };
 use crate::lib_http::response::{
     respond_http_json_serializable, respond_http_plain_text, send_response,
 };
 use crate::lib_http::types::HttpResponse;
+use crate::lib_metrics::{BUCKETS_COMPILATION, REGISTRY};
 use crate::{lib_essential_thread, GlobalVars};
+use futures::FutureExt;
+use serde::Serialize;
 use tokio::net::TcpStream;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, oneshot};
 
 pub enum CompilationRequest {
     CompDacV2(dac_hist::ReqParameters),
     CompDut(dut_hist::ReqParameters),
+    CompRange(range_hist::RangeParameters),
     CompDam(dam_hist::ReqParameters),
     CompDma(dma_hist::ReqParameters),
     CompDmt(dmt_hist::ReqParameters),
@@ -23,79 +28,385 @@ pub enum CompilationRequest {
     CompDri(dri_hist::DriHistParams),
     EnergyQuery(energy_hist::EnergyHistParams),
     EnergyStats(energy_stats::EnergyStatParams),
+    EnergyConsumption(energy_consumption::EnergyConsumptionParams),
     ExportDevTelemetries(dev_export::ReqParameters),
 }
 
+impl CompilationRequest {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            CompilationRequest::CompDacV2(_) => "dac",
+            CompilationRequest::CompDut(_) => "dut",
+            CompilationRequest::CompRange(rpars) => rpars.kind(),
+            CompilationRequest::CompDam(_) => "dam",
+            CompilationRequest::CompDma(_) => "dma",
+            CompilationRequest::CompDmt(_) => "dmt",
+            CompilationRequest::CompDal(_) => "dal",
+            CompilationRequest::CompDri(_) => "dri",
+            CompilationRequest::EnergyQuery(_) => "energy_query",
+            CompilationRequest::EnergyStats(_) => "energy_stats",
+            CompilationRequest::EnergyConsumption(_) => "energy_consumption",
+            CompilationRequest::ExportDevTelemetries(_) => "export",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Priority {
+    // Gráficos abertos por alguém que está esperando a resposta
+    Interactive,
+    // Exportações e estatísticas, que podem esperar
+    Bulk,
+}
+
+impl CompilationRequest {
+    pub fn priority(&self) -> Priority {
+        match self {
+            CompilationRequest::ExportDevTelemetries(_) => Priority::Bulk,
+            CompilationRequest::EnergyStats(_) => Priority::Bulk,
+            CompilationRequest::EnergyConsumption(_) => Priority::Bulk,
+            _ => Priority::Interactive,
+        }
+    }
+}
+
 pub enum MsgToCompilers {
     NewRequest(TcpStream, CompilationRequest, String),
-    CompilationDone(String),
+    CompilationDone(usize),
+    GetStatus(oneshot::Sender<QueueStatus>),
+}
+
+#[derive(Serialize)]
+pub struct QueueStatus {
+    pub workers: usize,
+    pub running: Vec<JobStatus>,
+    pub pending: Vec<JobStatus>,
+}
+
+#[derive(Serialize)]
+pub struct JobStatus {
+    pub id: usize,
+    pub kind: &'static str,
+    pub dev_id: String,
+    pub priority: Priority,
+    // Segundos desde que a requisição chegou
+    pub age_s: f64,
+    // Segundos desde que a compilação começou (só nas que estão rodando)
+    pub running_s: Option<f64>,
+    // Segundos que faltam para o prazo da requisição acabar
+    pub deadline_in_s: f64,
+}
+
+struct PendingJob {
+    id: usize,
+    socket: TcpStream,
+    request: CompilationRequest,
+    dev_id: String,
+    priority: Priority,
+    received_at: Instant,
+    deadline: Instant,
+}
+
+struct RunningJob {
+    kind: &'static str,
+    dev_id: String,
+    priority: Priority,
+    received_at: Instant,
+    started_at: Instant,
+    deadline: Instant,
 }
+
+const DISCARDED_HELP: &str = "Requisições descartadas por prazo esgotado ou cliente desconectado";
+
+// Requisição bulk esperando há mais tempo que isso passa a concorrer com as interativas
+const BULK_AGING: Duration = Duration::from_secs(60);
+
+/*
+Regras da fila:
+ - Só roda uma compilação por dev_id de cada vez;
+ - No máximo "workers" compilações ao mesmo tempo. Com "workers" >= 2 as bulk não ocupam todas as vagas, sempre sobra
+   uma para as interativas. Com um worker só as bulk também usam essa vaga, senão nunca rodariam;
+ - As interativas passam na frente das bulk, a não ser que a bulk já esteja esperando há mais de BULK_AGING;
+ - Requisições que passaram do prazo ou cujo cliente já desconectou são descartadas antes de rodar.
+*/
 pub async fn task_queue_manager(
     mut receiver: mpsc::Receiver<MsgToCompilers>,
     globs: Arc<GlobalVars>,
 ) {
-    let mut queue: Vec<(TcpStream, CompilationRequest, String)> = Vec::new();
-    let mut tasks_running: HashSet<String> = HashSet::new();
+    let config = &globs.configfile.compile_queue;
+    // Com um worker só não tem como reservar vaga para as interativas
+    let max_bulk = if config.workers > 1 {
+        config.workers - 1
+    } else {
+        1
+    };
+    let mut queue: Vec<PendingJob> = Vec::new();
+    let mut tasks_running: HashMap<usize, RunningJob> = HashMap::new();
     let mut n_req: usize = 0;
+    let queue_length = REGISTRY.gauge(
+        "compile_queue_length",
+        "Requisições aguardando na fila de compilação",
+        &[],
+    );
+    let running = REGISTRY.gauge("compile_running", "Compilações em andamento", &[]);
+    let mut check_interval = tokio::time::interval(Duration::from_secs(5));
     loop {
-        match receiver.recv().await.expect("Erro ao receber do mpsc") {
-            MsgToCompilers::NewRequest(socket, request, dev_id) => {
-                // Adiciona a requisição na fila
-                queue.push((socket, request, dev_id));
-            }
-            MsgToCompilers::CompilationDone(dev_id) => {
-                // Nothing to do here, it will be checked on the next lines
-                tasks_running.remove(&dev_id);
-            }
-        };
-        // Verifica se tem requisições na fila;
-        // Se estiver rodando muitas já, dá continue para esperar alguma terminar;
-        // Pega a próxima da fila e põe para rodar. Pode ser que mesmo que tenha vaga já exista uma tarefa para o mesmo dev_id e aí espera liberar uma vaga de interesse.
-        if (!queue.is_empty()) && (tasks_running.len() < 5) {
-            for i in 0..queue.len() {
-                if !tasks_running.contains(&queue[i].2) {
+        tokio::select! {
+            msg = receiver.recv() => match msg.expect("Erro ao receber do mpsc") {
+                MsgToCompilers::NewRequest(socket, request, dev_id) => {
+                    // Adiciona a requisição na fila
                     n_req += 1;
-                    let (mut socket, request, dev_id) = queue.remove(i);
-                    let globs = globs.clone();
-                    tasks_running.insert(dev_id.clone());
-                    std::thread::spawn(move || {
-                        let rt = tokio::runtime::Builder::new_current_thread()
-                            .enable_all()
-                            .build()
-                            .expect("Error creating tokio runtime");
-                        rt.block_on(async move {
-                            crate::LOG.append_log_tag_msg(
-                                "info",
-                                &format!("Iniciando compilação [{}] {}", n_req, dev_id),
-                            );
-                            let response = match executar_requisicao(request, &globs).await {
-                                Ok(v) => v,
-                                Err(err) => respond_http_plain_text(500, &err),
-                            };
-                            if let Err(err) = {
-                                send_response(&mut socket, &response).await // socket_write
-                            } {
-                                crate::LOG.append_log_tag_msg("ERROR[67]", &err.to_string());
-                            }
-                            crate::LOG.append_log_tag_msg(
-                                "info",
-                                &format!("Concluindo compilação [{}] {}", n_req, dev_id),
-                            );
-                            globs
-                                .to_compiler
-                                .send(MsgToCompilers::CompilationDone(dev_id))
-                                .await
-                                .expect("UNEXPECTED-53");
-                            tokio::time::sleep(std::time::Duration::from_secs(5)).await;
-                        })
+                    let priority = request.priority();
+                    let received_at = Instant::now();
+                    let deadline = received_at
+                        + match priority {
+                            Priority::Interactive => config.deadline_interactive,
+                            Priority::Bulk => config.deadline_bulk,
+                        };
+                    queue.push(PendingJob {
+                        id: n_req,
+                        socket,
+                        request,
+                        dev_id,
+                        priority,
+                        received_at,
+                        deadline,
                     });
-                    break;
                 }
+                MsgToCompilers::CompilationDone(id) => {
+                    tasks_running.remove(&id);
+                }
+                MsgToCompilers::GetStatus(reply) => {
+                    let _ = reply.send(queue_status(config.workers, &queue, &tasks_running));
+                }
+            },
+            _ = check_interval.tick() => {}
+        };
+
+        discard_dead_requests(&mut queue);
+
+        // Pega a próxima da fila e põe para rodar enquanto tiver vaga. Pode ser que mesmo que tenha vaga
+        // já exista uma tarefa para o mesmo dev_id e aí espera liberar uma vaga de interesse.
+        while tasks_running.len() < config.workers {
+            let n_bulk = tasks_running
+                .values()
+                .filter(|job| job.priority == Priority::Bulk)
+                .count();
+            let next = select_next(&queue, &tasks_running, n_bulk < max_bulk);
+            let Some(i) = next else {
+                break;
+            };
+            let job = queue.remove(i);
+            if socket_closed(&job.socket) {
+                log_discarded(&job, "client_closed");
+                continue;
             }
+            let now = Instant::now();
+            tasks_running.insert(
+                job.id,
+                RunningJob {
+                    kind: job.request.kind(),
+                    dev_id: job.dev_id.clone(),
+                    priority: job.priority,
+                    received_at: job.received_at,
+                    started_at: now,
+                    deadline: job.deadline,
+                },
+            );
+            start_compilation(job, globs.clone());
         }
+
+        queue_length.set(queue.len() as f64);
+        running.set(tasks_running.len() as f64);
     }
 }
 
+// Retorna o índice da próxima requisição que pode rodar
+fn select_next(
+    queue: &[PendingJob],
+    tasks_running: &HashMap<usize, RunningJob>,
+    bulk_allowed: bool,
+) -> Option<usize> {
+    let now = Instant::now();
+    queue
+        .iter()
+        .enumerate()
+        .filter(|(_, job)| bulk_allowed || job.priority != Priority::Bulk)
+        .filter(|(_, job)| !tasks_running.values().any(|r| r.dev_id == job.dev_id))
+        .min_by_key(|(_, job)| {
+            let effective = match job.priority {
+                Priority::Bulk if now.duration_since(job.received_at) > BULK_AGING => {
+                    Priority::Interactive
+                }
+                x => x,
+            };
+            (effective, job.id)
+        })
+        .map(|(i, _)| i)
+}
+
+fn discard_dead_requests(queue: &mut Vec<PendingJob>) {
+    let now = Instant::now();
+    let mut i = 0;
+    while i < queue.len() {
+        if queue[i].deadline <= now {
+            let mut job = queue.remove(i);
+            log_discarded(&job, "deadline_queue");
+            tokio::spawn(async move {
+                let response = respond_http_plain_text(503, "Tempo limite excedido na fila");
+                let _ = send_response(&mut job.socket, &response).await;
+            });
+        } else if socket_closed(&queue[i].socket) {
+            let job = queue.remove(i);
+            log_discarded(&job, "client_closed");
+        } else {
+            i += 1;
+        }
+    }
+}
+
+fn log_discarded(job: &PendingJob, reason: &'static str) {
+    crate::LOG.append_log_tag_msg(
+        "WARN",
+        &format!(
+            "Compilação descartada [{}] {} {}: {}",
+            job.id,
+            job.request.kind(),
+            job.dev_id,
+            reason
+        ),
+    );
+    REGISTRY
+        .counter(
+            "compile_discarded_total",
+            DISCARDED_HELP,
+            &[("reason", reason)],
+        )
+        .inc();
+}
+
+// O cliente não manda mais nada depois da requisição, então leitura com 0 bytes significa conexão fechada
+fn socket_closed(socket: &TcpStream) -> bool {
+    let mut buf = [0u8; 1];
+    match socket.peek(&mut buf).now_or_never() {
+        Some(Ok(0)) => true,
+        Some(Err(_)) => true,
+        _ => false,
+    }
+}
+
+fn queue_status(
+    workers: usize,
+    queue: &[PendingJob],
+    tasks_running: &HashMap<usize, RunningJob>,
+) -> QueueStatus {
+    let now = Instant::now();
+    let secs_until = |deadline: Instant| {
+        if deadline > now {
+            (deadline - now).as_secs_f64()
+        } else {
+            -(now - deadline).as_secs_f64()
+        }
+    };
+    let mut running: Vec<JobStatus> = tasks_running
+        .iter()
+        .map(|(id, job)| JobStatus {
+            id: *id,
+            kind: job.kind,
+            dev_id: job.dev_id.clone(),
+            priority: job.priority,
+            age_s: now.duration_since(job.received_at).as_secs_f64(),
+            running_s: Some(now.duration_since(job.started_at).as_secs_f64()),
+            deadline_in_s: secs_until(job.deadline),
+        })
+        .collect();
+    running.sort_by_key(|job| job.id);
+    let pending = queue
+        .iter()
+        .map(|job| JobStatus {
+            id: job.id,
+            kind: job.request.kind(),
+            dev_id: job.dev_id.clone(),
+            priority: job.priority,
+            age_s: now.duration_since(job.received_at).as_secs_f64(),
+            running_s: None,
+            deadline_in_s: secs_until(job.deadline),
+        })
+        .collect();
+    QueueStatus {
+        workers,
+        running,
+        pending,
+    }
+}
+
+fn start_compilation(job: PendingJob, globs: Arc<GlobalVars>) {
+    let PendingJob {
+        id,
+        mut socket,
+        request,
+        dev_id,
+        deadline,
+        ..
+    } = job;
+    std::thread::spawn(move || {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("Error creating tokio runtime");
+        rt.block_on(async move {
+            crate::LOG
+                .append_log_tag_msg("info", &format!("Iniciando compilação [{}] {}", id, dev_id));
+            let kind = request.kind();
+            let ts_start = Instant::now();
+            let time_left = deadline.saturating_duration_since(ts_start);
+            let response =
+                match tokio::time::timeout(time_left, executar_requisicao(request, &globs)).await {
+                    Ok(Ok(v)) => v,
+                    Ok(Err(err)) => respond_http_plain_text(500, &err),
+                    Err(_) => {
+                        crate::LOG.append_log_tag_msg(
+                            "WARN",
+                            &format!("Prazo esgotado na compilação [{}] {}", id, dev_id),
+                        );
+                        REGISTRY
+                            .counter(
+                                "compile_discarded_total",
+                                DISCARDED_HELP,
+                                &[("reason", "deadline_running")],
+                            )
+                            .inc();
+                        respond_http_plain_text(504, "Tempo limite excedido na compilação")
+                    }
+                };
+            REGISTRY
+                .histogram(
+                    "compile_duration_seconds",
+                    "Tempo de execução das compilações",
+                    &[("kind", kind)],
+                    BUCKETS_COMPILATION,
+                )
+                .observe_since(ts_start);
+            if let Err(err) = {
+                send_response(&mut socket, &response).await // socket_write
+            } {
+                crate::LOG.append_log_tag_msg("ERROR[67]", &err.to_string());
+            }
+            crate::LOG.append_log_tag_msg(
+                "info",
+                &format!("Concluindo compilação [{}] {}", id, dev_id),
+            );
+            globs
+                .to_compiler
+                .send(MsgToCompilers::CompilationDone(id))
+                .await
+                .expect("UNEXPECTED-53");
+            tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+        })
+    });
+}
+
 async fn executar_requisicao(
     request: CompilationRequest,
     globs: &Arc<GlobalVars>,
@@ -105,12 +416,13 @@ async fn executar_requisicao(
             dac_hist::process_comp_command_dac_v2(body, globs).await
         }
         CompilationRequest::CompDut(body) => dut_hist::process_comp_command_dut(body, globs).await,
+        CompilationRequest::CompRange(body) => range_hist::process_comp_range(body, globs).await,
         CompilationRequest::CompDam(body) => dam_hist::process_comp_command_dam(body, globs).await,
         CompilationRequest::CompDma(body) => dma_hist::process_comp_command_dma(body, globs).await,
         CompilationRequest::CompDmt(body) => dmt_hist::process_comp_command_dmt(body, globs).await,
         CompilationRequest::CompDal(body) => dal_hist::process_comp_command_dal(body, globs).await,
         CompilationRequest::ExportDevTelemetries(json_body) => {
-            dev_export::export_dev_telemetries(json_body, &globs.configfile.aws_config).await
+            dev_export::export_dev_telemetries(json_body, globs.telemetry_source.as_ref()).await
         }
         CompilationRequest::CompDri(body) => body
             .process_query(globs)
@@ -124,5 +436,117 @@ async fn executar_requisicao(
             .process(globs)
             .await
             .map(|results| respond_http_json_serializable(200, results)),
+        CompilationRequest::EnergyConsumption(body) => body
+            .process(globs)
+            .await
+            .map(|results| respond_http_json_serializable(200, results)),
     }
 }
+
+#[cfg(test)]
+async fn test_job(
+    listener: &tokio::net::TcpListener,
+    id: usize,
+    dev_id: &str,
+    priority: Priority,
+    age: Duration,
+) -> (PendingJob, TcpStream) {
+    let client = TcpStream::connect(listener.local_addr().unwrap())
+        .await
+        .unwrap();
+    let (socket, _) = listener.accept().await.unwrap();
+    let request: dut_hist::ReqParameters = serde_json::from_value(serde_json::json!({
+        "dev_id": dev_id, "interval_length_s": 86400, "ts_ini": "2024-03-01T00:00:00", "i_ts_ini": 0,
+        "i_ts_end": 86400, "ts_end": "2024-03-02T00:00:00", "open_end": false, "avoid_cache": false,
+        "offset_temp": 0.0, "timezone_offset": null,
+    }))
+    .unwrap();
+    let received_at = Instant::now() - age;
+    let job = PendingJob {
+        id,
+        socket,
+        request: CompilationRequest::CompDut(request),
+        dev_id: dev_id.to_owned(),
+        priority,
+        received_at,
+        deadline: received_at + Duration::from_secs(300),
+    };
+    (job, client)
+}
+
+#[test]
+fn test_select_next() {
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    rt.block_on(async {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut queue = Vec::new();
+        let mut clients = Vec::new();
+        for (id, dev_id, priority, age_s) in [
+            (1, "DAC1", Priority::Bulk, 0),
+            (2, "DUT1", Priority::Interactive, 0),
+            (3, "DUT2", Priority::Interactive, 0),
+        ] {
+            let (job, client) =
+                test_job(&listener, id, dev_id, priority, Duration::from_secs(age_s)).await;
+            queue.push(job);
+            clients.push(client);
+        }
+        let mut tasks_running = HashMap::new();
+        let now = Instant::now();
+        tasks_running.insert(
+            10,
+            RunningJob {
+                kind: "dut",
+                dev_id: "DUT1".to_owned(),
+                priority: Priority::Interactive,
+                received_at: now,
+                started_at: now,
+                deadline: now + Duration::from_secs(300),
+            },
+        );
+
+        // Interativa passa na frente da bulk, mas não roda junto com outra do mesmo dev_id
+        assert_eq!(select_next(&queue, &tasks_running, true), Some(2));
+        queue.remove(2);
+        assert_eq!(select_next(&queue, &tasks_running, true), Some(0));
+        assert_eq!(select_next(&queue, &tasks_running, false), None);
+
+        // Bulk esperando há mais de BULK_AGING concorre com as interativas pela ordem de chegada
+        let (job, client) =
+            test_job(&listener, 4, "DUT3", Priority::Interactive, Duration::ZERO).await;
+        queue.push(job);
+        clients.push(client);
+        assert_eq!(select_next(&queue, &tasks_running, true), Some(2));
+        queue[0].received_at = Instant::now() - BULK_AGING - Duration::from_secs(1);
+        assert_eq!(select_next(&queue, &tasks_running, true), Some(0));
+        assert_eq!(select_next(&queue, &tasks_running, false), Some(2));
+    });
+}
+
+#[test]
+fn test_discard_dead_requests() {
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    rt.block_on(async {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let (mut expired, _expired_client) =
+            test_job(&listener, 1, "DUT1", Priority::Interactive, Duration::ZERO).await;
+        expired.deadline = Instant::now() - Duration::from_secs(1);
+        let (closed, closed_client) =
+            test_job(&listener, 2, "DUT2", Priority::Interactive, Duration::ZERO).await;
+        let (alive, _alive_client) =
+            test_job(&listener, 3, "DUT3", Priority::Bulk, Duration::ZERO).await;
+        drop(closed_client);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let mut queue = vec![expired, closed, alive];
+        discard_dead_requests(&mut queue);
+        let ids: Vec<usize> = queue.iter().map(|job| job.id).collect();
+        assert_eq!(ids, vec![3]);
+    });
+}
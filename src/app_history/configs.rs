@@ -1,11 +1,31 @@
-use crate::diel_hist_tables::PrefixAndTable;
+use super::cache_files::PartCacheConfig;
+use crate::diel_hist_tables::{BigQueryHistoryTable, PrefixAndTable};
 use crate::envvars_loader;
 use crate::lib_dynamodb::client::AWSConfig;
+use crate::lib_http::auth::ApiToken;
+use crate::lib_telemetry_source::bigquery::BigQuerySourceConfig;
+
+#[derive(Clone)]
+pub enum TelemetrySourceConfig {
+    DynamoDB(AWSConfig),
+    BigQuery(BigQuerySourceConfig),
+    LocalFiles(String), // Pasta raiz dos arquivos
+}
+
+pub struct CompileQueueConfig {
+    // Quantidade máxima de compilações rodando ao mesmo tempo
+    pub workers: usize,
+    // Tempo máximo (fila + execução) de cada classe de requisição
+    pub deadline_interactive: std::time::Duration,
+    pub deadline_bulk: std::time::Duration,
+}
 
 pub struct ConfigFile {
-    pub aws_config: AWSConfig,
+    pub telemetry_source: TelemetrySourceConfig,
     pub LISTEN_SOCKET_HIST: String,
-    pub EXTERNAL_REQUESTS_TOKEN: Option<String>,
+    pub HTTP_API_TOKENS: Vec<ApiToken>,
+    pub compile_queue: CompileQueueConfig,
+    pub part_cache: PartCacheConfig,
     pub CUSTOM_TABLE_NAMES_DAC: Vec<PrefixAndTable>,
     pub CUSTOM_TABLE_NAMES_DUT: Vec<PrefixAndTable>,
     pub CUSTOM_TABLE_NAMES_DAM: Vec<PrefixAndTable>,
@@ -19,13 +39,20 @@ impl ConfigFile {
     pub fn from_env() -> Result<ConfigFile, String> {
         envvars_loader::load_env_vars();
 
-        let AWS_ACCESS_KEY_ID = envvars_loader::get_var_string_required("AWS_ACCESS_KEY_ID")?;
-        let AWS_SECRET_ACCESS_KEY =
-            envvars_loader::get_var_string_required("AWS_SECRET_ACCESS_KEY")?;
-        let AWS_SESSION_TOKEN = envvars_loader::get_var_string_optional("AWS_SESSION_TOKEN");
+        let HIST_TELEMETRY_SOURCE =
+            envvars_loader::get_var_string_optional("HIST_TELEMETRY_SOURCE");
         let LISTEN_SOCKET_HIST = envvars_loader::get_var_string_required("LISTEN_SOCKET_HIST")?;
         let EXTERNAL_REQUESTS_TOKEN =
             envvars_loader::get_var_string_optional("EXTERNAL_REQUESTS_TOKEN");
+        let HTTP_API_TOKENS: Option<Vec<ApiToken>> =
+            envvars_loader::get_var_structure_optional("HTTP_API_TOKENS")?;
+        let HIST_COMPILER_WORKERS = envvars_loader::get_var_u16_optional("HIST_COMPILER_WORKERS")?;
+        let HIST_DEADLINE_INTERACTIVE_S =
+            envvars_loader::get_var_u16_optional("HIST_DEADLINE_INTERACTIVE_S")?;
+        let HIST_DEADLINE_BULK_S = envvars_loader::get_var_u16_optional("HIST_DEADLINE_BULK_S")?;
+        let HIST_CACHE_MAX_MB = envvars_loader::get_var_u16_optional("HIST_CACHE_MAX_MB")?;
+        let HIST_CACHE_PARTIAL_TTL_S =
+            envvars_loader::get_var_u16_optional("HIST_CACHE_PARTIAL_TTL_S")?;
         let CUSTOM_TABLE_NAMES_DAC =
             envvars_loader::get_var_structure_required("CUSTOM_TABLE_NAMES_DAC")?;
         let CUSTOM_TABLE_NAMES_DUT =
@@ -41,16 +68,88 @@ impl ConfigFile {
         let CUSTOM_TABLE_NAMES_DAL =
             envvars_loader::get_var_structure_required("CUSTOM_TABLE_NAMES_DAL")?;
 
-        let aws_config = AWSConfig {
-            access_key_id: AWS_ACCESS_KEY_ID,
-            secret_access_key: AWS_SECRET_ACCESS_KEY,
-            session_token: AWS_SESSION_TOKEN,
+        let telemetry_source = match HIST_TELEMETRY_SOURCE.as_deref() {
+            None | Some("dynamodb") => {
+                let AWS_ACCESS_KEY_ID =
+                    envvars_loader::get_var_string_required("AWS_ACCESS_KEY_ID")?;
+                let AWS_SECRET_ACCESS_KEY =
+                    envvars_loader::get_var_string_required("AWS_SECRET_ACCESS_KEY")?;
+                let AWS_SESSION_TOKEN =
+                    envvars_loader::get_var_string_optional("AWS_SESSION_TOKEN");
+                TelemetrySourceConfig::DynamoDB(AWSConfig {
+                    access_key_id: AWS_ACCESS_KEY_ID,
+                    secret_access_key: AWS_SECRET_ACCESS_KEY,
+                    session_token: AWS_SESSION_TOKEN,
+                })
+            }
+            Some("bigquery") => {
+                let gcp_sa_key = envvars_loader::get_var_string_required("gcp_sa_key")?;
+                let gcp_project_id = envvars_loader::get_var_string_required("gcp_project_id")?;
+                let gcp_dataset_id = envvars_loader::get_var_string_required("gcp_dataset_id")?;
+                let gcp_default_table_id =
+                    envvars_loader::get_var_string_optional("gcp_default_table_id");
+                let dest_table = match gcp_default_table_id.as_deref() {
+                    None => BigQueryHistoryTable::DevType, // mesmo padrão do broker2db
+                    Some("@dev_type") => BigQueryHistoryTable::DevType,
+                    Some("@dev_gen") => BigQueryHistoryTable::DevGeneration,
+                    Some("@dev_id") => BigQueryHistoryTable::DevId,
+                    Some(table_id) => {
+                        if table_id.starts_with("@") {
+                            return Err(format!("Invalid gcp_default_table_id: {table_id}"));
+                        }
+                        BigQueryHistoryTable::SingleTable(table_id.to_owned())
+                    }
+                };
+                TelemetrySourceConfig::BigQuery(BigQuerySourceConfig {
+                    credentials_file: gcp_sa_key,
+                    project_id: gcp_project_id,
+                    dataset_id: gcp_dataset_id,
+                    dest_table,
+                })
+            }
+            Some("local_files") => {
+                let LOCAL_TELEMETRY_DIR =
+                    envvars_loader::get_var_string_required("LOCAL_TELEMETRY_DIR")?;
+                TelemetrySourceConfig::LocalFiles(LOCAL_TELEMETRY_DIR)
+            }
+            Some(x) => {
+                return Err(format!("Invalid HIST_TELEMETRY_SOURCE: {x}"));
+            }
+        };
+
+        // O token antigo continua valendo, com acesso a todas as rotas externas
+        let mut HTTP_API_TOKENS = HTTP_API_TOKENS.unwrap_or_default();
+        if let Some(token) = EXTERNAL_REQUESTS_TOKEN {
+            HTTP_API_TOKENS.push(ApiToken {
+                name: "EXTERNAL_REQUESTS_TOKEN".to_owned(),
+                token,
+                scopes: vec!["*".to_owned()],
+            });
+        }
+
+        let compile_queue = CompileQueueConfig {
+            workers: usize::from(HIST_COMPILER_WORKERS.unwrap_or(5).max(1)),
+            deadline_interactive: std::time::Duration::from_secs(
+                HIST_DEADLINE_INTERACTIVE_S.unwrap_or(300).into(),
+            ),
+            deadline_bulk: std::time::Duration::from_secs(
+                HIST_DEADLINE_BULK_S.unwrap_or(3600).into(),
+            ),
+        };
+
+        let part_cache = PartCacheConfig {
+            max_bytes: u64::from(HIST_CACHE_MAX_MB.unwrap_or(2048)) * 1024 * 1024,
+            partial_ttl: std::time::Duration::from_secs(
+                HIST_CACHE_PARTIAL_TTL_S.unwrap_or(600).into(),
+            ),
         };
 
         Ok(ConfigFile {
-            aws_config,
+            telemetry_source,
             LISTEN_SOCKET_HIST: LISTEN_SOCKET_HIST,
-            EXTERNAL_REQUESTS_TOKEN: EXTERNAL_REQUESTS_TOKEN,
+            HTTP_API_TOKENS,
+            compile_queue,
+            part_cache,
             CUSTOM_TABLE_NAMES_DAC: CUSTOM_TABLE_NAMES_DAC,
             CUSTOM_TABLE_NAMES_DUT: CUSTOM_TABLE_NAMES_DUT,
             CUSTOM_TABLE_NAMES_DAM: CUSTOM_TABLE_NAMES_DAM,
@@ -1,7 +1,9 @@
-use super::cache_files::build_part_file_name;
+use super::cache_files::PartEntry;
 use crate::compression::compiler_DAC::DACTelemetryCompiler;
+use crate::l1_virtual::dac_l1::dac_l1_calculator::L1Calculator;
 use crate::lib_http::response::{respond_http_json, respond_http_plain_text};
 use crate::lib_http::types::HttpResponse;
+use crate::lib_telemetry_source::source::{run_query, SourceQuery};
 use crate::telemetry_payloads::dac_payload_json::get_raw_telemetry_pack_dac;
 use crate::telemetry_payloads::dac_telemetry::{split_pack, HwInfoDAC, T_sensor_cfg, T_sensors};
 use crate::telemetry_payloads::dac_tsh_tsc::{calculateSubResf, calculateSupAq, FluidInterpData};
@@ -10,37 +12,149 @@ use crate::GlobalVars;
 use chrono::{Duration, NaiveDateTime};
 use serde::{Deserialize, Serialize};
 use std::convert::TryFrom;
-use std::io::Write;
 use std::sync::Arc;
 
+// A consulta vai até um pouco depois do fim do intervalo, para pegar os pacotes que terminam nele
+const QUERY_MARGIN_S: i64 = 60;
+
 pub async fn process_comp_command_dac_v2(
     rpars: ReqParameters,
     globs: &Arc<GlobalVars>,
 ) -> Result<HttpResponse, String> {
+    let compiled = match compile_interval(&rpars, globs, None).await {
+        Ok(Some(v)) => v,
+        Ok(None) => {
+            return Ok(respond_http_json(200, "{}"));
+        }
+        Err(response) => {
+            return Ok(response);
+        }
+    };
+    let hw_cfg = &rpars.hw_cfg;
+    let has_Psuc = hw_cfg.P0Psuc || hw_cfg.P1Psuc;
+    let has_Pliq = hw_cfg.P0Pliq || hw_cfg.P1Pliq;
+    let provision_error = compiled.provision_error;
+    let mut tcomp = compiled.tcomp;
+
+    let period_data = match tcomp.CheckClosePeriod(if rpars.open_end {
+        tcomp.last_index + 1
+    } else {
+        isize::try_from(rpars.interval_length_s).unwrap()
+    }) {
+        Err(err) => {
+            crate::LOG.append_log_tag_msg("ERROR", &format!("{}", err));
+            return Ok(respond_http_plain_text(400, "ERROR[120] CheckClosePeriod"));
+        }
+        Ok(v) => match v {
+            Some(v) => v,
+            None => {
+                return Ok(respond_http_json(200, "{}"));
+            }
+        },
+    };
+
+    let mut data = serde_json::json!({});
+    data["Lcmp"] = period_data.Lcmp.into();
+    data["Tamb"] = period_data.Tamb.into();
+    data["Tsuc"] = period_data.Tsuc.into();
+    data["Tliq"] = period_data.Tliq.into();
+    if has_Psuc {
+        data["Psuc"] = period_data.Psuc.into();
+    };
+    if has_Pliq {
+        data["Pliq"] = period_data.Pliq.into();
+    };
+    if hw_cfg.hasAutomation {
+        data["Levp"] = period_data.Levp.into();
+        data["Lcut"] = period_data.Lcut.into();
+    }
+    if compiled.has_calcs {
+        if has_Psuc {
+            data["Tsh"] = period_data.Tsh.into();
+        };
+        if has_Pliq {
+            data["Tsc"] = period_data.Tsc.into();
+        };
+    }
+    data["State"] = period_data.State.into();
+    data["Mode"] = period_data.Mode.into();
+
+    if let Some(true) = hw_cfg.debug_L1_fancoil {
+        data["L1raw"] = period_data.L1raw.into();
+        data["L1fancoil"] = period_data.L1fancoil.into();
+    }
+
+    data["numDeparts"] = period_data.numDeparts.into();
+    data["hoursOn"] = period_data.hoursOn.into();
+    data["hoursOff"] = period_data.hoursOff.into();
+    data["hoursBlocked"] = period_data.hoursBlocked.into();
+    data["startLcmp"] = serde_json::json!(period_data.startLcmp);
+    data["endLcmp"] = serde_json::json!(period_data.endLcmp);
+    data["provision_error"] = provision_error.into();
+    data["SavedData"] = period_data.savedData.into();
+    data["first_saved_data_index"] = period_data.first_saved_data_index.into();
+    // Um vetor "0/1" por falha avaliada para a configuração do DAC
+    data["faults"] = serde_json::json!(period_data.faults);
+
+    return Ok(respond_http_json(200, &data.to_string()));
+}
+
+// Intervalo compilado, com os vetores ainda abertos
+pub struct IntervalCompilation {
+    pub tcomp: DACTelemetryCompiler,
+    pub has_calcs: bool,
+    pub provision_error: bool,
+    // Estado do L1 no fim do intervalo, para continuar a compilação no intervalo seguinte
+    pub l1_state: Option<L1Calculator>,
+}
+
+// Retorna None quando não tem tabela para o dispositivo.
+// Se for informado o estado do L1 do intervalo anterior, não precisa processar os 15 minutos de preparação.
+// O intervalo anterior tem que terminar onde este começa.
+// Com um cache parcial a consulta continua depois do último pacote compilado, a partir do estado do L1 salvo.
+pub async fn compile_interval(
+    rpars: &ReqParameters,
+    globs: &Arc<GlobalVars>,
+    l1_state: Option<L1Calculator>,
+) -> Result<Option<IntervalCompilation>, HttpResponse> {
     let rpars_serialized = serde_json::to_string(&rpars).unwrap();
-    let hw_cfg = rpars.hw_cfg;
-    let dev_id = rpars.dev_id;
+    let hw_cfg = &rpars.hw_cfg;
+    let dev_id = &rpars.dev_id;
     let interval_length_s = rpars.interval_length_s;
-    let ts_ini = rpars.ts_ini;
     let timezone_offset = rpars.timezone_offset;
-    let timezone_offset_string = timezone_offset
-        .map(|v| v.to_string())
-        .unwrap_or("None".to_string());
     // Processa 15 minutos antes do período para preparar o estado do L1.
     // Não atualizamos i_ts_ini pois é usado para identificar os limites do gráfico
     // e não queremos que esses 15min vão para o gráfico.
     let ts_ini = {
-        let ts = NaiveDateTime::parse_from_str(&ts_ini, "%Y-%m-%dT%H:%M:%S")
-            .map_err(|e| e.to_string())?;
+        let ts = NaiveDateTime::parse_from_str(&rpars.ts_ini, "%Y-%m-%dT%H:%M:%S")
+            .map_err(|e| respond_http_plain_text(400, &e.to_string()))?;
 
         let ts = ts - chrono::Duration::minutes(15);
         ts.format("%Y-%m-%dT%H:%M:%S").to_string()
     };
+    // O nome do arquivo de cache usa o início com os 15 minutos, mesmo quando o estado do L1 vem de fora
+    let part = PartEntry::new(
+        dev_id,
+        &ts_ini,
+        timezone_offset,
+        rpars.i_ts_end,
+        &rpars_serialized,
+    );
+    let ts_ini = if l1_state.is_some() {
+        rpars.ts_ini.clone()
+    } else {
+        ts_ini
+    };
+    // O estado do L1 do intervalo anterior já inclui os pacotes lidos na consulta dele
+    let prev_query_end = if l1_state.is_some() {
+        rpars.i_ts_ini + QUERY_MARGIN_S
+    } else {
+        i64::MIN
+    };
 
     let i_ts_ini = rpars.i_ts_ini;
     let i_ts_end = rpars.i_ts_end;
-    let ts_end = rpars.ts_end;
-    let open_end = rpars.open_end;
+    let ts_end = &rpars.ts_end;
 
     let has_Psuc = hw_cfg.P0Psuc || hw_cfg.P1Psuc;
     let has_Pliq = hw_cfg.P0Pliq || hw_cfg.P1Pliq;
@@ -54,32 +168,45 @@ pub async fn process_comp_command_dac_v2(
 
     // Create accumulators
     // Verificar na pasta se tem query pronta para o dia selecionado.
-    let part_file_name = build_part_file_name(&dev_id, &ts_ini, &timezone_offset_string);
-    let accs: Accumulators = {
-        let r = if rpars.avoid_cache {
-            Err(String::from("Cache desabilitado"))
-        } else {
-            Ok(())
-        }
-        .and_then(|_| load_partial_query(&part_file_name, &rpars_serialized));
-        match r {
-            Ok(v) => v,
-            Err(_err) => {
-                let page_ts_ini = ts_ini.clone();
-                let tcomp = DACTelemetryCompiler::new(rpars.interval_length_s, &hw_cfg);
-                Accumulators {
-                    rpars: None,
-                    page_ts_ini,
-                    tcomp,
-                    timezone_offset,
-                }
-            }
+    let cached = if rpars.avoid_cache {
+        None
+    } else {
+        globs.part_cache.load::<Accumulators>(&part)
+    };
+    let (accs, complete) = match cached {
+        Some(v) => (v.data, v.complete),
+        None => {
+            let page_ts_ini = ts_ini.clone();
+            let tcomp = DACTelemetryCompiler::new(rpars.interval_length_s, hw_cfg);
+            let accs = Accumulators {
+                rpars: None,
+                page_ts_ini,
+                tcomp,
+                timezone_offset,
+                l1_state: None,
+            };
+            (accs, false)
         }
     };
+
+    let has_calcs = (has_Psuc || has_Pliq)
+        && hw_cfg
+            .fluid
+            .as_ref()
+            .is_some_and(|fluid| FluidInterpData::for_fluid(fluid).is_some());
+
+    // Dia já fechado e salvo inteiro no cache: não precisa consultar de novo
+    if complete {
+        return Ok(Some(IntervalCompilation {
+            tcomp: accs.tcomp,
+            has_calcs,
+            provision_error: false,
+            l1_state: accs.l1_state,
+        }));
+    }
+
     let mut page_ts_ini = accs.page_ts_ini;
-    // let mut fchk = accs.fchk;
     let mut tcomp = accs.tcomp;
-    // let mut fcomp = accs.fcomp;
 
     let mut fluid_info = match &hw_cfg.fluid {
         None => None,
@@ -111,29 +238,43 @@ pub async fn process_comp_command_dac_v2(
 
     if table_name.len() == 0 {
         crate::LOG.append_log_tag_msg("WARN", &format!("Unknown DAC generation: {}", dev_id));
-        return Ok(respond_http_json(200, "{}"));
+        return Ok(None);
     }
-    let mut dac_state = crate::l1_virtual::dac_l1::dac_l1_calculator::create_l1_calculator(&hw_cfg);
-
-    let querier = if table_name == "DAC20719XXXX_RAW" {
-        crate::lib_dynamodb::query::QuerierDevIdTimestamp::new_custom(
-            table_name,
-            "dac_id".to_owned(),
-            "timestamp".to_owned(),
-            dev_id.clone(),
-            &globs.configfile.aws_config,
-        )
+    // Cache sem o estado do L1 (salvo por versões antigas): refaz a consulta, os pontos já compilados são ignorados
+    let (resume_after, mut dac_state) = match accs.l1_state {
+        Some(cached_state) => (Some(page_ts_ini.clone()), cached_state),
+        None => (
+            None,
+            l1_state.unwrap_or_else(|| {
+                crate::l1_virtual::dac_l1::dac_l1_calculator::create_l1_calculator(hw_cfg)
+            }),
+        ),
+    };
+    let query_ts_ini = resume_after.clone().unwrap_or(ts_ini);
+
+    let query = if table_name == "DAC20719XXXX_RAW" {
+        SourceQuery::new_custom(table_name, "dac_id".to_owned(), dev_id.clone())
     } else {
-        crate::lib_dynamodb::query::QuerierDevIdTimestamp::new_diel_dev(
-            table_name,
-            dev_id.clone(),
-            &globs.configfile.aws_config,
-        )
+        SourceQuery::new_diel_dev(table_name, dev_id.clone())
     };
     let mut found_invalid_payload = false;
-    let result = querier
-        .run(&ts_ini, &ts_end, &mut |items| {
+    let result = run_query(
+        globs.telemetry_source.as_ref(),
+        &query,
+        &query_ts_ini,
+        ts_end,
+        &mut |items: Vec<serde_json::Value>| {
             for item in items {
+                let item_ts = item["timestamp"].as_str().unwrap_or_default();
+                if resume_after
+                    .as_deref()
+                    .is_some_and(|after| item_ts <= after)
+                {
+                    continue;
+                }
+                if item_ts > page_ts_ini.as_str() {
+                    page_ts_ini = item_ts.to_owned();
+                }
                 let payload = match get_raw_telemetry_pack_dac(&item) {
                     Ok(v) => v,
                     Err(err) => {
@@ -152,7 +293,8 @@ pub async fn process_comp_command_dac_v2(
                     &payload,
                     i_ts_ini,
                     i_ts_end,
-                    &hw_cfg,
+                    prev_query_end,
+                    hw_cfg,
                     &mut dac_state,
                     &mut |telemetry, L1, L1fancoil, index| {
                         if let Some(calcs) = &mut calcs {
@@ -175,7 +317,14 @@ pub async fn process_comp_command_dac_v2(
                                 };
                             };
                         }
-                        tcomp.AdcPontos(telemetry, index, &calcs, L1, L1fancoil, payload.samplingTime);
+                        tcomp.AdcPontos(
+                            telemetry,
+                            index,
+                            &calcs,
+                            L1,
+                            L1fancoil,
+                            payload.samplingTime,
+                        );
                     },
                 );
                 match result {
@@ -193,8 +342,9 @@ pub async fn process_comp_command_dac_v2(
                 };
             }
             return Ok(());
-        })
-        .await;
+        },
+    )
+    .await;
 
     let mut provision_error = false;
     if let Err(err) = result {
@@ -202,94 +352,29 @@ pub async fn process_comp_command_dac_v2(
             provision_error = true;
         } else if err.starts_with("ResourceNotFound:") {
             crate::LOG.append_log_tag_msg("WARN", &format!("Table not found for: {}", dev_id));
-            return Ok(respond_http_json(200, "{}"));
+            return Ok(None);
         } else {
-            return Ok(respond_http_plain_text(400, &format!("ERROR[78] {}", err)));
+            return Err(respond_http_plain_text(400, &format!("ERROR[78] {}", err)));
         }
     }
 
-    let mut accs = Accumulators {
+    let accs = Accumulators {
         rpars: Some(serde_json::from_str(&rpars_serialized).unwrap()),
         page_ts_ini,
         tcomp,
         timezone_offset,
+        l1_state: Some(dac_state),
     };
-    if (!rpars.avoid_cache) && accs.rpars.is_some() && (interval_length_s > 3000) {
-        let serialized = serde_json::to_string(&accs).unwrap();
-        match std::fs::File::create(&part_file_name) {
-            Err(err) => {
-                crate::LOG.append_log_tag_msg("ERROR", &format!("create failed: {}", err));
-            }
-            Ok(mut file) => {
-                match file.write_all(serialized.as_bytes()) {
-                    Err(err) => {
-                        crate::LOG.append_log_tag_msg("ERROR", &format!("write failed: {}", err));
-                    }
-                    Ok(_) => {}
-                };
-            }
-        };
+    if (!rpars.avoid_cache) && (interval_length_s > 3000) && !provision_error {
+        globs.part_cache.save(&part, &accs);
     }
 
-    let period_data = match accs.tcomp.CheckClosePeriod(if open_end {
-        accs.tcomp.last_index + 1
-    } else {
-        isize::try_from(interval_length_s).unwrap()
-    }) {
-        Err(err) => {
-            crate::LOG.append_log_tag_msg("ERROR", &format!("{}", err));
-            return Ok(respond_http_plain_text(400, "ERROR[120] CheckClosePeriod"));
-        }
-        Ok(v) => match v {
-            Some(v) => v,
-            None => {
-                return Ok(respond_http_json(200, "{}"));
-            }
-        },
-    };
-
-    let mut data = serde_json::json!({});
-    data["Lcmp"] = period_data.Lcmp.into();
-    data["Tamb"] = period_data.Tamb.into();
-    data["Tsuc"] = period_data.Tsuc.into();
-    data["Tliq"] = period_data.Tliq.into();
-    if has_Psuc {
-        data["Psuc"] = period_data.Psuc.into();
-    };
-    if has_Pliq {
-        data["Pliq"] = period_data.Pliq.into();
-    };
-    if hw_cfg.hasAutomation {
-        data["Levp"] = period_data.Levp.into();
-        data["Lcut"] = period_data.Lcut.into();
-    }
-    if calcs.is_some() {
-        if has_Psuc {
-            data["Tsh"] = period_data.Tsh.into();
-        };
-        if has_Pliq {
-            data["Tsc"] = period_data.Tsc.into();
-        };
-    }
-    data["State"] = period_data.State.into();
-    data["Mode"] = period_data.Mode.into();
-
-    if let Some(true) = hw_cfg.debug_L1_fancoil {
-        data["L1raw"] = period_data.L1raw.into();
-        data["L1fancoil"] = period_data.L1fancoil.into();
-    }
-
-    data["numDeparts"] = period_data.numDeparts.into();
-    data["hoursOn"] = period_data.hoursOn.into();
-    data["hoursOff"] = period_data.hoursOff.into();
-    data["hoursBlocked"] = period_data.hoursBlocked.into();
-    data["startLcmp"] = serde_json::json!(period_data.startLcmp);
-    data["endLcmp"] = serde_json::json!(period_data.endLcmp);
-    data["provision_error"] = provision_error.into();
-    data["SavedData"] = period_data.savedData.into();
-    data["first_saved_data_index"] = period_data.first_saved_data_index.into();
-
-    return Ok(respond_http_json(200, &data.to_string()));
+    Ok(Some(IntervalCompilation {
+        tcomp: accs.tcomp,
+        has_calcs: calcs.is_some(),
+        provision_error,
+        l1_state: accs.l1_state,
+    }))
 }
 
 pub fn parse_parameters(parsed: &serde_json::Value) -> Result<ReqParameters, HttpResponse> {
@@ -345,7 +430,7 @@ pub fn parse_parameters(parsed: &serde_json::Value) -> Result<ReqParameters, Htt
 
     let i_ts_end = i_ts_ini + interval_length_s;
 
-    let ts_end = NaiveDateTime::from_timestamp(i_ts_end + 60, 0)
+    let ts_end = NaiveDateTime::from_timestamp(i_ts_end + QUERY_MARGIN_S, 0)
         .format("%Y-%m-%dT%H:%M:%S")
         .to_string();
 
@@ -477,24 +562,6 @@ pub fn parse_parameters(parsed: &serde_json::Value) -> Result<ReqParameters, Htt
     });
 }
 
-fn load_partial_query(path: &str, rpars_serialized: &str) -> Result<Accumulators, String> {
-    let serialized = match std::fs::read_to_string(path) {
-        Ok(v) => v,
-        Err(err) => return Err(format!("{}", err)),
-    };
-    // let serialized = serde_json::to_string(&(&page_ts_ini, &fchk, &tcomp, &fcomp)).unwrap();
-    let accs = match serde_json::from_str::<Accumulators>(&serialized) {
-        Err(err) => return Err(format!("{}", err)),
-        Ok(v) => v,
-    };
-    let rpars_check = serde_json::to_string(&accs.rpars).unwrap();
-    if rpars_check == rpars_serialized {
-        return Ok(accs);
-    } else {
-        return Err("Query is not the same".to_string());
-    }
-}
-
 // #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
 // pub enum RequiredVarsDAC {
 //   L1only,
@@ -530,9 +597,127 @@ pub struct ReqParameters {
 #[derive(Serialize, Deserialize)]
 struct Accumulators {
     pub rpars: Option<ReqParameters>,
+    // Timestamp do último pacote compilado (início da consulta enquanto nenhum foi lido)
     pub page_ts_ini: String,
-    // pub fchk: DACFaultsChecker,
     pub tcomp: DACTelemetryCompiler,
-    // pub fcomp: DACFaultsCompiler,
     pub timezone_offset: Option<i64>,
+    #[serde(default)]
+    pub l1_state: Option<L1Calculator>,
+}
+
+#[test]
+fn test_compile_interval_partial_cache() {
+    use super::cache_files::{test_dir, PartCache, PartCacheConfig};
+    use super::configs::{CompileQueueConfig, ConfigFile, TelemetrySourceConfig};
+    use crate::lib_telemetry_source::local_files::LocalFilesSource;
+
+    let dir = test_dir("dac-hist-partial-cache");
+    let root_dir = dir.join("telemetry");
+    let dev_dir = root_dir.join("DAC21019XXXX_RAW").join("DAC210191234");
+    std::fs::create_dir_all(&dev_dir).unwrap();
+    let configfile = ConfigFile {
+        telemetry_source: TelemetrySourceConfig::LocalFiles(root_dir.to_str().unwrap().to_owned()),
+        LISTEN_SOCKET_HIST: "127.0.0.1:0".to_owned(),
+        HTTP_API_TOKENS: Vec::new(),
+        compile_queue: CompileQueueConfig {
+            workers: 1,
+            deadline_interactive: std::time::Duration::from_secs(60),
+            deadline_bulk: std::time::Duration::from_secs(60),
+        },
+        part_cache: PartCacheConfig {
+            max_bytes: 10 * 1024 * 1024,
+            partial_ttl: std::time::Duration::from_secs(3600),
+        },
+        CUSTOM_TABLE_NAMES_DAC: Vec::new(),
+        CUSTOM_TABLE_NAMES_DUT: Vec::new(),
+        CUSTOM_TABLE_NAMES_DAM: Vec::new(),
+        CUSTOM_TABLE_NAMES_DRI: Vec::new(),
+        CUSTOM_TABLE_NAMES_DMA: Vec::new(),
+        CUSTOM_TABLE_NAMES_DMT: Vec::new(),
+        CUSTOM_TABLE_NAMES_DAL: Vec::new(),
+    };
+    std::fs::create_dir_all(dir.join("parts")).unwrap();
+    let part_cache = PartCache::open(configfile.part_cache.clone(), dir.join("parts"));
+    let globs = Arc::new(GlobalVars {
+        configfile,
+        to_compiler: tokio::sync::mpsc::channel(1).0,
+        telemetry_source: Box::new(LocalFilesSource {
+            root_dir: root_dir.to_str().unwrap().to_owned(),
+        }),
+        part_cache,
+    });
+
+    // Dia seguinte, para o cache salvo ser parcial
+    let day = chrono::Utc::now().naive_utc().date() + Duration::days(1);
+    let day_start = day.and_hms_opt(0, 0, 0).unwrap();
+    let rpars = |avoid_cache: bool| {
+        parse_parameters(&serde_json::json!({
+            "dev_id": "DAC210191234", "interval_length_s": 86400,
+            "ts_ini": day_start.format("%Y-%m-%dT%H:%M:%S").to_string(),
+            "isVrf": false, "hasAutomation": false, "virtualL1": true,
+            "P0Psuc": false, "P1Psuc": false, "P0Pliq": false, "P1Pliq": false,
+            "T0_T1_T2": ["Tamb", "Tsuc", "Tliq"], "avoid_cache": avoid_cache,
+        }))
+        .ok()
+        .unwrap()
+    };
+    // Pacotes de 30 minutos desde antes da meia-noite, alternando a Tsuc a cada hora
+    let packs: Vec<(NaiveDateTime, serde_json::Value)> = (0..16)
+        .map(|k| {
+            let ts = day_start + Duration::minutes(30 * k) - Duration::seconds(15);
+            let t_suc = if (k / 2) % 2 == 0 { 5.0 } else { 25.0 };
+            let pack = serde_json::json!({
+                "dev_id": "DAC210191234",
+                "timestamp": ts.format("%Y-%m-%dT%H:%M:%S").to_string(), "samplingTime": 15,
+                "L1": vec![1; 120], "T0": vec![30.0; 120], "T1": vec![t_suc; 120], "T2": vec![40.0; 120],
+                "P0": vec![serde_json::Value::Null; 120], "P1": vec![serde_json::Value::Null; 120],
+            });
+            (ts, pack)
+        })
+        .collect();
+    let write_packs = |packs: &[(NaiveDateTime, serde_json::Value)]| {
+        std::fs::remove_dir_all(&dev_dir).unwrap();
+        std::fs::create_dir_all(&dev_dir).unwrap();
+        let mut files: std::collections::BTreeMap<String, String> = Default::default();
+        for (ts, pack) in packs {
+            let file = files
+                .entry(ts.format("%Y-%m-%d.jsonl").to_string())
+                .or_default();
+            file.push_str(&pack.to_string());
+            file.push('\n');
+        }
+        for (name, lines) in files {
+            std::fs::write(dev_dir.join(name), lines).unwrap();
+        }
+    };
+    let compile = |avoid_cache: bool| {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let compiled = rt
+            .block_on(compile_interval(&rpars(avoid_cache), &globs, None))
+            .ok()
+            .unwrap()
+            .unwrap();
+        (
+            serde_json::to_value(&compiled.tcomp).unwrap(),
+            serde_json::to_value(&compiled.l1_state).unwrap(),
+        )
+    };
+
+    // Primeira consulta com parte do dia
+    write_packs(&packs[..7]);
+    let partial = compile(false);
+    write_packs(&packs);
+    let full = compile(true);
+    assert_ne!(partial.0, full.0);
+
+    // Só os pacotes novos ficam na origem: o início do dia e o estado do L1 têm que vir do cache
+    write_packs(&packs[7..]);
+    let resumed = compile(false);
+    assert_eq!(resumed.0, full.0);
+    assert_eq!(resumed.1, full.1);
+
+    std::fs::remove_dir_all(&dir).unwrap();
 }
@@ -1,14 +1,14 @@
-use super::cache_files::build_part_file_name;
+use super::cache_files::PartEntry;
 use crate::compression::compiler_DAM::DAMTelemetryCompiler;
 use crate::lib_http::response::{respond_http_json, respond_http_plain_text};
 use crate::lib_http::types::HttpResponse;
+use crate::lib_telemetry_source::source::{run_query, SourceQuery};
 use crate::telemetry_payloads::dam_payload_json::get_raw_telemetry_pack_dam;
 use crate::telemetry_payloads::dam_telemetry::split_pack;
 use crate::GlobalVars;
 use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
 use std::convert::TryFrom;
-use std::io::Write;
 use std::sync::Arc;
 
 pub async fn process_comp_command_dam(
@@ -24,22 +24,24 @@ pub async fn process_comp_command_dam(
     let ts_end = rpars.ts_end;
     let open_end = rpars.open_end;
     let timezone_offset = rpars.timezone_offset;
-    let timezone_offset_string = timezone_offset
-        .map(|v| v.to_string())
-        .unwrap_or("None".to_string());
     // Create accumulators
     // Verificar na pasta se tem query pronta para o dia selecionado.
-    let part_file_name = build_part_file_name(&dev_id, &ts_ini, &timezone_offset_string);
+    let part = PartEntry::new(
+        &dev_id,
+        &ts_ini,
+        timezone_offset,
+        i_ts_end,
+        &rpars_serialized,
+    );
     let accs: Accumulators = {
         let r = if rpars.avoid_cache {
-            Err(String::from("Cache desabilitado"))
+            None
         } else {
-            Ok(())
-        }
-        .and_then(|_| load_partial_query(&part_file_name, &rpars_serialized));
+            globs.part_cache.load::<Accumulators>(&part)
+        };
         match r {
-            Ok(v) => v,
-            Err(_err) => {
+            Some(v) => v.data,
+            None => {
                 let page_ts_ini = ts_ini.clone();
                 let tcomp = DAMTelemetryCompiler::new();
                 Accumulators {
@@ -74,14 +76,14 @@ pub async fn process_comp_command_dam(
         return Ok(respond_http_json(200, "{}"));
     }
 
-    let querier = crate::lib_dynamodb::query::QuerierDevIdTimestamp::new_diel_dev(
-        table_name,
-        dev_id.clone(),
-        &globs.configfile.aws_config,
-    );
+    let query = SourceQuery::new_diel_dev(table_name, dev_id.clone());
     let mut found_invalid_payload = false;
-    let result = querier
-        .run(&ts_ini, &ts_end, &mut |items| {
+    let result = run_query(
+        globs.telemetry_source.as_ref(),
+        &query,
+        &ts_ini,
+        &ts_end,
+        &mut |items| {
             for item in items {
                 let payload = match get_raw_telemetry_pack_dam(&item) {
                     Ok(v) => v,
@@ -116,8 +118,9 @@ pub async fn process_comp_command_dam(
                 };
             }
             return Ok(());
-        })
-        .await;
+        },
+    )
+    .await;
 
     let mut provision_error = false;
     if let Err(err) = result {
@@ -138,20 +141,7 @@ pub async fn process_comp_command_dam(
         timezone_offset,
     };
     if (!rpars.avoid_cache) && accs.rpars.is_some() && (interval_length_s > 3000) {
-        let serialized = serde_json::to_string(&accs).unwrap();
-        match std::fs::File::create(&part_file_name) {
-            Err(err) => {
-                crate::LOG.append_log_tag_msg("ERROR", &format!("create failed: {}", err));
-            }
-            Ok(mut file) => {
-                match file.write_all(serialized.as_bytes()) {
-                    Err(err) => {
-                        crate::LOG.append_log_tag_msg("ERROR", &format!("write failed: {}", err));
-                    }
-                    Ok(_) => {}
-                };
-            }
-        };
+        globs.part_cache.save(&part, &accs);
     }
 
     // return this.CheckClosePeriod(index ? (index + 1) : interval_length_s);
@@ -262,24 +252,6 @@ pub fn parse_parameters(parsed: &serde_json::Value) -> Result<ReqParameters, Htt
     });
 }
 
-fn load_partial_query(path: &str, rpars_serialized: &str) -> Result<Accumulators, String> {
-    let serialized = match std::fs::read_to_string(path) {
-        Ok(v) => v,
-        Err(err) => return Err(format!("{}", err)),
-    };
-    // let serialized = serde_json::to_string(&(&page_ts_ini, &fchk, &tcomp, &fcomp)).unwrap();
-    let accs = match serde_json::from_str::<Accumulators>(&serialized) {
-        Err(err) => return Err(format!("{}", err)),
-        Ok(v) => v,
-    };
-    let rpars_check = serde_json::to_string(&accs.rpars).unwrap();
-    if rpars_check == rpars_serialized {
-        return Ok(accs);
-    } else {
-        return Err("Query is not the same".to_string());
-    }
-}
-
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ReqParameters {
     pub dev_id: String,
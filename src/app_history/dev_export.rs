@@ -1,30 +1,29 @@
-use crate::lib_dynamodb::client::AWSConfig;
 use crate::lib_http::response::respond_http_plain_text;
 use crate::lib_http::types::HttpResponse;
+use crate::lib_telemetry_source::source::{run_query, SourceQuery, TelemetrySource};
 use serde::{Deserialize, Serialize};
 
 pub async fn export_dev_telemetries(
     rpars: ReqParameters,
-    aws_config: &AWSConfig,
+    telemetry_source: &dyn TelemetrySource,
 ) -> Result<HttpResponse, String> {
-    let querier = crate::lib_dynamodb::query::QuerierDevIdTimestamp::new_diel_dev(
-        rpars.table_name.to_owned(),
-        rpars.dev_id.to_owned(),
-        aws_config,
-    );
+    let query = SourceQuery::new_diel_dev(rpars.table_name.to_owned(), rpars.dev_id.to_owned());
 
     let mut output = String::with_capacity(1_000_000);
-    let result = querier
-        .run(&rpars.ts_ini, &rpars.ts_end, &mut |items: Vec<
-            serde_json::Value,
-        >| {
+    let result = run_query(
+        telemetry_source,
+        &query,
+        &rpars.ts_ini,
+        &rpars.ts_end,
+        &mut |items: Vec<serde_json::Value>| {
             for item in items {
                 output.push_str(&item.to_string());
                 output.push_str("\n");
             }
             return Ok(());
-        })
-        .await;
+        },
+    )
+    .await;
 
     if let Err(err) = result {
         return Ok(respond_http_plain_text(400, &format!("ERROR[24] {}", err)));
@@ -1,14 +1,14 @@
-use super::cache_files::build_part_file_name;
+use super::cache_files::PartEntry;
 use crate::compression::compiler_DMA::DMATelemetryCompiler;
 use crate::lib_http::response::{respond_http_json, respond_http_plain_text};
 use crate::lib_http::types::HttpResponse;
+use crate::lib_telemetry_source::source::{run_query, SourceQuery};
 use crate::telemetry_payloads::dma_payload_json::get_raw_telemetry_pack_dma;
 use crate::telemetry_payloads::dma_telemetry::split_pack;
 use crate::GlobalVars;
 use chrono::{Duration, NaiveDateTime};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::io::Write;
 use std::sync::Arc;
 
 pub async fn process_comp_command_dma(
@@ -24,23 +24,25 @@ pub async fn process_comp_command_dma(
     let i_ts_end = rpars.i_ts_end; // end timestamp (string)
     let timezone_offset = rpars.timezone_offset; // timezone filtered
     let open_end = rpars.open_end;
-    let timezone_offset_string = timezone_offset
-        .map(|v| v.to_string())
-        .unwrap_or("None".to_string());
 
     // Create accumulators
     // Verificar na pasta se tem query pronta para o dia selecionado.
-    let part_file_name = build_part_file_name(&dev_id, &ts_ini, &timezone_offset_string);
+    let part = PartEntry::new(
+        &dev_id,
+        &ts_ini,
+        timezone_offset,
+        i_ts_end,
+        &rpars_serialized,
+    );
     let accs: DmaData = {
         let r = if rpars.avoid_cache {
-            Err(String::from("Cache desabilitado"))
+            None
         } else {
-            Ok(())
-        }
-        .and_then(|_| load_partial_query(&part_file_name, &rpars_serialized));
+            globs.part_cache.load::<DmaData>(&part)
+        };
         match r {
-            Ok(v) => v,
-            Err(_err) => {
+            Some(v) => v.data,
+            None => {
                 let page_ts_ini = ts_ini.clone();
                 let tcomp = DMATelemetryCompiler::new(rpars.interval_length_s);
                 DmaData {
@@ -76,11 +78,7 @@ pub async fn process_comp_command_dma(
         return Ok(respond_http_json(200, "{}"));
     }
 
-    let querier = crate::lib_dynamodb::query::QuerierDevIdTimestamp::new_diel_dev(
-        table_name,
-        dev_id.clone(),
-        &globs.configfile.aws_config,
-    );
+    let query = SourceQuery::new_diel_dev(table_name, dev_id.clone());
 
     let mut found_invalid_payload = false;
     let mut is_first_of_the_day: bool = true;
@@ -92,8 +90,12 @@ pub async fn process_comp_command_dma(
     let mut last_number_of_pulses: Option<i32> = None;
     let mut pulsesPerHour: HashMap<String, i32> = HashMap::new();
     let mut lastTelemetryTime: String = "".to_string();
-    let result = querier
-        .run(&ts_ini, &ts_end, &mut |items| {
+    let result = run_query(
+        globs.telemetry_source.as_ref(),
+        &query,
+        &ts_ini,
+        &ts_end,
+        &mut |items| {
             for i in 1..items.len() {
                 let mut payload = match get_raw_telemetry_pack_dma(&items[i]) {
                     Ok(v) => v,
@@ -156,8 +158,9 @@ pub async fn process_comp_command_dma(
                 last_number_of_pulses = Some(payload_pulses);
             }
             return Ok(());
-        })
-        .await;
+        },
+    )
+    .await;
 
     let mut provision_error = false;
     if let Err(err) = result {
@@ -197,17 +200,7 @@ pub async fn process_comp_command_dma(
     };
 
     if (!rpars.avoid_cache && accs.rpars.is_some() && (interval_length_s > 3000)) {
-        let serialized = serde_json::to_string(&dma_query_data).unwrap();
-        match std::fs::File::create(&part_file_name) {
-            Err(err) => {
-                crate::LOG.append_log_tag_msg("ERROR", &format!("create failed: {}", err));
-            }
-            Ok(mut file) => {
-                if let Err(err) = file.write_all(serialized.as_bytes()) {
-                    crate::LOG.append_log_tag_msg("ERROR", &format!("write failed: {}", err));
-                }
-            }
-        };
+        globs.part_cache.save(&part, &dma_query_data);
     }
 
     let period_data = match dma_query_data.tcomp.CheckClosePeriod(if open_end {
@@ -301,24 +294,6 @@ pub fn parse_parameters(parsed: &serde_json::Value) -> Result<ReqParameters, Htt
     });
 }
 
-fn load_partial_query(path: &str, rpars_serialized: &str) -> Result<DmaData, String> {
-    let serialized = match std::fs::read_to_string(path) {
-        Ok(v) => v,
-        Err(err) => return Err(format!("{}", err)),
-    };
-    // let serialized = serde_json::to_string(&(&page_ts_ini, &fchk, &tcomp, &fcomp)).unwrap();
-    let r = match serde_json::from_str::<DmaData>(&serialized) {
-        Err(err) => return Err(format!("{}", err)),
-        Ok(v) => v,
-    };
-    let rpars_check = serde_json::to_string(&r.rpars).unwrap();
-    if rpars_check == rpars_serialized {
-        return Ok(r);
-    } else {
-        return Err("Query is not the same".to_string());
-    }
-}
-
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct ReqParameters {
     pub dev_id: String,
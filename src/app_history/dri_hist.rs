@@ -2,20 +2,16 @@ use crate::compression::compiler_DRI::{
     DRICCNCompiledPeriod, DRICCNTelemetryCompiler, DRIVAVandFancoilCompiledPeriod,
     DRIVAVandFancoilTelemetryCompiler,
 };
+use crate::lib_telemetry_source::source::{run_query, SourceQuery};
 use crate::telemetry_payloads::dri::ccn::{split_pack_ccn, DriCCNTelemetry};
-use crate::telemetry_payloads::dri::chiller_carrier_hx::{
-    DriChillerCarrierHXTelemetry, TelemetryDriChillerCarrierHX,
-};
-use crate::telemetry_payloads::dri::chiller_carrier_xa::{
-    DriChillerCarrierXATelemetry, TelemetryDriChillerCarrierXA,
-};
-use crate::telemetry_payloads::dri::chiller_carrier_xa_hvar::{
-    DriChillerCarrierXAHvarTelemetry, TelemetryDriChillerCarrierXAHvar,
+use crate::telemetry_payloads::dri::generic::{
+    compile_hist, decode_payload, DriProfileCompiledPeriod, DriProfileTelemetry,
 };
+use crate::telemetry_payloads::dri::profile::{self, DriProfile};
 use crate::telemetry_payloads::dri::vav_fancoil::{
     split_pack_vav_and_fancoil, DriVAVandFancoilTelemetry,
 };
-use crate::telemetry_payloads::dri_telemetry::{ChillerParametersChangesHist, TelemetryDri};
+use crate::telemetry_payloads::dri_telemetry::TelemetryDri;
 use crate::GlobalVars;
 use chrono::{NaiveDate, NaiveDateTime};
 use serde::{Deserialize, Serialize};
@@ -51,19 +47,13 @@ impl DriHistParams {
                 .process_vav_and_fancoil_query(globs)
                 .await?
                 .map(DriCompiledPeriod::DRIVAVandFancoilCompiledPeriod),
-            "CHILLER_CARRIER_HX" => self
-                .process_chiller_carrier_hx_query(globs)
-                .await?
-                .map(DriCompiledPeriod::DRIChillerCarrierHXCompiledPeriod),
-            "CHILLER_CARRIER_XA" => self
-                .process_chiller_carrier_xa_query(globs)
-                .await?
-                .map(DriCompiledPeriod::DRIChillerCarrierXACompiledPeriod),
-            "CHILLER_CARRIER_XA_HVAR" => self
-                .process_chiller_carrier_xa_hvar_query(globs)
-                .await?
-                .map(DriCompiledPeriod::DRIChillerCarrierXAHvarCompiledPeriod),
-            _ => return Err("Unknown DRI type!".to_string()),
+            dri_type => match profile::registry().for_hist_type(dri_type) {
+                Some(profile) => self
+                    .process_profile_query(globs, &profile)
+                    .await?
+                    .map(DriCompiledPeriod::DRIProfileCompiledPeriod),
+                None => return Err("Unknown DRI type!".to_string()),
+            },
         };
         Ok(DriHist::new(self.dev_id, self.dri_type, day, tels))
     }
@@ -108,22 +98,23 @@ impl DriHistParams {
 
         let mut tcomp = DRICCNTelemetryCompiler::new(self.dri_interval);
 
-        let querier = crate::lib_dynamodb::query::QuerierDevIdTimestamp::new_diel_dev(
-            table_name,
-            self.dev_id.to_owned(),
-            &globs.configfile.aws_config,
-        );
+        let query = SourceQuery::new_diel_dev(table_name, self.dev_id.to_owned());
         let mut final_tels = Vec::new();
-        querier
-            .run(&ts_ini, &ts_end, &mut |items: Vec<TelemetryDri>| {
+        run_query(
+            globs.telemetry_source.as_ref(),
+            &query,
+            &ts_ini,
+            &ts_end,
+            &mut |items: Vec<TelemetryDri>| {
                 let mut x = items
                     .into_iter()
                     .filter_map(|tel| tel.try_into().ok())
                     .collect::<Vec<DriCCNTelemetry>>();
                 final_tels.append(&mut x);
                 Ok(())
-            })
-            .await?;
+            },
+        )
+        .await?;
 
         let day = self.day.to_string();
         let ts_ini = day.as_str();
@@ -210,14 +201,14 @@ impl DriHistParams {
 
         let mut tcomp = DRIVAVandFancoilTelemetryCompiler::new(self.dri_interval);
 
-        let querier = crate::lib_dynamodb::query::QuerierDevIdTimestamp::new_diel_dev(
-            table_name,
-            self.dev_id.to_owned(),
-            &globs.configfile.aws_config,
-        );
+        let query = SourceQuery::new_diel_dev(table_name, self.dev_id.to_owned());
         let mut final_tels = Vec::new();
-        querier
-            .run(&ts_ini, &ts_end, &mut |items: Vec<TelemetryDri>| {
+        run_query(
+            globs.telemetry_source.as_ref(),
+            &query,
+            &ts_ini,
+            &ts_end,
+            &mut |items: Vec<TelemetryDri>| {
                 let mut x = items
                     .into_iter()
                     .filter_map(|mut tel| {
@@ -227,8 +218,9 @@ impl DriHistParams {
                     .collect::<Vec<DriVAVandFancoilTelemetry>>();
                 final_tels.append(&mut x);
                 Ok(())
-            })
-            .await?;
+            },
+        )
+        .await?;
 
         let day = self.day.to_string();
         let ts_ini = day.as_str();
@@ -274,168 +266,11 @@ impl DriHistParams {
         Ok(result.unwrap())
     }
 
-    async fn process_chiller_carrier_hx_query(
-        &self,
-        globs: &Arc<GlobalVars>,
-    ) -> Result<Option<DriChillerCarrierHXCompiledPeriod>, String> {
-        let dev_id_upper = self.dev_id.to_uppercase();
-        let mut table_name = {
-            if (self.dev_id.len() == 12) && dev_id_upper.starts_with("DRI") {
-                format!("{}XXXX_RAW", &dev_id_upper[0..8])
-            } else {
-                String::new()
-            }
-        };
-
-        for custom in &globs.configfile.CUSTOM_TABLE_NAMES_DRI {
-            if dev_id_upper.starts_with(&custom.dev_prefix) {
-                table_name = custom.table_name.to_owned();
-                break;
-            }
-        }
-
-        if table_name.is_empty() {
-            return Err(format!("Unknown DRI generation: {}", self.dev_id));
-        }
-
-        let interval_length_s = 24 * 60 * 60;
-        let (ts_ini, ts_end) = {
-            let i_ts_ini = self.day.and_hms(0, 0, 0).timestamp();
-            let i_ts_end = i_ts_ini + interval_length_s;
-            let ts_ini = NaiveDateTime::from_timestamp(i_ts_ini, 0)
-                .format("%Y-%m-%dT%H:%M:%S")
-                .to_string();
-            let ts_end = NaiveDateTime::from_timestamp(i_ts_end, 0)
-                .format("%Y-%m-%dT%H:%M:%S")
-                .to_string();
-            (ts_ini, ts_end)
-        };
-
-        let querier = crate::lib_dynamodb::query::QuerierDevIdTimestamp::new_diel_dev(
-            table_name,
-            self.dev_id.to_owned(),
-            &globs.configfile.aws_config,
-        );
-        let mut final_tels = Vec::new();
-        querier
-            .run(&ts_ini, &ts_end, &mut |items: Vec<
-                TelemetryDriChillerCarrierHX,
-            >| {
-                let mut x = items
-                    .into_iter()
-                    .filter_map(|mut tel| {
-                        tel.formulas = self.formulas.clone();
-                        tel.try_into().ok()
-                    })
-                    .collect::<Vec<DriChillerCarrierHXTelemetry>>();
-                final_tels.append(&mut x);
-                Ok(())
-            })
-            .await?;
-
-        let (grouped_telemetries, params_changes_hist) =
-            DriChillerCarrierHXTelemetry::group_telemetries(
-                &self.dev_id,
-                final_tels.clone(),
-                self.chiller_carrier_hour_graphic.unwrap_or(false),
-            );
-
-        let mut grouped_averages =
-            DriChillerCarrierHXTelemetry::calculate_group_averages(&grouped_telemetries);
-
-        grouped_averages.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
-
-        let dri_chiller_carrier_hist = DriChillerCarrierHXCompiledPeriod {
-            params_grouped: grouped_averages,
-            params_changed: params_changes_hist,
-        };
-
-        Ok(Some(dri_chiller_carrier_hist))
-    }
-
-    async fn process_chiller_carrier_xa_query(
-        &self,
-        globs: &Arc<GlobalVars>,
-    ) -> Result<Option<DriChillerCarrierXACompiledPeriod>, String> {
-        let dev_id_upper = self.dev_id.to_uppercase();
-        let mut table_name = {
-            if (self.dev_id.len() == 12) && dev_id_upper.starts_with("DRI") {
-                format!("{}XXXX_RAW", &dev_id_upper[0..8])
-            } else {
-                String::new()
-            }
-        };
-
-        for custom in &globs.configfile.CUSTOM_TABLE_NAMES_DRI {
-            if dev_id_upper.starts_with(&custom.dev_prefix) {
-                table_name = custom.table_name.to_owned();
-                break;
-            }
-        }
-
-        if table_name.is_empty() {
-            return Err(format!("Unknown DRI generation: {}", self.dev_id));
-        }
-
-        let interval_length_s = 24 * 60 * 60;
-        let (ts_ini, ts_end) = {
-            let i_ts_ini = self.day.and_hms(0, 0, 0).timestamp();
-            let i_ts_end = i_ts_ini + interval_length_s;
-            let ts_ini = NaiveDateTime::from_timestamp(i_ts_ini, 0)
-                .format("%Y-%m-%dT%H:%M:%S")
-                .to_string();
-            let ts_end = NaiveDateTime::from_timestamp(i_ts_end, 0)
-                .format("%Y-%m-%dT%H:%M:%S")
-                .to_string();
-            (ts_ini, ts_end)
-        };
-
-        let querier = crate::lib_dynamodb::query::QuerierDevIdTimestamp::new_diel_dev(
-            table_name,
-            self.dev_id.to_owned(),
-            &globs.configfile.aws_config,
-        );
-        let mut final_tels = Vec::new();
-        querier
-            .run(&ts_ini, &ts_end, &mut |items: Vec<
-                TelemetryDriChillerCarrierXA,
-            >| {
-                let mut x = items
-                    .into_iter()
-                    .filter_map(|mut tel| {
-                        tel.formulas = self.formulas.clone();
-                        tel.try_into().ok()
-                    })
-                    .collect::<Vec<DriChillerCarrierXATelemetry>>();
-                final_tels.append(&mut x);
-                Ok(())
-            })
-            .await?;
-
-        let (grouped_telemetries, params_changes_hist) =
-            DriChillerCarrierXATelemetry::group_telemetries(
-                &self.dev_id,
-                final_tels.clone(),
-                self.chiller_carrier_hour_graphic.unwrap_or(false),
-            );
-
-        let mut grouped_averages =
-            DriChillerCarrierXATelemetry::calculate_group_averages(&grouped_telemetries);
-
-        grouped_averages.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
-
-        let dri_chiller_carrier_hist = DriChillerCarrierXACompiledPeriod {
-            params_grouped: grouped_averages,
-            params_changed: params_changes_hist,
-        };
-
-        Ok(Some(dri_chiller_carrier_hist))
-    }
-
-    async fn process_chiller_carrier_xa_hvar_query(
+    async fn process_profile_query(
         &self,
         globs: &Arc<GlobalVars>,
-    ) -> Result<Option<DriChillerCarrierXAHvarCompiledPeriod>, String> {
+        profile: &DriProfile,
+    ) -> Result<Option<DriProfileCompiledPeriod>, String> {
         let dev_id_upper = self.dev_id.to_uppercase();
         let mut table_name = {
             if (self.dev_id.len() == 12) && dev_id_upper.starts_with("DRI") {
@@ -469,46 +304,33 @@ impl DriHistParams {
             (ts_ini, ts_end)
         };
 
-        let querier = crate::lib_dynamodb::query::QuerierDevIdTimestamp::new_diel_dev(
-            table_name,
-            self.dev_id.to_owned(),
-            &globs.configfile.aws_config,
-        );
+        let query = SourceQuery::new_diel_dev(table_name, self.dev_id.to_owned());
         let mut final_tels = Vec::new();
-        querier
-            .run(&ts_ini, &ts_end, &mut |items: Vec<
-                TelemetryDriChillerCarrierXAHvar,
-            >| {
+        run_query(
+            globs.telemetry_source.as_ref(),
+            &query,
+            &ts_ini,
+            &ts_end,
+            &mut |items: Vec<serde_json::Value>| {
                 let mut x = items
                     .into_iter()
                     .filter_map(|mut tel| {
-                        tel.formulas = self.formulas.clone();
-                        tel.try_into().ok()
+                        tel["formulas"] = serde_json::json!(self.formulas);
+                        decode_payload(profile, tel).ok()
                     })
-                    .collect::<Vec<DriChillerCarrierXAHvarTelemetry>>();
+                    .collect::<Vec<DriProfileTelemetry>>();
                 final_tels.append(&mut x);
                 Ok(())
-            })
-            .await?;
-
-        let (grouped_telemetries, params_changes_hist) =
-            DriChillerCarrierXAHvarTelemetry::group_telemetries(
-                &self.dev_id,
-                final_tels.clone(),
-                self.chiller_carrier_hour_graphic.unwrap_or(false),
-            );
-
-        let mut grouped_averages =
-            DriChillerCarrierXAHvarTelemetry::calculate_group_averages(&grouped_telemetries);
-
-        grouped_averages.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
-
-        let dri_chiller_carrier_hist = DriChillerCarrierXAHvarCompiledPeriod {
-            params_grouped: grouped_averages,
-            params_changed: params_changes_hist,
-        };
-
-        Ok(Some(dri_chiller_carrier_hist))
+            },
+        )
+        .await?;
+
+        Ok(Some(compile_hist(
+            profile,
+            &self.dev_id,
+            &final_tels,
+            self.chiller_carrier_hour_graphic.unwrap_or(false),
+        )))
     }
 }
 
@@ -517,9 +339,7 @@ impl DriHistParams {
 pub enum DriCompiledPeriod {
     DRICCNCompiledPeriod(DRICCNCompiledPeriod),
     DRIVAVandFancoilCompiledPeriod(DRIVAVandFancoilCompiledPeriod),
-    DRIChillerCarrierHXCompiledPeriod(DriChillerCarrierHXCompiledPeriod),
-    DRIChillerCarrierXACompiledPeriod(DriChillerCarrierXACompiledPeriod),
-    DRIChillerCarrierXAHvarCompiledPeriod(DriChillerCarrierXAHvarCompiledPeriod),
+    DRIProfileCompiledPeriod(DriProfileCompiledPeriod),
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -545,21 +365,3 @@ impl DriHist {
         }
     }
 }
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct DriChillerCarrierHXCompiledPeriod {
-    params_grouped: Vec<DriChillerCarrierHXTelemetry>,
-    params_changed: Vec<ChillerParametersChangesHist>,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct DriChillerCarrierXACompiledPeriod {
-    params_grouped: Vec<DriChillerCarrierXATelemetry>,
-    params_changed: Vec<ChillerParametersChangesHist>,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct DriChillerCarrierXAHvarCompiledPeriod {
-    params_grouped: Vec<DriChillerCarrierXAHvarTelemetry>,
-    params_changed: Vec<ChillerParametersChangesHist>,
-}
@@ -1,62 +1,141 @@
-use super::cache_files::build_part_file_name;
+use super::cache_files::PartEntry;
 use crate::compression::compiler_DUT::DUTTelemetryCompiler;
-use crate::l1_virtual::dut_l1::l1_calc::create_l1_calculator;
+use crate::l1_virtual::dut_l1::l1_calc::{create_l1_calculator, L1Calculator};
 use crate::lib_http::response::{respond_http_json, respond_http_plain_text};
 use crate::lib_http::types::HttpResponse;
+use crate::lib_telemetry_source::source::{run_query, SourceQuery};
 use crate::telemetry_payloads::dut_payload_json::get_raw_telemetry_pack_dut;
 use crate::telemetry_payloads::dut_telemetry::{split_pack, HwInfoDUT};
 use crate::GlobalVars;
 use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
 use std::convert::TryFrom;
-use std::io::Write;
 use std::sync::Arc;
 
+// A consulta vai até um pouco depois do fim do intervalo, para pegar os pacotes que terminam nele
+const QUERY_MARGIN_S: i64 = 120;
+
 pub async fn process_comp_command_dut(
     rpars: ReqParameters,
     globs: &Arc<GlobalVars>,
 ) -> Result<HttpResponse, String> {
+    let compiled = match compile_interval(&rpars, globs, None).await {
+        Ok(Some(v)) => v,
+        Ok(None) => {
+            return Ok(respond_http_json(200, "{}"));
+        }
+        Err(response) => {
+            return Ok(response);
+        }
+    };
+    let provision_error = compiled.provision_error;
+    let mut tcomp = compiled.tcomp;
+
+    // return this.CheckClosePeriod(index ? (index + 1) : interval_length_s);
+    let period_data = match tcomp.CheckClosePeriod(if rpars.open_end {
+        tcomp.last_index + 1
+    } else {
+        isize::try_from(rpars.interval_length_s).unwrap()
+    }) {
+        Err(err) => {
+            crate::LOG.append_log_tag_msg("ERROR", &format!("{}", err));
+            return Ok(respond_http_plain_text(400, "ERROR[120] CheckClosePeriod"));
+        }
+        Ok(v) => match v {
+            Some(v) => v,
+            None => {
+                return Ok(respond_http_json(200, "{}"));
+            }
+        },
+    };
+
+    let data = serde_json::json!({
+      "Temp": period_data.Temp,
+      "Temp1": period_data.Temp1,
+      "Hum": period_data.Hum,
+      "State": period_data.State,
+      "Mode": period_data.Mode,
+      "eCO2": period_data.e_co2,
+      "TVOC": period_data.tvoc,
+      "L1": period_data.l1,
+      "hoursOnL1": period_data.hoursOnL1,
+      "hoursOffL1": period_data.hoursOffL1,
+      "provision_error": provision_error,
+      "numDeparts": period_data.numDeparts,
+      "hoursOnline": period_data.hoursOnline,
+    });
+    // data["Temp"] = period_data.Temp.into();
+    // data["Hum"] = period_data.Hum.into();
+    // data["State"] = period_data.State.into();
+    // data["Mode"] = period_data.Mode.into();
+    // data["eCO2"] = serde_json::Value::from(period_data.e_co2);
+    // data["TVOC"] = serde_json::Value::from(period_data.tvoc);
+    // data["provision_error"] = provision_error.into();
+
+    return Ok(respond_http_json(200, &data.to_string()));
+}
+
+// Intervalo compilado, com os vetores ainda abertos
+pub struct IntervalCompilation {
+    pub tcomp: DUTTelemetryCompiler,
+    pub provision_error: bool,
+    // Estado do L1 no fim do intervalo, para continuar a compilação no intervalo seguinte
+    pub l1_state: Option<L1Calculator>,
+}
+
+// Retorna None quando não tem tabela para o dispositivo.
+// O estado do L1, quando informado, é o do intervalo anterior, que tem que terminar onde este começa.
+pub async fn compile_interval(
+    rpars: &ReqParameters,
+    globs: &Arc<GlobalVars>,
+    l1_state: Option<L1Calculator>,
+) -> Result<Option<IntervalCompilation>, HttpResponse> {
     let rpars_serialized = serde_json::to_string(&rpars).unwrap();
-    let dev_id = rpars.dev_id;
+    let dev_id = &rpars.dev_id;
     let interval_length_s = rpars.interval_length_s;
-    let ts_ini = rpars.ts_ini;
+    let ts_ini = &rpars.ts_ini;
     let i_ts_ini = rpars.i_ts_ini;
     let i_ts_end = rpars.i_ts_end;
-    let ts_end = rpars.ts_end;
-    let open_end = rpars.open_end;
+    let ts_end = &rpars.ts_end;
     let offset_temp = rpars.offset_temp;
     let dev = HwInfoDUT {
         temperature_offset: offset_temp,
     };
     let timezone_offset = rpars.timezone_offset;
-    let timezone_offset_string = timezone_offset
-        .map(|v| v.to_string())
-        .unwrap_or("None".to_string());
 
     // Create accumulators
     // Verificar na pasta se tem query pronta para o dia selecionado.
-    let part_file_name = build_part_file_name(&dev_id, &ts_ini, &timezone_offset_string);
-    let accs: Accumulators = {
-        let r = if rpars.avoid_cache {
-            Err(String::from("Cache desabilitado"))
-        } else {
-            Ok(())
-        }
-        .and_then(|_| load_partial_query(&part_file_name, &rpars_serialized));
-        match r {
-            Ok(v) => v,
-            Err(_err) => {
-                let page_ts_ini = ts_ini.clone();
-                let tcomp = DUTTelemetryCompiler::new();
-                Accumulators {
-                    rpars: None,
-                    page_ts_ini,
-                    tcomp,
-                    timezone_offset,
-                }
-            }
+    let part = PartEntry::new(dev_id, ts_ini, timezone_offset, i_ts_end, &rpars_serialized);
+    let cached = if rpars.avoid_cache {
+        None
+    } else {
+        globs.part_cache.load::<Accumulators>(&part)
+    };
+    let (accs, complete) = match cached {
+        Some(v) => (v.data, v.complete),
+        None => {
+            let page_ts_ini = ts_ini.clone();
+            let tcomp = DUTTelemetryCompiler::new();
+            let accs = Accumulators {
+                rpars: None,
+                page_ts_ini,
+                tcomp,
+                timezone_offset,
+                l1_state: None,
+            };
+            (accs, false)
         }
     };
+
+    // Dia já fechado e salvo inteiro no cache: não precisa consultar de novo
+    if complete {
+        return Ok(Some(IntervalCompilation {
+            tcomp: accs.tcomp,
+            provision_error: false,
+            l1_state: accs.l1_state,
+        }));
+    }
+
     let page_ts_ini = accs.page_ts_ini;
     let mut tcomp = accs.tcomp;
 
@@ -77,19 +156,25 @@ pub async fn process_comp_command_dut(
 
     if table_name.len() == 0 {
         crate::LOG.append_log_tag_msg("WARN", &format!("Unknown DUT generation: {}", dev_id));
-        return Ok(respond_http_json(200, "{}"));
+        return Ok(None);
     }
 
-    let mut dut_l1_calc = create_l1_calculator(&dev);
+    // O estado do L1 do intervalo anterior já inclui os pacotes lidos na consulta dele
+    let prev_query_end = if l1_state.is_some() {
+        i_ts_ini + QUERY_MARGIN_S
+    } else {
+        i64::MIN
+    };
+    let mut dut_l1_calc = l1_state.unwrap_or_else(|| create_l1_calculator(&dev));
 
-    let querier = crate::lib_dynamodb::query::QuerierDevIdTimestamp::new_diel_dev(
-        table_name,
-        dev_id.clone(),
-        &globs.configfile.aws_config,
-    );
+    let query = SourceQuery::new_diel_dev(table_name, dev_id.clone());
     let mut found_invalid_payload = false;
-    let result = querier
-        .run(&ts_ini, &ts_end, &mut |items| {
+    let result = run_query(
+        globs.telemetry_source.as_ref(),
+        &query,
+        ts_ini,
+        ts_end,
+        &mut |items| {
             for item in items {
                 let payload = match get_raw_telemetry_pack_dut(&item) {
                     Ok(v) => v,
@@ -109,6 +194,7 @@ pub async fn process_comp_command_dut(
                     &payload,
                     i_ts_ini,
                     i_ts_end,
+                    prev_query_end,
                     &mut dut_l1_calc,
                     &mut |telemetry, index| {
                         tcomp.AdcPontos(telemetry, index);
@@ -131,8 +217,9 @@ pub async fn process_comp_command_dut(
                 };
             }
             return Ok(());
-        })
-        .await;
+        },
+    )
+    .await;
 
     let mut provision_error = false;
     if let Err(err) = result {
@@ -140,74 +227,28 @@ pub async fn process_comp_command_dut(
             provision_error = true;
         } else if err.starts_with("ResourceNotFound:") {
             crate::LOG.append_log_tag_msg("WARN", &format!("Table not found for: {}", dev_id));
-            return Ok(respond_http_json(200, "{}"));
+            return Ok(None);
         } else {
-            return Ok(respond_http_plain_text(400, &format!("ERROR[117] {}", err)));
+            return Err(respond_http_plain_text(400, &format!("ERROR[117] {}", err)));
         }
     }
 
-    let mut accs = Accumulators {
+    let accs = Accumulators {
         rpars: Some(serde_json::from_str(&rpars_serialized).unwrap()),
         page_ts_ini,
         tcomp,
         timezone_offset,
+        l1_state: Some(dut_l1_calc),
     };
-    if (!rpars.avoid_cache) && accs.rpars.is_some() && (interval_length_s > 3000) {
-        let serialized = serde_json::to_string(&accs).unwrap();
-        match std::fs::File::create(&part_file_name) {
-            Err(err) => {
-                crate::LOG.append_log_tag_msg("ERROR", &format!("create failed: {}", err));
-            }
-            Ok(mut file) => {
-                if let Err(err) = file.write_all(serialized.as_bytes()) {
-                    crate::LOG.append_log_tag_msg("ERROR", &format!("write failed: {}", err));
-                }
-            }
-        };
+    if (!rpars.avoid_cache) && (interval_length_s > 3000) && !provision_error {
+        globs.part_cache.save(&part, &accs);
     }
 
-    // return this.CheckClosePeriod(index ? (index + 1) : interval_length_s);
-    let period_data = match accs.tcomp.CheckClosePeriod(if open_end {
-        accs.tcomp.last_index + 1
-    } else {
-        isize::try_from(interval_length_s).unwrap()
-    }) {
-        Err(err) => {
-            crate::LOG.append_log_tag_msg("ERROR", &format!("{}", err));
-            return Ok(respond_http_plain_text(400, "ERROR[120] CheckClosePeriod"));
-        }
-        Ok(v) => match v {
-            Some(v) => v,
-            None => {
-                return Ok(respond_http_json(200, "{}"));
-            }
-        },
-    };
-
-    let data = serde_json::json!({
-      "Temp": period_data.Temp,
-      "Temp1": period_data.Temp1,
-      "Hum": period_data.Hum,
-      "State": period_data.State,
-      "Mode": period_data.Mode,
-      "eCO2": period_data.e_co2,
-      "TVOC": period_data.tvoc,
-      "L1": period_data.l1,
-      "hoursOnL1": period_data.hoursOnL1,
-      "hoursOffL1": period_data.hoursOffL1,
-      "provision_error": provision_error,
-      "numDeparts": period_data.numDeparts,
-      "hoursOnline": period_data.hoursOnline,
-    });
-    // data["Temp"] = period_data.Temp.into();
-    // data["Hum"] = period_data.Hum.into();
-    // data["State"] = period_data.State.into();
-    // data["Mode"] = period_data.Mode.into();
-    // data["eCO2"] = serde_json::Value::from(period_data.e_co2);
-    // data["TVOC"] = serde_json::Value::from(period_data.tvoc);
-    // data["provision_error"] = provision_error.into();
-
-    return Ok(respond_http_json(200, &data.to_string()));
+    Ok(Some(IntervalCompilation {
+        tcomp: accs.tcomp,
+        provision_error,
+        l1_state: accs.l1_state,
+    }))
 }
 
 pub fn parse_parameters(parsed: &serde_json::Value) -> Result<ReqParameters, HttpResponse> {
@@ -248,7 +289,7 @@ pub fn parse_parameters(parsed: &serde_json::Value) -> Result<ReqParameters, Htt
     ts_ini = &ts_ini_aux;
 
     let i_ts_end = i_ts_ini + interval_length_s;
-    let ts_end = NaiveDateTime::from_timestamp(i_ts_end + 120, 0)
+    let ts_end = NaiveDateTime::from_timestamp(i_ts_end + QUERY_MARGIN_S, 0)
         .format("%Y-%m-%dT%H:%M:%S")
         .to_string();
 
@@ -271,24 +312,6 @@ pub fn parse_parameters(parsed: &serde_json::Value) -> Result<ReqParameters, Htt
     });
 }
 
-fn load_partial_query(path: &str, rpars_serialized: &str) -> Result<Accumulators, String> {
-    let serialized = match std::fs::read_to_string(path) {
-        Ok(v) => v,
-        Err(err) => return Err(format!("{}", err)),
-    };
-    // let serialized = serde_json::to_string(&(&page_ts_ini, &fchk, &tcomp, &fcomp)).unwrap();
-    let accs = match serde_json::from_str::<Accumulators>(&serialized) {
-        Err(err) => return Err(format!("{}", err)),
-        Ok(v) => v,
-    };
-    let rpars_check = serde_json::to_string(&accs.rpars).unwrap();
-    if rpars_check == rpars_serialized {
-        return Ok(accs);
-    } else {
-        return Err("Query is not the same".to_string());
-    }
-}
-
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ReqParameters {
     pub dev_id: String,
@@ -303,10 +326,12 @@ pub struct ReqParameters {
     pub timezone_offset: Option<i64>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize)]
 struct Accumulators {
     pub rpars: Option<ReqParameters>,
     pub page_ts_ini: String,
     pub tcomp: DUTTelemetryCompiler,
     pub timezone_offset: Option<i64>,
+    #[serde(default)]
+    pub l1_state: Option<L1Calculator>,
 }
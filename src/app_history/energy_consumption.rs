@@ -0,0 +1,514 @@
+/*
+Consumo de energia (kWh) calculado a partir dos contadores acumulados do medidor (en_at_tri e en_re_tri).
+O consumo entre duas leituras é a diferença dos contadores, distribuída proporcionalmente ao tempo entre as duas,
+então um intervalo sem telemetrias (gap) é interpolado linearmente. Quando o contador diminui:
+ - se ele estava perto de "counter_max" e voltou para perto de zero, é uma volta do contador (rollover);
+ - se caiu para menos da metade, o contador foi zerado (reset) e o consumo é o valor atual;
+ - senão é só uma oscilação da leitura e o intervalo fica sem consumo.
+Cada total é separado nos postos tarifários (ponta, intermediário, fora de ponta...). Os postos são janelas de horário
+em dias da semana; nos feriados e fora das janelas o consumo vai para o posto padrão.
+*/
+use super::energy_hist::EnergyHistParams;
+use crate::telemetry_payloads::energy::padronized::PadronizedEnergyTelemetry;
+use crate::GlobalVars;
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, Timelike};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+// Intervalo entre leituras a partir do qual o consumo é considerado interpolado
+const DEFAULT_MAX_GAP_MINUTES: i64 = 30;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Granularity {
+    Hour,
+    Day,
+    Month,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EnergyConsumptionParams {
+    pub energy_device_id: String,
+    serial: String,
+    manufacturer: String,
+    model: String,
+    start_time: NaiveDateTime,
+    end_time: NaiveDateTime,
+    formulas: Option<HashMap<String, String>>,
+    granularity: Granularity,
+    // Valor máximo dos contadores do medidor, para identificar a volta do contador
+    counter_max: Option<f64>,
+    max_gap_minutes: Option<i64>,
+    tariff: Option<TariffConfig>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TariffWindow {
+    name: String,
+    // "18:00". Se end for menor ou igual a start, a janela passa da meia-noite.
+    start: NaiveTime,
+    end: NaiveTime,
+    // 1 = segunda ... 7 = domingo
+    weekdays: Vec<u32>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TariffConfig {
+    // Posto do consumo fora das janelas e nos feriados
+    default_period: String,
+    // Se duas janelas se sobrepõem, vale a primeira
+    windows: Vec<TariffWindow>,
+    #[serde(default)]
+    holidays: Vec<NaiveDate>,
+}
+
+impl Default for TariffConfig {
+    // Horário de ponta mais comum das distribuidoras, com o intermediário da tarifa branca
+    fn default() -> Self {
+        let time = |h| NaiveTime::from_hms_opt(h, 0, 0).unwrap();
+        let window = |name: &str, start, end| TariffWindow {
+            name: name.to_owned(),
+            start: time(start),
+            end: time(end),
+            weekdays: vec![1, 2, 3, 4, 5],
+        };
+        TariffConfig {
+            default_period: "off_peak".to_owned(),
+            windows: vec![
+                window("peak", 18, 21),
+                window("intermediate", 17, 18),
+                window("intermediate", 21, 22),
+            ],
+            holidays: Vec::new(),
+        }
+    }
+}
+
+impl TariffConfig {
+    fn validate(&self) -> Result<(), String> {
+        if self.default_period.is_empty() {
+            return Err("Tariff default_period is empty".to_owned());
+        }
+        for window in &self.windows {
+            if window.name.is_empty() {
+                return Err("Tariff window without name".to_owned());
+            }
+            if let Some(day) = window.weekdays.iter().find(|d| !(1..=7).contains(*d)) {
+                return Err(format!(
+                    "Invalid weekday in tariff window {}: {}",
+                    window.name, day
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn period_at(&self, instant: NaiveDateTime) -> &str {
+        if self.holidays.contains(&instant.date()) {
+            return &self.default_period;
+        }
+        let weekday = instant.weekday().number_from_monday();
+        let time = instant.time();
+        self.windows
+            .iter()
+            .find(|w| {
+                let in_window = if w.start < w.end {
+                    time >= w.start && time < w.end
+                } else {
+                    time >= w.start || time < w.end
+                };
+                in_window && w.weekdays.contains(&weekday)
+            })
+            .map_or(&self.default_period, |w| &w.name)
+    }
+
+    // Próximo instante depois de "instant" em que o posto pode mudar
+    fn next_boundary(&self, instant: NaiveDateTime) -> NaiveDateTime {
+        let next_hour = truncate(instant, Granularity::Hour) + Duration::hours(1);
+        self.windows
+            .iter()
+            .flat_map(|w| [w.start, w.end])
+            .map(|t| {
+                let same_day = instant.date().and_time(t);
+                if same_day > instant {
+                    same_day
+                } else {
+                    same_day + Duration::days(1)
+                }
+            })
+            .fold(next_hour, |a, b| a.min(b))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum QualityFlag {
+    // Nenhuma leitura cobre o período
+    NoData,
+    // Parte do consumo foi distribuída num intervalo sem telemetrias
+    Interpolated,
+    CounterReset,
+    CounterRollover,
+    // O contador diminuiu um pouco, o intervalo ficou sem consumo
+    CounterDecreased,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq)]
+pub struct PeriodConsumption {
+    active_kwh: f64,
+    reactive_kwh: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ConsumptionBucket {
+    timestamp: NaiveDateTime,
+    active_kwh: f64,
+    reactive_kwh: f64,
+    periods: BTreeMap<String, PeriodConsumption>,
+    // Fração do período coberta por leituras sem gap, de 0 a 1
+    coverage: f64,
+    flags: Vec<QualityFlag>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EnergyConsumption {
+    energy_device_id: String,
+    serial: String,
+    manufacturer: String,
+    model: String,
+    granularity: Granularity,
+    data: Vec<ConsumptionBucket>,
+}
+
+impl EnergyConsumptionParams {
+    pub async fn process(self, globs: &Arc<GlobalVars>) -> Result<EnergyConsumption, String> {
+        if self.start_time >= self.end_time {
+            return Err("start_time must be before end_time".to_owned());
+        }
+        let tariff = self.tariff.clone().unwrap_or_default();
+        tariff.validate()?;
+
+        let hist_params = EnergyHistParams {
+            energy_device_id: self.energy_device_id,
+            serial: self.serial,
+            manufacturer: self.manufacturer,
+            model: self.model,
+            start_time: self.start_time,
+            end_time: self.end_time,
+            formulas: self.formulas,
+            params: None,
+            calculate_demand_hour_graphic: None,
+        };
+        let mut tels = hist_params.process_common(globs).await?;
+        tels.sort_by_key(|tel| tel.timestamp);
+
+        let calculator = ConsumptionCalculator {
+            start_time: self.start_time,
+            end_time: self.end_time,
+            granularity: self.granularity,
+            counter_max: self.counter_max,
+            max_gap: Duration::minutes(self.max_gap_minutes.unwrap_or(DEFAULT_MAX_GAP_MINUTES)),
+            tariff: &tariff,
+        };
+        let data = calculator.calculate(&tels);
+
+        Ok(EnergyConsumption {
+            energy_device_id: hist_params.energy_device_id,
+            serial: hist_params.serial,
+            manufacturer: hist_params.manufacturer,
+            model: hist_params.model,
+            granularity: self.granularity,
+            data,
+        })
+    }
+}
+
+fn truncate(instant: NaiveDateTime, granularity: Granularity) -> NaiveDateTime {
+    let date = instant.date();
+    match granularity {
+        Granularity::Hour => date.and_hms_opt(instant.hour(), 0, 0).unwrap(),
+        Granularity::Day => date.and_hms_opt(0, 0, 0).unwrap(),
+        Granularity::Month => date.with_day(1).unwrap().and_hms_opt(0, 0, 0).unwrap(),
+    }
+}
+
+fn next_bucket(bucket: NaiveDateTime, granularity: Granularity) -> NaiveDateTime {
+    match granularity {
+        Granularity::Hour => bucket + Duration::hours(1),
+        Granularity::Day => bucket + Duration::days(1),
+        Granularity::Month => {
+            let date = bucket.date();
+            let (year, month) = match date.month() {
+                12 => (date.year() + 1, 1),
+                m => (date.year(), m + 1),
+            };
+            NaiveDate::from_ymd_opt(year, month, 1)
+                .unwrap()
+                .and_hms_opt(0, 0, 0)
+                .unwrap()
+        }
+    }
+}
+
+#[derive(Default)]
+struct BucketTotals {
+    periods: BTreeMap<String, PeriodConsumption>,
+    covered: Duration,
+    flags: Vec<QualityFlag>,
+}
+
+impl BucketTotals {
+    fn flag(&mut self, flag: QualityFlag) {
+        if !self.flags.contains(&flag) {
+            self.flags.push(flag);
+        }
+    }
+}
+
+enum Counter {
+    Active,
+    Reactive,
+}
+
+struct ConsumptionCalculator<'a> {
+    start_time: NaiveDateTime,
+    end_time: NaiveDateTime,
+    granularity: Granularity,
+    counter_max: Option<f64>,
+    max_gap: Duration,
+    tariff: &'a TariffConfig,
+}
+
+impl ConsumptionCalculator<'_> {
+    fn calculate(&self, tels: &[PadronizedEnergyTelemetry]) -> Vec<ConsumptionBucket> {
+        let mut buckets: BTreeMap<NaiveDateTime, BucketTotals> = BTreeMap::new();
+        let mut bucket = truncate(self.start_time, self.granularity);
+        while bucket < self.end_time {
+            buckets.insert(bucket, BucketTotals::default());
+            bucket = next_bucket(bucket, self.granularity);
+        }
+
+        self.add_counter(&mut buckets, tels, Counter::Active, |tel| tel.en_at_tri);
+        self.add_counter(&mut buckets, tels, Counter::Reactive, |tel| tel.en_re_tri);
+        self.add_coverage(&mut buckets, tels);
+
+        buckets
+            .into_iter()
+            .map(|(timestamp, mut totals)| {
+                let duration = next_bucket(timestamp, self.granularity).min(self.end_time)
+                    - timestamp.max(self.start_time);
+                let coverage = totals.covered.num_seconds() as f64 / duration.num_seconds() as f64;
+                if totals.covered.is_zero() {
+                    totals.flag(QualityFlag::NoData);
+                }
+                totals.flags.sort();
+                for period in totals.periods.values_mut() {
+                    period.active_kwh = round_3(period.active_kwh);
+                    period.reactive_kwh = round_3(period.reactive_kwh);
+                }
+                ConsumptionBucket {
+                    timestamp,
+                    active_kwh: round_3(totals.periods.values().map(|p| p.active_kwh).sum()),
+                    reactive_kwh: round_3(totals.periods.values().map(|p| p.reactive_kwh).sum()),
+                    periods: totals.periods,
+                    coverage: round_3(coverage.min(1.0)),
+                    flags: totals.flags,
+                }
+            })
+            .collect()
+    }
+
+    fn add_counter(
+        &self,
+        buckets: &mut BTreeMap<NaiveDateTime, BucketTotals>,
+        tels: &[PadronizedEnergyTelemetry],
+        counter: Counter,
+        value: impl Fn(&PadronizedEnergyTelemetry) -> Option<f64>,
+    ) {
+        let readings = tels.iter().filter_map(|tel| {
+            value(tel)
+                .filter(|v| v.is_finite())
+                .map(|v| (tel.timestamp, v))
+        });
+        let mut previous: Option<(NaiveDateTime, f64)> = None;
+        for (timestamp, current) in readings {
+            let Some((prev_timestamp, prev)) = previous else {
+                previous = Some((timestamp, current));
+                continue;
+            };
+            if timestamp <= prev_timestamp {
+                continue;
+            }
+            previous = Some((timestamp, current));
+
+            let (consumption, flag) = self.counter_delta(prev, current);
+            if let Some(flag) = flag {
+                if let Some(totals) = buckets.get_mut(&truncate(timestamp, self.granularity)) {
+                    totals.flag(flag);
+                }
+            }
+            let interpolated = timestamp - prev_timestamp > self.max_gap;
+            self.distribute(
+                buckets,
+                prev_timestamp,
+                timestamp,
+                consumption,
+                &counter,
+                interpolated,
+            );
+        }
+    }
+
+    fn counter_delta(&self, prev: f64, current: f64) -> (f64, Option<QualityFlag>) {
+        if current >= prev {
+            return (current - prev, None);
+        }
+        if let Some(max) = self.counter_max {
+            if prev > max * 0.9 && current < max * 0.1 {
+                return (max - prev + current, Some(QualityFlag::CounterRollover));
+            }
+        }
+        if current < prev * 0.5 {
+            (current, Some(QualityFlag::CounterReset))
+        } else {
+            (0.0, Some(QualityFlag::CounterDecreased))
+        }
+    }
+
+    // Distribui o consumo do intervalo proporcionalmente ao tempo, separando por período e por posto tarifário
+    fn distribute(
+        &self,
+        buckets: &mut BTreeMap<NaiveDateTime, BucketTotals>,
+        from: NaiveDateTime,
+        to: NaiveDateTime,
+        consumption: f64,
+        counter: &Counter,
+        interpolated: bool,
+    ) {
+        let total_seconds = (to - from).num_seconds() as f64;
+        let mut segment_start = from;
+        while segment_start < to {
+            let segment_end = self.tariff.next_boundary(segment_start).min(to);
+            // Só a parte do intervalo dentro do período consultado
+            let (inside_start, inside_end) = (
+                segment_start.max(self.start_time),
+                segment_end.min(self.end_time),
+            );
+            if inside_start < inside_end {
+                let bucket = truncate(inside_start, self.granularity);
+                if let Some(totals) = buckets.get_mut(&bucket) {
+                    let share = consumption * (inside_end - inside_start).num_seconds() as f64
+                        / total_seconds;
+                    let period = self.tariff.period_at(inside_start);
+                    let period = totals.periods.entry(period.to_owned()).or_default();
+                    match counter {
+                        Counter::Active => period.active_kwh += share,
+                        Counter::Reactive => period.reactive_kwh += share,
+                    }
+                    if interpolated {
+                        totals.flag(QualityFlag::Interpolated);
+                    }
+                }
+            }
+            segment_start = segment_end;
+        }
+    }
+
+    // Tempo coberto por leituras consecutivas sem gap, de qualquer um dos contadores
+    fn add_coverage(
+        &self,
+        buckets: &mut BTreeMap<NaiveDateTime, BucketTotals>,
+        tels: &[PadronizedEnergyTelemetry],
+    ) {
+        let readings = tels
+            .iter()
+            .filter(|tel| tel.en_at_tri.is_some() || tel.en_re_tri.is_some())
+            .map(|tel| tel.timestamp.max(self.start_time).min(self.end_time));
+        let mut previous: Option<NaiveDateTime> = None;
+        for timestamp in readings {
+            if let Some(prev) = previous {
+                if timestamp > prev && timestamp - prev <= self.max_gap {
+                    let mut segment_start = prev;
+                    while segment_start < timestamp {
+                        let bucket = truncate(segment_start, self.granularity);
+                        let segment_end = next_bucket(bucket, self.granularity).min(timestamp);
+                        if let Some(totals) = buckets.get_mut(&bucket) {
+                            totals.covered += segment_end - segment_start;
+                        }
+                        segment_start = segment_end;
+                    }
+                }
+            }
+            previous = Some(timestamp);
+        }
+    }
+}
+
+fn round_3(value: f64) -> f64 {
+    (value * 1000.0).round() / 1000.0
+}
+
+#[test]
+fn test_energy_consumption() {
+    let ts = |s: &str| NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap();
+    let tel = |s: &str, active: f64| {
+        let mut tel: PadronizedEnergyTelemetry =
+            serde_json::from_value(serde_json::json!({ "timestamp": ts(s) })).unwrap();
+        tel.en_at_tri = Some(active);
+        tel.en_re_tri = Some(active / 10.0);
+        tel
+    };
+    let tariff: TariffConfig = serde_json::from_value(serde_json::json!({
+        "default_period": "off_peak",
+        "windows": [{ "name": "peak", "start": "18:00:00", "end": "21:00:00", "weekdays": [1, 2, 3, 4, 5] }],
+        "holidays": ["2024-01-02"],
+    }))
+    .unwrap();
+    let calculator = ConsumptionCalculator {
+        start_time: ts("2024-01-01 17:00"),
+        end_time: ts("2024-01-02 19:00"),
+        granularity: Granularity::Hour,
+        counter_max: Some(1000.0),
+        max_gap: Duration::minutes(DEFAULT_MAX_GAP_MINUTES),
+        tariff: &tariff,
+    };
+    // 2024-01-01 é segunda-feira, 2024-01-02 é feriado
+    let tels = vec![
+        tel("2024-01-01 17:30", 100.0),
+        tel("2024-01-01 18:00", 110.0),
+        // Gap de 1 hora, interpolado
+        tel("2024-01-01 19:00", 130.0),
+        tel("2024-01-01 19:30", 20.0),
+        tel("2024-01-02 18:00", 990.0),
+        tel("2024-01-02 18:30", 5.0),
+    ];
+    let data = calculator.calculate(&tels);
+    assert_eq!(data.len(), 26);
+
+    assert_eq!(data[0].active_kwh, 10.0);
+    assert_eq!(data[0].coverage, 0.5);
+    assert_eq!(data[0].periods["off_peak"].reactive_kwh, 1.0);
+    assert_eq!(data[1].periods["peak"].active_kwh, 20.0);
+    assert_eq!(
+        data[1].flags,
+        vec![QualityFlag::NoData, QualityFlag::Interpolated]
+    );
+    // Contador zerado: o consumo é o valor atual, mais a parte do gap até o dia seguinte
+    assert_eq!(data[2].active_kwh, 20.0 + round_3(970.0 / 45.0));
+    assert_eq!(
+        data[2].flags,
+        vec![QualityFlag::Interpolated, QualityFlag::CounterReset]
+    );
+    // Volta do contador, no feriado
+    assert_eq!(data[25].active_kwh, 15.0);
+    assert!(!data[25].periods.contains_key("peak"));
+    assert!(data[25].flags.contains(&QualityFlag::CounterRollover));
+
+    let default_tariff = TariffConfig::default();
+    assert_eq!(
+        default_tariff.period_at(ts("2024-01-03 21:30")),
+        "intermediate"
+    );
+    assert_eq!(default_tariff.period_at(ts("2024-01-06 19:00")), "off_peak");
+}
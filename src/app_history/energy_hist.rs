@@ -1,8 +1,10 @@
-use crate::telemetry_payloads::energy::dme::EnergyDemandTelemetry;
+use crate::lib_telemetry_source::source::{run_query, SourceQuery};
+use crate::telemetry_payloads::energy::dme::{EnergyDemandTelemetry, DME_MANUFACTURER};
 use crate::GlobalVars;
 use std::sync::Arc;
 use std::{collections::HashMap, convert::TryInto};
 
+use crate::telemetry_payloads::energy::meters;
 use crate::telemetry_payloads::energy::padronized::formatPadronizedEnergyTelemetry;
 use crate::telemetry_payloads::energy::{dme::TelemetryDME, padronized::PadronizedEnergyTelemetry};
 use chrono::{Duration, NaiveDateTime};
@@ -26,25 +28,20 @@ pub struct EnergyHistParams {
 impl EnergyHistParams {
     pub async fn process_query(mut self, globs: &Arc<GlobalVars>) -> Result<EnergyHist, String> {
         if let Some(calculate_demand_hour_graphic) = self.calculate_demand_hour_graphic {
-            if self.manufacturer == "Diel Energia" {
-                let demands = self
-                    .process_demand_dme(calculate_demand_hour_graphic, globs)
-                    .await?;
-                return Ok(EnergyHist::new(
-                    self.energy_device_id,
-                    self.serial,
-                    self.manufacturer,
-                    self.model,
-                    Vec::new(),
-                    Some(demands),
-                ));
-            }
+            let demands = self
+                .process_demand(calculate_demand_hour_graphic, globs)
+                .await?;
+            return Ok(EnergyHist::new(
+                self.energy_device_id,
+                self.serial,
+                self.manufacturer,
+                self.model,
+                Vec::new(),
+                Some(demands),
+            ));
         }
 
-        let tels = match &self.manufacturer[..] {
-            "Diel Energia" => self.process_dme_query(globs).await,
-            _ => Err("Unknown manufacturer!".to_string()),
-        }?;
+        let tels = self.process_padronized_query(globs).await?;
 
         Ok(EnergyHist::new(
             self.energy_device_id,
@@ -56,7 +53,7 @@ impl EnergyHistParams {
         ))
     }
 
-    async fn process_dme_query(
+    async fn process_padronized_query(
         &self,
         globs: &Arc<GlobalVars>,
     ) -> Result<Vec<PadronizedEnergyTelemetry>, String> {
@@ -70,10 +67,21 @@ impl EnergyHistParams {
         Ok(formattedFinalTels)
     }
 
-    async fn process_common(
+    // Telemetrias do período já convertidas, sem filtro de parâmetros. Também usada pelo consumo (energy_consumption).
+    pub async fn process_common(
         &self,
         globs: &Arc<GlobalVars>,
     ) -> Result<Vec<PadronizedEnergyTelemetry>, String> {
+        // No DME o medidor é o do "type" de cada payload, nos outros fabricantes é o do cadastro
+        let meter = if self.manufacturer == DME_MANUFACTURER {
+            None
+        } else {
+            Some(
+                meters::registry()
+                    .for_meter(&self.manufacturer, &self.model)
+                    .ok_or_else(|| "Unknown manufacturer!".to_string())?,
+            )
+        };
         let dev_id_upper = self.energy_device_id.to_uppercase();
         let mut table_name = {
             if (self.energy_device_id.len() == 12) && dev_id_upper.starts_with("DRI") {
@@ -96,33 +104,62 @@ impl EnergyHistParams {
 
         let ts_ini = self.start_time.format("%Y-%m-%dT%H:%M:%S").to_string();
         let ts_end = self.end_time.format("%Y-%m-%dT%H:%M:%S").to_string();
-        let querier = crate::lib_dynamodb::query::QuerierDevIdTimestamp::new_custom(
-            table_name.to_owned(),
-            "dev_id".to_owned(),
-            "timestamp".to_owned(),
-            self.energy_device_id.to_owned(),
-            &globs.configfile.aws_config,
-        );
+        let query =
+            SourceQuery::new_diel_dev(table_name.to_owned(), self.energy_device_id.to_owned());
         let mut final_tels = Vec::new();
 
-        querier
-            .run(&ts_ini, &ts_end, &mut |tels: Vec<TelemetryDME>| {
-                let mut x = tels
-                    .into_iter()
-                    .filter_map(|mut tel| {
-                        tel.formulas = self.formulas.clone();
-                        tel.try_into().ok()
-                    })
-                    .collect::<Vec<PadronizedEnergyTelemetry>>();
-                final_tels.append(&mut x);
-                Ok(())
-            })
-            .await?;
+        match meter {
+            None => {
+                run_query(
+                    globs.telemetry_source.as_ref(),
+                    &query,
+                    &ts_ini,
+                    &ts_end,
+                    &mut |tels: Vec<TelemetryDME>| {
+                        let mut x = tels
+                            .into_iter()
+                            .filter_map(|mut tel| {
+                                tel.formulas = self.formulas.clone();
+                                tel.try_into().ok()
+                            })
+                            .collect::<Vec<PadronizedEnergyTelemetry>>();
+                        final_tels.append(&mut x);
+                        Ok(())
+                    },
+                )
+                .await?;
+            }
+            Some(meter) => {
+                run_query(
+                    globs.telemetry_source.as_ref(),
+                    &query,
+                    &ts_ini,
+                    &ts_end,
+                    &mut |tels: Vec<serde_json::Value>| {
+                        let mut x = tels
+                            .into_iter()
+                            .filter_map(|mut tel| {
+                                if let Some(tel) = tel.as_object_mut() {
+                                    tel.insert(
+                                        "formulas".to_owned(),
+                                        serde_json::json!(self.formulas),
+                                    );
+                                }
+                                meter.decode(&tel).ok()
+                            })
+                            .collect::<Vec<PadronizedEnergyTelemetry>>();
+                        final_tels.append(&mut x);
+                        Ok(())
+                    },
+                )
+                .await?;
+            }
+        }
 
         Ok(final_tels)
     }
 
-    async fn process_demand_dme(
+    async fn process_demand(
         &self,
         hour_interval: bool,
         globs: &Arc<GlobalVars>,
@@ -1,10 +1,17 @@
+use super::cache_files::PartCache;
 use super::compiler_queues::MsgToCompilers;
-use super::configs::ConfigFile;
+use super::configs::{ConfigFile, TelemetrySourceConfig};
+use crate::lib_telemetry_source::bigquery::BigQuerySource;
+use crate::lib_telemetry_source::dynamodb::DynamoDBSource;
+use crate::lib_telemetry_source::local_files::LocalFilesSource;
+use crate::lib_telemetry_source::source::TelemetrySource;
 use tokio::sync::mpsc;
 
 pub struct GlobalVars {
     pub configfile: ConfigFile,
     pub to_compiler: mpsc::Sender<MsgToCompilers>,
+    pub telemetry_source: Box<dyn TelemetrySource>,
+    pub part_cache: PartCache,
 }
 
 impl GlobalVars {
@@ -16,10 +23,34 @@ impl GlobalVars {
 pub fn create_globs(configfile: ConfigFile) -> (GlobalVars, mpsc::Receiver<MsgToCompilers>) {
     let (to_compiler, receiver_compiler) = mpsc::channel::<MsgToCompilers>(20000);
 
+    let telemetry_source = create_telemetry_source(&configfile.telemetry_source);
+    crate::LOG.append_log_tag_msg(
+        "INFO",
+        &format!("Telemetry source: {}", telemetry_source.source_name()),
+    );
+
+    let part_cache = PartCache::new(configfile.part_cache.clone());
+
     let globs = GlobalVars {
         configfile,
         to_compiler,
+        telemetry_source,
+        part_cache,
     };
 
     (globs, receiver_compiler)
 }
+
+fn create_telemetry_source(config: &TelemetrySourceConfig) -> Box<dyn TelemetrySource> {
+    match config {
+        TelemetrySourceConfig::DynamoDB(aws_config) => Box::new(DynamoDBSource {
+            aws_config: aws_config.clone(),
+        }),
+        TelemetrySourceConfig::BigQuery(config) => Box::new(BigQuerySource {
+            config: config.clone(),
+        }),
+        TelemetrySourceConfig::LocalFiles(root_dir) => Box::new(LocalFilesSource {
+            root_dir: root_dir.to_owned(),
+        }),
+    }
+}
@@ -1,12 +1,24 @@
 use super::cache_files::process_clear_cache;
 use super::compiler_queues::MsgToCompilers;
 use crate::app_history::compiler_queues::CompilationRequest;
-use crate::app_history::{dri_hist, energy_hist, energy_stats};
-use crate::lib_http::response::{respond_http_plain_text, send_response};
+use crate::app_history::{
+    dri_hist, energy_consumption, energy_hist, energy_stats, refrigerant_sat,
+};
+use crate::lib_http::auth::ApiTokens;
+use crate::lib_http::response::{
+    build_http_response, respond_http_json_serializable, respond_http_plain_text,
+};
+use crate::lib_http::router::{
+    respond_and_log, AuthPolicy, BodyParser, Handler, Method, Route, RouteRequest, RouteResult,
+    RouteSpec, Router, Visibility,
+};
 use crate::lib_http::types::{HttpRequest, HttpResponse};
+use crate::lib_metrics::{MetricsText, CONTENT_TYPE, REGISTRY};
 use crate::GlobalVars;
-use std::sync::Arc;
+use futures::future::BoxFuture;
+use std::sync::{Arc, OnceLock};
 use tokio::net::TcpStream;
+use tokio::sync::oneshot;
 
 /* TODO:
  - Comando para acrescentar pacote de até 60000 segundos em um arquivo. (cria o arquivo se não existir)
@@ -18,280 +30,329 @@ use tokio::net::TcpStream;
    - pode retornar dados de Temperatura e Pressão já convertidos, suavizados, em baixa resolução, tudo passado como parâmetro, no formato "1*120,0*300,*50"
 */
 
+// Rotas de compilação colocam a requisição na fila dos compiladores, que respondem pelo próprio socket
+type Deferred = (CompilationRequest, String);
+
+static ROUTER: OnceLock<Router<Deferred>> = OnceLock::new();
+
+fn build_router(globs: &Arc<GlobalVars>) -> Router<Deferred> {
+    use AuthPolicy::{ExternalToken, None as NoAuth};
+    use BodyParser::{Json, Raw};
+    use Method::{Any, Get, Post};
+    use Visibility::{Internal, Public};
+
+    // Requisições externas são uma nova funcionalidade. Só algumas rotas são públicas por enquanto.
+    #[rustfmt::skip]
+    let routes = vec![
+        Route::new(Any,  "/",                       Public,   NoAuth,                   Raw,  Handler::Sync(root)),
+        Route::new(Any,  "/health_check",           Public,   NoAuth,                   Raw,  Handler::Sync(health_check)),
+        Route::new(Any,  "/clear-cache",            Internal, NoAuth,                   Json, Handler::Sync(clear_cache)),
+        Route::new(Any,  "/comp-dri",               Internal, NoAuth,                   Json, Handler::Sync(comp_dri)),
+        Route::new(Any,  "/comp-dut",               Public,   ExternalToken("hist"),    Json, Handler::Sync(comp_dut)),
+        Route::new(Any,  "/comp-dma",               Internal, NoAuth,                   Json, Handler::Sync(comp_dma)),
+        Route::new(Any,  "/comp-dmt",               Internal, NoAuth,                   Json, Handler::Sync(comp_dmt)),
+        Route::new(Any,  "/comp-dal",               Internal, NoAuth,                   Json, Handler::Sync(comp_dal)),
+        Route::new(Any,  "/comp-dac-v2",            Public,   ExternalToken("hist"),    Json, Handler::Sync(comp_dac_v2)),
+        Route::new(Any,  "/comp-dam",               Public,   ExternalToken("hist"),    Json, Handler::Sync(comp_dam)),
+        Route::new(Any,  "/comp-dac-range",         Public,   ExternalToken("hist"),    Json, Handler::Sync(comp_dac_range)),
+        Route::new(Any,  "/comp-dut-range",         Public,   ExternalToken("hist"),    Json, Handler::Sync(comp_dut_range)),
+        Route::new(Any,  "/energy-query",           Internal, NoAuth,                   Json, Handler::Sync(energy_query)),
+        Route::new(Any,  "/energy-stats",           Internal, NoAuth,                   Json, Handler::Sync(energy_stats)),
+        Route::new(Any,  "/energy-consumption",     Internal, NoAuth,                   Json, Handler::Sync(energy_consumption)),
+        Route::new(Any,  "/export-dev-telemetries", Public,   ExternalToken("export"),  Json, Handler::Sync(export_dev_telemetries)),
+        Route::new(Any,  "/metrics",                Public,   ExternalToken("metrics"), Raw,  Handler::Sync(metrics)),
+        Route::new(Get,  "/queue-status",           Public,   ExternalToken("metrics"), Raw,  Handler::Async(queue_status)),
+        Route::new(Get,  "/cache-status",           Public,   ExternalToken("metrics"), Raw,  Handler::Sync(cache_status)),
+        Route::new(Get,  "/refrigerants",           Public,   ExternalToken("hist"),    Raw,  Handler::Sync(refrigerants)),
+        Route::new(Post, "/refrigerant-saturation", Public,   ExternalToken("hist"),    Json, Handler::Sync(refrigerant_saturation)),
+    ];
+
+    Router::new(
+        ApiTokens::new(globs.configfile.HTTP_API_TOKENS.clone()),
+        routes,
+    )
+    .with_middleware(Box::new(avoid_cache_on_external))
+}
+
 pub async fn on_http_req(
     req: HttpRequest,
     is_internal: bool,
     mut socket: TcpStream,
     globs: Arc<GlobalVars>,
 ) {
-    // let parsed_url = Url::parse("http://example.com/?a=1&b=2&c=3").unwrap();
-    // let hash_query: HashMap<_, _> = parsed_url.query_pairs().into_owned().collect();
-    // hash_query.get("a") = "1"
-    // parsed_url.path() = "/rust-lang/rust/issues"
-    // parsed_url.path_segments().map(|c| c.collect::<Vec<_>>()) = vec!["rust-lang", "rust", "issues"]
-
-    if !is_internal {
-        // Requisições externas são uma nova funcionalidade. Só algumas rotas estão permitidas por enquanto.
-        match &req.path[..] {
-            "/" => {}                       // OK
-            "/comp-dut" => {}               // OK
-            "/comp-dac-v2" => {}            // OK
-            "/comp-dam" => {}               // OK
-            "/health_check" => {}           // OK
-            "/export-dev-telemetries" => {} // OK
-            _ => {
-                crate::LOG.append_log_tag_msg(
-                    "ERROR",
-                    &format!("Invalid external request: {} {}", req.method, req.path),
-                );
-                let response =
-                    respond_http_plain_text(404, &(String::new() + "Not found: " + &req.path));
-                if let Err(err) = {
-                    send_response(&mut socket, &response).await // socket_write
-                } {
-                    crate::LOG.append_log_tag_msg("ERROR[51]", &err.to_string());
-                }
-            }
+    let router = ROUTER.get_or_init(|| build_router(&globs));
+    match router.dispatch(req, is_internal, &globs).await {
+        RouteResult::Respond(response) => {
+            respond_and_log(&mut socket, &response).await;
         }
-    }
-
-    // Verificar se é um endpoint síncrono
-    let response = match sync_routes(&req) {
-        Ok(x) => x,
-        Err(err) => Some(respond_http_plain_text(500, &err)),
-    };
-    if let Some(response) = response {
-        if let Err(err) = {
-            send_response(&mut socket, &response).await // socket_write
-        } {
-            crate::LOG.append_log_tag_msg("ERROR[66]", &err.to_string());
-        }
-        return;
-    }
-
-    // Os próximos endpoints são tarefas que devem ser enfileiradas
-    match async_routes(&req, is_internal, &globs) {
-        Ok((request, dev_id)) => {
+        RouteResult::Defer((request, dev_id)) => {
             globs
                 .to_compiler
                 .send(MsgToCompilers::NewRequest(socket, request, dev_id))
                 .await;
         }
-        Err(response) => {
-            // let response = respond_http_plain_text(500, &err);
-            if let Err(err) = {
-                send_response(&mut socket, &response).await // socket_write
-            } {
-                crate::LOG.append_log_tag_msg("ERROR[81]", &err.to_string());
-            }
-        }
     };
 }
 
-fn sync_routes(req: &HttpRequest) -> Result<Option<HttpResponse>, String> {
-    match &req.path[..] {
-        "/" => {
-            return Ok(Some(respond_http_plain_text(200, "Olá")));
-        }
-        "/health_check" => {
-            return Ok(Some(respond_http_plain_text(200, "Alive")));
-        }
-        "/clear-cache" => {
-            let body_str = String::from_utf8_lossy(&req.content);
-            let json_body =
-                serde_json::from_str(&body_str).map_err(|e| format!("ERROR37: {}", e))?;
-            return process_clear_cache(json_body).map(|r| Some(r));
-        }
-        _ => {
-            return Ok(None);
-        }
-    };
+// Compilações pedidas de fora não usam nem alimentam o cache de arquivos
+fn avoid_cache_on_external(spec: &RouteSpec, rreq: &mut RouteRequest) -> Result<(), HttpResponse> {
+    if rreq.is_internal || !spec.path.starts_with("/comp-") {
+        return Ok(());
+    }
+    if let Some(json_body) = rreq.json_mut() {
+        json_body["avoid_cache"] = true.into();
+    }
+    Ok(())
 }
 
-fn async_routes(
-    req: &HttpRequest,
-    is_internal: bool,
+fn root(
+    _rreq: &RouteRequest,
+    _globs: &Arc<GlobalVars>,
+) -> Result<RouteResult<Deferred>, HttpResponse> {
+    Ok(RouteResult::Respond(respond_http_plain_text(200, "Olá")))
+}
+
+fn health_check(
+    _rreq: &RouteRequest,
+    _globs: &Arc<GlobalVars>,
+) -> Result<RouteResult<Deferred>, HttpResponse> {
+    Ok(RouteResult::Respond(respond_http_plain_text(200, "Alive")))
+}
+
+fn metrics(
+    _rreq: &RouteRequest,
+    _globs: &Arc<GlobalVars>,
+) -> Result<RouteResult<Deferred>, HttpResponse> {
+    let mut out = MetricsText::new();
+    REGISTRY.render(&mut out);
+    Ok(RouteResult::Respond(build_http_response(
+        200,
+        out.into_bytes(),
+        CONTENT_TYPE,
+    )))
+}
+
+// Compilações rodando e aguardando na fila, com a idade de cada uma
+fn queue_status<'a>(
+    _rreq: &'a RouteRequest,
+    globs: &'a Arc<GlobalVars>,
+) -> BoxFuture<'a, Result<RouteResult<Deferred>, HttpResponse>> {
+    Box::pin(async move {
+        let (reply, status) = oneshot::channel();
+        globs
+            .to_compiler
+            .send(MsgToCompilers::GetStatus(reply))
+            .await
+            .map_err(|e| respond_http_plain_text(500, &format!("ERROR[71] {}", e)))?;
+        let status = status
+            .await
+            .map_err(|e| respond_http_plain_text(500, &format!("ERROR[72] {}", e)))?;
+        Ok(RouteResult::Respond(respond_http_json_serializable(
+            200, status,
+        )))
+    })
+}
+
+fn cache_status(
+    _rreq: &RouteRequest,
     globs: &Arc<GlobalVars>,
-) -> Result<(CompilationRequest, String), HttpResponse> {
-    match &req.path[..] {
-        "/comp-dri" => {
-            let body_str = String::from_utf8_lossy(&req.content);
-            let body = serde_json::from_str::<dri_hist::DriHistParams>(&body_str)
-                .map_err(|e| respond_http_plain_text(400, &e.to_string()))?;
-            let dev_id = body.dev_id.to_owned();
-            return Ok((CompilationRequest::CompDri(body), dev_id));
-        }
-        "/comp-dut" => {
-            let body_str = String::from_utf8_lossy(&req.content);
-            let mut json_body: serde_json::Value = serde_json::from_str(&body_str)
-                .map_err(|e| respond_http_plain_text(400, &format!("ERROR44: {}", e)))?;
-            if !is_internal {
-                if let (Some(token), Some(allowed_token)) = (
-                    json_body["token"].as_str(),
-                    &globs.configfile.EXTERNAL_REQUESTS_TOKEN,
-                ) {
-                    if token != allowed_token {
-                        return Err(respond_http_plain_text(403, "Token inválido"));
-                    }
-                } else {
-                    return Err(respond_http_plain_text(403, "Token não fornecido"));
-                }
-                json_body["avoid_cache"] = true.into();
-            }
-            let rpars = crate::app_history::dut_hist::parse_parameters(&json_body)?;
-            let dev_id = rpars.dev_id.to_owned();
-            return Ok((CompilationRequest::CompDut(rpars), dev_id));
-        }
-        "/comp-dma" => {
-            let body_str = String::from_utf8_lossy(&req.content);
-            let mut json_body: serde_json::Value = serde_json::from_str(&body_str)
-                .map_err(|e| respond_http_plain_text(400, &format!("ERROR44: {}", e)))?;
-            if !is_internal {
-                if let (Some(token), Some(allowed_token)) = (
-                    json_body["token"].as_str(),
-                    &globs.configfile.EXTERNAL_REQUESTS_TOKEN,
-                ) {
-                    if token != allowed_token {
-                        return Err(respond_http_plain_text(403, "Token inválido"));
-                    }
-                } else {
-                    return Err(respond_http_plain_text(403, "Token não fornecido"));
-                }
-                json_body["avoid_cache"] = true.into();
-            }
-            let rpars = crate::app_history::dma_hist::parse_parameters(&json_body)?;
-            let dev_id = rpars.dev_id.to_owned();
-            return Ok((CompilationRequest::CompDma(rpars), dev_id));
-        }
-        "/comp-dmt" => {
-            let body_str = String::from_utf8_lossy(&req.content);
-            let mut json_body: serde_json::Value = serde_json::from_str(&body_str)
-                .map_err(|e| respond_http_plain_text(400, &format!("ERROR44: {}", e)))?;
-            if !is_internal {
-                if let (Some(token), Some(allowed_token)) = (
-                    json_body["token"].as_str(),
-                    &globs.configfile.EXTERNAL_REQUESTS_TOKEN,
-                ) {
-                    if token != allowed_token {
-                        return Err(respond_http_plain_text(403, "Token inválido"));
-                    }
-                } else {
-                    return Err(respond_http_plain_text(403, "Token não fornecido"));
-                }
-                json_body["avoid_cache"] = true.into();
-            }
-            let rpars = crate::app_history::dmt_hist::parse_parameters(&json_body)?;
-            let dev_id = rpars.dev_id.to_owned();
-            return Ok((CompilationRequest::CompDmt(rpars), dev_id));
-        }
-        "/comp-dal" => {
-            let body_str = String::from_utf8_lossy(&req.content);
-            let mut json_body: serde_json::Value = serde_json::from_str(&body_str)
-                .map_err(|e| respond_http_plain_text(400, &format!("ERROR44: {}", e)))?;
-            if !is_internal {
-                if let (Some(token), Some(allowed_token)) = (
-                    json_body["token"].as_str(),
-                    &globs.configfile.EXTERNAL_REQUESTS_TOKEN,
-                ) {
-                    if token != allowed_token {
-                        return Err(respond_http_plain_text(403, "Token inválido"));
-                    }
-                } else {
-                    return Err(respond_http_plain_text(403, "Token não fornecido"));
-                }
-                json_body["avoid_cache"] = true.into();
-            }
-            let rpars = crate::app_history::dal_hist::parse_parameters(&json_body)?;
-            let dev_id = rpars.dev_id.to_owned();
-            return Ok((CompilationRequest::CompDal(rpars), dev_id));
-        }
-        "/comp-dac-v2" => {
-            let body_str = String::from_utf8_lossy(&req.content);
-            let mut json_body: serde_json::Value = serde_json::from_str(&body_str)
-                .map_err(|e| respond_http_plain_text(400, &format!("ERROR37: {}", e)))?;
-            if !is_internal {
-                if let (Some(token), Some(allowed_token)) = (
-                    json_body["token"].as_str(),
-                    &globs.configfile.EXTERNAL_REQUESTS_TOKEN,
-                ) {
-                    if token != allowed_token {
-                        return Err(respond_http_plain_text(403, "Token inválido"));
-                    }
-                } else {
-                    return Err(respond_http_plain_text(403, "Token não fornecido"));
-                }
-                json_body["avoid_cache"] = true.into();
-            }
-            let rpars = crate::app_history::dac_hist::parse_parameters(&json_body)?;
-            let dev_id = rpars.dev_id.to_owned();
-            return Ok((CompilationRequest::CompDacV2(rpars), dev_id));
-        }
-        "/comp-dam" => {
-            let body_str = String::from_utf8_lossy(&req.content);
-            let mut json_body: serde_json::Value = serde_json::from_str(&body_str)
-                .map_err(|e| respond_http_plain_text(400, &format!("ERROR42: {}", e)))?;
-            if !is_internal {
-                if let (Some(token), Some(allowed_token)) = (
-                    json_body["token"].as_str(),
-                    &globs.configfile.EXTERNAL_REQUESTS_TOKEN,
-                ) {
-                    if token != allowed_token {
-                        return Err(respond_http_plain_text(403, "Token inválido"));
-                    }
-                } else {
-                    return Err(respond_http_plain_text(403, "Token não fornecido"));
-                }
-                json_body["avoid_cache"] = true.into();
-            }
-            let rpars = crate::app_history::dam_hist::parse_parameters(&json_body)?;
-            let dev_id = rpars.dev_id.to_owned();
-            return Ok((CompilationRequest::CompDam(rpars), dev_id));
-        }
-        "/energy-query" => {
-            let body_str = String::from_utf8_lossy(&req.content);
-            let body = serde_json::from_str::<energy_hist::EnergyHistParams>(&body_str)
-                .map_err(|e| respond_http_plain_text(400, &e.to_string()))?;
-            let dev_id = body.energy_device_id.to_owned();
-            return Ok((CompilationRequest::EnergyQuery(body), dev_id));
-        }
-        "/energy-stats" => {
-            let body_str = String::from_utf8_lossy(&req.content);
-            let body = serde_json::from_str::<energy_stats::EnergyStatParams>(&body_str)
-                .map_err(|e| respond_http_plain_text(400, &e.to_string()))?;
-            let dev_id = body.energy_device_id.to_owned();
-            return Ok((CompilationRequest::EnergyStats(body), dev_id));
-        }
-        "/export-dev-telemetries" => {
-            // curl 'http://api.dielenergia.com:29547/export-dev-telemetries' --data-raw '{"token":"...","dev_id":"DRI008220235","table_name":"DRI00822XXXX_RAW","day_YMD":"2023-03-21"}' > 2023-03-21-DRI008220235.txt
-            let body_str = std::str::from_utf8(&req.content)
-                .map_err(|e| respond_http_plain_text(400, &format!("ERROR154: {}", e)))?;
-            let json_body: serde_json::Value = serde_json::from_str(&body_str)
-                .map_err(|e| respond_http_plain_text(400, &format!("ERROR155: {}", e)))?;
-            if !is_internal {
-                if let (Some(token), Some(allowed_token)) = (
-                    json_body["token"].as_str(),
-                    &globs.configfile.EXTERNAL_REQUESTS_TOKEN,
-                ) {
-                    if token != allowed_token {
-                        return Err(respond_http_plain_text(403, "Token inválido"));
-                    }
-                } else {
-                    return Err(respond_http_plain_text(403, "Token não fornecido"));
-                }
-            }
-            let rpars = crate::app_history::dev_export::parse_parameters(&json_body)?;
-            let dev_id = rpars.dev_id.to_owned();
-            return Ok((CompilationRequest::ExportDevTelemetries(rpars), dev_id));
-        }
-        _ => {
-            crate::LOG.append_log_tag_msg(
-                "ERROR",
-                &format!("Invalid request: {} {}", req.method, req.path),
-            );
-            return Err(respond_http_plain_text(
-                404,
-                &(String::new() + "Not found: " + &req.path),
-            ));
-        }
-    }
+) -> Result<RouteResult<Deferred>, HttpResponse> {
+    Ok(RouteResult::Respond(respond_http_json_serializable(
+        200,
+        globs.part_cache.status(),
+    )))
+}
+
+fn clear_cache(
+    rreq: &RouteRequest,
+    globs: &Arc<GlobalVars>,
+) -> Result<RouteResult<Deferred>, HttpResponse> {
+    let response = process_clear_cache(rreq.json().clone(), &globs.part_cache)
+        .unwrap_or_else(|err| respond_http_plain_text(500, &err));
+    Ok(RouteResult::Respond(response))
+}
+
+fn comp_dri(
+    rreq: &RouteRequest,
+    _globs: &Arc<GlobalVars>,
+) -> Result<RouteResult<Deferred>, HttpResponse> {
+    let body = serde_json::from_value::<dri_hist::DriHistParams>(rreq.json().clone())
+        .map_err(|e| respond_http_plain_text(400, &e.to_string()))?;
+    let dev_id = body.dev_id.to_owned();
+    Ok(RouteResult::Defer((
+        CompilationRequest::CompDri(body),
+        dev_id,
+    )))
+}
+
+fn comp_dut(
+    rreq: &RouteRequest,
+    _globs: &Arc<GlobalVars>,
+) -> Result<RouteResult<Deferred>, HttpResponse> {
+    let rpars = crate::app_history::dut_hist::parse_parameters(rreq.json())?;
+    let dev_id = rpars.dev_id.to_owned();
+    Ok(RouteResult::Defer((
+        CompilationRequest::CompDut(rpars),
+        dev_id,
+    )))
+}
+
+fn comp_dma(
+    rreq: &RouteRequest,
+    _globs: &Arc<GlobalVars>,
+) -> Result<RouteResult<Deferred>, HttpResponse> {
+    let rpars = crate::app_history::dma_hist::parse_parameters(rreq.json())?;
+    let dev_id = rpars.dev_id.to_owned();
+    Ok(RouteResult::Defer((
+        CompilationRequest::CompDma(rpars),
+        dev_id,
+    )))
+}
+
+fn comp_dmt(
+    rreq: &RouteRequest,
+    _globs: &Arc<GlobalVars>,
+) -> Result<RouteResult<Deferred>, HttpResponse> {
+    let rpars = crate::app_history::dmt_hist::parse_parameters(rreq.json())?;
+    let dev_id = rpars.dev_id.to_owned();
+    Ok(RouteResult::Defer((
+        CompilationRequest::CompDmt(rpars),
+        dev_id,
+    )))
+}
+
+fn comp_dal(
+    rreq: &RouteRequest,
+    _globs: &Arc<GlobalVars>,
+) -> Result<RouteResult<Deferred>, HttpResponse> {
+    let rpars = crate::app_history::dal_hist::parse_parameters(rreq.json())?;
+    let dev_id = rpars.dev_id.to_owned();
+    Ok(RouteResult::Defer((
+        CompilationRequest::CompDal(rpars),
+        dev_id,
+    )))
+}
+
+fn comp_dac_v2(
+    rreq: &RouteRequest,
+    _globs: &Arc<GlobalVars>,
+) -> Result<RouteResult<Deferred>, HttpResponse> {
+    let rpars = crate::app_history::dac_hist::parse_parameters(rreq.json())?;
+    let dev_id = rpars.dev_id.to_owned();
+    Ok(RouteResult::Defer((
+        CompilationRequest::CompDacV2(rpars),
+        dev_id,
+    )))
+}
+
+fn comp_dam(
+    rreq: &RouteRequest,
+    _globs: &Arc<GlobalVars>,
+) -> Result<RouteResult<Deferred>, HttpResponse> {
+    let rpars = crate::app_history::dam_hist::parse_parameters(rreq.json())?;
+    let dev_id = rpars.dev_id.to_owned();
+    Ok(RouteResult::Defer((
+        CompilationRequest::CompDam(rpars),
+        dev_id,
+    )))
+}
+
+fn comp_dac_range(
+    rreq: &RouteRequest,
+    _globs: &Arc<GlobalVars>,
+) -> Result<RouteResult<Deferred>, HttpResponse> {
+    let rpars = crate::app_history::range_hist::parse_parameters_dac(rreq.json())?;
+    let dev_id = rpars.dev_id.to_owned();
+    Ok(RouteResult::Defer((
+        CompilationRequest::CompRange(rpars),
+        dev_id,
+    )))
+}
+
+fn comp_dut_range(
+    rreq: &RouteRequest,
+    _globs: &Arc<GlobalVars>,
+) -> Result<RouteResult<Deferred>, HttpResponse> {
+    let rpars = crate::app_history::range_hist::parse_parameters_dut(rreq.json())?;
+    let dev_id = rpars.dev_id.to_owned();
+    Ok(RouteResult::Defer((
+        CompilationRequest::CompRange(rpars),
+        dev_id,
+    )))
+}
+
+fn energy_query(
+    rreq: &RouteRequest,
+    _globs: &Arc<GlobalVars>,
+) -> Result<RouteResult<Deferred>, HttpResponse> {
+    let body = serde_json::from_value::<energy_hist::EnergyHistParams>(rreq.json().clone())
+        .map_err(|e| respond_http_plain_text(400, &e.to_string()))?;
+    let dev_id = body.energy_device_id.to_owned();
+    Ok(RouteResult::Defer((
+        CompilationRequest::EnergyQuery(body),
+        dev_id,
+    )))
+}
+
+fn energy_stats(
+    rreq: &RouteRequest,
+    _globs: &Arc<GlobalVars>,
+) -> Result<RouteResult<Deferred>, HttpResponse> {
+    let body = serde_json::from_value::<energy_stats::EnergyStatParams>(rreq.json().clone())
+        .map_err(|e| respond_http_plain_text(400, &e.to_string()))?;
+    let dev_id = body.energy_device_id.to_owned();
+    Ok(RouteResult::Defer((
+        CompilationRequest::EnergyStats(body),
+        dev_id,
+    )))
+}
+
+fn energy_consumption(
+    rreq: &RouteRequest,
+    _globs: &Arc<GlobalVars>,
+) -> Result<RouteResult<Deferred>, HttpResponse> {
+    let body =
+        serde_json::from_value::<energy_consumption::EnergyConsumptionParams>(rreq.json().clone())
+            .map_err(|e| respond_http_plain_text(400, &e.to_string()))?;
+    let dev_id = body.energy_device_id.to_owned();
+    Ok(RouteResult::Defer((
+        CompilationRequest::EnergyConsumption(body),
+        dev_id,
+    )))
+}
+
+fn export_dev_telemetries(
+    rreq: &RouteRequest,
+    _globs: &Arc<GlobalVars>,
+) -> Result<RouteResult<Deferred>, HttpResponse> {
+    // curl 'http://api.dielenergia.com:29547/export-dev-telemetries' --data-raw '{"token":"...","dev_id":"DRI008220235","table_name":"DRI00822XXXX_RAW","day_YMD":"2023-03-21"}' > 2023-03-21-DRI008220235.txt
+    let rpars = crate::app_history::dev_export::parse_parameters(rreq.json())?;
+    let dev_id = rpars.dev_id.to_owned();
+    Ok(RouteResult::Defer((
+        CompilationRequest::ExportDevTelemetries(rpars),
+        dev_id,
+    )))
+}
+
+// Fluidos disponíveis, com a revisão e a origem de cada tabela
+fn refrigerants(
+    _rreq: &RouteRequest,
+    _globs: &Arc<GlobalVars>,
+) -> Result<RouteResult<Deferred>, HttpResponse> {
+    let list = crate::refrigerants::registry().list();
+    Ok(RouteResult::Respond(respond_http_json_serializable(
+        200, &list,
+    )))
+}
+
+fn refrigerant_saturation(
+    rreq: &RouteRequest,
+    _globs: &Arc<GlobalVars>,
+) -> Result<RouteResult<Deferred>, HttpResponse> {
+    let body = serde_json::from_value::<refrigerant_sat::SaturationParams>(rreq.json().clone())
+        .map_err(|e| respond_http_plain_text(400, &e.to_string()))?;
+    let response = refrigerant_sat::process(&body).map_err(|e| respond_http_plain_text(400, &e))?;
+    Ok(RouteResult::Respond(respond_http_json_serializable(
+        200, &response,
+    )))
 }
@@ -0,0 +1,329 @@
+use super::{dac_hist, dut_hist};
+use crate::compression::downsample::{Aggregation, Downsampler};
+use crate::lib_http::response::{respond_http_json, respond_http_plain_text};
+use crate::lib_http::types::HttpResponse;
+use crate::GlobalVars;
+use chrono::{Duration, NaiveDateTime};
+use std::sync::Arc;
+
+/*
+Compilação de um intervalo qualquer (vários dias) em uma requisição só.
+Cada dia é compilado como se fosse a requisição diária do dashboard, então os arquivos .part são
+compartilhados. O estado do L1 passa de um dia para o outro, evitando consultar de novo os 15 minutos de
+preparação. No fim os vetores são recortados para [ts_ini, ts_end) e reamostrados com `resolution_s`.
+*/
+
+const DAY_LENGTH_S: i64 = 24 * 60 * 60;
+const MAX_RANGE_DAYS: i64 = 31;
+// Quantidade de pontos usada quando a resolução não é informada
+const DEFAULT_POINTS: i64 = 1440;
+
+pub enum RangeDays {
+    Dac(Vec<dac_hist::ReqParameters>),
+    Dut(Vec<dut_hist::ReqParameters>),
+}
+
+pub struct RangeParameters {
+    pub dev_id: String,
+    pub ts_ini: String,
+    pub ts_end: String,
+    pub resolution_s: i64,
+    // Segundos entre o início do primeiro dia e ts_ini
+    pub offset_s: i64,
+    pub length_s: i64,
+    pub days: RangeDays,
+}
+
+impl RangeParameters {
+    pub fn kind(&self) -> &'static str {
+        match self.days {
+            RangeDays::Dac(_) => "dac_range",
+            RangeDays::Dut(_) => "dut_range",
+        }
+    }
+}
+
+pub fn parse_parameters_dac(parsed: &serde_json::Value) -> Result<RangeParameters, HttpResponse> {
+    let (mut rpars, day_starts) = parse_range(parsed)?;
+    let mut days = Vec::with_capacity(day_starts.len());
+    for day_start in day_starts {
+        // Mesmos parâmetros da requisição diária, para usar o mesmo arquivo de cache
+        let mut day_body = parsed.clone();
+        day_body["ts_ini"] = day_start.format("%Y-%m-%dT%H:%M:%S").to_string().into();
+        day_body["interval_length_s"] = DAY_LENGTH_S.into();
+        day_body["open_end"] = false.into();
+        days.push(dac_hist::parse_parameters(&day_body)?);
+    }
+    rpars.days = RangeDays::Dac(days);
+    Ok(rpars)
+}
+
+pub fn parse_parameters_dut(parsed: &serde_json::Value) -> Result<RangeParameters, HttpResponse> {
+    let (mut rpars, day_starts) = parse_range(parsed)?;
+    let mut days = Vec::with_capacity(day_starts.len());
+    for day_start in day_starts {
+        let mut day_body = parsed.clone();
+        day_body["day"] = day_start.format("%Y-%m-%d").to_string().into();
+        day_body["open_end"] = false.into();
+        days.push(dut_hist::parse_parameters(&day_body)?);
+    }
+    rpars.days = RangeDays::Dut(days);
+    Ok(rpars)
+}
+
+fn parse_range(
+    parsed: &serde_json::Value,
+) -> Result<(RangeParameters, Vec<NaiveDateTime>), HttpResponse> {
+    let dev_id = match parsed["dev_id"].as_str() {
+        Some(v) => v,
+        None => {
+            return Err(respond_http_plain_text(400, "Missing dev_id"));
+        }
+    };
+    let ts_ini = parse_timestamp(&parsed["ts_ini"], "ts_ini")?;
+    let ts_end = parse_timestamp(&parsed["ts_end"], "ts_end")?;
+    if ts_end <= ts_ini {
+        return Err(respond_http_plain_text(400, "ts_end must be after ts_ini"));
+    }
+    let length_s = (ts_end - ts_ini).num_seconds();
+
+    let first_day = ts_ini.date().and_hms_opt(0, 0, 0).unwrap();
+    let last_day = (ts_end - Duration::seconds(1))
+        .date()
+        .and_hms_opt(0, 0, 0)
+        .unwrap();
+    let num_days = (last_day - first_day).num_days() + 1;
+    if num_days > MAX_RANGE_DAYS {
+        return Err(respond_http_plain_text(
+            400,
+            &format!("Range too long (max {} days)", MAX_RANGE_DAYS),
+        ));
+    }
+
+    let resolution_s = match &parsed["resolution_s"] {
+        serde_json::Value::Null => ((length_s + DEFAULT_POINTS - 1) / DEFAULT_POINTS).max(1),
+        value => match value.as_i64() {
+            Some(v) if v >= 1 && v <= DAY_LENGTH_S => v,
+            _ => {
+                return Err(respond_http_plain_text(400, "Invalid resolution_s"));
+            }
+        },
+    };
+
+    let day_starts = (0..num_days)
+        .map(|i| first_day + Duration::days(i))
+        .collect();
+    let rpars = RangeParameters {
+        dev_id: dev_id.to_owned(),
+        ts_ini: ts_ini.format("%Y-%m-%dT%H:%M:%S").to_string(),
+        ts_end: ts_end.format("%Y-%m-%dT%H:%M:%S").to_string(),
+        resolution_s,
+        offset_s: (ts_ini - first_day).num_seconds(),
+        length_s,
+        days: RangeDays::Dac(Vec::new()),
+    };
+    Ok((rpars, day_starts))
+}
+
+fn parse_timestamp(value: &serde_json::Value, name: &str) -> Result<NaiveDateTime, HttpResponse> {
+    let text = match value.as_str() {
+        Some(v) => v,
+        None => {
+            return Err(respond_http_plain_text(400, &format!("Missing {}", name)));
+        }
+    };
+    NaiveDateTime::parse_from_str(text, "%Y-%m-%dT%H:%M:%S").map_err(|err| {
+        crate::LOG.append_log_tag_msg("ERROR", &format!("{} {}", text, err));
+        respond_http_plain_text(400, "Error parsing Date")
+    })
+}
+
+// Uma série reamostrada por variável
+struct RangeSeries {
+    vars: Vec<(&'static str, Downsampler)>,
+}
+
+impl RangeSeries {
+    fn new(rpars: &RangeParameters, vars: &[(&'static str, Aggregation)]) -> RangeSeries {
+        RangeSeries {
+            vars: vars
+                .iter()
+                .map(|(name, aggregation)| {
+                    let downsampler = Downsampler::new(
+                        *aggregation,
+                        rpars.resolution_s as isize,
+                        rpars.offset_s as isize,
+                        rpars.length_s as isize,
+                    );
+                    (*name, downsampler)
+                })
+                .collect(),
+        }
+    }
+
+    // Variáveis que não vierem no dia ficam vazias
+    fn push_day(&mut self, values: &[(&str, &str)]) {
+        for (name, downsampler) in &mut self.vars {
+            let vec = values
+                .iter()
+                .find(|(n, _)| n == name)
+                .map_or("", |(_, v)| v);
+            downsampler.push_vector(vec, DAY_LENGTH_S as isize);
+        }
+    }
+
+    fn into_response(self, rpars: &RangeParameters, provision_error: bool) -> HttpResponse {
+        let mut data = serde_json::json!({
+            "ts_ini": rpars.ts_ini,
+            "ts_end": rpars.ts_end,
+            "resolution_s": rpars.resolution_s,
+            "provision_error": provision_error,
+        });
+        for (name, downsampler) in self.vars {
+            data[name] = downsampler.finish().into();
+        }
+        respond_http_json(200, &data.to_string())
+    }
+}
+
+pub async fn process_comp_range(
+    rpars: RangeParameters,
+    globs: &Arc<GlobalVars>,
+) -> Result<HttpResponse, String> {
+    let response = match &rpars.days {
+        RangeDays::Dac(days) => compile_range_dac(&rpars, days, globs).await,
+        RangeDays::Dut(days) => compile_range_dut(&rpars, days, globs).await,
+    };
+    Ok(response.unwrap_or_else(|response| response))
+}
+
+async fn compile_range_dac(
+    rpars: &RangeParameters,
+    days: &[dac_hist::ReqParameters],
+    globs: &Arc<GlobalVars>,
+) -> Result<HttpResponse, HttpResponse> {
+    let hw_cfg = &days[0].hw_cfg;
+    let has_Psuc = hw_cfg.P0Psuc || hw_cfg.P1Psuc;
+    let has_Pliq = hw_cfg.P0Pliq || hw_cfg.P1Pliq;
+
+    let mut vars = vec![
+        ("Lcmp", Aggregation::Majority),
+        ("Tamb", Aggregation::Mean),
+        ("Tsuc", Aggregation::Mean),
+        ("Tliq", Aggregation::Mean),
+        ("State", Aggregation::Majority),
+        ("Mode", Aggregation::Majority),
+    ];
+    if has_Psuc {
+        vars.push(("Psuc", Aggregation::Mean));
+    }
+    if has_Pliq {
+        vars.push(("Pliq", Aggregation::Mean));
+    }
+    if hw_cfg.hasAutomation {
+        vars.push(("Levp", Aggregation::Majority));
+        vars.push(("Lcut", Aggregation::Majority));
+    }
+    let mut series: Option<RangeSeries> = None;
+
+    let mut l1_state = None;
+    let mut provision_error = false;
+    for day in days {
+        let compiled = match dac_hist::compile_interval(day, globs, l1_state.take()).await? {
+            Some(v) => v,
+            None => {
+                return Ok(respond_http_json(200, "{}"));
+            }
+        };
+        // As variáveis calculadas só são conhecidas depois de compilar o primeiro dia
+        let series = series.get_or_insert_with(|| {
+            if compiled.has_calcs && has_Psuc {
+                vars.push(("Tsh", Aggregation::Mean));
+            }
+            if compiled.has_calcs && has_Pliq {
+                vars.push(("Tsc", Aggregation::Mean));
+            }
+            RangeSeries::new(rpars, &vars)
+        });
+        provision_error = provision_error || compiled.provision_error;
+        l1_state = compiled.l1_state;
+        let mut tcomp = compiled.tcomp;
+        match tcomp.CheckClosePeriod(DAY_LENGTH_S as isize) {
+            Err(err) => {
+                crate::LOG.append_log_tag_msg("ERROR", &format!("{}", err));
+                return Err(respond_http_plain_text(400, "ERROR[120] CheckClosePeriod"));
+            }
+            Ok(None) => series.push_day(&[]),
+            Ok(Some(p)) => series.push_day(&[
+                ("Lcmp", &p.Lcmp),
+                ("Tamb", &p.Tamb),
+                ("Tsuc", &p.Tsuc),
+                ("Tliq", &p.Tliq),
+                ("State", &p.State),
+                ("Mode", &p.Mode),
+                ("Psuc", &p.Psuc),
+                ("Pliq", &p.Pliq),
+                ("Levp", &p.Levp),
+                ("Lcut", &p.Lcut),
+                ("Tsh", &p.Tsh),
+                ("Tsc", &p.Tsc),
+            ]),
+        };
+    }
+
+    let series = series.unwrap_or_else(|| RangeSeries::new(rpars, &vars));
+    Ok(series.into_response(rpars, provision_error))
+}
+
+async fn compile_range_dut(
+    rpars: &RangeParameters,
+    days: &[dut_hist::ReqParameters],
+    globs: &Arc<GlobalVars>,
+) -> Result<HttpResponse, HttpResponse> {
+    let mut series = RangeSeries::new(
+        rpars,
+        &[
+            ("Temp", Aggregation::Mean),
+            ("Temp1", Aggregation::Mean),
+            ("Hum", Aggregation::Mean),
+            ("eCO2", Aggregation::Mean),
+            ("TVOC", Aggregation::Mean),
+            ("State", Aggregation::Majority),
+            ("Mode", Aggregation::Majority),
+            ("L1", Aggregation::Majority),
+        ],
+    );
+
+    let mut l1_state = None;
+    let mut provision_error = false;
+    for day in days {
+        let compiled = match dut_hist::compile_interval(day, globs, l1_state.take()).await? {
+            Some(v) => v,
+            None => {
+                return Ok(respond_http_json(200, "{}"));
+            }
+        };
+        provision_error = provision_error || compiled.provision_error;
+        l1_state = compiled.l1_state;
+        let mut tcomp = compiled.tcomp;
+        match tcomp.CheckClosePeriod(DAY_LENGTH_S as isize) {
+            Err(err) => {
+                crate::LOG.append_log_tag_msg("ERROR", &format!("{}", err));
+                return Err(respond_http_plain_text(400, "ERROR[120] CheckClosePeriod"));
+            }
+            Ok(None) => series.push_day(&[]),
+            Ok(Some(p)) => series.push_day(&[
+                ("Temp", &p.Temp),
+                ("Temp1", &p.Temp1),
+                ("Hum", &p.Hum),
+                ("eCO2", &p.e_co2),
+                ("TVOC", &p.tvoc),
+                ("State", &p.State),
+                ("Mode", &p.Mode),
+                ("L1", &p.l1),
+            ]),
+        };
+    }
+
+    Ok(series.into_response(rpars, provision_error))
+}
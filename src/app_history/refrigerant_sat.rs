@@ -0,0 +1,55 @@
+use crate::refrigerants;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize)]
+pub struct SaturationParams {
+    fluid: String,
+    // Pressões em bar manométrico, como as do DAC
+    pressures: Vec<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SaturationPoint {
+    pressure: f64,
+    // Temperatura de orvalho (lado da sucção), usada no superaquecimento
+    dew: Option<f64>,
+    // Temperatura de bolha (lado do líquido), usada no subresfriamento. Igual à de orvalho nos fluidos sem glide.
+    bubble: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SaturationResponse {
+    fluid: String,
+    revision: u32,
+    zeotropic: bool,
+    points: Vec<SaturationPoint>,
+}
+
+/**
+ * Converte pressões em temperaturas de saturação com as mesmas tabelas usadas no cálculo do Tsh/Tsc.
+ * Pressões fora da faixa da tabela voltam com null.
+ */
+pub fn process(params: &SaturationParams) -> Result<SaturationResponse, String> {
+    let refrigerant = refrigerants::registry()
+        .get(&params.fluid)
+        .ok_or_else(|| format!("Fluido desconhecido: {}", params.fluid))?;
+    let points = params
+        .pressures
+        .iter()
+        .map(|&pressure| SaturationPoint {
+            pressure,
+            dew: refrigerant.dew.temperature_at(pressure).map(round_1),
+            bubble: refrigerant.bubble.temperature_at(pressure).map(round_1),
+        })
+        .collect();
+    Ok(SaturationResponse {
+        fluid: refrigerant.fluid.clone(),
+        revision: refrigerant.revision,
+        zeotropic: refrigerant.zeotropic,
+        points,
+    })
+}
+
+fn round_1(value: f64) -> f64 {
+    (value * 10.0).round() / 10.0
+}
@@ -1,9 +1,20 @@
+use crate::devs_status::StatusTimeouts;
 use crate::envvars_loader;
 use crate::lib_rumqtt::BrokerConfig;
+use crate::recent_telemetries::RecentWindow;
+use std::collections::HashMap;
 
 pub struct ConfigFile {
     pub listen_http_api: String,
     pub broker_config: BrokerConfig,
+    pub status_timeouts: HashMap<String, StatusTimeouts>, // Tipo do dispositivo ("DAC", "DUT", ... ou "default") => timeouts
+    pub status_topic: Option<String>, // Tópico base onde são publicadas as mudanças de status
+    pub status_webhook: Option<String>, // URL que recebe as mudanças de status em lotes
+    pub mqtt_topics: Vec<String>,
+    pub recent_windows: HashMap<String, RecentWindow>, // Tipo do dispositivo => tamanho do histórico recente
+    pub stream_max_clients: usize, // Limite de conexões simultâneas no stream de telemetrias
+    pub apiserver_internal_api: Option<String>, // Usado para conferir a lista de dispositivos cadastrados
+    pub evict_silent_days: u64, // Descarta do cache dispositivos sem mensagens há mais dias que isso (0 desativa)
 }
 
 impl ConfigFile {
@@ -15,6 +26,49 @@ impl ConfigFile {
         let username = envvars_loader::get_var_string_required("brokerConfig_username")?;
         let password = envvars_loader::get_var_string_required("brokerConfig_password")?;
         let listen_http_api = envvars_loader::get_var_string_required("listen_http_api_realtime")?;
+        let status_timeouts: HashMap<String, StatusTimeouts> =
+            envvars_loader::get_var_structure_optional("REALTIME_STATUS_TIMEOUTS")?
+                .unwrap_or_default();
+        for (dev_type, timeouts) in &status_timeouts {
+            if timeouts.late_s == 0 || timeouts.late_s >= timeouts.offline_s {
+                return Err(format!(
+                    "REALTIME_STATUS_TIMEOUTS inválido para {}: late_s precisa ser maior que zero e menor que offline_s",
+                    dev_type
+                ));
+            }
+        }
+        let status_topic = match envvars_loader::get_var_string_optional("REALTIME_STATUS_TOPIC") {
+            Some(topic) if topic.is_empty() => None,
+            Some(topic) => Some(topic),
+            None => Some("realtime/dev-status".to_owned()),
+        };
+        let recent_windows: HashMap<String, RecentWindow> =
+            envvars_loader::get_var_structure_optional("REALTIME_RECENT_WINDOWS")?
+                .unwrap_or_default();
+        let mqtt_topics: Vec<String> = envvars_loader::get_var_structure_optional(
+            "REALTIME_MQTT_TOPICS",
+        )?
+        .unwrap_or_else(|| {
+            vec![
+                "iotrelay/data/#".to_owned(),
+                "iotrelay/control/#".to_owned(),
+            ]
+        });
+        let stream_max_clients =
+            envvars_loader::get_var_structure_optional("REALTIME_STREAM_MAX_CLIENTS")?
+                .unwrap_or(200);
+        let apiserver_internal_api = envvars_loader::get_var_string_optional("STATS_SERVER_HTTP")
+            .map(|url| {
+                if url.contains("://") {
+                    url
+                } else {
+                    format!("http://{url}")
+                }
+            });
+        let evict_silent_days =
+            envvars_loader::get_var_structure_optional("REALTIME_EVICT_SILENT_DAYS")?.unwrap_or(30);
+        let status_webhook = envvars_loader::get_var_string_optional("REALTIME_STATUS_WEBHOOK")
+            .filter(|url| !url.is_empty());
 
         let broker_config = BrokerConfig {
             host,
@@ -28,6 +82,22 @@ impl ConfigFile {
         Ok(ConfigFile {
             listen_http_api,
             broker_config,
+            status_timeouts,
+            status_topic,
+            status_webhook,
+            mqtt_topics,
+            recent_windows,
+            stream_max_clients,
+            apiserver_internal_api,
+            evict_silent_days,
         })
     }
 }
+
+// Configuração do tipo do dispositivo ("DAC", "DUT", ...), ou a "default" se o tipo não tiver uma própria
+pub fn config_for_dev_type<'a, T>(configs: &'a HashMap<String, T>, dev_id: &str) -> Option<&'a T> {
+    dev_id
+        .get(0..3)
+        .and_then(|dev_type| configs.get(&dev_type.to_uppercase()))
+        .or_else(|| configs.get("default"))
+}
@@ -1,15 +1,56 @@
+use super::devs_status;
 use super::global_vars::DevLastMessage;
+use super::recent_telemetries;
+use crate::lib_metrics::REGISTRY;
 use crate::GlobalVars;
+use serde::Deserialize;
+use serde_json::json;
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     io::ErrorKind,
     sync::{
         atomic::{AtomicU64, Ordering},
         Arc,
     },
-    time::Duration,
+    time::{Duration, Instant},
 };
-use tokio::{io::AsyncWriteExt, sync::RwLock};
+use tokio::sync::RwLock;
+
+/*
+Cache das últimas mensagens (com o histórico recente) em ./cache/lastMessages.json, gravado a cada 3 minutos.
+Formato (versão 2): uma linha de cabeçalho JSON {"format","version","length","crc32"} seguida do JSON
+{ devId: DevLastMessage }. Antes de substituir o arquivo, o anterior é mantido em lastMessages.prev.json,
+e se o atual estiver corrompido (checksum ou JSON inválido) o serviço inicia com o anterior.
+Arquivos no formato antigo (só o JSON, sem cabeçalho) continuam sendo aceitos.
+
+Na mesma tarefa:
+ - Dispositivos sem mensagens há mais de REALTIME_EVICT_SILENT_DAYS dias são descartados.
+ - A cada hora a lista de dispositivos é conferida com o API-Server, e os que não estão mais
+   cadastrados no Celsius são descartados.
+
+  ['/diel-internal/realtime/getDevicesList']: () => {
+    devIds: string[] // Todos os dispositivos cadastrados
+  },
+*/
+
+const CACHE_FILE: &str = "./cache/lastMessages.json";
+const CACHE_FILE_TMP: &str = "./cache/lastMessages-tmp.json";
+const CACHE_FILE_PREV: &str = "./cache/lastMessages.prev.json";
+const SNAPSHOT_FORMAT: &str = "realtime-last-messages";
+const SNAPSHOT_VERSION: u32 = 2;
+
+#[derive(Deserialize)]
+struct SnapshotHeader {
+    format: String,
+    version: u32,
+    length: usize,
+    crc32: u32,
+}
+
+#[derive(Deserialize)]
+struct DevicesListResponse {
+    devIds: Vec<String>,
+}
 
 pub async fn run_service(globs: Arc<GlobalVars>) -> Result<(), String> {
     tokio::fs::create_dir_all("./cache")
@@ -22,11 +63,32 @@ pub async fn run_service(globs: Arc<GlobalVars>) -> Result<(), String> {
             &format!("Error on lastMessages SavingService loading cache: {err}"),
         );
     }
+    devs_status::load_from_last_timestamps(&globs).await;
 
-    // TODO: Remover do cache dispositivos não cadastrados no Celsius
-
+    let mut last_reconciliation: Option<Instant> = None;
     loop {
         tokio::time::sleep(Duration::from_millis(3 * 60 * 1000)).await;
+
+        evict_silent_devices(&globs).await;
+
+        let need_reconciliation = match last_reconciliation {
+            Some(last) => last.elapsed() > Duration::from_secs(3600),
+            None => true,
+        };
+        if need_reconciliation && globs.configfile.apiserver_internal_api.is_some() {
+            match reconcile_with_api_server(&globs).await {
+                Ok(()) => {
+                    last_reconciliation = Some(Instant::now());
+                }
+                Err(err) => {
+                    crate::LOG.append_log_tag_msg(
+                        "ERROR",
+                        &format!("Error on devices reconciliation: {err}"),
+                    );
+                }
+            }
+        }
+
         let result = dump_to_file(&globs).await;
         if let Err(err) = result {
             crate::LOG.append_log_tag_msg(
@@ -37,76 +99,223 @@ pub async fn run_service(globs: Arc<GlobalVars>) -> Result<(), String> {
     }
 }
 
-async fn dump_to_file(globs: &Arc<GlobalVars>) -> Result<(), String> {
-    let mut out_file = tokio::fs::OpenOptions::new()
-        .write(true)
-        .create(true)
-        .truncate(true)
-        .open("./cache/lastMessages-tmp.json")
+async fn evict_silent_devices(globs: &Arc<GlobalVars>) {
+    let max_silence_days = globs.configfile.evict_silent_days;
+    if max_silence_days == 0 {
+        return;
+    }
+    let limit = devs_status::now_millis().saturating_sub(max_silence_days * 24 * 3600 * 1000);
+    let silent: Vec<String> = globs
+        .last_timestamp
+        .read()
         .await
-        .map_err(|err| format!("[37] {err}"))?;
-    out_file
-        .write_all(b"{")
+        .iter()
+        .filter(|(_, ts)| ts.load(Ordering::Relaxed) < limit)
+        .map(|(dev_id, _)| dev_id.to_owned())
+        .collect();
+    if !silent.is_empty() {
+        crate::LOG.append_log_tag_msg(
+            "INFO",
+            &format!(
+                "Removing {} devices silent for more than {} days",
+                silent.len(),
+                max_silence_days
+            ),
+        );
+        remove_devices(globs, &silent, "silent").await;
+    }
+}
+
+async fn reconcile_with_api_server(globs: &Arc<GlobalVars>) -> Result<(), String> {
+    let Some(apiserver) = &globs.configfile.apiserver_internal_api else {
+        return Ok(());
+    };
+    let url = format!("{}/diel-internal/realtime/getDevicesList", apiserver);
+    let res = reqwest::Client::new()
+        .post(&url)
+        .json(&json!({}))
+        .timeout(Duration::from_secs(60))
+        .send()
         .await
-        .map_err(|err| format!("[38] {err}"))?;
-    // let mut resp_devs = json!({});
+        .map_err(|e| format!("[125] {e}"))?;
+    if !res.status().is_success() {
+        return Err(format!("[127] {} {}", url, res.status()));
+    }
+    let response: DevicesListResponse = res.json().await.map_err(|e| format!("[129] {e}"))?;
+    let registered: HashSet<String> = response.devIds.into_iter().collect();
+
+    let (cached, unregistered) = {
+        let last_timestamp = globs.last_timestamp.read().await;
+        let unregistered: Vec<String> = last_timestamp
+            .keys()
+            .filter(|dev_id| !registered.contains(*dev_id))
+            .cloned()
+            .collect();
+        (last_timestamp.len(), unregistered)
+    };
+
+    // Proteção contra resposta incompleta do API-Server: não remove a maior parte do cache de uma vez
+    if registered.is_empty() || unregistered.len() > cached / 2 {
+        return Err(format!(
+            "[142] Reconciliation would remove {} of {} devices ({} registered), ignoring",
+            unregistered.len(),
+            cached,
+            registered.len()
+        ));
+    }
+
+    if !unregistered.is_empty() {
+        crate::LOG.append_log_tag_msg(
+            "INFO",
+            &format!("Removing {} unregistered devices", unregistered.len()),
+        );
+        remove_devices(globs, &unregistered, "unregistered").await;
+    }
+    Ok(())
+}
+
+async fn remove_devices(globs: &Arc<GlobalVars>, dev_ids: &[String], reason: &'static str) {
+    {
+        let mut all_devs = globs.last_telemetry.write().await;
+        let mut last_timestamp = globs.last_timestamp.write().await;
+        for dev_id in dev_ids {
+            all_devs.remove(dev_id);
+            last_timestamp.remove(dev_id);
+        }
+    }
+    {
+        let mut devs = globs.devs_status.lock().unwrap();
+        for dev_id in dev_ids {
+            devs.remove(dev_id);
+        }
+    }
+    REGISTRY
+        .counter(
+            "realtime_cache_removed_total",
+            "Dispositivos removidos do cache do realtime",
+            &[("reason", reason)],
+        )
+        .add(dev_ids.len() as u64);
+}
+
+async fn dump_to_file(globs: &Arc<GlobalVars>) -> Result<(), String> {
+    let mut body = Vec::with_capacity(1024 * 1024);
+    body.push(b'{');
 
     let mut need_comma = false;
+    let now = devs_status::now_millis();
     let all_devs = globs.last_telemetry.read().await;
     for (dev_id, dev_info) in all_devs.iter() {
+        let mut dev_info = dev_info.write().await;
+        // Aproveita para descartar o histórico recente já vencido de quem parou de enviar
+        let window = recent_telemetries::window_for(globs, dev_id);
+        recent_telemetries::prune(&mut dev_info.recent, window, now);
         if need_comma {
-            out_file
-                .write_all(&format!(r#","{dev_id}":"#).as_bytes())
-                .await
-                .map_err(|err| format!("[38] {err}"))?;
-        } else {
-            out_file
-                .write_all(&format!(r#""{dev_id}":"#).as_bytes())
-                .await
-                .map_err(|err| format!("[38] {err}"))?;
-            need_comma = true;
+            body.push(b',');
         }
-        let dev_info = dev_info.read().await;
-        let bytes = serde_json::to_vec(&*dev_info).map_err(|err| format!("[25] {err}"))?;
-        out_file
-            .write_all(&bytes)
-            .await
-            .map_err(|err| format!("[38] {err}"))?;
+        need_comma = true;
+        serde_json::to_writer(&mut body, dev_id).map_err(|err| format!("[25] {err}"))?;
+        body.push(b':');
+        serde_json::to_writer(&mut body, &*dev_info).map_err(|err| format!("[25] {err}"))?;
     }
+    let num_devs = all_devs.len();
     drop(all_devs);
+    body.push(b'}');
+
+    REGISTRY
+        .gauge(
+            "realtime_cache_devices",
+            "Dispositivos com telemetria no cache do realtime",
+            &[],
+        )
+        .set(num_devs as f64);
 
-    out_file
-        .write_all(b"}")
+    tokio::fs::write(CACHE_FILE_TMP, encode_snapshot(&body))
         .await
         .map_err(|err| format!("[38] {err}"))?;
-    out_file
-        .flush()
-        .await
-        .map_err(|err| format!("[59] {err}"))?;
-    drop(out_file);
 
-    tokio::fs::rename("./cache/lastMessages-tmp.json", "./cache/lastMessages.json")
+    // Mantém o último arquivo bom como reserva antes de substituir
+    match tokio::fs::rename(CACHE_FILE, CACHE_FILE_PREV).await {
+        Ok(()) => {}
+        Err(err) if err.kind() == ErrorKind::NotFound => {}
+        Err(err) => return Err(format!("[62] {err}")),
+    }
+    tokio::fs::rename(CACHE_FILE_TMP, CACHE_FILE)
         .await
         .map_err(|err| format!("[63] {err}"))?;
 
     Ok(())
 }
 
-async fn load_from_cache(globs: &Arc<GlobalVars>) -> Result<(), String> {
-    // TODO: se der erro no parse do JSON (por exemplo arquivo corrompido) o sistema não vai conseguir se recuperar
+fn encode_snapshot(body: &[u8]) -> Vec<u8> {
+    let header = json!({
+        "format": SNAPSHOT_FORMAT,
+        "version": SNAPSHOT_VERSION,
+        "length": body.len(),
+        "crc32": crc32fast::hash(body),
+    });
+    let mut contents = header.to_string().into_bytes();
+    contents.push(b'\n');
+    contents.extend_from_slice(body);
+    contents
+}
 
-    let file_contents = match tokio::fs::read_to_string("./cache/lastMessages.json").await {
-        Ok(x) => x,
-        Err(err) => {
-            if err.kind() != ErrorKind::NotFound {
-                crate::LOG.append_log_tag_msg("WARN", &format!("Could not load cache: {err}"));
+fn decode_snapshot(contents: &[u8]) -> Result<HashMap<String, DevLastMessage>, String> {
+    let body = match contents.iter().position(|c| *c == b'\n') {
+        Some(pos) => match serde_json::from_slice::<SnapshotHeader>(&contents[..pos]) {
+            Ok(header) => {
+                if header.format != SNAPSHOT_FORMAT || header.version != SNAPSHOT_VERSION {
+                    return Err(format!(
+                        "[229] Unknown cache format: {} v{}",
+                        header.format, header.version
+                    ));
+                }
+                let body = &contents[pos + 1..];
+                if body.len() != header.length || crc32fast::hash(body) != header.crc32 {
+                    return Err("[235] Cache checksum mismatch".to_owned());
+                }
+                body
             }
-            "{}".to_owned()
-        }
+            Err(_) => contents,
+        },
+        // Formato antigo: só o JSON, sem cabeçalho
+        None => contents,
     };
+    serde_json::from_slice(body).map_err(|err| format!("[71] {err}"))
+}
+
+async fn read_snapshot(path: &str) -> Result<Option<HashMap<String, DevLastMessage>>, String> {
+    match tokio::fs::read(path).await {
+        Ok(contents) => decode_snapshot(&contents).map(Some),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(format!("[249] {err}")),
+    }
+}
 
-    let last_messages: HashMap<String, DevLastMessage> =
-        serde_json::from_str(&file_contents).map_err(|err| format!("[71] {err}"))?;
+async fn load_from_cache(globs: &Arc<GlobalVars>) -> Result<(), String> {
+    let last_messages = match read_snapshot(CACHE_FILE).await {
+        Ok(Some(x)) => x,
+        current => {
+            // Arquivo atual corrompido ou ausente (ex.: o serviço parou entre os dois renames)
+            if let Err(err) = &current {
+                crate::LOG.append_log_tag_msg(
+                    "WARN",
+                    &format!("Could not load cache, trying previous snapshot: {err}"),
+                );
+            }
+            match read_snapshot(CACHE_FILE_PREV).await {
+                Ok(Some(x)) => x,
+                Ok(None) => {
+                    current?;
+                    HashMap::new()
+                }
+                Err(err) => {
+                    current?;
+                    return Err(err);
+                }
+            }
+        }
+    };
 
     let mut all_devs = globs.last_telemetry.write().await;
     let mut last_timestamp = globs.last_timestamp.write().await;
@@ -143,3 +352,24 @@ fn definir_last_ts(last_timestamp: &mut HashMap<String, AtomicU64>, dev_id: &str
         last_timestamp.insert(dev_id.to_owned(), AtomicU64::new(ts_secs));
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_snapshot, encode_snapshot};
+
+    #[test]
+    fn test_snapshot_checksum() {
+        let body = br#"{"DAC1":{"telemetry":{"L":[1]},"ts":1700000000000}}"#;
+        let contents = encode_snapshot(body);
+        let devs = decode_snapshot(&contents).unwrap();
+        assert_eq!(devs["DAC1"].ts, 1700000000000);
+
+        // Formato antigo, sem cabeçalho
+        assert_eq!(decode_snapshot(body).unwrap().len(), 1);
+
+        let mut corrupted = contents.clone();
+        let last = corrupted.len() - 3;
+        corrupted[last] = b'2';
+        assert!(decode_snapshot(&corrupted).is_err());
+    }
+}
@@ -0,0 +1,527 @@
+use crate::configs::config_for_dev_type;
+use crate::lib_metrics::REGISTRY;
+use crate::live_stream;
+use crate::GlobalVars;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::mpsc;
+
+/*
+Máquina de estados de conexão de cada dispositivo (port do onDeviceMessage do realtime em Node):
+  - Qualquer mensagem (data/ ou control/) deixa o dispositivo ONLINE.
+  - Sem mensagens por mais de `late_s` ele fica LATE, e por mais de `offline_s` fica OFFLINE.
+As mudanças de estado são enviadas para a tarefa de notificação, que publica no broker e chama o webhook.
+*/
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum DevStatus {
+    Online,
+    Late,
+    Offline,
+}
+
+impl DevStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DevStatus::Online => "ONLINE",
+            DevStatus::Late => "LATE",
+            DevStatus::Offline => "OFFLINE",
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, Debug)]
+pub struct StatusTimeouts {
+    pub late_s: u64,
+    pub offline_s: u64,
+}
+
+pub struct DevStatusInfo {
+    pub status: DevStatus,
+    pub ts: u64, // Timestamp do servidor (ms) da última mensagem do dispositivo
+    pub ts_before: Option<u64>, // Timestamp da mensagem anterior à última
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct StatusTransition {
+    #[serde(rename = "devId")]
+    pub dev_id: String,
+    pub status: DevStatus,
+    #[serde(rename = "prevStatus")]
+    pub prev_status: Option<DevStatus>,
+    pub ts: u64,
+    #[serde(rename = "tsBefore")]
+    pub ts_before: Option<u64>,
+}
+
+pub fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_millis()
+        .try_into()
+        .expect("timestamp too large")
+}
+
+// Timeouts por tipo de dispositivo ("DAC", "DUT", ...), com "default" para os demais
+pub fn timeouts_for(timeouts: &HashMap<String, StatusTimeouts>, dev_id: &str) -> StatusTimeouts {
+    config_for_dev_type(timeouts, dev_id)
+        .copied()
+        .unwrap_or(StatusTimeouts {
+            late_s: 60,
+            offline_s: 300,
+        })
+}
+
+fn status_for_elapsed(timeouts: StatusTimeouts, elapsed_ms: u64) -> DevStatus {
+    if elapsed_ms > timeouts.offline_s * 1000 {
+        DevStatus::Offline
+    } else if elapsed_ms > timeouts.late_s * 1000 {
+        DevStatus::Late
+    } else {
+        DevStatus::Online
+    }
+}
+
+// Chamado a cada mensagem recebida do dispositivo
+pub fn on_device_message(globs: &Arc<GlobalVars>, dev_id: &str, now: u64) {
+    let transition = {
+        let mut devs = globs.devs_status.lock().unwrap();
+        match devs.get_mut(dev_id) {
+            Some(info) => {
+                let prev_status = info.status;
+                info.ts_before = Some(info.ts);
+                info.ts = now;
+                info.status = DevStatus::Online;
+                if prev_status == DevStatus::Online {
+                    return;
+                }
+                StatusTransition {
+                    dev_id: dev_id.to_owned(),
+                    status: DevStatus::Online,
+                    prev_status: Some(prev_status),
+                    ts: now,
+                    ts_before: info.ts_before,
+                }
+            }
+            None => {
+                devs.insert(
+                    dev_id.to_owned(),
+                    DevStatusInfo {
+                        status: DevStatus::Online,
+                        ts: now,
+                        ts_before: None,
+                    },
+                );
+                StatusTransition {
+                    dev_id: dev_id.to_owned(),
+                    status: DevStatus::Online,
+                    prev_status: None,
+                    ts: now,
+                    ts_before: None,
+                }
+            }
+        }
+    };
+    notify(globs, transition);
+}
+
+// Estado inicial a partir do cache de últimas mensagens, sem gerar notificações
+pub async fn load_from_last_timestamps(globs: &Arc<GlobalVars>) {
+    let now = now_millis();
+    let last_timestamp = globs.last_timestamp.read().await;
+    let mut devs = globs.devs_status.lock().unwrap();
+    for (dev_id, ts) in last_timestamp.iter() {
+        if devs.contains_key(dev_id) {
+            continue;
+        }
+        let ts = ts.load(Ordering::Relaxed);
+        let timeouts = timeouts_for(&globs.configfile.status_timeouts, dev_id);
+        devs.insert(
+            dev_id.to_owned(),
+            DevStatusInfo {
+                status: status_for_elapsed(timeouts, now.saturating_sub(ts)),
+                ts,
+                ts_before: None,
+            },
+        );
+    }
+}
+
+// Verifica periodicamente quem parou de enviar mensagens
+pub async fn run_sweeper(globs: Arc<GlobalVars>) -> Result<(), String> {
+    loop {
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        for transition in sweep(&globs, now_millis()) {
+            notify(&globs, transition);
+        }
+    }
+}
+
+fn sweep(globs: &Arc<GlobalVars>, now: u64) -> Vec<StatusTransition> {
+    let mut transitions = Vec::new();
+    let mut counts: HashMap<DevStatus, usize> = HashMap::new();
+    let mut devs = globs.devs_status.lock().unwrap();
+    for (dev_id, info) in devs.iter_mut() {
+        let timeouts = timeouts_for(&globs.configfile.status_timeouts, dev_id);
+        let status = status_for_elapsed(timeouts, now.saturating_sub(info.ts));
+        // A volta para ONLINE só acontece quando chega mensagem
+        if status != info.status && status != DevStatus::Online {
+            transitions.push(StatusTransition {
+                dev_id: dev_id.to_owned(),
+                status,
+                prev_status: Some(info.status),
+                ts: info.ts,
+                ts_before: info.ts_before,
+            });
+            info.status = status;
+        }
+        *counts.entry(info.status).or_default() += 1;
+    }
+    drop(devs);
+
+    for status in [DevStatus::Online, DevStatus::Late, DevStatus::Offline] {
+        let count = counts.get(&status).copied().unwrap_or(0);
+        REGISTRY
+            .gauge(
+                "devices_status",
+                "Dispositivos em cada estado de conexão",
+                &[("status", status.as_str())],
+            )
+            .set(count as f64);
+    }
+
+    transitions
+}
+
+fn notify(globs: &Arc<GlobalVars>, transition: StatusTransition) {
+    REGISTRY
+        .counter(
+            "status_transitions_total",
+            "Mudanças de estado de conexão dos dispositivos",
+            &[("status", transition.status.as_str())],
+        )
+        .inc();
+    live_stream::publish_status(globs, &transition);
+    if let Err(err) = globs.to_status_notifier.try_send(transition) {
+        crate::LOG.append_log_tag_msg_v2(
+            "ERROR",
+            &format!("Status notification dropped: {}", err),
+            false,
+        );
+    }
+}
+
+// Publica cada mudança no broker e envia para o webhook em lotes de até 1 segundo
+pub async fn run_notifier(
+    globs: Arc<GlobalVars>,
+    mut receiver: mpsc::Receiver<StatusTransition>,
+) -> Result<(), String> {
+    let http_client = reqwest::Client::new();
+    loop {
+        let batch = match recv_batch(&mut receiver).await {
+            Some(x) => x,
+            None => return Err("status channel closed".to_owned()),
+        };
+
+        if let Some(topic) = &globs.configfile.status_topic {
+            publish_transitions(&globs, topic, &batch).await;
+        }
+        if let Some(url) = &globs.configfile.status_webhook {
+            if let Err(err) = send_webhook(&http_client, url, &batch).await {
+                crate::LOG.append_log_tag_msg(
+                    "ERROR",
+                    &format!(
+                        "Status webhook failed ({} transitions): {}",
+                        batch.len(),
+                        err
+                    ),
+                );
+            }
+        }
+    }
+}
+
+// Aguarda a primeira mudança e junta as que chegarem até 1 segundo depois dela
+async fn recv_batch(
+    receiver: &mut mpsc::Receiver<StatusTransition>,
+) -> Option<Vec<StatusTransition>> {
+    let first = receiver.recv().await?;
+    let mut batch = vec![first];
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(1);
+    while batch.len() < 1000 {
+        match tokio::time::timeout_at(deadline, receiver.recv()).await {
+            Ok(Some(x)) => batch.push(x),
+            _ => break,
+        }
+    }
+    Some(batch)
+}
+
+async fn send_webhook(
+    http_client: &reqwest::Client,
+    url: &str,
+    batch: &[StatusTransition],
+) -> Result<(), reqwest::Error> {
+    let body = json!({ "transitions": batch });
+    http_client
+        .post(url)
+        .json(&body)
+        .timeout(Duration::from_secs(10))
+        .send()
+        .await
+        .and_then(|res| res.error_for_status())?;
+    Ok(())
+}
+
+// Cada mudança vai para <topic>/<devId>
+fn status_message(
+    topic: &str,
+    transition: &StatusTransition,
+) -> Result<(String, Vec<u8>), serde_json::Error> {
+    let payload = serde_json::to_vec(transition)?;
+    Ok((format!("{}/{}", topic, transition.dev_id), payload))
+}
+
+async fn publish_transitions(globs: &Arc<GlobalVars>, topic: &str, batch: &[StatusTransition]) {
+    let client = match globs.broker_client.read().await.clone() {
+        Some(x) => x,
+        None => {
+            crate::LOG.append_log_tag_msg_v2(
+                "ERROR",
+                "Status not published: no broker connection",
+                false,
+            );
+            return;
+        }
+    };
+    for transition in batch {
+        let (dev_topic, payload) = match status_message(topic, transition) {
+            Ok(x) => x,
+            Err(err) => {
+                crate::LOG.append_log_tag_msg("ERROR", &format!("[263] {err}"));
+                continue;
+            }
+        };
+        let result = client
+            .publish(dev_topic, rumqttc::QoS::AtLeastOnce, false, payload)
+            .await;
+        if let Err(err) = result {
+            crate::LOG.append_log_tag_msg("ERROR", &format!("Status not published: {}", err));
+        }
+    }
+}
+
+pub fn get_status_json(globs: &Arc<GlobalVars>, dev_ids: Option<&[String]>) -> serde_json::Value {
+    let devs = globs.devs_status.lock().unwrap();
+    let to_json = |info: &DevStatusInfo| {
+        json!({
+            "status": info.status,
+            "ts": info.ts,
+            "tsBefore": info.ts_before,
+        })
+    };
+    let mut resp_devs = json!({});
+    match dev_ids {
+        None => {
+            for (dev_id, info) in devs.iter() {
+                resp_devs[dev_id] = to_json(info);
+            }
+        }
+        Some(dev_ids) => {
+            for dev_id in dev_ids {
+                if let Some(info) = devs.get(dev_id) {
+                    resp_devs[dev_id] = to_json(info);
+                }
+            }
+        }
+    }
+    resp_devs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::configs::ConfigFile;
+    use crate::lib_rumqtt::BrokerConfig;
+
+    fn test_globs() -> (Arc<GlobalVars>, mpsc::Receiver<StatusTransition>) {
+        let configfile = ConfigFile {
+            listen_http_api: "127.0.0.1:0".to_owned(),
+            broker_config: BrokerConfig {
+                host: "localhost".to_owned(),
+                port: 1883,
+                username: "u".to_owned(),
+                password: "p".to_owned(),
+                use_tls: false,
+                ca_cert: None,
+            },
+            status_timeouts: HashMap::new(),
+            status_topic: None,
+            status_webhook: None,
+            mqtt_topics: Vec::new(),
+            recent_windows: HashMap::new(),
+            stream_max_clients: 1,
+            apiserver_internal_api: None,
+            evict_silent_days: 0,
+        };
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let (globs, receiver) = rt.block_on(GlobalVars::new(configfile));
+        (Arc::new(globs), receiver)
+    }
+
+    fn transition(
+        dev_id: &str,
+        status: DevStatus,
+        prev_status: Option<DevStatus>,
+        ts: u64,
+        ts_before: Option<u64>,
+    ) -> StatusTransition {
+        StatusTransition {
+            dev_id: dev_id.to_owned(),
+            status,
+            prev_status,
+            ts,
+            ts_before,
+        }
+    }
+
+    #[test]
+    fn test_status_timeouts_per_type() {
+        let timeouts: HashMap<String, StatusTimeouts> = serde_json::from_str(
+            r#"{"default":{"late_s":30,"offline_s":300},"DUT":{"late_s":120,"offline_s":600}}"#,
+        )
+        .unwrap();
+        let dac = timeouts_for(&timeouts, "DAC123");
+        let dut = timeouts_for(&timeouts, "dut123");
+        assert_eq!(status_for_elapsed(dac, 10_000), DevStatus::Online);
+        assert_eq!(status_for_elapsed(dac, 60_000), DevStatus::Late);
+        assert_eq!(status_for_elapsed(dac, 400_000), DevStatus::Offline);
+        assert_eq!(status_for_elapsed(dut, 60_000), DevStatus::Online);
+        assert_eq!(status_for_elapsed(dut, 400_000), DevStatus::Late);
+    }
+
+    #[test]
+    fn test_status_transitions() {
+        use DevStatus::{Late, Offline, Online};
+        let (globs, mut notifications) = test_globs();
+        let dev_id = "DAC210191234";
+        let summary = |x: &StatusTransition| (x.status, x.prev_status, x.ts, x.ts_before);
+
+        // Primeira mensagem do dispositivo
+        let t0 = 1_700_000_000_000;
+        on_device_message(&globs, dev_id, t0);
+        let first = notifications.try_recv().unwrap();
+        assert_eq!(summary(&first), (Online, None, t0, None));
+
+        // Continua ONLINE: não notifica de novo
+        let t1 = t0 + 10_000;
+        on_device_message(&globs, dev_id, t1);
+        assert!(notifications.try_recv().is_err());
+
+        // Sem mensagens: LATE depois de 60s e OFFLINE depois de 300s (timeouts padrão), uma vez cada
+        let late = sweep(&globs, t1 + 61_000);
+        assert_eq!(
+            late.iter().map(summary).collect::<Vec<_>>(),
+            [(Late, Some(Online), t1, Some(t0))]
+        );
+        assert!(sweep(&globs, t1 + 62_000).is_empty());
+        let offline = sweep(&globs, t1 + 301_000);
+        assert_eq!(
+            offline.iter().map(summary).collect::<Vec<_>>(),
+            [(Offline, Some(Late), t1, Some(t0))]
+        );
+        assert!(sweep(&globs, t1 + 900_000).is_empty());
+
+        // Volta para ONLINE com a próxima mensagem, levando o ts da última mensagem antes de sumir
+        let t2 = t1 + 1_000_000;
+        on_device_message(&globs, dev_id, t2);
+        let back = notifications.try_recv().unwrap();
+        assert_eq!(summary(&back), (Online, Some(Offline), t2, Some(t1)));
+        assert_eq!(back.dev_id, dev_id);
+        on_device_message(&globs, dev_id, t2 + 5_000);
+        assert!(notifications.try_recv().is_err());
+        assert!(sweep(&globs, t2 + 10_000).is_empty());
+
+        let status = get_status_json(&globs, Some(&[dev_id.to_owned()]));
+        assert_eq!(
+            status[dev_id],
+            json!({ "status": "ONLINE", "ts": t2 + 5_000, "tsBefore": t2 })
+        );
+    }
+
+    #[test]
+    fn test_status_notification_batches() {
+        use crate::lib_http::buffer::SocketReader;
+        use crate::lib_http::request::read_socket_http_request;
+        use crate::lib_http::response::{respond_http_plain_text, send_response};
+        use DevStatus::{Late, Offline, Online};
+
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            // O que chega junto vai no mesmo lote
+            let (sender, mut receiver) = mpsc::channel(10);
+            sender.send(transition("DAC1", Late, Some(Online), 2000, Some(1000))).await.unwrap();
+            sender.send(transition("DUT1", Online, None, 3000, None)).await.unwrap();
+            let batch = recv_batch(&mut receiver).await.unwrap();
+            assert_eq!(batch.len(), 2);
+            sender.send(transition("DAC1", Offline, Some(Late), 2000, Some(1000))).await.unwrap();
+            drop(sender);
+            assert_eq!(recv_batch(&mut receiver).await.unwrap().len(), 1);
+            assert!(recv_batch(&mut receiver).await.is_none());
+
+            // Broker: uma mensagem por dispositivo
+            let messages: Vec<(String, serde_json::Value)> = batch
+                .iter()
+                .map(|x| {
+                    let (topic, payload) = status_message("realtime/dev-status", x).unwrap();
+                    (topic, serde_json::from_slice(&payload).unwrap())
+                })
+                .collect();
+            assert_eq!(
+                messages,
+                [
+                    (
+                        "realtime/dev-status/DAC1".to_owned(),
+                        json!({ "devId": "DAC1", "status": "LATE", "prevStatus": "ONLINE", "ts": 2000, "tsBefore": 1000 }),
+                    ),
+                    (
+                        "realtime/dev-status/DUT1".to_owned(),
+                        json!({ "devId": "DUT1", "status": "ONLINE", "prevStatus": null, "ts": 3000, "tsBefore": null }),
+                    ),
+                ]
+            );
+
+            // Webhook: o lote inteiro em uma requisição
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!("http://{}/status", listener.local_addr().unwrap());
+            let server = tokio::spawn(async move {
+                let (socket, _) = listener.accept().await.unwrap();
+                let mut reader = SocketReader::new(socket, 1000);
+                let req = read_socket_http_request(&mut reader, None).await.unwrap();
+                let mut socket = reader.get_socket();
+                send_response(&mut socket, &respond_http_plain_text(200, "OK"))
+                    .await
+                    .unwrap();
+                req
+            });
+            send_webhook(&reqwest::Client::new(), &url, &batch).await.unwrap();
+            let req = server.await.unwrap();
+            assert_eq!(req.path, "/status");
+            let body: serde_json::Value = serde_json::from_slice(&req.content).unwrap();
+            assert_eq!(body["transitions"][0], messages[0].1);
+            assert_eq!(body["transitions"][1], messages[1].1);
+            assert_eq!(body["transitions"].as_array().unwrap().len(), 2);
+        });
+    }
+}
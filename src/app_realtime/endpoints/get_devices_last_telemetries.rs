@@ -1,5 +1,5 @@
 use crate::{
-    global_vars::GlobalVars,
+    global_vars::{DevLastMessage, GlobalVars},
     lib_http::{
         response::respond_http_json_bytes,
         types::{HttpRequest, HttpResponse},
@@ -46,16 +46,14 @@ pub async fn get_devices_last_telemetries(
             for (dev_id, dev_info) in all_devs.iter() {
                 let dev_info = dev_info.read().await;
                 // response.push_str(&serde_json::to_string(&*dev_info).map_err(|err| format!("[48] {err}"))?);
-                resp_devs[dev_id] =
-                    serde_json::to_value(&*dev_info).map_err(|err| format!("[50] {err}"))?;
+                resp_devs[dev_id] = last_message_json(&dev_info);
             }
         }
         Some(dev_ids) => {
             for dev_id in &dev_ids {
                 if let Some(dev_info) = all_devs.get(dev_id) {
                     let dev_info = dev_info.read().await;
-                    resp_devs[dev_id] =
-                        serde_json::to_value(&*dev_info).map_err(|err| format!("[58] {err}"))?;
+                    resp_devs[dev_id] = last_message_json(&dev_info);
                 };
             }
         }
@@ -68,3 +66,11 @@ pub async fn get_devices_last_telemetries(
     let response = serde_json::to_vec(&response).map_err(|err| format!("[68] {err}"))?;
     Ok(respond_http_json_bytes(200, response))
 }
+
+// O histórico recente fica de fora, ele tem o endpoint próprio getDevicesRecentTelemetries
+fn last_message_json(dev_info: &DevLastMessage) -> serde_json::Value {
+    json!({
+        "ts": dev_info.ts,
+        "telemetry": dev_info.telemetry,
+    })
+}
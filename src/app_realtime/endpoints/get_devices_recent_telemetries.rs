@@ -0,0 +1,70 @@
+use crate::{
+    devs_status::now_millis,
+    global_vars::GlobalVars,
+    lib_http::{
+        response::respond_http_json_bytes,
+        types::{HttpRequest, HttpResponse},
+    },
+    recent_telemetries,
+};
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::Arc;
+
+/*
+  ['/diel-internal/realtime-rs/getDevicesRecentTelemetries']: (reqParams: {
+    devIds: string[]
+    since?: number // Só telemetrias que chegaram depois deste timestamp (ms)
+  }) => {
+    recentTelemetries: {
+      [devId: string]: {
+        ts: number // Timestamp do servidor de quando a telemetria chegou
+        telemetry: any // JSON que chegou em tópico 'data/...'
+      }[] // Da mais antiga para a mais nova
+    }
+  },
+
+*/
+
+#[derive(Deserialize)]
+pub struct ParamsGetDevicesRecentTelemetries {
+    pub devIds: Vec<String>,
+    pub since: Option<u64>,
+}
+
+pub async fn get_devices_recent_telemetries(
+    req: &HttpRequest,
+    globs: &Arc<GlobalVars>,
+) -> Result<HttpResponse, String> {
+    let req_params: ParamsGetDevicesRecentTelemetries =
+        serde_json::from_slice(&req.content).map_err(|e| e.to_string())?;
+
+    let now = now_millis();
+    let since = req_params.since.unwrap_or(0);
+    let all_devs = globs.last_telemetry.read().await;
+    let mut resp_devs = json!({});
+
+    for dev_id in &req_params.devIds {
+        let Some(dev_info) = all_devs.get(dev_id) else {
+            continue;
+        };
+        // Dispositivos que pararam de enviar ainda podem ter itens fora da janela
+        let window = recent_telemetries::window_for(globs, dev_id);
+        let limit = now.saturating_sub(window.minutes * 60 * 1000).max(since);
+        let dev_info = dev_info.read().await;
+        let items: Vec<&recent_telemetries::RecentTelemetry> = dev_info
+            .recent
+            .iter()
+            .filter(|item| item.ts > limit)
+            .collect();
+        resp_devs[dev_id] = serde_json::to_value(&items).map_err(|err| format!("[58] {err}"))?;
+    }
+    drop(all_devs);
+
+    let response = json!({
+      "recentTelemetries": resp_devs,
+    });
+
+    let response = serde_json::to_vec(&response).map_err(|err| format!("[66] {err}"))?;
+    Ok(respond_http_json_bytes(200, response))
+}
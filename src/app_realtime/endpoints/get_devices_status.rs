@@ -0,0 +1,48 @@
+use crate::{
+    devs_status,
+    global_vars::GlobalVars,
+    lib_http::{
+        response::respond_http_json_bytes,
+        types::{HttpRequest, HttpResponse},
+    },
+};
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::Arc;
+
+/*
+  ['/diel-internal/realtime-rs/getDevicesStatus']: (reqParams: {
+    devIds?: string[]
+  }) => {
+    devicesStatus: {
+      [devId: string]: {
+        status: 'ONLINE'|'LATE'|'OFFLINE'
+        ts: number // Timestamp do servidor da última vez que chegou mensagem do dispostivo
+        tsBefore: number|null // Timestamp do servidor da mensagem anterior
+      }
+    }
+  },
+
+*/
+
+#[derive(Deserialize)]
+pub struct ParamsGetDevicesStatus {
+    pub devIds: Option<Vec<String>>,
+}
+
+pub async fn get_devices_status(
+    req: &HttpRequest,
+    globs: &Arc<GlobalVars>,
+) -> Result<HttpResponse, String> {
+    let req_params: ParamsGetDevicesStatus =
+        serde_json::from_slice(&req.content).map_err(|e| e.to_string())?;
+
+    let resp_devs = devs_status::get_status_json(globs, req_params.devIds.as_deref());
+
+    let response = json!({
+      "devicesStatus": resp_devs,
+    });
+
+    let response = serde_json::to_vec(&response).map_err(|err| format!("[48] {err}"))?;
+    Ok(respond_http_json_bytes(200, response))
+}
@@ -1,39 +1,61 @@
+use crate::devs_status::{DevStatusInfo, StatusTransition};
+use crate::live_stream::LiveEvent;
+use crate::recent_telemetries::RecentTelemetry;
 use crate::ConfigFile;
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::HashMap,
-    sync::{atomic::AtomicU64, Arc},
+    collections::{HashMap, VecDeque},
+    sync::{
+        atomic::{AtomicU64, AtomicUsize},
+        Arc, Mutex,
+    },
 };
-use tokio::sync::RwLock;
+use tokio::sync::{broadcast, mpsc, RwLock};
 
 pub struct GlobalVars {
     pub configfile: ConfigFile,
-    // pub broker_client: RwLock<Option<Arc<rumqttc::AsyncClient>>>,
+    pub broker_client: RwLock<Option<Arc<rumqttc::AsyncClient>>>,
     pub last_telemetry: RwLock<HashMap<String, RwLock<DevLastMessage>>>,
     pub last_timestamp: RwLock<HashMap<String, AtomicU64>>, // Timestamp do servidor da última vez que chegou mensagem do dispostivo
+    pub devs_status: Mutex<HashMap<String, DevStatusInfo>>, // Estado ONLINE/LATE/OFFLINE de cada dispositivo
+    pub to_status_notifier: mpsc::Sender<StatusTransition>,
+    pub live_events: broadcast::Sender<Arc<LiveEvent>>, // Telemetrias e status para os clientes do stream
+    pub live_clients: AtomicUsize,
 }
 
 #[derive(Deserialize, Serialize)]
 pub struct DevLastMessage {
     pub telemetry: serde_json::Value, // último JSON que chegou em tópico 'data/...'
     pub ts: u64, // Timestamp do servidor da última vez que chegou mensagem do dispostivo
-                 // pub topic?: TopicType // Tópico 'data/...' que foi usado, e não o tipo do dispositivo. O DMA por exemplo usa tópico de DUT.
-                 // pub tsBefore: number // Timestamp do servidor da telemetria anterior à atual
+    // pub topic?: TopicType // Tópico 'data/...' que foi usado, e não o tipo do dispositivo. O DMA por exemplo usa tópico de DUT.
+    // pub tsBefore: number // Timestamp do servidor da telemetria anterior à atual
+    #[serde(default)]
+    pub processed: bool, // Se a telemetria veio do iotrelay, já com as grandezas calculadas
+    #[serde(default)]
+    pub recent: VecDeque<RecentTelemetry>, // Telemetrias dos últimos minutos, da mais antiga para a mais nova
 }
 
 impl GlobalVars {
-    pub async fn new(configfile: ConfigFile) -> GlobalVars {
+    pub async fn new(configfile: ConfigFile) -> (GlobalVars, mpsc::Receiver<StatusTransition>) {
         create_globs(configfile).await
     }
 }
 
-pub async fn create_globs(configfile: ConfigFile) -> GlobalVars {
+pub async fn create_globs(
+    configfile: ConfigFile,
+) -> (GlobalVars, mpsc::Receiver<StatusTransition>) {
+    let (to_status_notifier, status_receiver) = mpsc::channel(10000);
+    let (live_events, _) = broadcast::channel(10000);
     let globs = GlobalVars {
         configfile,
-        // broker_client: RwLock::new(None),
+        broker_client: RwLock::new(None),
         last_telemetry: RwLock::new(HashMap::new()),
         last_timestamp: RwLock::new(HashMap::new()),
+        devs_status: Mutex::new(HashMap::new()),
+        to_status_notifier,
+        live_events,
+        live_clients: AtomicUsize::new(0),
     };
 
-    globs
+    (globs, status_receiver)
 }
@@ -1,38 +1,130 @@
 use super::endpoints::get_devices_last_telemetries::get_devices_last_telemetries;
 use super::endpoints::get_devices_last_ts::get_devices_last_ts;
-use crate::lib_http::response::{respond_http_plain_text, send_response};
-use crate::lib_http::types::HttpRequest;
+use super::endpoints::get_devices_recent_telemetries::get_devices_recent_telemetries;
+use super::endpoints::get_devices_status::get_devices_status;
+use super::live_stream::{self, LiveSubscription};
+use crate::lib_http::auth::ApiTokens;
+use crate::lib_http::response::{build_http_response, respond_http_plain_text};
+use crate::lib_http::router::{
+    respond_and_log, AuthPolicy, BodyParser, Handler, Method, Route, RouteRequest, RouteResult,
+    Router, Visibility,
+};
+use crate::lib_http::types::{HttpRequest, HttpResponse};
+use crate::lib_metrics::{MetricsText, CONTENT_TYPE, REGISTRY};
 use crate::GlobalVars;
-use std::sync::Arc;
+use futures::future::BoxFuture;
+use std::sync::{Arc, OnceLock};
 use tokio::net::TcpStream;
 
+// O stream de telemetrias fica com o socket depois que a rota valida os parâmetros
+type Deferred = LiveSubscription;
+
+static ROUTER: OnceLock<Router<Deferred>> = OnceLock::new();
+
+fn build_router() -> Router<Deferred> {
+    use AuthPolicy::None as NoAuth;
+    use BodyParser::Raw;
+    use Method::Any;
+    use Visibility::Public;
+
+    #[rustfmt::skip]
+    let routes = vec![
+        Route::new(Any, "/diel-internal/realtime-rs/getDevicesLastTelemetries",   Public, NoAuth, Raw, Handler::Async(devices_last_telemetries)),
+        Route::new(Any, "/diel-internal/realtime-rs/getDevicesLastTS",            Public, NoAuth, Raw, Handler::Async(devices_last_ts)),
+        Route::new(Any, "/diel-internal/realtime-rs/getDevicesStatus",            Public, NoAuth, Raw, Handler::Async(devices_status)),
+        Route::new(Any, "/diel-internal/realtime-rs/getDevicesRecentTelemetries", Public, NoAuth, Raw, Handler::Async(devices_recent_telemetries)),
+        Route::new(Any, "/diel-internal/realtime-rs/liveStream",                  Public, NoAuth, Raw, Handler::Sync(live_stream_route)),
+        Route::new(Any, "/metrics",                                               Public, NoAuth, Raw, Handler::Sync(metrics)),
+    ];
+
+    Router::new(ApiTokens::default(), routes)
+}
+
 pub async fn on_http_req(
     req: HttpRequest,
     is_internal: bool,
     mut socket: TcpStream,
     globs: Arc<GlobalVars>,
 ) {
-    let response = match &req.path[..] {
-        "/diel-internal/realtime-rs/getDevicesLastTelemetries" => {
-            get_devices_last_telemetries(&req, &globs)
-                .await
-                .unwrap_or_else(|err| respond_http_plain_text(400, &err))
+    let router = ROUTER.get_or_init(build_router);
+    match router.dispatch(req, is_internal, &globs).await {
+        RouteResult::Respond(response) => {
+            respond_and_log(&mut socket, &response).await;
         }
-        "/diel-internal/realtime-rs/getDevicesLastTS" => get_devices_last_ts(&req, &globs)
-            .await
-            .unwrap_or_else(|err| respond_http_plain_text(400, &err)),
-        _ => {
-            crate::LOG.append_log_tag_msg(
-                "ERROR",
-                &format!("Invalid request: {} {}", req.method, req.path),
-            );
-            respond_http_plain_text(404, &(String::new() + "Not found: " + &req.path))
+        RouteResult::Defer(subscription) => {
+            // O serviço HTTP atende uma requisição por vez, então o stream precisa seguir em outra tarefa
+            tokio::spawn(live_stream::serve_stream(socket, subscription, globs));
         }
     };
-    // let response = respond_http_plain_text(500, &err);
-    if let Err(err) = {
-        send_response(&mut socket, &response).await // socket_write
-    } {
-        crate::LOG.append_log_tag_msg("ERROR[81]", &err.to_string());
+}
+
+fn metrics(
+    _rreq: &RouteRequest,
+    _globs: &Arc<GlobalVars>,
+) -> Result<RouteResult<Deferred>, HttpResponse> {
+    let mut out = MetricsText::new();
+    REGISTRY.render(&mut out);
+    Ok(RouteResult::Respond(build_http_response(
+        200,
+        out.into_bytes(),
+        CONTENT_TYPE,
+    )))
+}
+
+fn devices_last_telemetries<'a>(
+    rreq: &'a RouteRequest,
+    globs: &'a Arc<GlobalVars>,
+) -> BoxFuture<'a, Result<RouteResult<Deferred>, HttpResponse>> {
+    Box::pin(async move {
+        let response = get_devices_last_telemetries(&rreq.req, globs)
+            .await
+            .unwrap_or_else(|err| respond_http_plain_text(400, &err));
+        Ok(RouteResult::Respond(response))
+    })
+}
+
+fn devices_last_ts<'a>(
+    rreq: &'a RouteRequest,
+    globs: &'a Arc<GlobalVars>,
+) -> BoxFuture<'a, Result<RouteResult<Deferred>, HttpResponse>> {
+    Box::pin(async move {
+        let response = get_devices_last_ts(&rreq.req, globs)
+            .await
+            .unwrap_or_else(|err| respond_http_plain_text(400, &err));
+        Ok(RouteResult::Respond(response))
+    })
+}
+
+fn devices_status<'a>(
+    rreq: &'a RouteRequest,
+    globs: &'a Arc<GlobalVars>,
+) -> BoxFuture<'a, Result<RouteResult<Deferred>, HttpResponse>> {
+    Box::pin(async move {
+        let response = get_devices_status(&rreq.req, globs)
+            .await
+            .unwrap_or_else(|err| respond_http_plain_text(400, &err));
+        Ok(RouteResult::Respond(response))
+    })
+}
+
+fn live_stream_route(
+    rreq: &RouteRequest,
+    _globs: &Arc<GlobalVars>,
+) -> Result<RouteResult<Deferred>, HttpResponse> {
+    match live_stream::parse_subscription(&rreq.req) {
+        Ok(subscription) => Ok(RouteResult::Defer(subscription)),
+        Err(err) => Ok(RouteResult::Respond(respond_http_plain_text(400, &err))),
     }
 }
+
+fn devices_recent_telemetries<'a>(
+    rreq: &'a RouteRequest,
+    globs: &'a Arc<GlobalVars>,
+) -> BoxFuture<'a, Result<RouteResult<Deferred>, HttpResponse>> {
+    Box::pin(async move {
+        let response = get_devices_recent_telemetries(&rreq.req, globs)
+            .await
+            .unwrap_or_else(|err| respond_http_plain_text(400, &err));
+        Ok(RouteResult::Respond(response))
+    })
+}
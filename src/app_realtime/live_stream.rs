@@ -0,0 +1,306 @@
+use crate::devs_status::{DevStatusInfo, StatusTransition};
+use crate::global_vars::DevLastMessage;
+use crate::lib_http::types::HttpRequest;
+use crate::lib_metrics::REGISTRY;
+use crate::GlobalVars;
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+use tokio::sync::{broadcast, RwLock};
+
+/*
+Stream de telemetrias e mudanças de status via Server-Sent Events, para os dashboards não precisarem
+consultar getDevicesLastTelemetries a cada poucos segundos. O cliente informa os dispositivos de interesse
+(lista de dev_ids e/ou prefixos) e a conexão fica aberta recebendo:
+  event: telemetry  data: {"devId","ts","processed","payload"}  (payload dos tópicos data/...)
+  event: status     data: {"devId","status","prevStatus","ts","tsBefore"}
+Logo ao conectar são enviados o status e a última telemetria conhecidos dos dispositivos selecionados.
+"processed" indica que a mensagem veio do iotrelay (tópico iotrelay/...), já com Lcmp, Tsh, etc.
+Cliente que não consome o que é enviado (escrita parada por mais de WRITE_TIMEOUT) é desconectado.
+*/
+
+const WRITE_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub struct LiveEvent {
+    pub dev_id: String,
+    pub event: &'static str,
+    pub data: String, // JSON já serializado, para não repetir o trabalho para cada cliente
+}
+
+#[derive(Deserialize)]
+pub struct ParamsLiveStream {
+    pub devIds: Option<Vec<String>>,
+    pub devIdPrefixes: Option<Vec<String>>,
+}
+
+pub struct LiveSubscription {
+    dev_ids: HashSet<String>,
+    prefixes: Vec<String>,
+}
+
+impl LiveSubscription {
+    pub fn matches(&self, dev_id: &str) -> bool {
+        self.dev_ids.contains(dev_id) || self.prefixes.iter().any(|p| dev_id.starts_with(p))
+    }
+}
+
+pub fn parse_subscription(req: &HttpRequest) -> Result<LiveSubscription, String> {
+    let req_params: ParamsLiveStream =
+        serde_json::from_slice(&req.content).map_err(|e| e.to_string())?;
+    let dev_ids: HashSet<String> = req_params.devIds.unwrap_or_default().into_iter().collect();
+    let prefixes = req_params.devIdPrefixes.unwrap_or_default();
+    if dev_ids.is_empty() && prefixes.is_empty() {
+        return Err("[52] Informe devIds ou devIdPrefixes".to_owned());
+    }
+    Ok(LiveSubscription { dev_ids, prefixes })
+}
+
+pub fn publish_telemetry(
+    globs: &Arc<GlobalVars>,
+    dev_id: &str,
+    ts: u64,
+    processed: bool,
+    payload: &serde_json::Value,
+) {
+    // Sem clientes conectados não precisa nem serializar
+    if globs.live_events.receiver_count() == 0 {
+        return;
+    }
+    let data = json!({
+        "devId": dev_id,
+        "ts": ts,
+        "processed": processed,
+        "payload": payload,
+    });
+    let _ = globs.live_events.send(Arc::new(LiveEvent {
+        dev_id: dev_id.to_owned(),
+        event: "telemetry",
+        data: data.to_string(),
+    }));
+}
+
+pub fn publish_status(globs: &Arc<GlobalVars>, transition: &StatusTransition) {
+    if globs.live_events.receiver_count() == 0 {
+        return;
+    }
+    let data = match serde_json::to_string(transition) {
+        Ok(x) => x,
+        Err(err) => {
+            crate::LOG.append_log_tag_msg("ERROR", &format!("[92] {err}"));
+            return;
+        }
+    };
+    let _ = globs.live_events.send(Arc::new(LiveEvent {
+        dev_id: transition.dev_id.to_owned(),
+        event: "status",
+        data,
+    }));
+}
+
+pub async fn serve_stream(
+    mut socket: TcpStream,
+    subscription: LiveSubscription,
+    globs: Arc<GlobalVars>,
+) {
+    let clients = globs.live_clients.fetch_add(1, Ordering::Relaxed) + 1;
+    let result = if clients > globs.configfile.stream_max_clients {
+        let response = "HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\n\r\n";
+        write_with_timeout(&mut socket, response.as_bytes(), "[115]").await
+    } else {
+        update_clients_gauge(clients);
+        stream_events(&mut socket, &subscription, &globs).await
+    };
+    let clients = globs.live_clients.fetch_sub(1, Ordering::Relaxed) - 1;
+    update_clients_gauge(clients);
+    if let Err(err) = result {
+        crate::LOG.append_log_tag_msg_v2("INFO", &format!("Live stream ended: {err}"), false);
+    }
+}
+
+fn update_clients_gauge(clients: usize) {
+    REGISTRY
+        .gauge(
+            "live_stream_clients",
+            "Clientes conectados no stream de telemetrias",
+            &[],
+        )
+        .set(clients as f64);
+}
+
+async fn stream_events(
+    socket: &mut TcpStream,
+    subscription: &LiveSubscription,
+    globs: &Arc<GlobalVars>,
+) -> Result<(), String> {
+    // Inscreve antes de montar o estado inicial para não perder eventos entre as duas coisas
+    let mut receiver = globs.live_events.subscribe();
+
+    let header = "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: keep-alive\r\nX-Accel-Buffering: no\r\n\r\n";
+    write_with_timeout(socket, header.as_bytes(), "[148]").await?;
+
+    let initial = initial_events(subscription, &globs.devs_status, &globs.last_telemetry).await;
+    for event in &initial {
+        write_event(socket, event).await?;
+    }
+
+    let mut heartbeat = tokio::time::interval(Duration::from_secs(15));
+    loop {
+        tokio::select! {
+            received = receiver.recv() => match received {
+                Ok(event) => {
+                    if subscription.matches(&event.dev_id) {
+                        write_event(socket, &event).await?;
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    // Cliente lento: avisa quantos eventos foram perdidos e continua do ponto atual
+                    let event = LiveEvent {
+                        dev_id: String::new(),
+                        event: "lagged",
+                        data: json!({ "skipped": skipped }).to_string(),
+                    };
+                    write_event(socket, &event).await?;
+                }
+                Err(broadcast::error::RecvError::Closed) => {
+                    return Err("events channel closed".to_owned());
+                }
+            },
+            _ = heartbeat.tick() => {
+                // Comentário SSE, mantém a conexão viva em proxies e detecta cliente desconectado
+                write_with_timeout(socket, b": ping\n\n", "[181]").await?;
+            }
+        }
+    }
+}
+
+async fn initial_events(
+    subscription: &LiveSubscription,
+    devs_status: &Mutex<HashMap<String, DevStatusInfo>>,
+    last_telemetry: &RwLock<HashMap<String, RwLock<DevLastMessage>>>,
+) -> Vec<LiveEvent> {
+    let mut events = Vec::new();
+    {
+        let devs = devs_status.lock().unwrap();
+        for (dev_id, info) in devs.iter() {
+            if !subscription.matches(dev_id) {
+                continue;
+            }
+            let transition = StatusTransition {
+                dev_id: dev_id.to_owned(),
+                status: info.status,
+                prev_status: None,
+                ts: info.ts,
+                ts_before: info.ts_before,
+            };
+            if let Ok(data) = serde_json::to_string(&transition) {
+                events.push(LiveEvent {
+                    dev_id: dev_id.to_owned(),
+                    event: "status",
+                    data,
+                });
+            }
+        }
+    }
+    let all_devs = last_telemetry.read().await;
+    for (dev_id, dev_info) in all_devs.iter() {
+        if !subscription.matches(dev_id) {
+            continue;
+        }
+        let dev_info = dev_info.read().await;
+        let data = json!({
+            "devId": dev_id,
+            "ts": dev_info.ts,
+            "processed": dev_info.processed,
+            "payload": dev_info.telemetry,
+        });
+        events.push(LiveEvent {
+            dev_id: dev_id.to_owned(),
+            event: "telemetry",
+            data: data.to_string(),
+        });
+    }
+    events
+}
+
+async fn write_event(socket: &mut TcpStream, event: &LiveEvent) -> Result<(), String> {
+    let message = format!("event: {}\ndata: {}\n\n", event.event, event.data);
+    write_with_timeout(socket, message.as_bytes(), "[230]").await
+}
+
+// Sem o timeout um cliente que parou de ler deixaria a tarefa presa no write_all com o buffer do socket cheio
+async fn write_with_timeout(
+    socket: &mut TcpStream,
+    bytes: &[u8],
+    code: &str,
+) -> Result<(), String> {
+    match tokio::time::timeout(WRITE_TIMEOUT, socket.write_all(bytes)).await {
+        Ok(result) => result.map_err(|err| format!("{code} {err}")),
+        Err(_) => Err(format!("{code} write timed out")),
+    }
+}
+
+#[test]
+fn test_live_subscription() {
+    let parse = |body: serde_json::Value| {
+        parse_subscription(&HttpRequest::new_post("/", body.to_string().into_bytes()))
+    };
+    assert!(parse(json!({})).is_err());
+    assert!(parse(json!({ "devIds": [], "devIdPrefixes": [] })).is_err());
+    assert!(parse(json!({ "devIds": "DAC1" })).is_err());
+
+    let subscription =
+        parse(json!({ "devIds": ["DUT301221234"], "devIdPrefixes": ["DAC"] })).unwrap();
+    assert!(subscription.matches("DUT301221234"));
+    assert!(subscription.matches("DAC210191234"));
+    assert!(!subscription.matches("DUT301229999"));
+    assert!(!subscription.matches("DUT3012212345"));
+
+    let devs_status = Mutex::new(HashMap::new());
+    let last_telemetry = RwLock::new(HashMap::new());
+    for (dev_id, ts) in [
+        ("DAC210191234", 1000),
+        ("DUT301221234", 2000),
+        ("DRI009221234", 3000),
+    ] {
+        devs_status.lock().unwrap().insert(
+            dev_id.to_owned(),
+            DevStatusInfo {
+                status: crate::devs_status::DevStatus::Online,
+                ts,
+                ts_before: None,
+            },
+        );
+        last_telemetry.try_write().unwrap().insert(
+            dev_id.to_owned(),
+            RwLock::new(DevLastMessage {
+                telemetry: json!({ "dev_id": dev_id }),
+                ts,
+                processed: false,
+                recent: Default::default(),
+            }),
+        );
+    }
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    let events = rt.block_on(initial_events(&subscription, &devs_status, &last_telemetry));
+    let mut events: Vec<(&str, &str)> = events
+        .iter()
+        .map(|event| (event.event, event.dev_id.as_str()))
+        .collect();
+    events.sort();
+    assert_eq!(
+        events,
+        vec![
+            ("status", "DAC210191234"),
+            ("status", "DUT301221234"),
+            ("telemetry", "DAC210191234"),
+            ("telemetry", "DUT301221234"),
+        ]
+    );
+}
@@ -8,6 +8,7 @@ pub async fn task_mqtt_broker_reader(globs: Arc<GlobalVars>) {
     let broker_config = &globs.configfile.broker_config;
     loop {
         let result_msg = task_mqtt_client_broker_rumqtt(&globs).await;
+        *(globs.broker_client.write().await) = None;
         crate::LOG.append_log_tag_msg(
             "error",
             &format!(
@@ -33,14 +34,18 @@ pub async fn connect_to_mqtt_broker(globs: &Arc<GlobalVars>) -> Result<rumqttc::
     let (eventloop, client_mqtt) = abrir_conexao_broker_rumqtt(broker_config, &client_id).await?;
 
     // Faz subscribe nos tópicos de interesse
-    let topics = ["iotrelay/data/#", "iotrelay/control/#"];
-    for topic in topics {
+    for topic in &globs.configfile.mqtt_topics {
         client_mqtt
             .subscribe(topic, rumqttc::QoS::ExactlyOnce)
             .await
             .map_err(|e| e.to_string())?;
     }
 
+    // O mesmo cliente é usado para publicar as mudanças de status
+    {
+        *(globs.broker_client.write().await) = Some(Arc::new(client_mqtt));
+    }
+
     // Just loop on incoming messages.
     crate::LOG.append_log_tag_msg(
         "info",
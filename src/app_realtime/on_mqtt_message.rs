@@ -1,4 +1,7 @@
+use super::devs_status;
 use super::global_vars::DevLastMessage;
+use super::live_stream;
+use super::recent_telemetries;
 use crate::GlobalVars;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
@@ -6,6 +9,7 @@ use std::time::{SystemTime, UNIX_EPOCH};
 use tokio::sync::RwLock;
 
 /*
+Port em src/app_realtime/devs_status.rs:
 
 function onDeviceMessage(devId: string) {
   let devLastMessages = lastMessages[devId];
@@ -59,6 +63,8 @@ async fn process_payload_on_valid_topic(
     packet: rumqttc::Publish,
     is_data: bool,
 ) {
+    // Mensagens republicadas pelo iotrelay já vêm com as grandezas calculadas (Lcmp, Tsh, ...)
+    let processed = packet.topic.starts_with("iotrelay/");
     let (_payload_str, payload_json, dev_id) = match parse_payload_json(&packet) {
         ResultJsonParse::Ok(x) => x,
         ResultJsonParse::Ignore => {
@@ -78,6 +84,9 @@ async fn process_payload_on_valid_topic(
         .try_into()
         .expect("timestamp too large");
 
+    // Atualiza o status de conexão (ONLINE/LATE/OFFLINE)
+    devs_status::on_device_message(&globs, &dev_id, now_millis);
+
     // Atualiza o last_timestamp
     {
         let mut need_insert = false;
@@ -100,12 +109,15 @@ async fn process_payload_on_valid_topic(
 
     // Atualiza o last_telemetry
     if is_data {
+        live_stream::publish_telemetry(&globs, &dev_id, now_millis, processed, &payload_json);
         let need_insert;
         match globs.last_telemetry.read().await.get(&dev_id) {
             Some(dev_info) => {
                 let mut dev_info = dev_info.write().await;
+                recent_telemetries::push(&globs, &dev_id, &mut dev_info, now_millis, &payload_json);
                 dev_info.ts = now_millis;
                 dev_info.telemetry = payload_json;
+                dev_info.processed = processed;
                 return;
             }
             None => {
@@ -113,10 +125,14 @@ async fn process_payload_on_valid_topic(
             }
         };
         if need_insert {
-            let dev_info = DevLastMessage {
+            let mut dev_info = DevLastMessage {
                 ts: now_millis,
-                telemetry: payload_json,
+                telemetry: serde_json::Value::Null,
+                processed,
+                recent: Default::default(),
             };
+            recent_telemetries::push(&globs, &dev_id, &mut dev_info, now_millis, &payload_json);
+            dev_info.telemetry = payload_json;
             globs
                 .last_telemetry
                 .write()
@@ -0,0 +1,97 @@
+use crate::configs::config_for_dev_type;
+use crate::global_vars::DevLastMessage;
+use crate::GlobalVars;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+/*
+Histórico curto das telemetrias de cada dispositivo (os últimos N minutos), guardado junto com a última
+telemetria em DevLastMessage e por isso também no cache em disco. Serve os gráficos de "última hora"
+dos dashboards sem precisar passar pelo rusthist e pelo DynamoDB.
+O tamanho é configurado por tipo de dispositivo em REALTIME_RECENT_WINDOWS.
+*/
+
+#[derive(Deserialize, Clone, Copy, Debug)]
+pub struct RecentWindow {
+    pub minutes: u64,
+    pub max_items: usize, // Limite de memória para dispositivos que enviam telemetrias com muita frequência
+}
+
+const DEFAULT_WINDOW: RecentWindow = RecentWindow {
+    minutes: 60,
+    max_items: 60,
+};
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct RecentTelemetry {
+    pub ts: u64, // Timestamp do servidor de quando a telemetria chegou
+    pub telemetry: serde_json::Value,
+}
+
+pub fn window_for(globs: &Arc<GlobalVars>, dev_id: &str) -> RecentWindow {
+    config_for_dev_type(&globs.configfile.recent_windows, dev_id)
+        .copied()
+        .unwrap_or(DEFAULT_WINDOW)
+}
+
+pub fn push(
+    globs: &Arc<GlobalVars>,
+    dev_id: &str,
+    dev_info: &mut DevLastMessage,
+    now: u64,
+    telemetry: &serde_json::Value,
+) {
+    let window = window_for(globs, dev_id);
+    if window.minutes == 0 || window.max_items == 0 {
+        dev_info.recent.clear();
+        return;
+    }
+    dev_info.recent.push_back(RecentTelemetry {
+        ts: now,
+        telemetry: telemetry.clone(),
+    });
+    prune(&mut dev_info.recent, window, now);
+}
+
+// Descarta o que passou do tamanho ou da janela de tempo
+pub fn prune(recent: &mut VecDeque<RecentTelemetry>, window: RecentWindow, now: u64) {
+    while recent.len() > window.max_items {
+        recent.pop_front();
+    }
+    let limit = now.saturating_sub(window.minutes * 60 * 1000);
+    while recent.front().is_some_and(|item| item.ts < limit) {
+        recent.pop_front();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{prune, RecentTelemetry, RecentWindow};
+    use std::collections::VecDeque;
+
+    #[test]
+    fn test_recent_window_prune() {
+        let window = RecentWindow {
+            minutes: 1,
+            max_items: 3,
+        };
+        let mut recent: VecDeque<RecentTelemetry> = [0, 10_000, 50_000, 70_000, 90_000]
+            .into_iter()
+            .map(|ts| RecentTelemetry {
+                ts,
+                telemetry: serde_json::Value::Null,
+            })
+            .collect();
+        prune(&mut recent, window, 90_000);
+        assert_eq!(
+            recent.iter().map(|x| x.ts).collect::<Vec<_>>(),
+            [50_000, 70_000, 90_000]
+        );
+        prune(&mut recent, window, 125_000);
+        assert_eq!(
+            recent.iter().map(|x| x.ts).collect::<Vec<_>>(),
+            [70_000, 90_000]
+        );
+    }
+}
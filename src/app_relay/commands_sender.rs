@@ -1,8 +1,9 @@
+use crate::lib_metrics::{Counter, REGISTRY};
 use crate::GlobalVars;
 use futures::StreamExt;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, RwLock};
 use MsgToBroker::*;
 
 pub enum MsgToBroker {
@@ -16,6 +17,46 @@ impl std::fmt::Debug for MsgToBroker {
     }
 }
 
+// Conexão com um broker para onde as mensagens são encaminhadas. O cliente fica None enquanto está desconectado.
+pub struct BrokerConn {
+    pub name: String,
+    pub ingest: bool, // Os dispositivos estão neste broker, então os comandos para eles vão por aqui
+    pub publish: bool, // Recebe as telemetrias processadas
+    pub max_attempts: u32, // Tentativas de publicação antes de desistir da mensagem (0 = sem limite)
+    pub client: RwLock<Option<Arc<rumqttc::AsyncClient>>>,
+    pub sent: Arc<Counter>,
+    pub errors: Arc<Counter>,
+    pub dropped: Arc<Counter>,
+}
+
+impl BrokerConn {
+    pub fn new(name: &str, ingest: bool, publish: bool, max_attempts: u32) -> Self {
+        let labels = [("broker", name)];
+        BrokerConn {
+            name: name.to_owned(),
+            ingest,
+            publish,
+            max_attempts,
+            client: RwLock::new(None),
+            sent: REGISTRY.counter(
+                "broker_publish_total",
+                "Mensagens encaminhadas, por broker",
+                &labels,
+            ),
+            errors: REGISTRY.counter(
+                "broker_publish_error_total",
+                "Erros ao encaminhar mensagens, por broker",
+                &labels,
+            ),
+            dropped: REGISTRY.counter(
+                "broker_publish_dropped_total",
+                "Mensagens descartadas depois de esgotar as tentativas, por broker",
+                &labels,
+            ),
+        }
+    }
+}
+
 pub async fn task_mqtt_broker_writer(
     receiver: mpsc::Receiver<MsgToBroker>,
     globs: Arc<GlobalVars>,
@@ -24,69 +65,150 @@ pub async fn task_mqtt_broker_writer(
     stream
         .for_each_concurrent(None, |msg| async {
             let MsgToBroker::MessageToTopic(topic, packet_payload) = msg;
-            let mut tentativa = 1;
-            loop {
-                let broker = {
-                    let client = globs
-                        .broker_client
-                        .read()
-                        .await
-                        .as_ref()
-                        .map(|client| client.clone());
-                    match client {
-                        None => {
-                            crate::LOG.append_log_tag_msg(
-                                "ERR_FWBRKR",
-                                &format!(
-                                    "[E1][T{}] {:?} {:?} {}",
-                                    tentativa, &topic, packet_payload, "No connection"
-                                ),
-                            );
-                            tokio::time::sleep(std::time::Duration::from_secs(1)).await;
-                            tentativa += 1;
-                            continue;
-                        }
-                        Some(client) => client,
-                    }
-                };
+            // Cada broker tem suas próprias tentativas, um broker fora do ar não atrasa os outros
+            let brokers = publish_brokers(&globs.brokers);
+            futures::future::join_all(
+                brokers.map(|broker| publish_to_broker(&globs, broker, &topic, &packet_payload)),
+            )
+            .await;
+        })
+        .await;
+}
+
+// Brokers que recebem as mensagens encaminhadas (telemetrias processadas)
+pub fn publish_brokers(brokers: &[BrokerConn]) -> impl Iterator<Item = &BrokerConn> {
+    brokers.iter().filter(|broker| broker.publish)
+}
+
+// Brokers onde estão os dispositivos, por onde vão os comandos para eles
+pub fn ingest_brokers(brokers: &[BrokerConn]) -> impl Iterator<Item = &BrokerConn> {
+    brokers.iter().filter(|broker| broker.ingest)
+}
+
+// Retorna false se desistiu da mensagem depois de max_attempts tentativas
+pub async fn publish_to_broker(
+    globs: &Arc<GlobalVars>,
+    broker_conn: &BrokerConn,
+    topic: &str,
+    packet_payload: &str,
+) -> bool {
+    let mut tentativa = 1;
+    loop {
+        if broker_conn.max_attempts != 0 && tentativa > broker_conn.max_attempts {
+            crate::LOG.append_log_tag_msg(
+                "ERR_FWBRKR",
+                &format!(
+                    "[E3][T{}][{}] {} {} {}",
+                    tentativa - 1,
+                    broker_conn.name,
+                    topic,
+                    packet_payload,
+                    "Giving up"
+                ),
+            );
+            broker_conn.dropped.inc();
+            return false;
+        }
+        let broker = {
+            let client = broker_conn
+                .client
+                .read()
+                .await
+                .as_ref()
+                .map(|client| client.clone());
+            match client {
+                None => {
+                    crate::LOG.append_log_tag_msg(
+                        "ERR_FWBRKR",
+                        &format!(
+                            "[E1][T{}][{}] {:?} {:?} {}",
+                            tentativa, broker_conn.name, topic, packet_payload, "No connection"
+                        ),
+                    );
+                    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+                    tentativa += 1;
+                    continue;
+                }
+                Some(client) => client,
+            }
+        };
 
-                let fut = tokio::time::timeout(
-                    std::time::Duration::from_secs(3),
-                    broker.publish(
-                        &topic,
-                        rumqttc::QoS::AtLeastOnce,
-                        false,
-                        packet_payload.as_bytes(),
-                    ), // rumqttc::QoS::AtLeastOnce
+        let fut = tokio::time::timeout(
+            std::time::Duration::from_secs(3),
+            broker.publish(
+                topic,
+                rumqttc::QoS::AtLeastOnce,
+                false,
+                packet_payload.as_bytes(),
+            ), // rumqttc::QoS::AtLeastOnce
+        );
+        let result = fut
+            .await
+            .map_err(|_e| "Publish to broker operation timed out".to_owned())
+            .and_then(|v| v.map_err(|err| format!("MQTT Error: {}", err)));
+        match result {
+            Err(err) => {
+                // ("Erro ao encaminhar a mensagem para o broker");
+                crate::LOG.append_log_tag_msg(
+                    "ERR_FWBRKR",
+                    &format!(
+                        "[E2][T{}][{}] {} {} {}",
+                        tentativa, broker_conn.name, topic, packet_payload, err
+                    ),
                 );
-                let result = fut
-                    .await
-                    .map_err(|_e| "Publish to broker operation timed out".to_owned())
-                    .and_then(|v| v.map_err(|err| format!("MQTT Error: {}", err)));
-                match result {
-                    Err(err) => {
-                        // ("Erro ao encaminhar a mensagem para o broker");
-                        crate::LOG.append_log_tag_msg(
-                            "ERR_FWBRKR",
-                            &format!("[E2][T{}] {} {} {}", tentativa, &topic, packet_payload, err),
-                        );
-                        globs.stats.fwbroker_error.fetch_add(1, Ordering::Relaxed);
-                        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
-                        tentativa += 1;
-                        continue;
-                    }
-                    Ok(()) => {
-                        if tentativa > 1 {
-                            crate::LOG.append_log_tag_msg(
-                                "FWBRKR_OK",
-                                &format!("[T{}] {} {}", tentativa, &topic, packet_payload),
-                            );
-                        }
-                        globs.stats.fwbroker_sent.fetch_add(1, Ordering::Relaxed);
-                    }
-                };
-                break;
+                globs.stats.fwbroker_error.fetch_add(1, Ordering::Relaxed);
+                broker_conn.errors.inc();
+                tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+                tentativa += 1;
+                continue;
             }
-        })
-        .await;
+            Ok(()) => {
+                if tentativa > 1 {
+                    crate::LOG.append_log_tag_msg(
+                        "FWBRKR_OK",
+                        &format!(
+                            "[T{}][{}] {} {}",
+                            tentativa, broker_conn.name, topic, packet_payload
+                        ),
+                    );
+                }
+                globs.stats.fwbroker_sent.fetch_add(1, Ordering::Relaxed);
+                broker_conn.sent.inc();
+            }
+        };
+        return true;
+    }
+}
+
+#[test]
+fn test_brokers_fan_out() {
+    use crate::app_relay::configs::{RelayBroker, RelayBrokerInfo};
+
+    let infos: Vec<RelayBrokerInfo> = serde_json::from_value(serde_json::json!([
+        { "name": "fan-out-ingest", "host": "a", "port": 1883, "username": "u", "password": "p",
+          "use_tls": false, "role": "ingest" },
+        { "name": "fan-out-publish", "host": "b", "port": 1883, "username": "u", "password": "p",
+          "use_tls": false, "role": "publish" },
+        { "name": "fan-out-both", "host": "c", "port": 1883, "username": "u", "password": "p",
+          "use_tls": false },
+    ]))
+    .unwrap();
+    // Mesma conversão do create_globs
+    let brokers: Vec<BrokerConn> = infos
+        .into_iter()
+        .map(RelayBroker::from)
+        .map(|broker| BrokerConn::new(&broker.name, broker.role.ingest(), broker.role.publish(), 1))
+        .collect();
+
+    let names = |selected: Vec<&BrokerConn>| -> Vec<String> {
+        selected.iter().map(|broker| broker.name.clone()).collect()
+    };
+    assert_eq!(
+        names(publish_brokers(&brokers).collect()),
+        vec!["fan-out-publish", "fan-out-both"]
+    );
+    assert_eq!(
+        names(ingest_brokers(&brokers).collect()),
+        vec!["fan-out-ingest", "fan-out-both"]
+    );
 }
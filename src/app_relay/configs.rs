@@ -1,13 +1,15 @@
 use crate::envvars_loader;
 use crate::lib_rumqtt::BrokerConfig;
+use crate::lib_state_store::store::{MemoryStoreConfig, StateStoreConfig};
 use serde::Deserialize;
 
 pub struct ConfigFile {
     pub listen_http_api: String,
     pub apiserver_internal_api: String,
-    pub broker_config: BrokerConfig,
-    pub url_redis: String,
-    pub redis_prefix: String,
+    pub brokers: Vec<RelayBroker>,
+    pub broker_publish_max_attempts: u32,
+    pub hwcfg_resync_interval: std::time::Duration,
+    pub state_store: StateStoreConfig,
 }
 
 impl ConfigFile {
@@ -17,21 +19,25 @@ impl ConfigFile {
         let LISTEN_SOCKET_IOTRELAY_HTTP =
             envvars_loader::get_var_string_required("LISTEN_SOCKET_IOTRELAY_HTTP")?;
         let STATS_SERVER_HTTP = envvars_loader::get_var_string_required("STATS_SERVER_HTTP")?;
-        let URL_REDIS = envvars_loader::get_var_string_required("URL_REDIS")?;
+        let URL_REDIS = envvars_loader::get_var_string_optional("URL_REDIS");
         let BROKER_TLS_CA_PUBLIC_CERT =
             envvars_loader::get_var_string_optional("BROKER_TLS_CA_PUBLIC_CERT");
-        let BROKER: BrokerInfo = envvars_loader::get_var_structure_required("BROKER")?;
+        let BROKERS: Option<Vec<RelayBrokerInfo>> =
+            envvars_loader::get_var_structure_optional("BROKERS")?;
         let redis_prefix = envvars_loader::get_var_string_optional("REDIS_PREFIX")
             .unwrap_or_else(|| "relay/".to_owned());
+        let state_store = load_state_store_config(URL_REDIS, redis_prefix)?;
+        let broker_publish_max_attempts = load_publish_max_attempts()?;
+        let hwcfg_resync_interval = load_hwcfg_resync_interval()?;
 
-        let broker_config = BrokerConfig {
-            host: BROKER.host,
-            port: BROKER.port,
-            username: BROKER.username,
-            password: BROKER.password,
-            use_tls: BROKER.use_tls,
-            ca_cert: BROKER_TLS_CA_PUBLIC_CERT,
+        let brokers = match BROKERS {
+            Some(brokers) => brokers.into_iter().map(RelayBroker::from).collect(),
+            None => {
+                let BROKER: BrokerInfo = envvars_loader::get_var_structure_required("BROKER")?;
+                vec![legacy_broker(BROKER, BROKER_TLS_CA_PUBLIC_CERT)]
+            }
         };
+        check_brokers(&brokers)?;
 
         let apiserver_internal_api = if STATS_SERVER_HTTP.contains("://") {
             STATS_SERVER_HTTP
@@ -42,13 +48,174 @@ impl ConfigFile {
         Ok(ConfigFile {
             listen_http_api: LISTEN_SOCKET_IOTRELAY_HTTP,
             apiserver_internal_api,
-            broker_config,
-            url_redis: URL_REDIS,
-            redis_prefix,
+            brokers,
+            broker_publish_max_attempts,
+            hwcfg_resync_interval,
+            state_store,
         })
     }
 }
 
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum BrokerRole {
+    Ingest,  // Só recebe telemetrias
+    Publish, // Só recebe as mensagens encaminhadas pelo iotrelay
+    #[default]
+    Both,
+}
+
+impl BrokerRole {
+    pub fn ingest(&self) -> bool {
+        *self != BrokerRole::Publish
+    }
+    pub fn publish(&self) -> bool {
+        *self != BrokerRole::Ingest
+    }
+}
+
+pub struct RelayBroker {
+    pub name: String,
+    pub broker_config: BrokerConfig,
+    pub role: BrokerRole,
+    pub topics: Vec<String>, // Só usado se o papel incluir "ingest"
+}
+
+// Item da lista BROKERS
+#[derive(Deserialize)]
+pub struct RelayBrokerInfo {
+    pub name: String,
+    pub host: String,
+    pub port: u16,
+    pub username: String,
+    pub password: String,
+    pub use_tls: bool,
+    pub ca_cert: Option<String>,
+    #[serde(default)]
+    pub role: BrokerRole,
+    pub topics: Option<Vec<String>>,
+}
+
+impl From<RelayBrokerInfo> for RelayBroker {
+    fn from(info: RelayBrokerInfo) -> Self {
+        RelayBroker {
+            name: info.name,
+            broker_config: BrokerConfig {
+                host: info.host,
+                port: info.port,
+                username: info.username,
+                password: info.password,
+                use_tls: info.use_tls,
+                ca_cert: info.ca_cert,
+            },
+            role: info.role,
+            topics: info.topics.unwrap_or_else(default_ingest_topics),
+        }
+    }
+}
+
+// Formato antigo: um único broker, usado para receber e para publicar
+fn legacy_broker(BROKER: BrokerInfo, ca_cert: Option<String>) -> RelayBroker {
+    RelayBroker {
+        name: "default".to_owned(),
+        broker_config: BrokerConfig {
+            host: BROKER.host,
+            port: BROKER.port,
+            username: BROKER.username,
+            password: BROKER.password,
+            use_tls: BROKER.use_tls,
+            ca_cert,
+        },
+        role: BrokerRole::Both,
+        topics: default_ingest_topics(),
+    }
+}
+
+pub fn default_ingest_topics() -> Vec<String> {
+    vec![
+        r"$share/iotrelay/data/#".to_owned(),
+        r"$share/iotrelay/control/#".to_owned(),
+        "apiserver/#".to_owned(),
+    ]
+}
+
+fn check_brokers(brokers: &[RelayBroker]) -> Result<(), String> {
+    if brokers.is_empty() {
+        return Err("BROKERS must have at least one broker".to_owned());
+    }
+    for (i, broker) in brokers.iter().enumerate() {
+        if broker.name.is_empty() {
+            return Err("BROKERS: missing broker name".to_owned());
+        }
+        if brokers[..i].iter().any(|other| other.name == broker.name) {
+            return Err(format!("BROKERS: duplicated broker name: {}", broker.name));
+        }
+        if broker.broker_config.use_tls && broker.broker_config.ca_cert.is_none() {
+            return Err(format!("BROKERS: missing ca_cert for {}", broker.name));
+        }
+    }
+    Ok(())
+}
+
+// Tentativas de publicação de cada mensagem no broker antes de desistir dela (0 = sem limite)
+pub fn load_publish_max_attempts() -> Result<u32, String> {
+    let max_attempts = envvars_loader::get_var_structure_optional("BROKER_PUBLISH_MAX_ATTEMPTS")?;
+    Ok(max_attempts.unwrap_or(60))
+}
+
+// Intervalo entre as sincronizações completas das configs de hardware com o API-Server
+pub fn load_hwcfg_resync_interval() -> Result<std::time::Duration, String> {
+    let interval_s: Option<u64> =
+        envvars_loader::get_var_structure_optional("HWCFG_RESYNC_INTERVAL_S")?;
+    let interval_s = interval_s.unwrap_or(3600);
+    if interval_s == 0 {
+        return Err("HWCFG_RESYNC_INTERVAL_S must be greater than 0".to_owned());
+    }
+    Ok(std::time::Duration::from_secs(interval_s))
+}
+
+// Usado também pelo telemetry_service, que tem as mesmas conversões do iotrelay
+pub fn load_state_store_config(
+    url_redis: Option<String>,
+    redis_prefix: String,
+) -> Result<StateStoreConfig, String> {
+    let STATE_STORE = envvars_loader::get_var_string_optional("STATE_STORE");
+    let STATE_STORE_FALLBACK = envvars_loader::get_var_bool_optional("STATE_STORE_FALLBACK")?;
+    let STATE_STORE_MAX_DEVICES: Option<usize> =
+        envvars_loader::get_var_structure_optional("STATE_STORE_MAX_DEVICES")?;
+    let STATE_STORE_SNAPSHOT_FILE =
+        envvars_loader::get_var_string_optional("STATE_STORE_SNAPSHOT_FILE");
+    let STATE_STORE_SNAPSHOT_INTERVAL_S =
+        envvars_loader::get_var_u16_optional("STATE_STORE_SNAPSHOT_INTERVAL_S")?;
+
+    let memory_config = MemoryStoreConfig {
+        max_devices: STATE_STORE_MAX_DEVICES.unwrap_or(100_000).max(1),
+        snapshot_file: STATE_STORE_SNAPSHOT_FILE,
+        snapshot_interval: std::time::Duration::from_secs(
+            STATE_STORE_SNAPSHOT_INTERVAL_S.unwrap_or(300).max(1).into(),
+        ),
+    };
+
+    match STATE_STORE.as_deref() {
+        None | Some("redis") => Ok(StateStoreConfig::Redis {
+            url: url_redis
+                .ok_or_else(|| "Faltou informar a configuração 'URL_REDIS'".to_owned())?,
+            prefix: redis_prefix,
+            fallback: if STATE_STORE_FALLBACK == Some(false) {
+                None
+            } else {
+                Some(memory_config)
+            },
+        }),
+        Some("memory") => Ok(StateStoreConfig::Memory(memory_config)),
+        Some("file") => {
+            let STATE_STORE_DIR = envvars_loader::get_var_string_required("STATE_STORE_DIR")?;
+            Ok(StateStoreConfig::File(STATE_STORE_DIR))
+        }
+        Some(x) => Err(format!("Invalid STATE_STORE: {x}")),
+    }
+}
+
 #[derive(Deserialize)]
 pub struct BrokerInfo {
     pub host: String,
@@ -57,3 +224,55 @@ pub struct BrokerInfo {
     pub password: String,
     pub use_tls: bool,
 }
+
+#[test]
+fn test_brokers_config() {
+    let BROKER: BrokerInfo = serde_json::from_value(serde_json::json!({
+        "host": "mqtt.local", "port": 8883, "username": "relay", "password": "x", "use_tls": true,
+    }))
+    .unwrap();
+    let legacy = legacy_broker(BROKER, Some("ca.pem".to_owned()));
+    assert_eq!(legacy.name, "default");
+    assert_eq!(legacy.role, BrokerRole::Both);
+    assert_eq!(legacy.topics, default_ingest_topics());
+    assert_eq!(legacy.broker_config.ca_cert.as_deref(), Some("ca.pem"));
+    assert!(check_brokers(&[legacy]).is_ok());
+
+    let parse = |value: serde_json::Value| -> Vec<RelayBroker> {
+        let infos: Vec<RelayBrokerInfo> = serde_json::from_value(value).unwrap();
+        infos.into_iter().map(RelayBroker::from).collect()
+    };
+    let brokers = parse(serde_json::json!([
+        { "name": "devices", "host": "a", "port": 1883, "username": "u", "password": "p", "use_tls": false,
+          "role": "ingest", "topics": ["data/#"] },
+        { "name": "backend", "host": "b", "port": 1883, "username": "u", "password": "p", "use_tls": false,
+          "role": "publish" },
+        { "name": "legacy", "host": "c", "port": 8883, "username": "u", "password": "p", "use_tls": true,
+          "ca_cert": "ca.pem" },
+    ]));
+    assert!(check_brokers(&brokers).is_ok());
+    let roles: Vec<(bool, bool)> = brokers
+        .iter()
+        .map(|broker| (broker.role.ingest(), broker.role.publish()))
+        .collect();
+    assert_eq!(roles, vec![(true, false), (false, true), (true, true)]);
+    assert_eq!(brokers[0].topics, vec!["data/#"]);
+    assert_eq!(brokers[1].topics, default_ingest_topics());
+
+    let brokers = parse(serde_json::json!([
+        { "name": "a", "host": "a", "port": 1883, "username": "u", "password": "p", "use_tls": false },
+        { "name": "a", "host": "b", "port": 1883, "username": "u", "password": "p", "use_tls": false },
+    ]));
+    assert_eq!(
+        check_brokers(&brokers).unwrap_err(),
+        "BROKERS: duplicated broker name: a"
+    );
+    let brokers = parse(serde_json::json!([
+        { "name": "a", "host": "a", "port": 8883, "username": "u", "password": "p", "use_tls": true },
+    ]));
+    assert_eq!(
+        check_brokers(&brokers).unwrap_err(),
+        "BROKERS: missing ca_cert for a"
+    );
+    assert!(check_brokers(&[]).is_err());
+}
@@ -0,0 +1,319 @@
+use super::commands_sender::{ingest_brokers, publish_to_broker};
+use crate::lib_metrics::REGISTRY;
+use crate::GlobalVars;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::oneshot;
+
+/*
+Comandos enviados para os dispositivos pelo tópico commands/<dev_id>, com acompanhamento da resposta.
+Se o comando informar `expectMsgtype`, a primeira mensagem em control/... do mesmo dispositivo com esse
+msgtype depois da publicação é considerada a resposta. Sem resposta dentro do timeout o comando fica
+como timed_out. Os últimos comandos de cada dispositivo ficam guardados em memória para consulta.
+
+  queued -> published -> acknowledged
+                      -> timed_out
+         -> failed (não conseguiu publicar em nenhum broker)
+*/
+
+const MAX_COMMANDS_PER_DEV: usize = 50;
+pub const DEFAULT_TIMEOUT_S: u64 = 30;
+pub const MAX_TIMEOUT_S: u64 = 300;
+
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum CommandStatus {
+    Queued,
+    Published,
+    Acknowledged,
+    TimedOut,
+    Failed,
+}
+
+impl CommandStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            CommandStatus::Queued => "queued",
+            CommandStatus::Published => "published",
+            CommandStatus::Acknowledged => "acknowledged",
+            CommandStatus::TimedOut => "timed_out",
+            CommandStatus::Failed => "failed",
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct CommandRequest {
+    pub devId: String,
+    pub payload: serde_json::Value,
+    pub expectMsgtype: Option<String>, // msgtype da resposta esperada em control/...
+    pub timeoutS: Option<u64>,
+    pub wait: Option<bool>, // Se a requisição HTTP deve aguardar a resposta do dispositivo
+}
+
+#[derive(Serialize, Clone)]
+pub struct CommandRecord {
+    pub id: u64,
+    #[serde(rename = "devId")]
+    pub dev_id: String,
+    pub topic: String,
+    pub payload: serde_json::Value,
+    #[serde(rename = "expectMsgtype")]
+    pub expect_msgtype: Option<String>,
+    pub status: CommandStatus,
+    #[serde(rename = "tsQueued")]
+    pub ts_queued: u64,
+    #[serde(rename = "tsPublished")]
+    pub ts_published: Option<u64>,
+    #[serde(rename = "tsReply")]
+    pub ts_reply: Option<u64>,
+    pub reply: Option<serde_json::Value>,
+}
+
+pub struct CommandLog {
+    next_id: AtomicU64,
+    by_dev: Mutex<HashMap<String, VecDeque<CommandRecord>>>,
+    waiters: Mutex<HashMap<u64, oneshot::Sender<serde_json::Value>>>,
+}
+
+impl CommandLog {
+    pub fn new() -> Self {
+        CommandLog {
+            next_id: AtomicU64::new(1),
+            by_dev: Mutex::new(HashMap::new()),
+            waiters: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn insert(&self, record: CommandRecord) {
+        let mut by_dev = self.by_dev.lock().unwrap();
+        let commands = by_dev.entry(record.dev_id.to_owned()).or_default();
+        commands.push_back(record);
+        while commands.len() > MAX_COMMANDS_PER_DEV {
+            commands.pop_front();
+        }
+    }
+
+    fn update(&self, dev_id: &str, id: u64, change: impl FnOnce(&mut CommandRecord)) {
+        let mut by_dev = self.by_dev.lock().unwrap();
+        let record = by_dev
+            .get_mut(dev_id)
+            .and_then(|commands| commands.iter_mut().find(|x| x.id == id));
+        if let Some(record) = record {
+            change(record);
+        }
+    }
+
+    fn get(&self, dev_id: &str, id: u64) -> Option<CommandRecord> {
+        let by_dev = self.by_dev.lock().unwrap();
+        by_dev
+            .get(dev_id)
+            .and_then(|commands| commands.iter().find(|x| x.id == id))
+            .cloned()
+    }
+
+    fn deliver_reply(&self, dev_id: &str, payload_json: &serde_json::Value) {
+        let Some(msgtype) = payload_json["msgtype"].as_str() else {
+            return;
+        };
+        let command_id = {
+            let by_dev = self.by_dev.lock().unwrap();
+            let Some(commands) = by_dev.get(dev_id) else {
+                return;
+            };
+            let waiters = self.waiters.lock().unwrap();
+            commands
+                .iter()
+                // A resposta pode chegar antes do comando ser marcado como published
+                .find(|x| {
+                    x.expect_msgtype.as_deref() == Some(msgtype) && waiters.contains_key(&x.id)
+                })
+                .map(|x| x.id)
+        };
+        let Some(command_id) = command_id else {
+            return;
+        };
+        let waiter = self.waiters.lock().unwrap().remove(&command_id);
+        if let Some(waiter) = waiter {
+            let _ = waiter.send(payload_json.clone());
+        }
+    }
+
+    pub fn list(&self, dev_id: &str) -> Vec<CommandRecord> {
+        let by_dev = self.by_dev.lock().unwrap();
+        match by_dev.get(dev_id) {
+            Some(commands) => commands.iter().cloned().collect(),
+            None => Vec::new(),
+        }
+    }
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_millis()
+        .try_into()
+        .expect("timestamp too large")
+}
+
+fn count_status(status: CommandStatus) {
+    REGISTRY
+        .counter(
+            "device_commands_total",
+            "Comandos para dispositivos, por estado alcançado",
+            &[("status", status.as_str())],
+        )
+        .inc();
+}
+
+// Registra o comando como queued e retorna o registro inicial. O envio é feito por run_command.
+pub fn queue_command(globs: &Arc<GlobalVars>, request: &CommandRequest) -> CommandRecord {
+    let record = CommandRecord {
+        id: globs.commands.next_id.fetch_add(1, Ordering::Relaxed),
+        dev_id: request.devId.to_owned(),
+        topic: format!("commands/{}", request.devId),
+        payload: request.payload.clone(),
+        expect_msgtype: request.expectMsgtype.clone(),
+        status: CommandStatus::Queued,
+        ts_queued: now_millis(),
+        ts_published: None,
+        ts_reply: None,
+        reply: None,
+    };
+    globs.commands.insert(record.clone());
+    count_status(CommandStatus::Queued);
+    record
+}
+
+// Publica o comando nos brokers dos dispositivos e, se tiver resposta esperada, aguarda por ela
+pub async fn run_command(
+    globs: Arc<GlobalVars>,
+    record: CommandRecord,
+    timeout: Duration,
+) -> CommandRecord {
+    let dev_id = &record.dev_id;
+
+    // O waiter é registrado antes de publicar para não perder uma resposta muito rápida
+    let reply_receiver = record.expect_msgtype.as_ref().map(|_| {
+        let (sender, receiver) = oneshot::channel();
+        globs
+            .commands
+            .waiters
+            .lock()
+            .unwrap()
+            .insert(record.id, sender);
+        receiver
+    });
+
+    let payload_str = record.payload.to_string();
+    let brokers = ingest_brokers(&globs.brokers);
+    let results = futures::future::join_all(
+        brokers.map(|broker| publish_to_broker(&globs, broker, &record.topic, &payload_str)),
+    )
+    .await;
+
+    if !results.contains(&true) {
+        globs.commands.waiters.lock().unwrap().remove(&record.id);
+        globs
+            .commands
+            .update(dev_id, record.id, |x| x.status = CommandStatus::Failed);
+        count_status(CommandStatus::Failed);
+        return globs.commands.get(dev_id, record.id).unwrap_or(record);
+    }
+
+    globs.commands.update(dev_id, record.id, |x| {
+        x.status = CommandStatus::Published;
+        x.ts_published = Some(now_millis());
+    });
+    count_status(CommandStatus::Published);
+
+    if let Some(reply_receiver) = reply_receiver {
+        match tokio::time::timeout(timeout, reply_receiver).await {
+            Ok(Ok(reply)) => {
+                globs.commands.update(dev_id, record.id, |x| {
+                    x.status = CommandStatus::Acknowledged;
+                    x.ts_reply = Some(now_millis());
+                    x.reply = Some(reply);
+                });
+                count_status(CommandStatus::Acknowledged);
+            }
+            _ => {
+                globs.commands.waiters.lock().unwrap().remove(&record.id);
+                globs
+                    .commands
+                    .update(dev_id, record.id, |x| x.status = CommandStatus::TimedOut);
+                count_status(CommandStatus::TimedOut);
+            }
+        }
+    }
+
+    globs.commands.get(dev_id, record.id).unwrap_or(record)
+}
+
+// Chamado para cada mensagem em control/...: entrega a resposta ao comando mais antigo que espera por ela
+pub fn on_control_message(globs: &Arc<GlobalVars>, dev_id: &str, payload_json: &serde_json::Value) {
+    globs.commands.deliver_reply(dev_id, payload_json);
+}
+
+#[cfg(test)]
+fn test_record(id: u64, dev_id: &str, expect_msgtype: Option<&str>) -> CommandRecord {
+    CommandRecord {
+        id,
+        dev_id: dev_id.to_owned(),
+        topic: format!("commands/{}", dev_id),
+        payload: serde_json::json!({}),
+        expect_msgtype: expect_msgtype.map(|x| x.to_owned()),
+        status: CommandStatus::Published,
+        ts_queued: 0,
+        ts_published: Some(0),
+        ts_reply: None,
+        reply: None,
+    }
+}
+
+#[test]
+fn test_command_reply_correlation() {
+    let log = CommandLog::new();
+    let mut receivers = Vec::new();
+    for (id, dev_id) in [(1, "DAC1"), (2, "DAC1"), (3, "DAC2")] {
+        log.insert(test_record(id, dev_id, Some("setMode")));
+        let (sender, receiver) = oneshot::channel();
+        log.waiters.lock().unwrap().insert(id, sender);
+        receivers.push(receiver);
+    }
+
+    // msgtype diferente ou sem msgtype não responde nenhum comando
+    log.deliver_reply("DAC1", &serde_json::json!({ "msgtype": "getStatus" }));
+    log.deliver_reply("DAC1", &serde_json::json!({ "mode": 1 }));
+    assert!(receivers[0].try_recv().is_err());
+
+    // A resposta vai para o comando mais antigo do mesmo dispositivo que ainda espera
+    log.deliver_reply("DAC1", &serde_json::json!({ "msgtype": "setMode", "n": 1 }));
+    assert_eq!(receivers[0].try_recv().unwrap()["n"], 1);
+    assert!(receivers[1].try_recv().is_err());
+    log.deliver_reply("DAC1", &serde_json::json!({ "msgtype": "setMode", "n": 2 }));
+    assert_eq!(receivers[1].try_recv().unwrap()["n"], 2);
+    assert!(receivers[2].try_recv().is_err());
+    assert!(log.waiters.lock().unwrap().contains_key(&3));
+}
+
+#[test]
+fn test_command_log_eviction() {
+    let log = CommandLog::new();
+    let total = MAX_COMMANDS_PER_DEV as u64 + 5;
+    for id in 1..=total {
+        log.insert(test_record(id, "DAC1", None));
+    }
+    log.insert(test_record(total + 1, "DAC2", None));
+
+    let commands = log.list("DAC1");
+    assert_eq!(commands.len(), MAX_COMMANDS_PER_DEV);
+    assert_eq!(commands.first().unwrap().id, 6);
+    assert_eq!(commands.last().unwrap().id, total);
+    assert!(log.get("DAC1", 5).is_none());
+    assert_eq!(log.list("DAC2").len(), 1);
+}
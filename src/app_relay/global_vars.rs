@@ -1,14 +1,17 @@
-use super::commands_sender::MsgToBroker;
+use super::commands_sender::{BrokerConn, MsgToBroker};
 use super::configs::ConfigFile;
 use super::dash_update::DevHwConfig;
+use super::device_commands::CommandLog;
+use super::hw_config::DevCfgVersion;
 use super::statistics;
+use crate::lib_state_store::store::{create_state_store, DeviceStateStore};
 use crate::telemetry_payloads::dac_telemetry::HwInfoDAC;
 use crate::telemetry_payloads::dri_telemetry::HwInfoDRI;
 use crate::telemetry_payloads::dut_telemetry::HwInfoDUT;
 use std::collections::HashMap;
 use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
-use tokio::sync::{mpsc, Mutex, RwLock};
+use tokio::sync::{mpsc, Mutex};
 
 pub struct GlobalVars {
     pub configfile: ConfigFile,
@@ -17,16 +20,29 @@ pub struct GlobalVars {
     pub default_dac_hw: HwInfoDAC,
     pub default_dut_hw: HwInfoDUT,
     pub default_dri_hw: HwInfoDRI,
-    pub broker_client: RwLock<Option<Arc<rumqttc::AsyncClient>>>,
-    pub redis_client: Mutex<Option<redis::aio::ConnectionManager>>,
+    pub brokers: Vec<BrokerConn>, // Na mesma ordem de configfile.brokers
+    pub state_store: Arc<dyn DeviceStateStore>,
     pub certs_vld: HashMap<String, String>,
     pub to_broker: mpsc::Sender<MsgToBroker>,
+    pub commands: CommandLog,
     pub need_update_configs: AtomicBool,
     pub stats: statistics::StatisticsCounters,
 }
 
 pub struct ConversionVars {
     pub devs: HashMap<String, DevHwConfig>,
+    pub cfg_versions: HashMap<String, DevCfgVersion>,
+    pub cfg_revision: Option<u64>, // Última revisão recebida do API-Server
+}
+
+impl ConversionVars {
+    pub fn new() -> ConversionVars {
+        ConversionVars {
+            devs: HashMap::new(),
+            cfg_versions: HashMap::new(),
+            cfg_revision: None,
+        }
+    }
 }
 
 impl GlobalVars {
@@ -38,12 +54,29 @@ impl GlobalVars {
 pub fn create_globs(configfile: ConfigFile) -> (GlobalVars, mpsc::Receiver<MsgToBroker>) {
     let (sender_fila, receiver_fila) = mpsc::channel::<MsgToBroker>(20000);
 
+    let brokers = configfile
+        .brokers
+        .iter()
+        .map(|broker| {
+            BrokerConn::new(
+                &broker.name,
+                broker.role.ingest(),
+                broker.role.publish(),
+                configfile.broker_publish_max_attempts,
+            )
+        })
+        .collect();
+
+    let state_store = create_state_store(&configfile.state_store);
+    crate::LOG.append_log_tag_msg(
+        "INFO",
+        &format!("Device state store: {}", state_store.store_name()),
+    );
+
     let globs = GlobalVars {
         configfile,
         configs_ready: Mutex::new(false),
-        conv_vars: Mutex::new(ConversionVars {
-            devs: HashMap::new(),
-        }),
+        conv_vars: Mutex::new(ConversionVars::new()),
         default_dac_hw: HwInfoDAC {
             isVrf: false,
             calculate_L1_fancoil: Some(false),
@@ -70,10 +103,11 @@ pub fn create_globs(configfile: ConfigFile) -> (GlobalVars, mpsc::Receiver<MsgTo
             temperature_offset: 0.0,
         },
         default_dri_hw: HwInfoDRI { formulas: None },
-        broker_client: RwLock::new(None),
-        redis_client: Mutex::new(None),
+        brokers,
+        state_store,
         certs_vld: HashMap::new(),
         to_broker: sender_fila,
+        commands: CommandLog::new(),
         need_update_configs: AtomicBool::new(true),
         stats: statistics::StatisticsCounters::new(),
     };
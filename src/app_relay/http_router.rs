@@ -1,49 +1,282 @@
-use crate::app_relay::dash_update::make_cfg_update_request;
-use crate::lib_http::response::{build_http_response, respond_http_plain_text, send_response};
+use crate::app_relay::dash_update::{self, make_cfg_update_request, DeltaOutcome};
+use crate::app_relay::device_commands::{self, CommandRecord, CommandRequest};
+use crate::lib_http::auth::ApiTokens;
+use crate::lib_http::response::{
+    build_http_response, respond_http_json_serializable, respond_http_plain_text,
+};
+use crate::lib_http::router::{
+    respond_and_log, AuthPolicy, BodyParser, Handler, Method, Route, RouteRequest, RouteResult,
+    Router, Visibility,
+};
 use crate::lib_http::types::{HttpRequest, HttpResponse};
+use crate::lib_metrics::{MetricsText, CONTENT_TYPE, REGISTRY};
 use crate::GlobalVars;
+use futures::future::BoxFuture;
 use regex::Regex;
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
 use tokio::net::TcpStream;
 
+// O /send-command que aguarda a resposta do dispositivo fica com o socket até o comando terminar
+pub struct PendingCommand {
+    record: CommandRecord,
+    timeout: Duration,
+}
+pub type Deferred = PendingCommand;
+
+static ROUTER: OnceLock<Router<Deferred>> = OnceLock::new();
+
+fn build_router() -> Router<Deferred> {
+    Router::new(ApiTokens::default(), relay_routes())
+}
+
+/** Rotas do iotrelay, também servidas pelo telemetry_service junto com as dele */
+pub fn relay_routes() -> Vec<Route<Deferred>> {
+    use AuthPolicy::None as NoAuth;
+    use BodyParser::{Json, Raw};
+    use Method::{Any, Post};
+    use Visibility::{Internal, Public};
+
+    #[rustfmt::skip]
+    let routes = vec![
+        Route::new(Any,  "/health_check",        Public,   NoAuth, Raw,  Handler::Sync(health_check)),
+        Route::new(Any,  "/status-charts-v1",    Public,   NoAuth, Raw,  Handler::Async(status_charts_v1)),
+        Route::new(Any,  "/force-cfgs-update",   Public,   NoAuth, Raw,  Handler::Sync(force_cfgs_update)),
+        Route::new(Post, "/cfgs-delta",          Internal, NoAuth, Json, Handler::Async(cfgs_delta)),
+        Route::new(Any,  "/devices-cfg-version", Public,   NoAuth, Json, Handler::Async(devices_cfg_version)),
+        Route::new(Post, "/send-command",        Internal, NoAuth, Json, Handler::Async(send_command)),
+        Route::new(Post, "/commands-log",        Internal, NoAuth, Json, Handler::Sync(commands_log)),
+        Route::new(Any,  "/metrics",             Public,   NoAuth, Raw,  Handler::Sync(metrics)),
+    ];
+    routes
+}
+
 pub async fn on_http_req(
     req: HttpRequest,
-    _is_internal: bool,
+    is_internal: bool,
+    socket: TcpStream,
+    globs: Arc<GlobalVars>,
+) {
+    let router = ROUTER.get_or_init(build_router);
+    serve_dispatched(router, req, is_internal, socket, globs).await;
+}
+
+pub async fn serve_dispatched(
+    router: &Router<Deferred>,
+    req: HttpRequest,
+    is_internal: bool,
     mut socket: TcpStream,
     globs: Arc<GlobalVars>,
 ) {
-    let response = match &req.path[..] {
-        "/health_check" => respond_http_plain_text(200, "Alive"),
-        "/status-charts-v1" => build_status_charts_v1(&req)
-            .await
-            .unwrap_or_else(|err| respond_http_plain_text(400, &err)),
-        "/force-cfgs-update" => {
-            // Endpoint usado pelo API-Server para informar que o iotrelay precisa solicitar update de configs
-            let globs = globs.clone();
+    match router.dispatch(req, is_internal, &globs).await {
+        RouteResult::Respond(response) => {
+            respond_and_log(&mut socket, &response).await;
+        }
+        RouteResult::Defer(pending) => {
+            // O serviço HTTP atende uma requisição por vez, então a espera pelo dispositivo segue em outra tarefa
             tokio::spawn(async move {
-                let result = make_cfg_update_request(&globs, &globs.conv_vars).await;
-                if let Err(err) = result {
-                    crate::LOG.append_log_tag_msg(
-                        "ERROR",
-                        &format!("Error make_cfg_update_request: {}", err),
-                    );
-                }
+                let record =
+                    device_commands::run_command(globs, pending.record, pending.timeout).await;
+                let response = respond_http_json_serializable(200, record);
+                respond_and_log(&mut socket, &response).await;
             });
-            respond_http_plain_text(200, "Received")
-        }
-        _ => {
-            crate::LOG.append_log_tag_msg(
-                "ERROR",
-                &format!("Invalid request: {} {}", req.method, req.path),
-            );
-            respond_http_plain_text(404, &format!("Not found: {}", &req.path))
         }
     };
-    if let Err(err) = {
-        send_response(&mut socket, &response).await // socket_write
-    } {
-        crate::LOG.append_log_tag_msg("ERROR[33]", &err.to_string());
+}
+
+fn health_check(
+    _rreq: &RouteRequest,
+    _globs: &Arc<GlobalVars>,
+) -> Result<RouteResult<Deferred>, HttpResponse> {
+    Ok(RouteResult::Respond(respond_http_plain_text(200, "Alive")))
+}
+
+fn metrics(
+    _rreq: &RouteRequest,
+    globs: &Arc<GlobalVars>,
+) -> Result<RouteResult<Deferred>, HttpResponse> {
+    let mut out = MetricsText::new();
+    crate::statistics::write_metrics(globs, &mut out);
+    REGISTRY.render(&mut out);
+    Ok(RouteResult::Respond(build_http_response(
+        200,
+        out.into_bytes(),
+        CONTENT_TYPE,
+    )))
+}
+
+fn status_charts_v1<'a>(
+    rreq: &'a RouteRequest,
+    _globs: &'a Arc<GlobalVars>,
+) -> BoxFuture<'a, Result<RouteResult<Deferred>, HttpResponse>> {
+    Box::pin(async move {
+        let response = build_status_charts_v1(&rreq.req)
+            .await
+            .unwrap_or_else(|err| respond_http_plain_text(400, &err));
+        Ok(RouteResult::Respond(response))
+    })
+}
+
+fn force_cfgs_update(
+    _rreq: &RouteRequest,
+    globs: &Arc<GlobalVars>,
+) -> Result<RouteResult<Deferred>, HttpResponse> {
+    // Endpoint usado pelo API-Server para informar que o iotrelay precisa solicitar update de configs
+    let globs = globs.clone();
+    tokio::spawn(async move {
+        let result = make_cfg_update_request(&globs, &globs.conv_vars).await;
+        if let Err(err) = result {
+            crate::LOG
+                .append_log_tag_msg("ERROR", &format!("Error make_cfg_update_request: {}", err));
+        }
+    });
+    Ok(RouteResult::Respond(respond_http_plain_text(
+        200, "Received",
+    )))
+}
+
+/*
+  ['/cfgs-delta']: (reqParams: DevsCfgDocument) => { // Versão 2 com incremental=true, ver hw_config
+    result: 'applied'|'duplicate'|'resync' // 'resync': faltou alguma revisão, será feita a sincronização completa
+    updated?: number
+    removed?: number
+    errors?: string[]
+  }
+*/
+fn cfgs_delta<'a>(
+    rreq: &'a RouteRequest,
+    globs: &'a Arc<GlobalVars>,
+) -> BoxFuture<'a, Result<RouteResult<Deferred>, HttpResponse>> {
+    Box::pin(async move {
+        let outcome = dash_update::apply_cfg_delta(globs, rreq.json())
+            .await
+            .map_err(|err| respond_http_plain_text(400, &err))?;
+        let response = match outcome {
+            DeltaOutcome::Applied(summary) => serde_json::json!({
+                "result": "applied",
+                "updated": summary.updated,
+                "removed": summary.removed,
+                "errors": summary.errors.iter().map(|x| x.to_string()).collect::<Vec<_>>(),
+            }),
+            DeltaOutcome::Duplicate => serde_json::json!({ "result": "duplicate" }),
+            DeltaOutcome::Resync => serde_json::json!({ "result": "resync" }),
+        };
+        Ok(RouteResult::Respond(respond_http_json_serializable(
+            200, response,
+        )))
+    })
+}
+
+/*
+  ['/devices-cfg-version']: (reqParams?: { devIds?: string[] }) => {
+    revision: number|null // Última revisão de configs recebida do API-Server
+    devices: {
+      [devId: string]: null | { // null: sem config do API-Server, processado com a config padrão
+        family: string
+        version: number // Versão do documento de configs (ver hw_config)
+        revision: number|null
+        cfgHash: number|null
+        tsApplied: number
+        error: string|null // Config inválida, processado com a config padrão
+      }
     }
+  }
+*/
+#[derive(serde::Deserialize, Default)]
+struct ParamsDevicesCfgVersion {
+    devIds: Option<Vec<String>>,
+}
+
+fn devices_cfg_version<'a>(
+    rreq: &'a RouteRequest,
+    globs: &'a Arc<GlobalVars>,
+) -> BoxFuture<'a, Result<RouteResult<Deferred>, HttpResponse>> {
+    Box::pin(async move {
+        let params = match rreq.json() {
+            serde_json::Value::Null => ParamsDevicesCfgVersion::default(),
+            body => serde_json::from_value::<ParamsDevicesCfgVersion>(body.clone())
+                .map_err(|e| respond_http_plain_text(400, &e.to_string()))?,
+        };
+        let conv_vars = globs.conv_vars.lock().await;
+        let devices: serde_json::Map<String, serde_json::Value> = match &params.devIds {
+            Some(dev_ids) => dev_ids
+                .iter()
+                .map(|dev_id| {
+                    let version = conv_vars.cfg_versions.get(dev_id);
+                    (dev_id.to_owned(), serde_json::json!(version))
+                })
+                .collect(),
+            None => conv_vars
+                .cfg_versions
+                .iter()
+                .map(|(dev_id, version)| (dev_id.to_owned(), serde_json::json!(version)))
+                .collect(),
+        };
+        Ok(RouteResult::Respond(respond_http_json_serializable(
+            200,
+            serde_json::json!({ "revision": conv_vars.cfg_revision, "devices": devices }),
+        )))
+    })
+}
+
+/*
+  ['/send-command']: (reqParams: {
+    devId: string
+    payload: any // Publicado em commands/<devId>
+    expectMsgtype?: string // msgtype da resposta em control/... que confirma o comando
+    timeoutS?: number // Padrão 30, máximo 300
+    wait?: boolean // Aguardar a resposta antes de responder a requisição (padrão true)
+  }) => CommandRecord
+
+  ['/commands-log']: (reqParams: { devId: string }) => { commands: CommandRecord[] }
+*/
+fn send_command<'a>(
+    rreq: &'a RouteRequest,
+    globs: &'a Arc<GlobalVars>,
+) -> BoxFuture<'a, Result<RouteResult<Deferred>, HttpResponse>> {
+    Box::pin(async move {
+        let request = serde_json::from_value::<CommandRequest>(rreq.json().clone())
+            .map_err(|e| respond_http_plain_text(400, &e.to_string()))?;
+        let timeout_s = request
+            .timeoutS
+            .unwrap_or(device_commands::DEFAULT_TIMEOUT_S);
+        if timeout_s > device_commands::MAX_TIMEOUT_S {
+            return Err(respond_http_plain_text(
+                400,
+                &format!(
+                    "timeoutS deve ser no máximo {}",
+                    device_commands::MAX_TIMEOUT_S
+                ),
+            ));
+        }
+        let timeout = Duration::from_secs(timeout_s);
+        let record = device_commands::queue_command(globs, &request);
+        if request.wait.unwrap_or(true) {
+            return Ok(RouteResult::Defer(PendingCommand { record, timeout }));
+        }
+        tokio::spawn(device_commands::run_command(
+            globs.clone(),
+            record.clone(),
+            timeout,
+        ));
+        Ok(RouteResult::Respond(respond_http_json_serializable(
+            200, record,
+        )))
+    })
+}
+
+fn commands_log(
+    rreq: &RouteRequest,
+    globs: &Arc<GlobalVars>,
+) -> Result<RouteResult<Deferred>, HttpResponse> {
+    let dev_id = rreq.json()["devId"]
+        .as_str()
+        .ok_or_else(|| respond_http_plain_text(400, "Faltou parâmetro 'devId'"))?;
+    let commands = globs.commands.list(dev_id);
+    Ok(RouteResult::Respond(respond_http_json_serializable(
+        200,
+        serde_json::json!({ "commands": commands }),
+    )))
 }
 
 async fn build_status_charts_v1(req: &HttpRequest) -> Result<HttpResponse, String> {
@@ -0,0 +1,651 @@
+use super::dash_update::DevHwConfig;
+use super::global_vars::ConversionVars;
+use super::state_persistence;
+use crate::telemetry_payloads::{
+    dac_telemetry::{HwInfoDAC, T_sensor_cfg, T_sensors},
+    dri_telemetry::HwInfoDRI,
+    dut_telemetry::HwInfoDUT,
+};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/*
+Documento de configuração de hardware enviado pelo API-Server (/diel-internal/bgtasks/getDevsCfg).
+
+Versão 2:
+{
+  version: 2,
+  revision?: number,      // Revisão das configs no API-Server, informada por dispositivo em /devices-cfg-version
+  incremental?: boolean,  // false (padrão): lista completa, os dispositivos que não vierem são removidos
+                          // true: só os dispositivos listados (e os de "removed") são alterados
+  removed?: string[],
+  dacs?: DacCfg[], duts?: DutCfg[], dris?: DriCfg[],
+  dams?: DamCfg[], dmas?: DmaCfg[], dmts?: DmtCfg[], dals?: DalCfg[],
+}
+
+Documentos sem "version" são da versão 1 (formato antigo): "dacs", "duts" e "dris" são obrigatórios e os
+dispositivos que não vierem continuam com a config que já tinham.
+
+Deltas (apiserver/hwcfg-delta ou POST /cfgs-delta) são documentos da versão 2 com incremental=true e a
+revisão funcionando como número de sequência: cada delta tem que ter a revisão seguinte à atual. Revisões
+já aplicadas são ignoradas e, se faltar alguma, é feita uma sincronização completa pelo getDevsCfg.
+
+Cada linha é validada separadamente. Se uma linha for inválida o erro informa o dispositivo e o campo, e o
+dispositivo passa a ser processado com a config padrão até receber uma config válida.
+*/
+
+pub const LATEST_VERSION: u32 = 2;
+
+#[derive(Deserialize)]
+struct DevsCfgDocument {
+    #[serde(default = "legacy_version")]
+    version: u32,
+    revision: Option<u64>,
+    #[serde(default)]
+    incremental: bool,
+    #[serde(default)]
+    removed: Vec<String>,
+    dacs: Option<Vec<serde_json::Value>>,
+    duts: Option<Vec<serde_json::Value>>,
+    dris: Option<Vec<serde_json::Value>>,
+    dams: Option<Vec<serde_json::Value>>,
+    dmas: Option<Vec<serde_json::Value>>,
+    dmts: Option<Vec<serde_json::Value>>,
+    dals: Option<Vec<serde_json::Value>>,
+}
+
+fn legacy_version() -> u32 {
+    1
+}
+
+#[derive(Deserialize)]
+struct DacCfgRow {
+    DAC_TYPE: Option<String>,
+    DAC_APPL: Option<String>,
+    FLUID_TYPE: Option<String>,
+    hasAutomation: bool,
+    isVrf: bool,
+    P0Psuc: bool,
+    P1Psuc: bool,
+    P0Pliq: bool,
+    P1Pliq: bool,
+    P0multQuad: Option<f64>,
+    P0multLin: Option<f64>,
+    P0ofst: Option<f64>,
+    P1multQuad: Option<f64>,
+    P1multLin: Option<f64>,
+    P1ofst: Option<f64>,
+    T0_T1_T2: Option<Vec<Option<String>>>,
+    L1CalcCfg: Option<L1CalcCfgRow>,
+    calculate_L1_fancoil: Option<bool>,
+    debug_L1_fancoil: Option<bool>,
+    virtualL1: Option<bool>,
+}
+
+#[derive(Deserialize)]
+struct L1CalcCfgRow {
+    psucOffset: Option<f64>,
+}
+
+#[derive(Deserialize)]
+struct DutCfgRow {
+    TEMPERATURE_OFFSET: Option<f64>,
+}
+
+#[derive(Deserialize)]
+struct DriCfgRow {
+    FORMULAS: Option<HashMap<String, String>>,
+}
+
+#[derive(Deserialize)]
+struct DamCfgRow {
+    TEMPERATURE_OFFSET: Option<f64>,
+}
+
+#[derive(Deserialize)]
+struct DmaCfgRow {
+    LITERS_PER_PULSE: Option<f64>,
+}
+
+#[derive(Deserialize)]
+struct DmtCfgRow {}
+
+#[derive(Deserialize)]
+struct DalCfgRow {}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HwInfoDAM {
+    pub temperature_offset: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HwInfoDMA {
+    pub liters_per_pulse: Option<f64>,
+}
+
+// Versão da config com que cada dispositivo está sendo processado
+#[derive(Serialize, Clone)]
+pub struct DevCfgVersion {
+    pub family: &'static str,
+    pub version: u32,
+    pub revision: Option<u64>,
+    #[serde(rename = "cfgHash")]
+    pub cfg_hash: Option<u32>, // crc32 da linha recebida, ausente se a linha for inválida
+    #[serde(rename = "tsApplied")]
+    pub ts_applied: u64,
+    pub error: Option<String>, // Se preenchido o dispositivo está usando a config padrão
+}
+
+pub struct CfgError {
+    pub family: &'static str,
+    pub dev_id: String,
+    pub field: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for CfgError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.field.is_empty() {
+            write!(f, "{} {}: {}", self.family, self.dev_id, self.message)
+        } else {
+            write!(
+                f,
+                "{} {} {}: {}",
+                self.family, self.dev_id, self.field, self.message
+            )
+        }
+    }
+}
+
+pub struct CfgUpdateSummary {
+    pub version: u32,
+    pub updated: usize,
+    pub removed: usize,
+    pub errors: Vec<CfgError>,
+}
+
+pub enum DeltaResult {
+    Applied(CfgUpdateSummary),
+    Duplicate { current: u64 }, // Revisão já aplicada, ignorado
+    // Faltou alguma revisão entre a atual e a recebida (ou ainda não houve sincronização completa)
+    Gap { current: Option<u64>, received: u64 },
+}
+
+struct FieldError {
+    field: &'static str,
+    message: String,
+}
+
+fn field_error(field: &'static str, message: &str) -> FieldError {
+    FieldError {
+        field,
+        message: message.to_owned(),
+    }
+}
+
+trait CfgRow: DeserializeOwned {
+    const FAMILY: &'static str;
+    const ID_FIELD: &'static str;
+    fn into_hw_config(self) -> Result<DevHwConfig, FieldError>;
+}
+
+impl CfgRow for DacCfgRow {
+    const FAMILY: &'static str = "DAC";
+    const ID_FIELD: &'static str = "DAC_ID";
+
+    fn into_hw_config(self) -> Result<DevHwConfig, FieldError> {
+        if self.P0Psuc || self.P0Pliq {
+            require_sensor_cfg(&self.P0multQuad, &self.P0multLin, &self.P0ofst, "P0")?;
+        }
+        if self.P1Psuc || self.P1Pliq {
+            require_sensor_cfg(&self.P1multQuad, &self.P1multLin, &self.P1ofst, "P1")?;
+        }
+        let t_cfg = match &self.T0_T1_T2 {
+            None => None,
+            Some(t0_t1_t2) => {
+                if t0_t1_t2.len() != 3 {
+                    return Err(field_error("T0_T1_T2", "expected 3 items"));
+                }
+                Some(T_sensor_cfg {
+                    Tamb: find_t_sensor(t0_t1_t2, "Tamb"),
+                    Tsuc: find_t_sensor(t0_t1_t2, "Tsuc"),
+                    Tliq: find_t_sensor(t0_t1_t2, "Tliq"),
+                })
+            }
+        };
+        let hw_cfg = HwInfoDAC {
+            isVrf: self.isVrf,
+            calculate_L1_fancoil: self.calculate_L1_fancoil,
+            debug_L1_fancoil: self.debug_L1_fancoil,
+            hasAutomation: self.hasAutomation,
+            P0Psuc: self.P0Psuc,
+            P1Psuc: self.P1Psuc,
+            P0Pliq: self.P0Pliq,
+            P1Pliq: self.P1Pliq,
+            P0multQuad: self.P0multQuad.unwrap_or(0.0),
+            P0multLin: self.P0multLin.unwrap_or(0.0),
+            P0ofst: self.P0ofst.unwrap_or(0.0),
+            P1multQuad: self.P1multQuad.unwrap_or(0.0),
+            P1multLin: self.P1multLin.unwrap_or(0.0),
+            P1ofst: self.P1ofst.unwrap_or(0.0),
+            fluid: self.FLUID_TYPE,
+            t_cfg,
+            simulate_l1: self.virtualL1.unwrap_or(false),
+            l1_psuc_offset: self.L1CalcCfg.and_then(|x| x.psucOffset).unwrap_or(0.0),
+            DAC_APPL: self.DAC_APPL,
+            DAC_TYPE: self.DAC_TYPE,
+        };
+        let token = serialize_token(&hw_cfg)?;
+        Ok(DevHwConfig::DAC(hw_cfg, token))
+    }
+}
+
+impl CfgRow for DutCfgRow {
+    const FAMILY: &'static str = "DUT";
+    const ID_FIELD: &'static str = "DUT_ID";
+
+    fn into_hw_config(self) -> Result<DevHwConfig, FieldError> {
+        let hw_cfg = HwInfoDUT {
+            temperature_offset: self.TEMPERATURE_OFFSET.unwrap_or(0.0),
+        };
+        let token = serialize_token(&hw_cfg)?;
+        Ok(DevHwConfig::DUT(hw_cfg, token))
+    }
+}
+
+impl CfgRow for DriCfgRow {
+    const FAMILY: &'static str = "DRI";
+    const ID_FIELD: &'static str = "DRI_ID";
+
+    fn into_hw_config(self) -> Result<DevHwConfig, FieldError> {
+        Ok(DevHwConfig::DRI(HwInfoDRI {
+            formulas: self.FORMULAS,
+        }))
+    }
+}
+
+impl CfgRow for DamCfgRow {
+    const FAMILY: &'static str = "DAM";
+    const ID_FIELD: &'static str = "DAM_ID";
+
+    fn into_hw_config(self) -> Result<DevHwConfig, FieldError> {
+        Ok(DevHwConfig::DAM(HwInfoDAM {
+            temperature_offset: self.TEMPERATURE_OFFSET.unwrap_or(0.0),
+        }))
+    }
+}
+
+impl CfgRow for DmaCfgRow {
+    const FAMILY: &'static str = "DMA";
+    const ID_FIELD: &'static str = "DMA_ID";
+
+    fn into_hw_config(self) -> Result<DevHwConfig, FieldError> {
+        if let Some(liters_per_pulse) = self.LITERS_PER_PULSE {
+            if liters_per_pulse <= 0.0 {
+                return Err(field_error("LITERS_PER_PULSE", "must be greater than 0"));
+            }
+        }
+        Ok(DevHwConfig::DMA(HwInfoDMA {
+            liters_per_pulse: self.LITERS_PER_PULSE,
+        }))
+    }
+}
+
+impl CfgRow for DmtCfgRow {
+    const FAMILY: &'static str = "DMT";
+    const ID_FIELD: &'static str = "DMT_ID";
+
+    fn into_hw_config(self) -> Result<DevHwConfig, FieldError> {
+        Ok(DevHwConfig::DMT)
+    }
+}
+
+impl CfgRow for DalCfgRow {
+    const FAMILY: &'static str = "DAL";
+    const ID_FIELD: &'static str = "DAL_ID";
+
+    fn into_hw_config(self) -> Result<DevHwConfig, FieldError> {
+        Ok(DevHwConfig::DAL)
+    }
+}
+
+fn require_sensor_cfg(
+    mult_quad: &Option<f64>,
+    mult_lin: &Option<f64>,
+    ofst: &Option<f64>,
+    sensor: &'static str,
+) -> Result<(), FieldError> {
+    let (field_quad, field_lin, field_ofst) = match sensor {
+        "P0" => ("P0multQuad", "P0multLin", "P0ofst"),
+        _ => ("P1multQuad", "P1multLin", "P1ofst"),
+    };
+    let message = format!("required when {}Psuc or {}Pliq is set", sensor, sensor);
+    if mult_quad.is_none() {
+        return Err(field_error(field_quad, &message));
+    }
+    if mult_lin.is_none() {
+        return Err(field_error(field_lin, &message));
+    }
+    if ofst.is_none() {
+        return Err(field_error(field_ofst, &message));
+    }
+    Ok(())
+}
+
+fn find_t_sensor(t0_t1_t2: &[Option<String>], name: &str) -> Option<T_sensors> {
+    match t0_t1_t2.iter().position(|x| x.as_deref() == Some(name)) {
+        Some(0) => Some(T_sensors::T0),
+        Some(1) => Some(T_sensors::T1),
+        Some(2) => Some(T_sensors::T2),
+        _ => None,
+    }
+}
+
+fn serialize_token<T: Serialize>(hw_cfg: &T) -> Result<Vec<u8>, FieldError> {
+    state_persistence::serialize_state_obj(hw_cfg).map_err(|err| FieldError {
+        field: "",
+        message: format!("Error serializing device cfg: {}", err),
+    })
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_millis()
+        .try_into()
+        .expect("timestamp too large")
+}
+
+fn parse_row<T: CfgRow>(dev_id: &str, row: &serde_json::Value) -> Result<DevHwConfig, CfgError> {
+    let cfg_error = |field: String, message: String| CfgError {
+        family: T::FAMILY,
+        dev_id: dev_id.to_owned(),
+        field,
+        message,
+    };
+    let typed_row: T = serde_path_to_error::deserialize(row).map_err(|err| {
+        let message = err.inner().to_string();
+        let mut field = err.path().to_string();
+        if field == "." {
+            // "missing field `isVrf`" não tem caminho, o nome do campo vem na mensagem
+            field = message.split('`').nth(1).unwrap_or_default().to_owned();
+        }
+        cfg_error(field, message)
+    })?;
+    typed_row
+        .into_hw_config()
+        .map_err(|err| cfg_error(err.field.to_owned(), err.message))
+}
+
+struct DocumentApplier<'a> {
+    conv: &'a mut ConversionVars,
+    version: u32,
+    revision: Option<u64>,
+    ts_applied: u64,
+    present: HashSet<String>,
+    summary: CfgUpdateSummary,
+}
+
+impl DocumentApplier<'_> {
+    fn apply_list<T: CfgRow>(&mut self, list_name: &str, list: &[serde_json::Value]) {
+        for (index, row) in list.iter().enumerate() {
+            let Some(dev_id) = row[T::ID_FIELD].as_str() else {
+                // Linha sem identificação, não tem como saber qual dispositivo seria
+                self.summary.errors.push(CfgError {
+                    family: T::FAMILY,
+                    dev_id: format!("{}[{}]", list_name, index),
+                    field: T::ID_FIELD.to_owned(),
+                    message: "missing or not a string".to_owned(),
+                });
+                continue;
+            };
+            self.present.insert(dev_id.to_owned());
+            match parse_row::<T>(dev_id, row) {
+                Ok(hw_cfg) => {
+                    self.conv.devs.insert(dev_id.to_owned(), hw_cfg);
+                    let row_hash = crc32fast::hash(row.to_string().as_bytes());
+                    self.set_version::<T>(dev_id, Some(row_hash), None);
+                    self.summary.updated += 1;
+                }
+                Err(err) => {
+                    self.conv.devs.remove(dev_id);
+                    self.set_version::<T>(dev_id, None, Some(err.to_string()));
+                    self.summary.errors.push(err);
+                }
+            }
+        }
+    }
+
+    fn set_version<T: CfgRow>(
+        &mut self,
+        dev_id: &str,
+        cfg_hash: Option<u32>,
+        error: Option<String>,
+    ) {
+        self.conv.cfg_versions.insert(
+            dev_id.to_owned(),
+            DevCfgVersion {
+                family: T::FAMILY,
+                version: self.version,
+                revision: self.revision,
+                cfg_hash,
+                ts_applied: self.ts_applied,
+                error,
+            },
+        );
+    }
+}
+
+// Valida e aplica o documento do API-Server nas configs usadas nas conversões
+pub fn apply_document(
+    parsed: &serde_json::Value,
+    conv: &mut ConversionVars,
+) -> Result<CfgUpdateSummary, String> {
+    let doc = parse_document(parsed)?;
+    Ok(apply_parsed(doc, conv))
+}
+
+// Aplica um delta enviado pelo API-Server (MQTT ou POST), conferindo a sequência das revisões
+pub fn apply_delta(
+    parsed: &serde_json::Value,
+    conv: &mut ConversionVars,
+) -> Result<DeltaResult, String> {
+    let doc = parse_document(parsed)?;
+    if doc.version < 2 || !doc.incremental {
+        return Err("Invalid cfg delta [62]: must be version 2 with incremental=true".to_owned());
+    }
+    let Some(revision) = doc.revision else {
+        return Err("Invalid cfg delta [63]: missing 'revision'".to_owned());
+    };
+    match conv.cfg_revision {
+        Some(current) if revision <= current => Ok(DeltaResult::Duplicate { current }),
+        Some(current) if revision == current + 1 => {
+            Ok(DeltaResult::Applied(apply_parsed(doc, conv)))
+        }
+        current => Ok(DeltaResult::Gap {
+            current,
+            received: revision,
+        }),
+    }
+}
+
+fn parse_document(parsed: &serde_json::Value) -> Result<DevsCfgDocument, String> {
+    let doc: DevsCfgDocument = serde_path_to_error::deserialize(parsed)
+        .map_err(|err| format!("Invalid dash response [55]: {} {}", err.path(), err.inner()))?;
+    if doc.version == 0 || doc.version > LATEST_VERSION {
+        return Err(format!(
+            "Unsupported dash response version [59]: {}",
+            doc.version
+        ));
+    }
+    if doc.version == 1 {
+        let lists = [
+            ("dacs", &doc.dacs),
+            ("duts", &doc.duts),
+            ("dris", &doc.dris),
+        ];
+        for (list_name, list) in lists {
+            if list.is_none() {
+                return Err(format!(
+                    "Invalid dash response [60]: missing '{}'",
+                    list_name
+                ));
+            }
+        }
+    }
+
+    Ok(doc)
+}
+
+fn apply_parsed(doc: DevsCfgDocument, conv: &mut ConversionVars) -> CfgUpdateSummary {
+    let mut applier = DocumentApplier {
+        conv,
+        version: doc.version,
+        revision: doc.revision,
+        ts_applied: now_millis(),
+        present: HashSet::new(),
+        summary: CfgUpdateSummary {
+            version: doc.version,
+            updated: 0,
+            removed: 0,
+            errors: Vec::new(),
+        },
+    };
+
+    for dev_id in &doc.removed {
+        let removed_cfg = applier.conv.devs.remove(dev_id).is_some();
+        let removed_version = applier.conv.cfg_versions.remove(dev_id).is_some();
+        if removed_cfg || removed_version {
+            applier.summary.removed += 1;
+        }
+    }
+
+    applier.apply_list::<DriCfgRow>("dris", doc.dris.as_deref().unwrap_or_default());
+    applier.apply_list::<DutCfgRow>("duts", doc.duts.as_deref().unwrap_or_default());
+    applier.apply_list::<DacCfgRow>("dacs", doc.dacs.as_deref().unwrap_or_default());
+    applier.apply_list::<DamCfgRow>("dams", doc.dams.as_deref().unwrap_or_default());
+    applier.apply_list::<DmaCfgRow>("dmas", doc.dmas.as_deref().unwrap_or_default());
+    applier.apply_list::<DmtCfgRow>("dmts", doc.dmts.as_deref().unwrap_or_default());
+    applier.apply_list::<DalCfgRow>("dals", doc.dals.as_deref().unwrap_or_default());
+
+    if doc.version >= 2 && !doc.incremental {
+        // Documento completo: quem não veio não tem mais config no API-Server
+        let conv = &mut *applier.conv;
+        let absent: HashSet<String> = conv
+            .devs
+            .keys()
+            .chain(conv.cfg_versions.keys())
+            .filter(|dev_id| !applier.present.contains(*dev_id))
+            .cloned()
+            .collect();
+        for dev_id in &absent {
+            conv.devs.remove(dev_id);
+            conv.cfg_versions.remove(dev_id);
+        }
+        applier.summary.removed += absent.len();
+    }
+
+    if doc.revision.is_some() {
+        applier.conv.cfg_revision = doc.revision;
+    }
+
+    applier.summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_typed_cfg_document() {
+        let mut conv = ConversionVars::new();
+        let doc = serde_json::json!({
+            "dacs": [
+                { "DAC_ID": "DAC1", "isVrf": false, "hasAutomation": true, "P0Psuc": true, "P1Psuc": false,
+                  "P0Pliq": false, "P1Pliq": false, "P0multQuad": 0, "P0multLin": 1.5, "P0ofst": -0.2,
+                  "T0_T1_T2": ["Tamb", "Tsuc", null] },
+                { "DAC_ID": "DAC2", "isVrf": false, "hasAutomation": false, "P0Psuc": true, "P1Psuc": false,
+                  "P0Pliq": false, "P1Pliq": false },
+                { "DAC_ID": "DAC3", "isVrf": "no" },
+            ],
+            "duts": [{ "DUT_ID": "DUT1", "TEMPERATURE_OFFSET": 1.5 }],
+            "dris": [],
+        });
+        let summary = apply_document(&doc, &mut conv).unwrap();
+        assert_eq!(summary.version, 1);
+        assert_eq!(summary.updated, 2);
+        assert!(matches!(conv.devs.get("DAC1"), Some(DevHwConfig::DAC(..))));
+        assert!(conv.devs.get("DAC2").is_none());
+        let errors: Vec<String> = summary.errors.iter().map(|x| x.to_string()).collect();
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].starts_with("DAC DAC2 P0multQuad: required"));
+        assert!(errors[1].starts_with("DAC DAC3 isVrf: invalid type"));
+        assert!(conv.cfg_versions["DAC2"].error.is_some());
+
+        // Versão 2 incremental não remove quem não veio; completa remove
+        let doc = serde_json::json!({
+            "version": 2, "revision": 7, "incremental": true,
+            "removed": ["DUT1"],
+            "dmas": [{ "DMA_ID": "DMA1", "LITERS_PER_PULSE": 10 }],
+        });
+        let summary = apply_document(&doc, &mut conv).unwrap();
+        assert_eq!(summary.removed, 1);
+        assert!(conv.devs.contains_key("DAC1"));
+        assert_eq!(conv.cfg_versions["DMA1"].revision, Some(7));
+        assert_eq!(conv.cfg_revision, Some(7));
+
+        let doc =
+            serde_json::json!({ "version": 2, "revision": 8, "dals": [{ "DAL_ID": "DAL1" }] });
+        apply_document(&doc, &mut conv).unwrap();
+        let mut dev_ids: Vec<&String> = conv.devs.keys().collect();
+        dev_ids.sort();
+        assert_eq!(dev_ids, vec!["DAL1"]);
+        assert!(!conv.cfg_versions.contains_key("DAC2"));
+
+        let doc = serde_json::json!({ "version": 3 });
+        assert!(apply_document(&doc, &mut conv).is_err());
+    }
+
+    #[test]
+    fn test_cfg_delta_sequence() {
+        let mut conv = ConversionVars::new();
+        let delta = |revision: u64, dev_id: &str| {
+            serde_json::json!({
+                "version": 2, "revision": revision, "incremental": true,
+                "duts": [{ "DUT_ID": dev_id, "TEMPERATURE_OFFSET": 0.5 }],
+            })
+        };
+
+        // Sem sincronização completa ainda não tem como saber se faltou algo
+        assert!(matches!(
+            apply_delta(&delta(1, "DUT1"), &mut conv),
+            Ok(DeltaResult::Gap { current: None, .. })
+        ));
+
+        let full = serde_json::json!({ "version": 2, "revision": 10, "duts": [] });
+        apply_document(&full, &mut conv).unwrap();
+        assert!(matches!(
+            apply_delta(&delta(11, "DUT1"), &mut conv),
+            Ok(DeltaResult::Applied(summary)) if summary.updated == 1 && summary.errors.is_empty()
+        ));
+        assert!(matches!(
+            apply_delta(&delta(11, "DUT2"), &mut conv),
+            Ok(DeltaResult::Duplicate { current: 11 })
+        ));
+        assert!(matches!(
+            apply_delta(&delta(13, "DUT3"), &mut conv),
+            Ok(DeltaResult::Gap {
+                current: Some(11),
+                received: 13
+            })
+        ));
+        assert!(conv.devs.contains_key("DUT1"));
+        assert!(!conv.devs.contains_key("DUT2") && !conv.devs.contains_key("DUT3"));
+
+        // Documento completo não é aceito como delta
+        assert!(apply_delta(&full, &mut conv).is_err());
+    }
+}
@@ -5,7 +5,9 @@ use crate::GlobalVars;
 use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 
-pub async fn task_mqtt_client_broker(globs: Arc<GlobalVars>) {
+// Cada broker da configuração tem a sua própria tarefa, que reconecta de forma independente dos outros
+pub async fn task_mqtt_client_broker(globs: Arc<GlobalVars>, broker_index: usize) {
+    let broker_name = &globs.configfile.brokers[broker_index].name;
     loop {
         {
             if !*globs.configs_ready.lock().await {
@@ -19,55 +21,57 @@ pub async fn task_mqtt_client_broker(globs: Arc<GlobalVars>) {
                 continue;
             }
         }
-        let result_msg = task_mqtt_client_broker_rumqtt(&globs).await;
+        let result_msg = task_mqtt_client_broker_rumqtt(&globs, broker_index).await;
+        // Enquanto não reconecta, o envio para este broker fica aguardando
+        *(globs.brokers[broker_index].client.write().await) = None;
         crate::LOG.append_log_tag_msg(
             "error",
             &format!(
-                "task_mqtt_client_broker interrupted, will restart: {:?}",
-                result_msg
+                "task_mqtt_client_broker interrupted, will restart: [{}] {:?}",
+                broker_name, result_msg
             ),
         );
         tokio::time::sleep(std::time::Duration::from_secs(3)).await;
     }
 }
 
-pub async fn connect_to_mqtt_broker(globs: &Arc<GlobalVars>) -> Result<rumqttc::EventLoop, String> {
-    let broker_config = &globs.configfile.broker_config;
+pub async fn connect_to_mqtt_broker(
+    globs: &Arc<GlobalVars>,
+    broker_index: usize,
+) -> Result<rumqttc::EventLoop, String> {
+    let broker = &globs.configfile.brokers[broker_index];
+    let broker_config = &broker.broker_config;
     // Create the client. Use an ID. A real system should try harder to use a unique ID.
     let pseudo_random = SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .expect("Time went backwards")
         .as_millis()
         % 100000;
-    let client_id = format!("iotrelay-{}", pseudo_random);
+    let client_id = format!("iotrelay-{}-{}", broker_index, pseudo_random);
 
     // Abre a conexão com o broker (vernemq)
     let (eventloop, client_mqtt) = abrir_conexao_broker_rumqtt(broker_config, &client_id).await?;
 
-    // Faz subscribe nos tópicos de interesse
-    client_mqtt
-        .subscribe(r"$share/iotrelay/data/#", rumqttc::QoS::AtLeastOnce)
-        .await
-        .map_err(|e| e.to_string())?;
-    client_mqtt
-        .subscribe(r"$share/iotrelay/control/#", rumqttc::QoS::AtLeastOnce)
-        .await
-        .map_err(|e| e.to_string())?;
-    client_mqtt
-        .subscribe("apiserver/#", rumqttc::QoS::AtLeastOnce)
-        .await
-        .map_err(|e| e.to_string())?;
+    // Faz subscribe nos tópicos de interesse. Brokers só de publicação não recebem nada.
+    if broker.role.ingest() {
+        for topic in &broker.topics {
+            client_mqtt
+                .subscribe(topic, rumqttc::QoS::AtLeastOnce)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+    }
 
     {
-        *(globs.broker_client.write().await) = Some(Arc::new(client_mqtt));
+        *(globs.brokers[broker_index].client.write().await) = Some(Arc::new(client_mqtt));
     }
 
     // Just loop on incoming messages.
     crate::LOG.append_log_tag_msg(
         "info",
         &format!(
-            "Awaiting events from: {}:{}",
-            broker_config.host, broker_config.port
+            "Awaiting events from: [{}] {}:{} ({:?})",
+            broker.name, broker_config.host, broker_config.port, broker.role
         ),
     );
 
@@ -78,11 +82,18 @@ pub async fn connect_to_mqtt_broker(globs: &Arc<GlobalVars>) -> Result<rumqttc::
     Ok(eventloop)
 }
 
-async fn task_mqtt_client_broker_rumqtt(globs: &Arc<GlobalVars>) -> Result<String, String> {
-    let broker_config = &globs.configfile.broker_config;
-    let mut eventloop = connect_to_mqtt_broker(globs).await?;
+async fn task_mqtt_client_broker_rumqtt(
+    globs: &Arc<GlobalVars>,
+    broker_index: usize,
+) -> Result<String, String> {
+    let broker = &globs.configfile.brokers[broker_index];
+    let mut eventloop = connect_to_mqtt_broker(globs, broker_index).await?;
     loop {
-        let packet = next_mqtt_message_rumqtt(&mut eventloop, broker_config).await?;
+        // Mesmo sem receber telemetrias o eventloop precisa ser consumido para as publicações saírem
+        let packet = next_mqtt_message_rumqtt(&mut eventloop, &broker.broker_config).await?;
+        if !broker.role.ingest() {
+            continue;
+        }
 
         let payload_str = match std::str::from_utf8(&packet.payload) {
             Ok(v) => v,
@@ -1,4 +1,6 @@
 use super::commands_sender::MsgToBroker;
+use super::dash_update;
+use super::device_commands;
 use super::payload_conversions::{
     convert_control_payload, convert_data_payload, PayloadConversionResult,
 };
@@ -16,6 +18,12 @@ pub async fn process_payload(topic: &str, payload_str: &str, globs: &Arc<GlobalV
         globs.stats.topic_ctrl.fetch_add(1, Ordering::Relaxed);
     }
 
+    // Mensagens do API-Server não são de dispositivos, não têm dev_id
+    if topic.starts_with("apiserver/") {
+        dash_update::on_apiserver_message(globs, topic, payload_str).await;
+        return;
+    }
+
     // Ignore invalid payload
     if !payload_str.starts_with('{') {
         // For example: "Current RMT state:..."
@@ -97,13 +105,29 @@ pub fn check_and_forward_payload(
 
 async fn process_payload_on_data(
     globs: &Arc<GlobalVars>,
-    mut payload_json: serde_json::Value,
+    payload_json: serde_json::Value,
     dev_id: String,
     topic: &str,
     payload_str: &str,
 ) {
     globs.stats.topic_data.fetch_add(1, Ordering::Relaxed);
 
+    let processing_result =
+        match convert_data_message(globs, payload_json, &dev_id, topic, payload_str).await {
+            Some(v) => v,
+            None => return,
+        };
+    check_and_forward_payload(processing_result, topic, payload_str, &dev_id, globs, true);
+}
+
+// Valida o timestamp do pacote e aplica as conversões de telemetria. Também usado pelo replay.
+pub async fn convert_data_message(
+    globs: &Arc<GlobalVars>,
+    mut payload_json: serde_json::Value,
+    dev_id: &str,
+    topic: &str,
+    payload_str: &str,
+) -> Option<PayloadConversionResult> {
     let pack_ts = match payload_json["timestamp"].as_str() {
         Some(timestamp_str) => {
             match NaiveDateTime::parse_from_str(&timestamp_str, "%Y-%m-%dT%H:%M:%S") {
@@ -111,14 +135,14 @@ async fn process_payload_on_data(
                 Err(err) => {
                     let message = format!("{} {} {}", topic, err, payload_json.to_string());
                     crate::LOG.append_log_tag_msg("ERROR", &message);
-                    return;
+                    return None;
                 }
             }
         }
         None => {
             let message = format!("{} {} {}", topic, "No timestamp", payload_json.to_string());
             crate::LOG.append_log_tag_msg("ERROR", &message);
-            return;
+            return None;
         }
     };
 
@@ -130,8 +154,7 @@ async fn process_payload_on_data(
         }
     };
 
-    let processing_result = convert_data_payload(payload_json, payload_str, &dev_id, &globs).await;
-    check_and_forward_payload(processing_result, topic, payload_str, &dev_id, globs, true);
+    Some(convert_data_payload(payload_json, payload_str, dev_id, globs).await)
 }
 
 fn process_payload_on_control(
@@ -141,6 +164,9 @@ fn process_payload_on_control(
     topic: &str,
     payload_str: &str,
 ) {
+    // Respostas de comandos enviados pelo /send-command
+    device_commands::on_control_message(globs, &dev_id, &payload_json);
+
     // Tratamento do iotrelay feito para o tempo real
     let processing_result =
         convert_control_payload(payload_json.clone(), topic, payload_str, &dev_id, &globs);
@@ -153,13 +179,8 @@ fn process_payload_on_others(
     dev_id: String,
     topic: &str,
 ) {
-    if topic == "apiserver/hwcfg-change" {
-        // Houve mudança de config de hardware, marca o booleano para solicitar versão atualizada
-        globs.need_update_configs.store(true, Ordering::Relaxed);
-    } else {
-        // Ignorar, tópico desconhecido
-        println!("ERROR89: Ignoring unknown topic: {}", topic);
-    }
+    // Ignorar, tópico desconhecido
+    println!("ERROR89: Ignoring unknown topic: {}", topic);
 }
 
 pub fn build_topic(dev_id: &str, in_topic: &str) -> String {
@@ -1,29 +1,30 @@
+use super::commands_sender::MsgToBroker;
 use super::dash_update::DevHwConfig;
-use super::redis_connection::{get_dev_state_redis, save_dev_state_redis};
-use super::state_persistence::serialize_state_obj;
+use super::state_persistence::{get_dev_state, save_dev_state, serialize_state_obj};
+use crate::dac_faults::checker::{DacFaultsChecker, FaultChange};
+use crate::dac_faults::rules::FaultSample;
 use crate::l1_virtual::dac_l1::dac_l1_calculator;
 use crate::l1_virtual::dut_l1::l1_calc as dut_l1_calculator;
-use crate::telemetry_payloads::dri::chiller_carrier_hx::{
-    convert_chiller_carrier_hx_payload, TelemetryDriChillerCarrierHX,
-};
-use crate::telemetry_payloads::dri::chiller_carrier_xa::{
-    convert_chiller_carrier_xa_payload, TelemetryDriChillerCarrierXA,
-};
-use crate::telemetry_payloads::dri::chiller_carrier_xa_hvar::{
-    convert_chiller_carrier_xa_hvar_payload, TelemetryDriChillerCarrierXAHvar,
-};
+use crate::lib_metrics::REGISTRY;
+use crate::telemetry_payloads::dri::generic::decode_payload;
+use crate::telemetry_payloads::dri::profile::{self as dri_profile, DriProfile};
 use crate::telemetry_payloads::dri::vav_fancoil::convert_vav_and_fancoil_payload;
 use crate::telemetry_payloads::dri_telemetry::{HwInfoDRI, TelemetryDri};
 use crate::telemetry_payloads::energy::dme::TelemetryDME;
-use crate::telemetry_payloads::telemetry_formats::TelemetryPackDutV2Full;
+use crate::telemetry_payloads::energy::meters as energy_meters;
+use crate::telemetry_payloads::telemetry_formats::{
+    TelemetryPackDAC_v2, TelemetryPackDAC_v3, TelemetryPackDutV2Full,
+};
 use crate::telemetry_payloads::{
     dac_payload_json, dac_telemetry, dal_payload_json::get_raw_telemetry_pack_dal, dal_telemetry,
     dmt_payload_json, dmt_telemetry, dut_telemetry, energy::dme,
 };
 use crate::GlobalVars;
+use chrono::NaiveDateTime;
 use serde_cbor;
 use std::str::FromStr;
 use std::sync::Arc;
+use tokio::sync::mpsc::{self, error::TrySendError};
 
 pub enum PayloadConversionResult {
     WithoutConversion,            // Encaminhar o payload sem conversão
@@ -192,10 +193,10 @@ async fn process_data_dac(
 ) -> Result<PayloadConversionResult, String> {
     globs.stats.msgsz_dac.on_data(payload_str.len());
 
-    let dac_state_db: Option<Vec<u8>> = get_dev_state_redis(&dev_id, globs).await?;
+    let dac_state_db: Option<Vec<u8>> = get_dev_state(&dev_id, globs).await?;
 
-    let (payload_obj, dac_state_bytes) = {
-        let payload_obj = match dac_payload_json::get_raw_telemetry_pack_dac(&payload_json) {
+    let (payload_obj, dac_state_bytes, fault_changes, active_faults) = {
+        let raw_pack = match dac_payload_json::get_raw_telemetry_pack_dac(&payload_json) {
             Ok(v) => v,
             Err(err) => {
                 return Err(format!("Ignoring invalid payload(s): {err}"));
@@ -226,6 +227,7 @@ async fn process_data_dac(
             None => DacDbState {
                 cfg_token: latest_cfg_token.to_owned(),
                 state: dac_l1_calculator::create_l1_calculator(&hw_cfg),
+                faults: None,
             },
             Some(dac_state_db) => {
                 match serde_cbor::from_reader::<DacDbState, &[u8]>(dac_state_db.as_slice()) {
@@ -236,6 +238,7 @@ async fn process_data_dac(
                             DacDbState {
                                 cfg_token: latest_cfg_token.to_owned(),
                                 state: dac_l1_calculator::create_l1_calculator(&hw_cfg),
+                                faults: None,
                             }
                         }
                     }
@@ -247,6 +250,7 @@ async fn process_data_dac(
                         DacDbState {
                             cfg_token: latest_cfg_token.to_owned(),
                             state: dac_l1_calculator::create_l1_calculator(&hw_cfg),
+                            faults: None,
                         }
                     }
                 }
@@ -254,7 +258,7 @@ async fn process_data_dac(
         };
 
         let payload_obj =
-            match dac_telemetry::convert_payload(&payload_obj, &hw_cfg, &mut dac_db_state.state) {
+            match dac_telemetry::convert_payload(&raw_pack, &hw_cfg, &mut dac_db_state.state) {
                 Ok(v) => v,
                 Err(err) => {
                     return Err(format!(
@@ -264,14 +268,26 @@ async fn process_data_dac(
                 }
             };
 
+        // O estado das falhas fica junto com o do L1 e também recomeça quando a config muda
+        let faults_checker = dac_db_state
+            .faults
+            .get_or_insert_with(|| DacFaultsChecker::new(&hw_cfg));
+        let fault_changes = check_dac_faults(faults_checker, &raw_pack, &payload_obj);
+        let active_faults: Vec<String> = faults_checker
+            .active_faults()
+            .into_iter()
+            .map(|fault_id| fault_id.to_owned())
+            .collect();
+
         let mut dac_state_bytes: Vec<u8> = Vec::new();
         serde_cbor::to_writer(&mut dac_state_bytes, &dac_db_state)
             .map_err(|err| format!("[266] {err}"))?;
 
-        (payload_obj, dac_state_bytes)
+        (payload_obj, dac_state_bytes, fault_changes, active_faults)
     };
 
-    save_dev_state_redis(&dev_id, globs, dac_state_bytes).await?;
+    save_dev_state(&dev_id, globs, dac_state_bytes).await?;
+    publish_dac_fault_changes(dev_id, fault_changes, &active_faults, &globs.to_broker);
 
     payload_json["Lcmp"] = serde_json::json!(payload_obj.Lcmp);
     if payload_obj.Lcut.is_some() {
@@ -323,6 +339,76 @@ async fn process_data_dac(
     Ok(Converted(payload_json))
 }
 
+// Avalia as regras de falha em cada amostra do pacote, o timestamp do pacote é o da última amostra
+fn check_dac_faults(
+    checker: &mut DacFaultsChecker,
+    raw_pack: &TelemetryPackDAC_v2,
+    pack: &TelemetryPackDAC_v3,
+) -> Vec<FaultChange> {
+    let pack_ts = match NaiveDateTime::parse_from_str(&raw_pack.timestamp, "%Y-%m-%dT%H:%M:%S") {
+        Ok(date) => date.and_utc().timestamp(),
+        Err(_) => return Vec::new(),
+    };
+    let value = |vec: &Option<Vec<Option<f64>>>, i: usize| {
+        vec.as_ref().and_then(|vec| vec.get(i).copied().flatten())
+    };
+    let pack_length = pack.Lcmp.len();
+    let mut changes = Vec::new();
+    for i in 0..pack_length {
+        let sample = FaultSample {
+            ts: pack_ts - raw_pack.samplingTime * (pack_length - i - 1) as i64,
+            Lcmp: pack.Lcmp[i].map(|lcmp| lcmp != 0),
+            Tamb: value(&pack.Tamb, i),
+            Tsuc: value(&pack.Tsuc, i),
+            Tliq: value(&pack.Tliq, i),
+            Psuc: value(&pack.Psuc, i),
+            Pliq: value(&pack.Pliq, i),
+            Tsh: value(&pack.Tsh, i),
+            Tsc: value(&pack.Tsc, i),
+        };
+        changes.extend(checker.on_sample(&sample));
+    }
+    changes
+}
+
+// Publica em iotrelay/faults/dac/<dev_id> cada falha que ativou ou normalizou.
+// A fila para os brokers é a mesma dos comandos e das telemetrias: se estiver cheia o evento é descartado
+// em vez de segurar a conversão das telemetrias.
+fn publish_dac_fault_changes(
+    dev_id: &str,
+    fault_changes: Vec<FaultChange>,
+    active_faults: &[String],
+    to_broker: &mpsc::Sender<MsgToBroker>,
+) {
+    let topic = format!("iotrelay/faults/dac/{}", dev_id);
+    for change in fault_changes {
+        let timestamp = chrono::DateTime::from_timestamp(change.ts, 0)
+            .map(|ts| ts.naive_utc().format("%Y-%m-%dT%H:%M:%S").to_string());
+        let payload = serde_json::json!({
+            "dev_id": dev_id,
+            "timestamp": timestamp,
+            "fault_id": change.fault_id,
+            "active": change.active,
+            "active_faults": active_faults,
+        });
+        let message = MsgToBroker::MessageToTopic(topic.clone(), payload.to_string());
+        match to_broker.try_send(message) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => {
+                REGISTRY
+                    .counter(
+                        "dac_fault_events_dropped_total",
+                        "Eventos de falha de DAC descartados por causa da fila para os brokers cheia",
+                        &[],
+                    )
+                    .inc();
+            }
+            // Só acontece quando a tarefa que envia para os brokers já terminou
+            Err(TrySendError::Closed(_)) => {}
+        }
+    }
+}
+
 async fn process_data_dut(
     dev_id: &str,
     payload_json: serde_json::Value,
@@ -331,7 +417,7 @@ async fn process_data_dut(
 ) -> Result<PayloadConversionResult, String> {
     globs.stats.msgsz_dut.on_data(payload_str.len());
 
-    let dut_state_db: Option<Vec<u8>> = get_dev_state_redis(&dev_id, globs).await?;
+    let dut_state_db: Option<Vec<u8>> = get_dev_state(&dev_id, globs).await?;
 
     let (payload_json, dac_state_bytes) = {
         let payload_obj: TelemetryPackDutV2Full = match serde_json::from_str(payload_str) {
@@ -414,7 +500,7 @@ async fn process_data_dut(
         (payload_json, dut_state_bytes)
     };
 
-    save_dev_state_redis(&dev_id, globs, dac_state_bytes).await?;
+    save_dev_state(&dev_id, globs, dac_state_bytes).await?;
 
     return Ok(Converted(payload_json));
 }
@@ -484,7 +570,9 @@ async fn process_data_dri(
         }
     };
 
-    let is_dme_payload = dme::DME_METERS.contains(&payload_type);
+    let is_dme_payload = energy_meters::registry()
+        .for_payload_type(payload_type)
+        .is_some();
     if is_dme_payload {
         return process_data_dri_type_dme(payload_str, dev_id, hw_cfg, payload_type);
     }
@@ -495,29 +583,16 @@ async fn process_data_dri(
         return process_data_dri_type_vav_fancoil(payload_str, dev_id, hw_cfg, payload_type);
     }
 
-    let is_chiller_carrier_30xa_hvar = payload_type == "CHILLER-CARRIER-30XAB-HVAR";
-    if is_chiller_carrier_30xa_hvar {
-        return process_data_dri_type_chiller_carrier_30xa_hvar(
-            payload_str,
+    if let Some(profile) = dri_profile::registry().for_payload(payload_type, &payload_json) {
+        return process_data_dri_profile(
+            payload_json.clone(),
             dev_id,
             hw_cfg,
             payload_type,
+            &profile,
         );
     }
 
-    let is_chiller_carrier_xa_payload = payload_type.starts_with("CHILLER-CARRIER-30XA");
-    if is_chiller_carrier_xa_payload {
-        return process_data_dri_type_chiller_carrier_xa(payload_str, dev_id, hw_cfg, payload_type);
-    }
-
-    let is_chiller_carrier_hx_payload = payload_type == "CHILLER-CARRIER-30HXE"
-        || payload_type == "CHILLER-CARRIER-30GXE"
-        || payload_type == "CHILLER-CARRIER-30HXF"
-        || payload_str.contains("CHIL_S_S");
-    if is_chiller_carrier_hx_payload {
-        return process_data_dri_type_chiller_carrier_hx(payload_str, dev_id, hw_cfg, payload_type);
-    }
-
     return Ok(WithoutConversion);
 }
 fn process_data_dri_type_dme(
@@ -596,117 +671,27 @@ fn process_data_dri_type_vav_fancoil(
 
     return Ok(Converted(telemetry_obj));
 }
-fn process_data_dri_type_chiller_carrier_xa(
-    payload_str: &str,
-    dev_id: &str,
-    hw_cfg: &HwInfoDRI,
-    payload_type: &str,
-) -> Result<PayloadConversionResult, String> {
-    let payload_obj: TelemetryDriChillerCarrierXA = match serde_json::from_str(payload_str) {
-        Ok(v) => v,
-        Err(err) => {
-            return Err(format!("Ignoring invalid payload: {}", &err));
-        }
-    };
-
-    let payload_obj = match convert_chiller_carrier_xa_payload(payload_obj, hw_cfg) {
-        Ok(v) => v,
-        Err(err) => {
-            return Err(format!("Ignoring invalid payload: {}", &err));
-        }
-    };
-
-    let telemetry_obj = match serde_json::json!(payload_obj) {
-        serde_json::Value::Object(m) => {
-            let mut m = m;
-            m.insert(
-                "dev_id".to_string(),
-                serde_json::Value::String(dev_id.to_string()),
-            );
-            m.insert(
-                "type".to_string(),
-                serde_json::Value::String(payload_type.to_string()),
-            );
-            serde_json::Value::Object(m)
-        }
-        v => v,
-    };
-
-    return Ok(Converted(telemetry_obj));
-}
-fn process_data_dri_type_chiller_carrier_hx(
-    payload_str: &str,
-    dev_id: &str,
-    hw_cfg: &HwInfoDRI,
-    payload_type: &str,
-) -> Result<PayloadConversionResult, String> {
-    let payload_obj: TelemetryDriChillerCarrierHX = match serde_json::from_str(payload_str) {
-        Ok(v) => v,
-        Err(err) => {
-            return Err(format!("Ignoring invalid payload: {}", &err));
-        }
-    };
-
-    let payload_obj = match convert_chiller_carrier_hx_payload(payload_obj, hw_cfg) {
-        Ok(v) => v,
-        Err(err) => {
-            return Err(format!("Ignoring invalid payload: {}", &err));
-        }
-    };
-
-    let telemetry_obj = match serde_json::json!(payload_obj) {
-        serde_json::Value::Object(m) => {
-            let mut m = m;
-            m.insert(
-                "dev_id".to_string(),
-                serde_json::Value::String(dev_id.to_string()),
-            );
-            m.insert(
-                "type".to_string(),
-                serde_json::Value::String(payload_type.to_string()),
-            );
-            serde_json::Value::Object(m)
-        }
-        v => v,
-    };
-
-    return Ok(Converted(telemetry_obj));
-}
-fn process_data_dri_type_chiller_carrier_30xa_hvar(
-    payload_str: &str,
+fn process_data_dri_profile(
+    mut payload_json: serde_json::Value,
     dev_id: &str,
     hw_cfg: &HwInfoDRI,
     payload_type: &str,
+    profile: &DriProfile,
 ) -> Result<PayloadConversionResult, String> {
-    let payload_obj = match serde_json::from_str::<TelemetryDriChillerCarrierXAHvar>(payload_str) {
-        Ok(v) => v,
-        Err(err) => {
-            return Err(format!("Ignoring invalid payload: {}", &err));
-        }
-    };
+    if let Some(formulas) = &hw_cfg.formulas {
+        payload_json["formulas"] = serde_json::json!(formulas);
+    }
 
-    let payload_obj = match convert_chiller_carrier_xa_hvar_payload(payload_obj, hw_cfg) {
+    let payload_obj = match decode_payload(profile, payload_json) {
         Ok(v) => v,
         Err(err) => {
             return Err(format!("Ignoring invalid payload: {}", &err));
         }
     };
 
-    let telemetry_obj = match serde_json::json!(payload_obj) {
-        serde_json::Value::Object(m) => {
-            let mut m = m;
-            m.insert(
-                "dev_id".to_string(),
-                serde_json::Value::String(dev_id.to_string()),
-            );
-            m.insert(
-                "type".to_string(),
-                serde_json::Value::String(payload_type.to_string()),
-            );
-            serde_json::Value::Object(m)
-        }
-        v => v,
-    };
+    let mut telemetry_obj = payload_obj.to_json(profile);
+    telemetry_obj["dev_id"] = dev_id.into();
+    telemetry_obj["type"] = payload_type.into();
 
     return Ok(Converted(telemetry_obj));
 }
@@ -910,6 +895,9 @@ fn validate_string_temperature(
 struct DacDbState {
     pub cfg_token: Vec<u8>,
     pub state: dac_l1_calculator::L1Calculator,
+    // Estados salvos antes da detecção de falhas não têm o campo
+    #[serde(default)]
+    pub faults: Option<DacFaultsChecker>,
 }
 
 #[derive(serde::Serialize, serde::Deserialize)]
@@ -921,3 +909,64 @@ struct DutDbState {
 /*
     Se duas instâncias tiverem configs diferentes (que pode acontecer por versão de código diferente) as duas vão ficar zerando as configs toda hora.
 */
+
+#[test]
+fn test_dac_fault_events() {
+    let cfg: crate::telemetry_payloads::dac_telemetry::HwInfoDAC = serde_json::from_value(serde_json::json!({
+        "isVrf": false, "calculate_L1_fancoil": null, "debug_L1_fancoil": null, "hasAutomation": false,
+        "P0Psuc": true, "P1Psuc": false, "P0Pliq": false, "P1Pliq": true,
+        "P0multQuad": 0.0, "P1multQuad": 0.0, "P0multLin": 1.0, "P1multLin": 1.0, "P0ofst": 0.0, "P1ofst": 0.0,
+        "fluid": "r410a", "t_cfg": null, "simulateL1": false, "l1_psuc_offset": 0.0, "DAC_APPL": null, "DAC_TYPE": null,
+    }))
+    .unwrap();
+    let mut checker = DacFaultsChecker::new(&cfg);
+    let (to_broker, mut receiver) = mpsc::channel::<MsgToBroker>(10);
+
+    // Superaquecimento alto por 25 minutos e depois normal por mais 25
+    for i in 0..200 {
+        let ts = 1_700_000_000 + i * 15;
+        let sample = FaultSample {
+            ts,
+            Lcmp: Some(true),
+            Tamb: Some(30.0 + (ts % 7) as f64 / 10.0),
+            Tsuc: Some(12.0 + (ts / 15 % 5) as f64 / 10.0),
+            Tliq: Some(40.0 + (ts / 15 % 3) as f64 / 10.0),
+            Psuc: Some(8.5 + (ts % 11) as f64 / 100.0),
+            Pliq: Some(25.0 + (ts % 13) as f64 / 100.0),
+            Tsh: Some(if i < 100 { 20.0 } else { 8.0 }),
+            Tsc: Some(5.0),
+        };
+        let changes = checker.on_sample(&sample);
+        let active_faults: Vec<String> = checker
+            .active_faults()
+            .iter()
+            .map(|fault_id| fault_id.to_string())
+            .collect();
+        publish_dac_fault_changes("DAC000000001", changes, &active_faults, &to_broker);
+    }
+
+    let mut events = Vec::new();
+    while let Ok(MsgToBroker::MessageToTopic(topic, payload)) = receiver.try_recv() {
+        assert_eq!(topic, "iotrelay/faults/dac/DAC000000001");
+        events.push(serde_json::from_str::<serde_json::Value>(&payload).unwrap());
+    }
+    assert_eq!(
+        events,
+        vec![
+            serde_json::json!({
+                "dev_id": "DAC000000001",
+                "timestamp": "2023-11-14T22:33:20",
+                "fault_id": "high_superheat",
+                "active": true,
+                "active_faults": ["high_superheat"],
+            }),
+            serde_json::json!({
+                "dev_id": "DAC000000001",
+                "timestamp": "2023-11-14T22:53:20",
+                "fault_id": "high_superheat",
+                "active": false,
+                "active_faults": [],
+            }),
+        ]
+    );
+}
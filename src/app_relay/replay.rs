@@ -0,0 +1,321 @@
+use super::commands_sender::MsgToBroker;
+use super::configs::ConfigFile;
+use super::dash_update::parse_dash_update;
+use super::global_vars::GlobalVars;
+use super::on_mqtt_message::{build_topic, convert_data_message, parse_packet};
+use super::payload_conversions::PayloadConversionResult;
+use crate::lib_state_store::store::{MemoryStoreConfig, StateStoreConfig};
+use serde_json::json;
+use std::io::{BufRead, Write};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+/*
+Reprocessa offline telemetrias gravadas usando o mesmo caminho de conversão do iotrelay.
+Cada linha do arquivo de entrada pode ser {"topic": "...", "payload": ...} ou só o payload
+(como na saída do /export-dev-telemetries), e nesse caso o tópico é montado como data/<tipo>/<dev_id>.
+O estado dos dispositivos (L1 virtual, etc) fica em memória no lugar do Redis e as configs de hardware
+vêm de um arquivo no mesmo formato da resposta do API-Server usada pelo dash_update.
+As mensagens que as conversões publicam nos brokers (eventos de falha de DAC) vão para a saída
+logo depois da telemetria que as gerou.
+*/
+
+#[derive(Default, Debug)]
+pub struct ReplaySummary {
+    pub records: usize,
+    pub converted: usize,
+    pub unchanged: usize,
+    pub ignored: usize,
+    pub skipped: usize,
+    pub errors: usize,
+    pub events: usize, // Mensagens publicadas pelas conversões
+}
+
+pub async fn run_replay(
+    input_path: &str,
+    hwcfg_path: &str,
+    output_path: &str,
+) -> Result<ReplaySummary, String> {
+    let (globs, mut receiver_fila) = create_replay_globs(hwcfg_path)?;
+    let globs = Arc::new(globs);
+
+    let input = std::fs::File::open(input_path)
+        .map_err(|err| format!("ERROR[41] {}: {}", input_path, err))?;
+    let output = std::fs::File::create(output_path)
+        .map_err(|err| format!("ERROR[43] {}: {}", output_path, err))?;
+    let mut writer = std::io::BufWriter::new(output);
+
+    let mut summary = ReplaySummary::default();
+    for (line_index, line) in std::io::BufReader::new(input).lines().enumerate() {
+        let line = line.map_err(|err| format!("ERROR[48] {}: {}", input_path, err))?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        summary.records += 1;
+
+        let (topic, payload_str) = match parse_record(line) {
+            Ok(v) => v,
+            Err(err) => {
+                let message = format!("line {}: {}", line_index + 1, err);
+                crate::LOG.append_log_tag_msg("ERROR", &message);
+                summary.errors += 1;
+                continue;
+            }
+        };
+
+        // Os payloads de control não passam pelas conversões de telemetria
+        if !topic.starts_with("data/") {
+            summary.skipped += 1;
+            continue;
+        }
+
+        let (payload_json, dev_id) = match parse_packet(&payload_str) {
+            Ok(v) if v.1.len() >= 3 => v,
+            Ok(_) => {
+                let message = format!("line {}: Invalid dev_id", line_index + 1);
+                crate::LOG.append_log_tag_msg("ERROR", &message);
+                summary.errors += 1;
+                continue;
+            }
+            Err(err) => {
+                let message = format!("line {}: {}", line_index + 1, err);
+                crate::LOG.append_log_tag_msg("ERROR", &message);
+                summary.errors += 1;
+                continue;
+            }
+        };
+
+        let result =
+            convert_data_message(&globs, payload_json.clone(), &dev_id, &topic, &payload_str).await;
+        let payload_out = match result {
+            Some(PayloadConversionResult::WithoutConversion) => {
+                summary.unchanged += 1;
+                Some(payload_json)
+            }
+            Some(PayloadConversionResult::Converted(payload_json)) => {
+                summary.converted += 1;
+                Some(payload_json)
+            }
+            Some(PayloadConversionResult::IgnorePayload) => {
+                summary.ignored += 1;
+                None
+            }
+            Some(PayloadConversionResult::Error(err)) => {
+                let message = format!("line {}: {}", line_index + 1, err);
+                crate::LOG.append_log_tag_msg("ERROR", &message);
+                summary.errors += 1;
+                None
+            }
+            None => {
+                summary.errors += 1;
+                None
+            }
+        };
+
+        if let Some(payload_out) = payload_out {
+            write_record(&mut writer, &build_topic(&dev_id, &topic), payload_out)?;
+        }
+        // Os eventos são enviados com try_send durante a conversão, então já estão na fila
+        while let Ok(MsgToBroker::MessageToTopic(topic, payload)) = receiver_fila.try_recv() {
+            summary.events += 1;
+            let payload = serde_json::from_str(&payload).unwrap_or_else(|_| payload.into());
+            write_record(&mut writer, &topic, payload)?;
+        }
+    }
+
+    writer
+        .flush()
+        .map_err(|err| format!("ERROR[107] {}", err))?;
+
+    Ok(summary)
+}
+
+fn write_record(
+    writer: &mut impl Write,
+    topic: &str,
+    payload: serde_json::Value,
+) -> Result<(), String> {
+    let record = json!({
+        "topic": topic,
+        "payload": payload,
+    });
+    writeln!(writer, "{}", record).map_err(|err| format!("ERROR[104] {}", err))
+}
+
+fn create_replay_globs(
+    hwcfg_path: &str,
+) -> Result<(GlobalVars, mpsc::Receiver<MsgToBroker>), String> {
+    // Nenhuma conexão externa é aberta no replay, as configs de rede ficam vazias
+    let configfile = ConfigFile {
+        listen_http_api: String::new(),
+        apiserver_internal_api: String::new(),
+        brokers: Vec::new(),
+        broker_publish_max_attempts: 1,
+        hwcfg_resync_interval: std::time::Duration::from_secs(3600),
+        state_store: StateStoreConfig::Memory(MemoryStoreConfig {
+            max_devices: usize::MAX,
+            snapshot_file: None,
+            snapshot_interval: std::time::Duration::from_secs(300),
+        }),
+    };
+    let (mut globs, receiver_fila) = GlobalVars::new(configfile);
+
+    let hwcfg = std::fs::read_to_string(hwcfg_path)
+        .map_err(|err| format!("ERROR[131] {}: {}", hwcfg_path, err))?;
+    let hwcfg = serde_json::from_str::<serde_json::Value>(&hwcfg)
+        .map_err(|err| format!("ERROR[133] {}: {}", hwcfg_path, err))?;
+    let summary = parse_dash_update(&hwcfg, globs.conv_vars.get_mut())?;
+    *globs.configs_ready.get_mut() = true;
+    crate::LOG.append_log_tag_msg(
+        "INFO",
+        &format!(
+            "Configs de hardware: versão {}, {} dispositivos, {} inválidos",
+            summary.version,
+            summary.updated,
+            summary.errors.len()
+        ),
+    );
+
+    Ok((globs, receiver_fila))
+}
+
+fn parse_record(line: &str) -> Result<(String, String), String> {
+    let record = serde_json::from_str::<serde_json::Value>(line)
+        .map_err(|err| format!("Invalid record: {}", err))?;
+
+    if let (Some(topic), Some(payload)) = (record["topic"].as_str(), record.get("payload")) {
+        let payload_str = match payload.as_str() {
+            Some(v) => v.to_owned(),
+            None => payload.to_string(),
+        };
+        return Ok((topic.to_owned(), payload_str));
+    }
+
+    // Sem tópico, o registro é o próprio payload
+    let dev_id = match record["dev_id"].as_str() {
+        Some(v) if v.len() >= 3 => v,
+        _ => return Err("Record without topic and dev_id".to_owned()),
+    };
+    let topic = format!("data/{}/{}", dev_id[0..3].to_lowercase(), dev_id);
+    Ok((topic, line.to_owned()))
+}
+
+#[test]
+fn test_replay() {
+    let dir = std::env::temp_dir().join(format!(
+        "replay-{}",
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_millis()
+    ));
+    std::fs::create_dir_all(&dir).unwrap();
+    let input_path = dir.join("input.jsonl");
+    let hwcfg_path = dir.join("hwcfg.json");
+    let output_path = dir.join("output.jsonl");
+
+    let hwcfg = json!({
+        "dacs": [{
+            "DAC_ID": "DAC000000001", "isVrf": false, "hasAutomation": false,
+            "P0Psuc": true, "P1Psuc": false, "P0Pliq": false, "P1Pliq": true,
+            "P0multQuad": 0, "P0multLin": 0.1, "P0ofst": 0, "P1multQuad": 0, "P1multLin": 0.1, "P1ofst": 0,
+            "FLUID_TYPE": "r410a", "T0_T1_T2": ["Tamb", "Tsuc", "Tliq"],
+        }],
+        "duts": [{ "DUT_ID": "DUT000000001", "TEMPERATURE_OFFSET": 1.0 }],
+        "dris": [],
+    });
+    std::fs::write(&hwcfg_path, hwcfg.to_string()).unwrap();
+
+    // Pacotes de 30 minutos: superaquecimento alto na primeira hora e normal na segunda
+    let dac_pack = |timestamp: &str, t_suc: f64| {
+        json!({
+            "dev_id": "DAC000000001", "timestamp": timestamp, "samplingTime": 15,
+            "L1": vec![1; 120], "T0": vec![30.0; 120], "T1": vec![t_suc; 120], "T2": vec![40.0; 120],
+            "P0": vec![85; 120], "P1": vec![250; 120],
+        })
+    };
+    let input = [
+        dac_pack("2024-03-01T10:29:45", 25.0).to_string(),
+        dac_pack("2024-03-01T10:59:45", 25.0).to_string(),
+        dac_pack("2024-03-01T11:29:45", 12.0).to_string(),
+        dac_pack("2024-03-01T11:59:45", 12.0).to_string(),
+        json!({ "topic": "data/dut/DUT000000001", "payload": {
+            "dev_id": "DUT000000001", "timestamp": "2024-03-01T12:00:00", "samplingTime": 5,
+            "Temperature": [20.0, 20.5], "Humidity": [50.0, 51.0],
+        } })
+        .to_string(),
+        json!({ "topic": "control/dut/DUT000000001", "payload": { "dev_id": "DUT000000001" } })
+            .to_string(),
+        "not json".to_owned(),
+    ];
+    std::fs::write(&input_path, input.join("\n")).unwrap();
+
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let summary = rt
+        .block_on(run_replay(
+            input_path.to_str().unwrap(),
+            hwcfg_path.to_str().unwrap(),
+            output_path.to_str().unwrap(),
+        ))
+        .unwrap();
+    let output = std::fs::read_to_string(&output_path).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(
+        (
+            summary.records,
+            summary.converted,
+            summary.skipped,
+            summary.errors
+        ),
+        (7, 5, 1, 1)
+    );
+    assert_eq!(summary.events, 2);
+    let records: Vec<serde_json::Value> = output
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    let topics: Vec<&str> = records
+        .iter()
+        .map(|record| record["topic"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        topics,
+        vec![
+            "iotrelay/data/dac/DAC000000001",
+            "iotrelay/faults/dac/DAC000000001",
+            "iotrelay/data/dac/DAC000000001",
+            "iotrelay/data/dac/DAC000000001",
+            "iotrelay/faults/dac/DAC000000001",
+            "iotrelay/data/dac/DAC000000001",
+            "iotrelay/data/dut/DUT000000001",
+        ]
+    );
+    assert_eq!(records[0]["payload"]["Tsh"][0], 19.5);
+    assert_eq!(records[3]["payload"]["Tsh"][0], 6.5);
+    assert_eq!(
+        records[1]["payload"],
+        json!({
+            "dev_id": "DAC000000001", "timestamp": "2024-03-01T10:20:00", "fault_id": "high_superheat",
+            "active": true, "active_faults": ["high_superheat"],
+        })
+    );
+    assert_eq!(
+        records[4]["payload"],
+        json!({
+            "dev_id": "DAC000000001", "timestamp": "2024-03-01T11:15:00", "fault_id": "high_superheat",
+            "active": false, "active_faults": [],
+        })
+    );
+    assert_eq!(
+        records[6]["payload"],
+        json!({
+            "dev_id": "DUT000000001", "timestamp": "2024-03-01T12:00:00", "samplingTime": 5, "GMT": null,
+            "Temperature": [21.0, 21.5], "Humidity": [50.0, 51.0],
+        })
+    );
+}
@@ -1,4 +1,6 @@
+use crate::GlobalVars;
 use serde_cbor;
+use std::sync::Arc;
 
 // fn get_dac_state_globs(dev_id: &str, conv: &mut ConversionVars, hw_cfg: &HwInfoDAC) -> Result<Box<L1Calculator>, String> {
 //     let dac_state_list = &mut conv.dac_state_list;
@@ -13,3 +15,31 @@ where
     let dev_state_bytes = serde_cbor::to_vec(&dev_state).map_err(|err| err.to_string())?;
     return Ok(dev_state_bytes);
 }
+
+// O estado fica no store configurado (Redis, memória ou arquivos), ver lib_state_store
+pub async fn get_dev_state(
+    dev_id: &str,
+    globs: &Arc<GlobalVars>,
+) -> Result<Option<Vec<u8>>, String> {
+    globs.state_store.get_state(dev_id).await
+}
+
+pub async fn save_dev_state(
+    dev_id: &str,
+    globs: &Arc<GlobalVars>,
+    dev_state_bytes: Vec<u8>,
+) -> Result<(), String> {
+    globs.state_store.save_state(dev_id, dev_state_bytes).await
+}
+
+pub async fn save_dev_state_obj<T>(
+    dev_id: &str,
+    globs: &Arc<GlobalVars>,
+    dev_state: T,
+) -> Result<(), String>
+where
+    T: serde::Serialize,
+{
+    let dev_state_bytes = serialize_state_obj(dev_state)?;
+    return save_dev_state(dev_id, globs, dev_state_bytes).await;
+}
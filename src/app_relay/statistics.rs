@@ -1,3 +1,4 @@
+use crate::lib_metrics::{queue_depth, CounterDeltas, MetricsText};
 use crate::GlobalVars;
 use serde_json::json;
 use std::sync::atomic::Ordering;
@@ -75,25 +76,27 @@ impl MessageSizeCounters {
 pub async fn task_stats(globs: Arc<GlobalVars>) {
     const INTERVAL: u64 = 60; // em segundos
     let mut ts_start = std::time::Instant::now();
+    let mut deltas = CounterDeltas::default();
 
     loop {
         let elapsed = ts_start.elapsed().as_secs();
+        let stats = &globs.stats;
 
         let message = serde_json::json!({
             "origin": "iotrelay-v1",
             "interval": elapsed,
-            "http_reqs": get_reset_atomic_usize(&globs.stats.http_reqs),
-            "mqtt_recv": get_reset_atomic_usize(&globs.stats.mqtt_recv),
-            "topic_data": get_reset_atomic_usize(&globs.stats.topic_data),
-            "topic_ctrl": get_reset_atomic_usize(&globs.stats.topic_ctrl),
-            "fwbroker_sent": get_reset_atomic_usize(&globs.stats.fwbroker_sent),
-            "fwbroker_error": get_reset_atomic_usize(&globs.stats.fwbroker_error),
-            "msgsz_dac": gerar_dev_msgsz_vec(&globs.stats.msgsz_dac),
-            "msgsz_dut": gerar_dev_msgsz_vec(&globs.stats.msgsz_dut),
-            "msgsz_dam": gerar_dev_msgsz_vec(&globs.stats.msgsz_dam),
-            "msgsz_dma": gerar_dev_msgsz_vec(&globs.stats.msgsz_dma),
-            "msgsz_dmt": gerar_dev_msgsz_vec(&globs.stats.msgsz_dmt),
-            "msgsz_dal": gerar_dev_msgsz_vec(&globs.stats.msgsz_dal),
+            "http_reqs": deltas.delta("http_reqs", &stats.http_reqs),
+            "mqtt_recv": deltas.delta("mqtt_recv", &stats.mqtt_recv),
+            "topic_data": deltas.delta("topic_data", &stats.topic_data),
+            "topic_ctrl": deltas.delta("topic_ctrl", &stats.topic_ctrl),
+            "fwbroker_sent": deltas.delta("fwbroker_sent", &stats.fwbroker_sent),
+            "fwbroker_error": deltas.delta("fwbroker_error", &stats.fwbroker_error),
+            "msgsz_dac": gerar_dev_msgsz_vec("dac", &stats.msgsz_dac, &mut deltas),
+            "msgsz_dut": gerar_dev_msgsz_vec("dut", &stats.msgsz_dut, &mut deltas),
+            "msgsz_dam": gerar_dev_msgsz_vec("dam", &stats.msgsz_dam, &mut deltas),
+            "msgsz_dma": gerar_dev_msgsz_vec("dma", &stats.msgsz_dma, &mut deltas),
+            "msgsz_dmt": gerar_dev_msgsz_vec("dmt", &stats.msgsz_dmt, &mut deltas),
+            "msgsz_dal": gerar_dev_msgsz_vec("dal", &stats.msgsz_dal, &mut deltas),
         });
 
         // ts_start.add_assign(Duration::from_secs(INTERVAL));
@@ -110,41 +113,97 @@ pub async fn task_stats(globs: Arc<GlobalVars>) {
     }
 }
 
-fn gerar_dev_msgsz_vec(msgsz_dev: &MessageSizeCounters) -> serde_json::Value {
-    let data_bytes = msgsz_dev.data_bytes.load(Ordering::Relaxed);
-    let data_count = msgsz_dev.data_count.load(Ordering::Relaxed);
-    let data_max = msgsz_dev.data_max.load(Ordering::Relaxed);
-    let ctrl_bytes = msgsz_dev.ctrl_bytes.load(Ordering::Relaxed);
-    let ctrl_count = msgsz_dev.ctrl_count.load(Ordering::Relaxed);
-    let ctrl_max = msgsz_dev.ctrl_max.load(Ordering::Relaxed);
-
-    msgsz_dev
-        .data_bytes
-        .fetch_sub(data_bytes, Ordering::Relaxed);
-    msgsz_dev
-        .data_count
-        .fetch_sub(data_count, Ordering::Relaxed);
-    msgsz_dev.data_max.store(0, Ordering::Relaxed);
-    msgsz_dev
-        .ctrl_bytes
-        .fetch_sub(ctrl_bytes, Ordering::Relaxed);
-    msgsz_dev
-        .ctrl_count
-        .fetch_sub(ctrl_count, Ordering::Relaxed);
-    msgsz_dev.ctrl_max.store(0, Ordering::Relaxed);
+pub fn gerar_dev_msgsz_vec(
+    dev_type: &str,
+    msgsz_dev: &MessageSizeCounters,
+    deltas: &mut CounterDeltas,
+) -> serde_json::Value {
+    // Os máximos são do intervalo, então continuam sendo zerados a cada leitura
+    let data_max = msgsz_dev.data_max.swap(0, Ordering::Relaxed);
+    let ctrl_max = msgsz_dev.ctrl_max.swap(0, Ordering::Relaxed);
 
     return json!({
-        "data_bytes": data_bytes,
-        "data_count": data_count,
+        "data_bytes": deltas.delta(&format!("{dev_type}_data_bytes"), &msgsz_dev.data_bytes),
+        "data_count": deltas.delta(&format!("{dev_type}_data_count"), &msgsz_dev.data_count),
         "data_max": data_max,
-        "ctrl_bytes": ctrl_bytes,
-        "ctrl_count": ctrl_count,
+        "ctrl_bytes": deltas.delta(&format!("{dev_type}_ctrl_bytes"), &msgsz_dev.ctrl_bytes),
+        "ctrl_count": deltas.delta(&format!("{dev_type}_ctrl_count"), &msgsz_dev.ctrl_count),
         "ctrl_max": ctrl_max,
     });
 }
 
-fn get_reset_atomic_usize(atomic_value: &AtomicUsize) -> usize {
-    let value = atomic_value.load(Ordering::Relaxed);
-    atomic_value.fetch_sub(value, Ordering::Relaxed);
-    value
+pub fn write_msgsz_metrics(out: &mut MetricsText, msgsz_devs: &[(&str, &MessageSizeCounters)]) {
+    out.family(
+        "mqtt_message_bytes_total",
+        "counter",
+        "Bytes recebidos em mensagens MQTT",
+    );
+    for (dev_type, msgsz) in msgsz_devs {
+        let data_labels = [("dev_type", *dev_type), ("kind", "data")];
+        let ctrl_labels = [("dev_type", *dev_type), ("kind", "ctrl")];
+        let data_bytes = msgsz.data_bytes.load(Ordering::Relaxed);
+        let ctrl_bytes = msgsz.ctrl_bytes.load(Ordering::Relaxed);
+        out.sample("mqtt_message_bytes_total", &data_labels, data_bytes as f64);
+        out.sample("mqtt_message_bytes_total", &ctrl_labels, ctrl_bytes as f64);
+    }
+    out.family("mqtt_messages_total", "counter", "Mensagens MQTT recebidas");
+    for (dev_type, msgsz) in msgsz_devs {
+        let data_labels = [("dev_type", *dev_type), ("kind", "data")];
+        let ctrl_labels = [("dev_type", *dev_type), ("kind", "ctrl")];
+        let data_count = msgsz.data_count.load(Ordering::Relaxed);
+        let ctrl_count = msgsz.ctrl_count.load(Ordering::Relaxed);
+        out.sample("mqtt_messages_total", &data_labels, data_count as f64);
+        out.sample("mqtt_messages_total", &ctrl_labels, ctrl_count as f64);
+    }
+}
+
+pub fn write_metrics(globs: &Arc<GlobalVars>, out: &mut MetricsText) {
+    let stats = &globs.stats;
+    let counter = |x: &AtomicUsize| x.load(Ordering::Relaxed);
+    out.counter(
+        "http_reqs_total",
+        "Requisições HTTP recebidas",
+        counter(&stats.http_reqs),
+    );
+    out.counter(
+        "mqtt_recv_total",
+        "Mensagens MQTT recebidas",
+        counter(&stats.mqtt_recv),
+    );
+    out.counter(
+        "topic_data_total",
+        "Mensagens recebidas em tópicos data/",
+        counter(&stats.topic_data),
+    );
+    out.counter(
+        "topic_ctrl_total",
+        "Mensagens recebidas em tópicos de controle",
+        counter(&stats.topic_ctrl),
+    );
+    out.counter(
+        "fwbroker_sent_total",
+        "Mensagens encaminhadas ao broker",
+        counter(&stats.fwbroker_sent),
+    );
+    out.counter(
+        "fwbroker_error_total",
+        "Erros ao encaminhar mensagens ao broker",
+        counter(&stats.fwbroker_error),
+    );
+    write_msgsz_metrics(
+        out,
+        &[
+            ("dac", &stats.msgsz_dac),
+            ("dut", &stats.msgsz_dut),
+            ("dam", &stats.msgsz_dam),
+            ("dma", &stats.msgsz_dma),
+            ("dmt", &stats.msgsz_dmt),
+            ("dal", &stats.msgsz_dal),
+        ],
+    );
+    out.gauge(
+        "to_broker_queue_depth",
+        "Mensagens aguardando envio ao broker",
+        queue_depth(&globs.to_broker),
+    );
 }
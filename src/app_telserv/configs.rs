@@ -1,9 +1,15 @@
-use crate::app_relay::configs::BrokerInfo;
+use crate::app_relay::configs::{
+    load_hwcfg_resync_interval, load_publish_max_attempts, load_state_store_config, BrokerInfo,
+};
 use crate::diel_hist_tables::{BigQueryHistoryTable, CustomTableRule};
 use crate::envvars_loader;
 use crate::lib_bigquery::client::GCPConfig;
+use crate::lib_bigquery::saver::{self, BqSaverConfig};
+use crate::lib_dead_letter::{self, DeadLetterConfig};
 use crate::lib_dynamodb::client::AWSConfig;
+use crate::lib_local_store::layout::LocalStoreConfig;
 use crate::lib_rumqtt::BrokerConfig;
+use crate::lib_state_store::store::StateStoreConfig;
 
 pub struct ConfigFile {
     pub listen_http_api: String,
@@ -13,18 +19,23 @@ pub struct ConfigFile {
     pub topics: Vec<String>,
 
     pub gcp_config: Option<GCPConfig>,
+    pub bq_saver_config: BqSaverConfig,
 
     pub aws_config: Option<AWSConfig>,
     pub default_aws_table_name: Option<String>,
     pub custom_aws_table_rules: Option<Vec<CustomTableRule>>,
 
-    pub url_redis: String,
+    pub local_store_config: Option<LocalStoreConfig>,
+    pub dead_letter_config: Option<DeadLetterConfig>,
+
+    pub state_store: StateStoreConfig,
 
     pub enable_forward_to_broker: bool,
+    pub broker_publish_max_attempts: u32,
+    pub hwcfg_resync_interval: std::time::Duration,
     pub enable_save_to_dynamodb: bool,
     pub enable_save_to_bigquery: bool,
     pub gcp_dest_table: BigQueryHistoryTable,
-    pub redis_prefix: String,
 }
 
 impl ConfigFile {
@@ -50,13 +61,16 @@ impl ConfigFile {
         let disable_save_to_bigquery =
             envvars_loader::get_var_bool_optional("disable_save_to_bigquery")?;
         let listen_http_api = envvars_loader::get_var_string_optional("LISTEN_HTTP_API_TELSERV");
-        let URL_REDIS = envvars_loader::get_var_string_required("URL_REDIS")?;
+        let URL_REDIS = envvars_loader::get_var_string_optional("URL_REDIS");
         let brokerConfig_topics: Vec<String> =
             envvars_loader::get_var_structure_required("brokerConfig_topics")?;
         let awsConfig_default_table_name =
             envvars_loader::get_var_string_optional("awsConfig_default_table_name");
         let awsConfig_custom_table_rules: Option<Vec<CustomTableRule>> =
             envvars_loader::get_var_structure_optional("awsConfig_custom_table_rules")?;
+        let LOCAL_TELEMETRY_DIR = envvars_loader::get_var_string_optional("LOCAL_TELEMETRY_DIR");
+        let LOCAL_TELEMETRY_COMPRESS =
+            envvars_loader::get_var_bool_optional("LOCAL_TELEMETRY_COMPRESS")?;
         let redis_prefix = envvars_loader::get_var_string_optional("REDIS_PREFIX")
             .unwrap_or_else(|| "tel/".to_owned());
 
@@ -106,6 +120,14 @@ impl ConfigFile {
             None
         };
 
+        let dead_letter_config = lib_dead_letter::load_config()?;
+        let bq_saver_config = saver::load_config(dead_letter_config.is_some())?;
+
+        let local_store_config = LOCAL_TELEMETRY_DIR.map(|root_dir| LocalStoreConfig {
+            root_dir,
+            compress: LOCAL_TELEMETRY_COMPRESS == Some(true),
+        });
+
         let apiserver_internal_api = if STATS_SERVER_HTTP.contains("://") {
             STATS_SERVER_HTTP
         } else {
@@ -116,17 +138,25 @@ impl ConfigFile {
         let enable_save_to_dynamodb = disable_save_to_dynamodb != Some(true);
         let enable_save_to_bigquery = disable_save_to_bigquery != Some(true);
 
+        let url_redis = if enable_forward_to_broker {
+            URL_REDIS
+        } else {
+            URL_REDIS.map(|url| format!("{}1", url))
+        };
+        let state_store = load_state_store_config(url_redis, redis_prefix)?;
+        let broker_publish_max_attempts = load_publish_max_attempts()?;
+        let hwcfg_resync_interval = load_hwcfg_resync_interval()?;
+
         Ok(ConfigFile {
             listen_http_api: listen_http_api.unwrap_or_else(|| "0.0.0.0:29582".to_owned()),
             apiserver_internal_api,
-            url_redis: if enable_forward_to_broker {
-                URL_REDIS
-            } else {
-                format!("{}1", URL_REDIS)
-            },
+            state_store,
             topics: brokerConfig_topics,
             default_aws_table_name: awsConfig_default_table_name,
             custom_aws_table_rules: awsConfig_custom_table_rules,
+            local_store_config,
+            dead_letter_config,
+            bq_saver_config,
 
             broker_config,
             gcp_config: if enable_save_to_bigquery {
@@ -141,10 +171,11 @@ impl ConfigFile {
             },
 
             enable_forward_to_broker,
+            broker_publish_max_attempts,
+            hwcfg_resync_interval,
             enable_save_to_dynamodb,
             enable_save_to_bigquery,
             gcp_dest_table,
-            redis_prefix,
         })
     }
 }
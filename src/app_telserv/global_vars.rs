@@ -2,12 +2,16 @@ use super::configs::ConfigFile;
 use crate::app_br2db::on_table_not_found::{
     on_table_not_found_bigquery, on_table_not_found_dynamodb,
 };
-use crate::app_relay::commands_sender::MsgToBroker;
+use crate::app_relay::commands_sender::{BrokerConn, MsgToBroker};
+use crate::app_relay::device_commands::CommandLog;
 pub use crate::app_relay::global_vars::ConversionVars;
 use crate::diel_hist_tables::{self, TablesConfig};
 use crate::lib_bigquery::client::BigQueryClient;
-use crate::lib_bigquery::saver::SaveToBqEvent;
+use crate::lib_bigquery::saver::{BqSaverHealth, SaveToBqEvent};
+use crate::lib_dead_letter::DeadLetters;
 use crate::lib_dynamodb::client::DynamoDBClientDiel;
+use crate::lib_local_store::saver::SaveToLocalEvent;
+use crate::lib_state_store::store::{create_state_store, DeviceStateStore};
 use crate::log::LogInfo;
 use crate::telemetry_payloads::dac_telemetry::HwInfoDAC;
 use crate::telemetry_payloads::dri_telemetry::HwInfoDRI;
@@ -17,7 +21,7 @@ use regex::Regex;
 use std::collections::HashMap;
 use std::sync::atomic::{AtomicBool, AtomicUsize};
 use std::sync::Arc;
-use tokio::sync::{mpsc, Mutex, RwLock};
+use tokio::sync::{mpsc, Mutex};
 
 pub struct GlobalVars {
     pub configfile: configs::ConfigFile,
@@ -26,17 +30,21 @@ pub struct GlobalVars {
     pub default_dac_hw: HwInfoDAC,
     pub default_dut_hw: HwInfoDUT,
     pub default_dri_hw: HwInfoDRI,
-    pub broker_client: RwLock<Option<Arc<rumqttc::AsyncClient>>>,
-    pub redis_client: Mutex<Option<redis::aio::ConnectionManager>>,
+    pub brokers: Vec<BrokerConn>, // Um só, o mesmo de onde as telemetrias são lidas
+    pub state_store: Arc<dyn DeviceStateStore>,
     pub to_broker: mpsc::Sender<MsgToBroker>,
+    pub commands: CommandLog,
     pub to_bigquery: mpsc::Sender<SaveToBqEvent>,
+    pub bq_saver: BqSaverHealth,
+    pub to_local_store: mpsc::Sender<SaveToLocalEvent>,
     pub need_update_configs: AtomicBool,
     pub stats: statistics::StatisticsCounters,
 
     pub last_table_create_command_aws: Mutex<Option<std::time::Instant>>,
-    pub last_table_create_command_bq: Mutex<Option<std::time::Instant>>,
+    pub last_table_create_command_bq: Mutex<HashMap<String, std::time::Instant>>, // Por tabela
     pub client_dynamo: Option<DynamoDBClientDiel>,
     pub client_bigquery: Option<BigQueryClient>,
+    pub dead_letters: DeadLetters,
     pub log_info: Mutex<LogInfo>,
     pub tables: TablesConfig,
     pub valid_dev_id_checker: Regex,
@@ -51,6 +59,7 @@ impl GlobalVars {
         GlobalVars,
         mpsc::Receiver<MsgToBroker>,
         mpsc::Receiver<SaveToBqEvent>,
+        mpsc::Receiver<SaveToLocalEvent>,
     ) {
         create_globs(configfile).await
     }
@@ -62,10 +71,13 @@ pub async fn create_globs(
     GlobalVars,
     mpsc::Receiver<MsgToBroker>,
     mpsc::Receiver<SaveToBqEvent>,
+    mpsc::Receiver<SaveToLocalEvent>,
 ) {
     // let (sender_stats, receiver_stats) = mpsc::channel::<StatsEvent>(2000);
     let (sender_fila, receiver_fila) = mpsc::channel::<MsgToBroker>(20000);
     let (sender_bigquery, receiver_bigquery) = crate::lib_bigquery::saver::create_channel();
+    let (sender_local_store, receiver_local_store) =
+        crate::lib_local_store::saver::create_channel();
 
     // Cria o objeto de conexão com o DynamoDB
     let client_dynamo = if let Some(aws_config) = &configfile.aws_config {
@@ -99,22 +111,42 @@ pub async fn create_globs(
         topicError_c: HashMap::new(),
     };
 
+    let brokers = vec![BrokerConn::new(
+        "default",
+        true,
+        configfile.enable_forward_to_broker,
+        configfile.broker_publish_max_attempts,
+    )];
+
+    let state_store = create_state_store(&configfile.state_store);
+    crate::LOG.append_log_tag_msg(
+        "INFO",
+        &format!("Device state store: {}", state_store.store_name()),
+    );
+
+    let dead_letters = DeadLetters::open(
+        &configfile.dead_letter_config,
+        client_dynamo.is_some(),
+        client_bigquery.is_some(),
+    );
+
+    let bq_saver = BqSaverHealth::new(&configfile.bq_saver_config);
+
     let globs = GlobalVars {
         configfile,
         // stats,
         stats: statistics::StatisticsCounters::new(),
         last_table_create_command_aws: Mutex::new(None),
-        last_table_create_command_bq: Mutex::new(None),
+        last_table_create_command_bq: Mutex::new(HashMap::new()),
         client_dynamo,
         client_bigquery,
+        dead_letters,
         tables,
         valid_dev_id_checker: Regex::new(r"^D[A-Z0-9]{2}\d{9}$").expect("ERRO 24"),
         valid_dev_type_checker: Regex::new(r"^D[A-Z0-9]{2}\d").expect("ERRO 24"),
 
         configs_ready: Mutex::new(false),
-        conv_vars: Mutex::new(ConversionVars {
-            devs: HashMap::new(),
-        }),
+        conv_vars: Mutex::new(ConversionVars::new()),
         default_dac_hw: HwInfoDAC {
             isVrf: false,
             calculate_L1_fancoil: Some(false),
@@ -141,15 +173,23 @@ pub async fn create_globs(
             temperature_offset: 0.0,
         },
         default_dri_hw: HwInfoDRI { formulas: None },
-        broker_client: RwLock::new(None),
-        redis_client: Mutex::new(None),
+        brokers,
+        state_store,
         // certs_vld: HashMap::new(),
         to_broker: sender_fila,
+        commands: CommandLog::new(),
         to_bigquery: sender_bigquery,
+        bq_saver,
+        to_local_store: sender_local_store,
         need_update_configs: AtomicBool::new(true),
         log_info: Mutex::new(log_info),
         insercoes_bq_em_curso: AtomicUsize::new(0),
     };
 
-    (globs, receiver_fila, receiver_bigquery)
+    (
+        globs,
+        receiver_fila,
+        receiver_bigquery,
+        receiver_local_store,
+    )
 }
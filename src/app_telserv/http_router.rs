@@ -0,0 +1,26 @@
+use crate::app_br2db::dead_letters;
+use crate::app_relay::http_router::{relay_routes, serve_dispatched, Deferred};
+use crate::lib_http::auth::ApiTokens;
+use crate::lib_http::router::Router;
+use crate::lib_http::types::HttpRequest;
+use crate::GlobalVars;
+use std::sync::{Arc, OnceLock};
+use tokio::net::TcpStream;
+
+static ROUTER: OnceLock<Router<Deferred>> = OnceLock::new();
+
+fn build_router() -> Router<Deferred> {
+    let mut routes = relay_routes();
+    routes.extend(dead_letters::routes());
+    Router::new(ApiTokens::default(), routes)
+}
+
+pub async fn on_http_req(
+    req: HttpRequest,
+    is_internal: bool,
+    socket: TcpStream,
+    globs: Arc<GlobalVars>,
+) {
+    let router = ROUTER.get_or_init(build_router);
+    serve_dispatched(router, req, is_internal, socket, globs).await;
+}
@@ -1,3 +1,4 @@
+use crate::telemetry_payloads::dri::profile;
 use serde_json::Map;
 
 pub fn merge_processed_values(
@@ -88,128 +89,67 @@ fn merge_dut_values(payload_json: &mut serde_json::Value, processed_payload: &se
     }
 }
 
+// Campos dos DRIs que não são convertidos por perfil (VAV/fancoil e medidores de energia)
+const DRI_FIXED_KEYS: [&str; 45] = [
+    "FanStatus",
+    "Fanspeed",
+    "Lock",
+    "Mode",
+    "Setpoint",
+    "TempAmb",
+    "ThermOn",
+    "ValveOn",
+    "demanda",
+    "demanda_ap",
+    "demanda_at",
+    "demanda_med_at",
+    "en_ap_tri",
+    "en_at_tri",
+    "en_re_tri",
+    "erro",
+    "fp",
+    "fp_a",
+    "fp_b",
+    "fp_c",
+    "freq",
+    "i_a",
+    "i_b",
+    "i_c",
+    "pot_ap_a",
+    "pot_ap_b",
+    "pot_ap_c",
+    "pot_ap_tri",
+    "pot_at_a",
+    "pot_at_b",
+    "pot_at_c",
+    "pot_at_tri",
+    "pot_re_a",
+    "pot_re_b",
+    "pot_re_c",
+    "pot_re_tri",
+    "record_date",
+    "v_a",
+    "v_ab",
+    "v_b",
+    "v_bc",
+    "v_c",
+    "v_ca",
+    "v_tri_ll",
+    "v_tri_ln",
+];
+
 fn merge_dri_values(payload_json: &mut serde_json::Value, processed_payload: &serde_json::Value) {
     let mut orig_raw: Map<String, serde_json::Value> = Map::new();
     let payload_obj = payload_json.as_object_mut().unwrap();
     let processed_obj = processed_payload.as_object().unwrap();
 
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "ALM");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "CAPA_T");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "CAPB_T");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "CAP_T");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "CHIL_OCC");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "CHIL_S_S");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "COND_EWT");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "COND_LWT");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "COND_SP");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "COOL_EWT");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "COOL_LWT");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "CPA1_CUR");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "CPA1_DGT");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "CPA1_OP");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "CPA1_TMP");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "CPA2_CUR");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "CPA2_DGT");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "CPA2_OP");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "CPA2_TMP");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "CPB1_CUR");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "CPB1_DGT");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "CPB1_OP");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "CPB1_TMP");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "CPB2_CUR");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "CPB2_DGT");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "CPB2_OP");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "CPB2_TMP");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "CP_A1");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "CP_A2");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "CP_B1");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "CP_B2");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "CTRL_PNT");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "CTRL_TYP");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "DEM_LIM");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "DOP_A1");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "DOP_A2");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "DOP_B1");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "DOP_B2");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "DP_A");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "DP_B");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "EMSTOP");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "EXV_A");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "EXV_B");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "FanStatus");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "Fanspeed");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "HR_CP_A");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "HR_CP_A1");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "HR_CP_A2");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "HR_CP_B");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "HR_CP_B1");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "HR_CP_B2");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "HR_MACH");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "HR_MACH_B");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "LAG_LIM");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "Lock");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "Mode");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "OAT");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "OP_A");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "OP_B");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "SCT_A");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "SCT_B");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "SLC_HM");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "SLT_A");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "SLT_B");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "SP");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "SP_A");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "SP_B");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "SP_OCC");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "SST_A");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "SST_B");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "STATUS");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "Setpoint");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "TempAmb");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "ThermOn");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "ValveOn");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "alarm_1");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "alarm_2");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "alarm_3");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "alarm_4");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "alarm_5");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "demanda");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "demanda_ap");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "demanda_at");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "demanda_med_at");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "en_ap_tri");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "en_at_tri");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "en_re_tri");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "erro");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "fp");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "fp_a");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "fp_b");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "fp_c");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "freq");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "i_a");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "i_b");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "i_c");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "pot_ap_a");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "pot_ap_b");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "pot_ap_c");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "pot_ap_tri");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "pot_at_a");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "pot_at_b");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "pot_at_c");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "pot_at_tri");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "pot_re_a");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "pot_re_b");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "pot_re_c");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "pot_re_tri");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "record_date");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "v_a");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "v_ab");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "v_b");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "v_bc");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "v_c");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "v_ca");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "v_tri_ll");
-    put_calculated(payload_obj, &mut orig_raw, processed_obj, "v_tri_ln");
+    for property in DRI_FIXED_KEYS {
+        put_calculated(payload_obj, &mut orig_raw, processed_obj, property);
+    }
+    // Campos dos modelos descritos por perfil (chillers, VRFs...)
+    for property in profile::registry().all_field_names() {
+        put_calculated(payload_obj, &mut orig_raw, processed_obj, property);
+    }
 
     if !orig_raw.is_empty() {
         payload_obj.insert("orig_raw".to_owned(), serde_json::Value::Object(orig_raw));
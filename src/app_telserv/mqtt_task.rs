@@ -41,7 +41,7 @@ pub async fn connect_to_mqtt_broker(globs: &Arc<GlobalVars>) -> Result<rumqttc::
     }
 
     {
-        *(globs.broker_client.write().await) = Some(Arc::new(client_mqtt));
+        *(globs.brokers[0].client.write().await) = Some(Arc::new(client_mqtt));
     }
 
     // Just loop on incoming messages.
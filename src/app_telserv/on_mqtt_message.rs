@@ -1,10 +1,13 @@
 use super::merge_calculated_values::merge_processed_values;
+use crate::app_relay::dash_update;
+use crate::app_relay::device_commands;
 use crate::app_relay::on_mqtt_message::check_and_forward_payload;
 use crate::app_relay::on_mqtt_message::parse_packet;
 use crate::app_relay::payload_conversions::convert_control_payload;
 use crate::app_relay::payload_conversions::convert_data_payload;
 use crate::save_to_bigquery;
 use crate::save_to_dynamodb;
+use crate::save_to_local_files;
 use crate::GlobalVars;
 use chrono::NaiveDateTime;
 use std::sync::atomic::Ordering;
@@ -130,6 +133,11 @@ async fn process_payload_on_data(
         }
     }
 
+    // Assim como no DynamoDB, os arquivos locais guardam o payload sem os cálculos do iotrelay
+    if globs.configfile.local_store_config.is_some() && topic.starts_with("data/") {
+        save_to_local_files::save_telemetry_to_local_files(topic, &payload_json, &globs).await;
+    }
+
     // Tratamento do iotrelay feito para o tempo real
     let processed_payload;
     if configs_ready {
@@ -169,6 +177,9 @@ fn process_payload_on_control(globs: &Arc<GlobalVars>, packet: rumqttc::Publish)
         return;
     };
 
+    // Respostas de comandos enviados pelo /send-command
+    device_commands::on_control_message(globs, &dev_id, &payload_json);
+
     // Tratamento do iotrelay feito para o tempo real
     let processing_result =
         convert_control_payload(payload_json.clone(), topic, payload_str, &dev_id, &globs);
@@ -183,12 +194,9 @@ fn process_payload_on_control(globs: &Arc<GlobalVars>, packet: rumqttc::Publish)
 }
 
 fn process_payload_on_others(globs: &Arc<GlobalVars>, packet: rumqttc::Publish) {
-    let topic = &packet.topic;
-    if topic == "apiserver/hwcfg-change" {
-        // Houve mudança de config de hardware, marca o booleano para solicitar versão atualizada
-        globs.need_update_configs.store(true, Ordering::Relaxed);
-    } else {
-        // Ignorar, tópico desconhecido
-        println!("ERROR89: Ignoring unknown topic: {}", topic);
-    }
+    let globs = globs.clone();
+    tokio::spawn(async move {
+        let payload_str = String::from_utf8_lossy(&packet.payload);
+        dash_update::on_apiserver_message(&globs, &packet.topic, &payload_str).await;
+    });
 }
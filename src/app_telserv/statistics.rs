@@ -1,6 +1,6 @@
-use crate::app_relay::statistics::MessageSizeCounters;
+use crate::app_relay::statistics::{gerar_dev_msgsz_vec, write_msgsz_metrics, MessageSizeCounters};
+use crate::lib_metrics::{queue_depth, CounterDeltas, MetricsText};
 use crate::GlobalVars;
-use serde_json::json;
 use std::io::Write;
 use std::str::FromStr;
 use std::sync::atomic::AtomicUsize;
@@ -20,6 +20,8 @@ pub struct StatisticsCounters {
     pub payloads_discarded: AtomicUsize,
     pub payloads_with_insert_error: AtomicUsize,
     pub bq_rows_inserted: AtomicUsize,
+    pub local_saved_telemetry: AtomicUsize,
+    pub local_store_error: AtomicUsize,
     pub mqtt_recv: AtomicUsize,
     pub http_reqs: AtomicUsize,
 
@@ -53,6 +55,8 @@ impl StatisticsCounters {
             payloads_discarded: AtomicUsize::new(0),
             payloads_with_insert_error: AtomicUsize::new(0),
             bq_rows_inserted: AtomicUsize::new(0),
+            local_saved_telemetry: AtomicUsize::new(0),
+            local_store_error: AtomicUsize::new(0),
 
             fwbroker_sent: AtomicUsize::new(0),
             fwbroker_error: AtomicUsize::new(0),
@@ -73,10 +77,11 @@ pub async fn run_service(globs: Arc<GlobalVars>) {
     // "127.0.0.1:8080"
     const INTERVAL: u64 = 120; // em segundos
     let mut ts_start = std::time::Instant::now();
+    let mut deltas = CounterDeltas::default();
 
     loop {
         let elapsed = ts_start.elapsed().as_secs();
-        montar_e_enviar_estatisticas(elapsed, &globs);
+        montar_e_enviar_estatisticas(elapsed, &globs, &mut deltas);
         ts_start = std::time::Instant::now();
         tokio::time::sleep(std::time::Duration::from_secs(INTERVAL)).await;
     }
@@ -103,14 +108,15 @@ pub async fn run_service(globs: Arc<GlobalVars>) {
 // 	}
 // }
 
-fn montar_e_enviar_estatisticas(elapsed: u64, globs: &Arc<GlobalVars>) {
+fn montar_e_enviar_estatisticas(elapsed: u64, globs: &Arc<GlobalVars>, deltas: &mut CounterDeltas) {
     let stats = &globs.stats;
-    let saved_telemetry = get_reset_atomic_usize(&stats.saved_telemetry);
-    let bigquery_insertions = get_reset_atomic_usize(&stats.bigquery_insertions);
+    let saved_telemetry = deltas.delta("saved_telemetry", &stats.saved_telemetry);
+    let bigquery_insertions = deltas.delta("bigquery_insertions", &stats.bigquery_insertions);
+    let local_saved_telemetry = deltas.delta("local_saved_telemetry", &stats.local_saved_telemetry);
 
     {
         let mut time_nosave_s = stats.time_nosave_s.load(Ordering::Relaxed);
-        if saved_telemetry == 0 && bigquery_insertions == 0 {
+        if saved_telemetry == 0 && bigquery_insertions == 0 && local_saved_telemetry == 0 {
             if time_nosave_s < 100_000 {
                 time_nosave_s += elapsed as usize;
             }
@@ -128,31 +134,34 @@ fn montar_e_enviar_estatisticas(elapsed: u64, globs: &Arc<GlobalVars>) {
         "origin": "telserv-v2",
         "interval": elapsed,
 
-        "unknown_topic": get_reset_atomic_usize(&stats.unknown_topic),
-        "dev_id_missing": get_reset_atomic_usize(&stats.dev_id_missing),
-        "dynamodb_error": get_reset_atomic_usize(&stats.dynamodb_error),
+        "unknown_topic": deltas.delta("unknown_topic", &stats.unknown_topic),
+        "dev_id_missing": deltas.delta("dev_id_missing", &stats.dev_id_missing),
+        "dynamodb_error": deltas.delta("dynamodb_error", &stats.dynamodb_error),
         "aws_saved_telemetry": saved_telemetry,
-        "aws_saved_control": get_reset_atomic_usize(&stats.saved_control),
-        "aws_saved_command": get_reset_atomic_usize(&stats.saved_command),
+        "aws_saved_control": deltas.delta("saved_control", &stats.saved_control),
+        "aws_saved_command": deltas.delta("saved_command", &stats.saved_command),
         "bigquery_insertions": bigquery_insertions,
-        "payloads_received": get_reset_atomic_usize(&stats.payloads_received),
-        "payloads_discarded": get_reset_atomic_usize(&stats.payloads_discarded),
-        "payloads_with_insert_error": get_reset_atomic_usize(&stats.payloads_with_insert_error),
-        "bq_rows_inserted": get_reset_atomic_usize(&stats.bq_rows_inserted),
-
-        "http_reqs": get_reset_atomic_usize(&globs.stats.http_reqs),
-        "mqtt_recv": get_reset_atomic_usize(&globs.stats.mqtt_recv),
-        "topic_data": get_reset_atomic_usize(&globs.stats.topic_data),
-        "topic_ctrl": get_reset_atomic_usize(&globs.stats.topic_ctrl),
-        "fwbroker_sent": get_reset_atomic_usize(&globs.stats.fwbroker_sent),
-        "fwbroker_error": get_reset_atomic_usize(&globs.stats.fwbroker_error),
-
-        "msgsz_dac": gerar_dev_msgsz_vec(&globs.stats.msgsz_dac),
-        "msgsz_dut": gerar_dev_msgsz_vec(&globs.stats.msgsz_dut),
-        "msgsz_dam": gerar_dev_msgsz_vec(&globs.stats.msgsz_dam),
-        "msgsz_dma": gerar_dev_msgsz_vec(&globs.stats.msgsz_dma),
-        "msgsz_dmt": gerar_dev_msgsz_vec(&globs.stats.msgsz_dmt),
-        "msgsz_dal": gerar_dev_msgsz_vec(&globs.stats.msgsz_dal),
+        "payloads_received": deltas.delta("payloads_received", &stats.payloads_received),
+        "payloads_discarded": deltas.delta("payloads_discarded", &stats.payloads_discarded),
+        "payloads_with_insert_error": deltas.delta("payloads_with_insert_error", &stats.payloads_with_insert_error),
+        "bq_rows_inserted": deltas.delta("bq_rows_inserted", &stats.bq_rows_inserted),
+        "local_saved_telemetry": local_saved_telemetry,
+        "local_store_error": deltas.delta("local_store_error", &stats.local_store_error),
+        "bq_saver": globs.bq_saver.stats_json(deltas),
+
+        "http_reqs": deltas.delta("http_reqs", &stats.http_reqs),
+        "mqtt_recv": deltas.delta("mqtt_recv", &stats.mqtt_recv),
+        "topic_data": deltas.delta("topic_data", &stats.topic_data),
+        "topic_ctrl": deltas.delta("topic_ctrl", &stats.topic_ctrl),
+        "fwbroker_sent": deltas.delta("fwbroker_sent", &stats.fwbroker_sent),
+        "fwbroker_error": deltas.delta("fwbroker_error", &stats.fwbroker_error),
+
+        "msgsz_dac": gerar_dev_msgsz_vec("dac", &stats.msgsz_dac, deltas),
+        "msgsz_dut": gerar_dev_msgsz_vec("dut", &stats.msgsz_dut, deltas),
+        "msgsz_dam": gerar_dev_msgsz_vec("dam", &stats.msgsz_dam, deltas),
+        "msgsz_dma": gerar_dev_msgsz_vec("dma", &stats.msgsz_dma, deltas),
+        "msgsz_dmt": gerar_dev_msgsz_vec("dmt", &stats.msgsz_dmt, deltas),
+        "msgsz_dal": gerar_dev_msgsz_vec("dal", &stats.msgsz_dal, deltas),
     });
 
     let payload = message.to_string();
@@ -167,41 +176,129 @@ fn montar_e_enviar_estatisticas(elapsed: u64, globs: &Arc<GlobalVars>) {
     // });
 }
 
-fn gerar_dev_msgsz_vec(msgsz_dev: &MessageSizeCounters) -> serde_json::Value {
-    let data_bytes = msgsz_dev.data_bytes.load(Ordering::Relaxed);
-    let data_count = msgsz_dev.data_count.load(Ordering::Relaxed);
-    let data_max = msgsz_dev.data_max.load(Ordering::Relaxed);
-    let ctrl_bytes = msgsz_dev.ctrl_bytes.load(Ordering::Relaxed);
-    let ctrl_count = msgsz_dev.ctrl_count.load(Ordering::Relaxed);
-    let ctrl_max = msgsz_dev.ctrl_max.load(Ordering::Relaxed);
-
-    msgsz_dev
-        .data_bytes
-        .fetch_sub(data_bytes, Ordering::Relaxed);
-    msgsz_dev
-        .data_count
-        .fetch_sub(data_count, Ordering::Relaxed);
-    msgsz_dev.data_max.store(0, Ordering::Relaxed);
-    msgsz_dev
-        .ctrl_bytes
-        .fetch_sub(ctrl_bytes, Ordering::Relaxed);
-    msgsz_dev
-        .ctrl_count
-        .fetch_sub(ctrl_count, Ordering::Relaxed);
-    msgsz_dev.ctrl_max.store(0, Ordering::Relaxed);
-
-    return json!({
-        "data_bytes": data_bytes,
-        "data_count": data_count,
-        "data_max": data_max,
-        "ctrl_bytes": ctrl_bytes,
-        "ctrl_count": ctrl_count,
-        "ctrl_max": ctrl_max,
-    });
-}
-
-fn get_reset_atomic_usize(atomic_value: &AtomicUsize) -> usize {
-    let value = atomic_value.load(Ordering::Relaxed);
-    atomic_value.fetch_sub(value, Ordering::Relaxed);
-    value
+pub fn write_metrics(globs: &Arc<GlobalVars>, out: &mut MetricsText) {
+    let stats = &globs.stats;
+    let counter = |x: &AtomicUsize| x.load(Ordering::Relaxed);
+    out.counter(
+        "unknown_topic_total",
+        "Payloads com tópico desconhecido",
+        counter(&stats.unknown_topic),
+    );
+    out.counter(
+        "dev_id_missing_total",
+        "Payloads sem dev_id",
+        counter(&stats.dev_id_missing),
+    );
+    out.counter(
+        "dynamodb_error_total",
+        "Erros ao salvar no DynamoDB",
+        counter(&stats.dynamodb_error),
+    );
+    out.counter(
+        "aws_saved_telemetry_total",
+        "Telemetrias salvas no DynamoDB",
+        counter(&stats.saved_telemetry),
+    );
+    out.counter(
+        "aws_saved_control_total",
+        "Mensagens de controle salvas no DynamoDB",
+        counter(&stats.saved_control),
+    );
+    out.counter(
+        "aws_saved_command_total",
+        "Comandos salvos no DynamoDB",
+        counter(&stats.saved_command),
+    );
+    out.counter(
+        "bigquery_insertions_total",
+        "Inserções feitas no BigQuery",
+        counter(&stats.bigquery_insertions),
+    );
+    out.counter(
+        "payloads_received_total",
+        "Payloads recebidos",
+        counter(&stats.payloads_received),
+    );
+    out.counter(
+        "payloads_discarded_total",
+        "Payloads descartados",
+        counter(&stats.payloads_discarded),
+    );
+    out.counter(
+        "payloads_with_insert_error_total",
+        "Payloads com erro de inserção no BigQuery",
+        counter(&stats.payloads_with_insert_error),
+    );
+    out.counter(
+        "bq_rows_inserted_total",
+        "Linhas inseridas no BigQuery",
+        counter(&stats.bq_rows_inserted),
+    );
+    out.counter(
+        "local_saved_telemetry_total",
+        "Telemetrias salvas em arquivos locais",
+        counter(&stats.local_saved_telemetry),
+    );
+    out.counter(
+        "local_store_error_total",
+        "Erros ao salvar em arquivos locais",
+        counter(&stats.local_store_error),
+    );
+    out.counter(
+        "http_reqs_total",
+        "Requisições HTTP recebidas",
+        counter(&stats.http_reqs),
+    );
+    out.counter(
+        "mqtt_recv_total",
+        "Mensagens MQTT recebidas",
+        counter(&stats.mqtt_recv),
+    );
+    out.counter(
+        "topic_data_total",
+        "Mensagens recebidas em tópicos data/",
+        counter(&stats.topic_data),
+    );
+    out.counter(
+        "topic_ctrl_total",
+        "Mensagens recebidas em tópicos de controle",
+        counter(&stats.topic_ctrl),
+    );
+    out.counter(
+        "fwbroker_sent_total",
+        "Mensagens encaminhadas ao broker",
+        counter(&stats.fwbroker_sent),
+    );
+    out.counter(
+        "fwbroker_error_total",
+        "Erros ao encaminhar mensagens ao broker",
+        counter(&stats.fwbroker_error),
+    );
+    write_msgsz_metrics(
+        out,
+        &[
+            ("dac", &stats.msgsz_dac),
+            ("dut", &stats.msgsz_dut),
+            ("dam", &stats.msgsz_dam),
+            ("dma", &stats.msgsz_dma),
+            ("dmt", &stats.msgsz_dmt),
+            ("dal", &stats.msgsz_dal),
+        ],
+    );
+    out.gauge(
+        "to_broker_queue_depth",
+        "Mensagens aguardando envio ao broker",
+        queue_depth(&globs.to_broker),
+    );
+    out.gauge(
+        "to_bigquery_queue_depth",
+        "Eventos aguardando o saver do BigQuery",
+        queue_depth(&globs.to_bigquery),
+    );
+    out.gauge(
+        "to_local_store_queue_depth",
+        "Linhas aguardando gravação em arquivos locais",
+        queue_depth(&globs.to_local_store),
+    );
+    globs.bq_saver.write_metrics(out);
 }
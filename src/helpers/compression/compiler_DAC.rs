@@ -8,6 +8,8 @@ use std::iter::FromIterator;
 use std::str::FromStr;
 
 use super::compiler_common::SingleVariableCompilerBuilder;
+use super::compiler_DAC_faults::DACFaultsCompiler;
+use crate::dac_faults::rules::FaultSample;
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct DACTelemetryCompiler {
@@ -41,6 +43,7 @@ pub struct DACTelemetryCompiler {
     pub v_mode: SingleVariableCompiler,
     pub v_saved_data: SingleVariableCompiler,
     pub first_saved_data_index: Option<isize>,
+    pub v_faults: DACFaultsCompiler,
 }
 
 impl DACTelemetryCompiler {
@@ -102,6 +105,7 @@ impl DACTelemetryCompiler {
             v_mode: SingleVariableCompiler::create(),
             v_saved_data: SingleVariableCompiler::create(),
             first_saved_data_index: None,
+            v_faults: DACFaultsCompiler::new(cfg),
         };
     }
 
@@ -204,6 +208,18 @@ impl DACTelemetryCompiler {
             self.v_tsc.adc_ponto_float(index, calcs.Tsc, sampling_time);
             self.v_tsh.adc_ponto_float(index, calcs.Tsh, sampling_time);
         }
+        let fault_sample = FaultSample {
+            ts: index as i64,
+            Lcmp: telemetry.Lcmp,
+            Tamb: telemetry.Tamb,
+            Tsuc: telemetry.Tsuc,
+            Tliq: telemetry.Tliq,
+            Psuc: telemetry.Psuc,
+            Pliq: telemetry.Pliq,
+            Tsh: calcs.as_ref().and_then(|calcs| calcs.Tsh),
+            Tsc: calcs.as_ref().and_then(|calcs| calcs.Tsc),
+        };
+        self.v_faults.on_telemetry(&fault_sample, index, sampling_time);
 
         if self.first_saved_data_index.is_none() && telemetry.saved_data.unwrap_or(false) {
             self.first_saved_data_index = Some(index);
@@ -238,6 +254,7 @@ impl DACTelemetryCompiler {
         let vecState = self.v_state.fechar_vetor_completo(periodLength);
         let vecMode = self.v_mode.fechar_vetor_completo(periodLength);
         let vecSaveData = self.v_saved_data.fechar_vetor_completo(periodLength);
+        let faults = self.v_faults.close_period(periodLength);
         let (numDeparts, hoursOn, hoursOff, iStartLcmp, iEndLcmp) =
             CalcularEstatisticasUso(&vecLcmp);
         let (_, hoursLevp, _, _, _) = CalcularEstatisticasUso(&vecLevp);
@@ -277,6 +294,7 @@ impl DACTelemetryCompiler {
                 None
             },
             first_saved_data_index: self.first_saved_data_index,
+            faults,
         }));
     }
 }
@@ -364,5 +382,6 @@ pub struct CompiledPeriod {
     pub startLcmp: Option<usize>,
     pub endLcmp: Option<usize>,
     pub savedData: String,
-    pub first_saved_data_index: Option<isize>
+    pub first_saved_data_index: Option<isize>,
+    pub faults: HashMap<String, String>,
 }
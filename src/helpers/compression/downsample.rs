@@ -0,0 +1,167 @@
+/*
+Reamostragem dos vetores compilados ("valor*quantidade,...") para uma resolução menor.
+Os vetores de vários períodos são passados em sequência e o resultado é uma série só, com um ponto a cada
+`step` amostras. Funciona por streaming para não precisar guardar os vetores completos de vários dias.
+*/
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Aggregation {
+    // Média ponderada pelo tempo (temperaturas, pressões)
+    Mean,
+    // Valor que ficou mais tempo no intervalo (L1, State, Mode)
+    Majority,
+}
+
+pub struct Downsampler {
+    aggregation: Aggregation,
+    step: isize,
+    // Amostras que ainda serão ignoradas antes do início da série
+    skip: isize,
+    // Amostras que ainda cabem na série
+    remaining: isize,
+    // Amostras já acumuladas no ponto atual
+    filled: isize,
+    sum: f64,
+    sum_count: isize,
+    counts: Vec<(String, isize)>,
+    out: Vec<(String, isize)>,
+}
+
+impl Downsampler {
+    pub fn new(aggregation: Aggregation, step: isize, skip: isize, length: isize) -> Downsampler {
+        Downsampler {
+            aggregation,
+            step: step.max(1),
+            skip: skip.max(0),
+            remaining: length.max(0),
+            filled: 0,
+            sum: 0.0,
+            sum_count: 0,
+            counts: Vec::new(),
+            out: Vec::new(),
+        }
+    }
+
+    // Acrescenta um vetor compilado de `period_length` amostras. Se o vetor for menor é completado com vazio.
+    pub fn push_vector(&mut self, vec: &str, period_length: isize) {
+        let mut pushed = 0;
+        for (value, count) in parse_compiled_vector(vec) {
+            let count = count.min(period_length - pushed);
+            if count <= 0 {
+                break;
+            }
+            self.push_run(value, count);
+            pushed += count;
+        }
+        if pushed < period_length {
+            self.push_run("", period_length - pushed);
+        }
+    }
+
+    pub fn push_run(&mut self, value: &str, count: isize) {
+        let skipped = count.min(self.skip);
+        self.skip -= skipped;
+        let mut count = (count - skipped).min(self.remaining);
+        while count > 0 {
+            let n = count.min(self.step - self.filled);
+            self.accumulate(value, n);
+            self.filled += n;
+            self.remaining -= n;
+            count -= n;
+            if self.filled == self.step {
+                self.close_point();
+            }
+        }
+    }
+
+    pub fn finish(mut self) -> String {
+        if self.filled > 0 {
+            self.close_point();
+        }
+        self.out
+            .into_iter()
+            .map(|(val, count)| match count {
+                1isize => val,
+                c => format!("{val}*{c}"),
+            })
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    fn accumulate(&mut self, value: &str, n: isize) {
+        if value.is_empty() {
+            return;
+        }
+        match self.aggregation {
+            Aggregation::Mean => {
+                if let Ok(v) = value.parse::<f64>() {
+                    self.sum += v * (n as f64);
+                    self.sum_count += n;
+                }
+            }
+            Aggregation::Majority => match self.counts.iter_mut().find(|(v, _)| v == value) {
+                Some(entry) => entry.1 += n,
+                None => self.counts.push((value.to_owned(), n)),
+            },
+        }
+    }
+
+    fn close_point(&mut self) {
+        let value = match self.aggregation {
+            Aggregation::Mean if self.sum_count > 0 => {
+                let mean = self.sum / (self.sum_count as f64);
+                ((mean * 100.0).round() / 100.0).to_string()
+            }
+            Aggregation::Mean => String::new(),
+            Aggregation::Majority => {
+                let mut best: Option<(String, isize)> = None;
+                for (v, c) in self.counts.drain(..) {
+                    if best.as_ref().map_or(true, |(_, best_c)| c > *best_c) {
+                        best = Some((v, c));
+                    }
+                }
+                best.map(|(v, _)| v).unwrap_or_default()
+            }
+        };
+        match self.out.last_mut() {
+            Some((last, count)) if *last == value => *count += 1,
+            _ => self.out.push((value, 1)),
+        }
+        self.filled = 0;
+        self.sum = 0.0;
+        self.sum_count = 0;
+        self.counts.clear();
+    }
+}
+
+// "23.5*10,,24*3" => [("23.5", 10), ("", 1), ("24", 3)]
+pub fn parse_compiled_vector(vec: &str) -> Vec<(&str, isize)> {
+    if vec.is_empty() {
+        return Vec::new();
+    }
+    vec.split(',')
+        .map(|item| match item.rsplit_once('*') {
+            Some((value, count)) => (value, count.parse::<isize>().unwrap_or(1)),
+            None => (item, 1),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Aggregation, Downsampler};
+
+    #[test]
+    fn test_downsample_across_periods() {
+        // Dois períodos de 10 amostras, ignorando as 2 primeiras e pegando 16, com 4 amostras por ponto
+        let mut l1 = Downsampler::new(Aggregation::Majority, 4, 2, 16);
+        l1.push_vector("0*5,1*5", 10);
+        l1.push_vector("1*3", 10);
+        assert_eq!(l1.finish(), "0,1*2,");
+
+        let mut temp = Downsampler::new(Aggregation::Mean, 5, 0, 20);
+        temp.push_vector("20*5,21*3,*2", 10);
+        temp.push_vector("22.5*10", 10);
+        assert_eq!(temp.finish(), "20,21,22.5*2");
+    }
+}
@@ -0,0 +1,253 @@
+use super::rules::{
+    CompressorState, DacFaultRule, FaultSample, FaultSensor, SensorStuckRule, ShortCyclingRule,
+    SubcoolingRule, SuctionPressureRule, SuperheatRule,
+};
+use crate::telemetry_payloads::dac_telemetry::HwInfoDAC;
+use crate::telemetry_payloads::dac_tsh_tsc::FluidInterpData;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// Intervalo sem telemetrias a partir do qual as regras recomeçam do zero
+const MAX_GAP_S: i64 = 600;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct FaultState {
+    active: bool,
+    // Desde quando a condição está diferente do estado atual
+    changing_since: Option<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FaultChange {
+    pub fault_id: String,
+    pub active: bool,
+    pub ts: i64,
+}
+
+/** Regras aplicáveis à configuração do DAC e o estado de cada falha. Serializável para o cache e o Redis. */
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DacFaultsChecker {
+    short_cycling: ShortCyclingRule,
+    high_superheat: Option<SuperheatRule>,
+    low_superheat: Option<SuperheatRule>,
+    subcooling: Option<SubcoolingRule>,
+    suction_pressure: Option<SuctionPressureRule>,
+    sensor_stuck: Vec<SensorStuckRule>,
+    states: BTreeMap<String, FaultState>,
+    compressor: CompressorState,
+    last_ts: Option<i64>,
+}
+
+impl DacFaultsChecker {
+    pub fn new(cfg: &HwInfoDAC) -> Self {
+        let has_Psuc = cfg.P0Psuc || cfg.P1Psuc;
+        let has_Pliq = cfg.P0Pliq || cfg.P1Pliq;
+        let has_fluid = cfg
+            .fluid
+            .as_ref()
+            .is_some_and(|fluid| FluidInterpData::for_fluid(fluid).is_some());
+
+        let mut sensors = vec![FaultSensor::Tamb, FaultSensor::Tsuc, FaultSensor::Tliq];
+        if has_Psuc {
+            sensors.push(FaultSensor::Psuc);
+        }
+        if has_Pliq {
+            sensors.push(FaultSensor::Pliq);
+        }
+
+        let mut checker = DacFaultsChecker {
+            short_cycling: ShortCyclingRule::new(6),
+            high_superheat: (has_Psuc && has_fluid).then_some(SuperheatRule {
+                high: true,
+                limit: 15.0,
+            }),
+            low_superheat: (has_Psuc && has_fluid).then_some(SuperheatRule {
+                high: false,
+                limit: 2.0,
+            }),
+            subcooling: (has_Pliq && has_fluid).then_some(SubcoolingRule {
+                min: 1.0,
+                max: 15.0,
+            }),
+            suction_pressure: has_Psuc.then_some(SuctionPressureRule {
+                min_psuc: 0.5,
+                min_tevap: -15.0,
+                max_tevap: 15.0,
+            }),
+            sensor_stuck: sensors
+                .into_iter()
+                .map(|sensor| SensorStuckRule::new(sensor, 2 * 3600))
+                .collect(),
+            states: BTreeMap::new(),
+            compressor: CompressorState::default(),
+            last_ts: None,
+        };
+        let fault_ids: Vec<String> = checker
+            .rules_mut()
+            .iter()
+            .map(|rule| rule.fault_id().to_owned())
+            .collect();
+        for fault_id in fault_ids {
+            checker.states.insert(fault_id, FaultState::default());
+        }
+        checker
+    }
+
+    fn rules_mut(&mut self) -> Vec<&mut dyn DacFaultRule> {
+        let mut rules: Vec<&mut dyn DacFaultRule> = vec![&mut self.short_cycling];
+        if let Some(rule) = &mut self.high_superheat {
+            rules.push(rule);
+        }
+        if let Some(rule) = &mut self.low_superheat {
+            rules.push(rule);
+        }
+        if let Some(rule) = &mut self.subcooling {
+            rules.push(rule);
+        }
+        if let Some(rule) = &mut self.suction_pressure {
+            rules.push(rule);
+        }
+        for rule in &mut self.sensor_stuck {
+            rules.push(rule);
+        }
+        rules
+    }
+
+    /** Processa uma amostra e retorna as falhas que mudaram de estado. Amostras fora de ordem são ignoradas. */
+    pub fn on_sample(&mut self, sample: &FaultSample) -> Vec<FaultChange> {
+        if self.last_ts.is_some_and(|last_ts| sample.ts <= last_ts) {
+            return Vec::new();
+        }
+        let gap = self
+            .last_ts
+            .is_some_and(|last_ts| sample.ts - last_ts > MAX_GAP_S);
+        self.last_ts = Some(sample.ts);
+
+        if gap {
+            self.compressor = CompressorState::default();
+            for state in self.states.values_mut() {
+                state.changing_since = None;
+            }
+        }
+        match sample.Lcmp {
+            Some(true) if self.compressor.on_since.is_none() => {
+                self.compressor.on_since = Some(sample.ts);
+                self.compressor.last_change = Some(sample.ts);
+            }
+            Some(false) if self.compressor.on_since.is_some() => {
+                self.compressor.on_since = None;
+                self.compressor.last_change = Some(sample.ts);
+            }
+            _ => {}
+        }
+
+        let compressor = self.compressor.clone();
+        let mut results = Vec::new();
+        for rule in self.rules_mut() {
+            if gap {
+                rule.reset();
+            }
+            let condition = rule.check(sample, &compressor);
+            results.push((rule.fault_id().to_owned(), rule.min_duration_s(), condition));
+        }
+
+        let mut changes = Vec::new();
+        for (fault_id, min_duration_s, condition) in results {
+            let state = self.states.entry(fault_id.clone()).or_default();
+            let Some(condition) = condition else {
+                // Sem dados: mantém o estado, mas a contagem de tempo recomeça
+                state.changing_since = None;
+                continue;
+            };
+            if condition == state.active {
+                state.changing_since = None;
+                continue;
+            }
+            let since = *state.changing_since.get_or_insert(sample.ts);
+            if sample.ts - since >= min_duration_s {
+                state.active = condition;
+                state.changing_since = None;
+                changes.push(FaultChange {
+                    fault_id,
+                    active: condition,
+                    ts: sample.ts,
+                });
+            }
+        }
+        changes
+    }
+
+    /** Todas as falhas avaliadas para esta configuração, com o estado atual */
+    pub fn fault_states(&self) -> impl Iterator<Item = (&str, bool)> {
+        self.states
+            .iter()
+            .map(|(fault_id, state)| (fault_id.as_str(), state.active))
+    }
+
+    pub fn active_faults(&self) -> Vec<&str> {
+        self.fault_states()
+            .filter(|(_, active)| *active)
+            .map(|(fault_id, _)| fault_id)
+            .collect()
+    }
+}
+
+#[test]
+fn test_dac_faults_checker() {
+    let cfg: HwInfoDAC = serde_json::from_value(serde_json::json!({
+        "isVrf": false, "calculate_L1_fancoil": null, "debug_L1_fancoil": null, "hasAutomation": false,
+        "P0Psuc": true, "P1Psuc": false, "P0Pliq": false, "P1Pliq": true,
+        "P0multQuad": 0.0, "P1multQuad": 0.0, "P0multLin": 1.0, "P1multLin": 1.0, "P0ofst": 0.0, "P1ofst": 0.0,
+        "fluid": "r410a", "t_cfg": null, "simulateL1": false, "l1_psuc_offset": 0.0, "DAC_APPL": null, "DAC_TYPE": null,
+    }))
+    .unwrap();
+    let mut checker = DacFaultsChecker::new(&cfg);
+    let ids: Vec<&str> = checker.fault_states().map(|(id, _)| id).collect();
+    assert!(ids.contains(&"high_superheat"));
+    assert!(ids.contains(&"sensor_stuck_Pliq"));
+
+    let sample = |ts: i64, lcmp: bool, tsh: f64| FaultSample {
+        ts,
+        Lcmp: Some(lcmp),
+        Tamb: Some(30.0 + (ts % 7) as f64 / 10.0),
+        Tsuc: Some(12.0 + (ts / 15 % 5) as f64 / 10.0),
+        Tliq: Some(40.0 + (ts / 15 % 3) as f64 / 10.0),
+        Psuc: Some(8.5 + (ts % 11) as f64 / 100.0),
+        Pliq: Some(25.0),
+        Tsh: Some(tsh),
+        Tsc: Some(5.0),
+    };
+
+    // Superaquecimento alto só depois de 5 minutos de compressor estável mais 15 minutos com a condição
+    let mut changes = Vec::new();
+    for i in 0..200 {
+        changes.extend(checker.on_sample(&sample(i * 15, true, 20.0)));
+    }
+    assert_eq!(
+        changes,
+        vec![FaultChange {
+            fault_id: "high_superheat".to_owned(),
+            active: true,
+            ts: 300 + 900,
+        }]
+    );
+    assert_eq!(checker.active_faults(), vec!["high_superheat"]);
+
+    // Liga e desliga a cada 2 minutos: ciclagem curta. Pliq parado por 2h com o compressor mudando: sensor travado.
+    let mut changes = Vec::new();
+    for i in 200..800 {
+        changes.extend(checker.on_sample(&sample(i * 15, (i / 8) % 2 == 0, 8.0)));
+    }
+    let ids: Vec<(&str, bool)> = changes
+        .iter()
+        .map(|c| (c.fault_id.as_str(), c.active))
+        .collect();
+    assert!(ids.contains(&("short_cycling", true)));
+    assert!(ids.contains(&("sensor_stuck_Pliq", true)));
+    assert!(!ids.iter().any(|(id, _)| id.starts_with("sensor_stuck_T")));
+
+    // Depois de um buraco nas telemetrias, a contagem recomeça
+    assert!(checker
+        .on_sample(&sample(800 * 15 + 3600, true, 8.0))
+        .is_empty());
+}
@@ -0,0 +1,9 @@
+/*
+Detecção de falhas do DAC a partir das telemetrias já convertidas (Lcmp, Tsuc, Tliq, Psuc, Pliq, Tsh, Tsc).
+Cada regra implementa DacFaultRule e só diz se a condição de falha está presente na amostra. O DacFaultsChecker
+cuida do tempo: a falha só fica ativa depois da condição se manter por um tempo mínimo, e só volta ao normal depois
+de ficar o mesmo tempo sem a condição.
+Usado pelo rusthist (seção "faults" do histórico do DAC) e pelo iotrelay (publica as mudanças de estado).
+*/
+pub mod checker;
+pub mod rules;
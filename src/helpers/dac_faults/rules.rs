@@ -0,0 +1,280 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+// Tempo com o compressor ligado até o ciclo estabilizar e as regras de superaquecimento/pressão valerem
+pub const STABLE_AFTER_S: i64 = 300;
+
+/** Uma leitura dos sensores do DAC, com o timestamp em segundos */
+#[derive(Debug, Default, Clone)]
+pub struct FaultSample {
+    pub ts: i64,
+    pub Lcmp: Option<bool>,
+    pub Tamb: Option<f64>,
+    pub Tsuc: Option<f64>,
+    pub Tliq: Option<f64>,
+    pub Psuc: Option<f64>,
+    pub Pliq: Option<f64>,
+    pub Tsh: Option<f64>,
+    pub Tsc: Option<f64>,
+}
+
+/** Estado do compressor mantido pelo checker e compartilhado com as regras */
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct CompressorState {
+    pub on_since: Option<i64>,
+    pub last_change: Option<i64>,
+}
+
+impl CompressorState {
+    /** Compressor ligado há pelo menos STABLE_AFTER_S */
+    pub fn is_stable_on(&self, ts: i64) -> bool {
+        self.on_since
+            .is_some_and(|on_since| ts - on_since >= STABLE_AFTER_S)
+    }
+}
+
+pub trait DacFaultRule: Send + Sync {
+    /// Nome da falha, usado no vetor do histórico e nos eventos publicados pelo iotrelay
+    fn fault_id(&self) -> &str;
+    /// Tempo que a condição precisa se manter (ou ficar ausente) para mudar o estado da falha
+    fn min_duration_s(&self) -> i64;
+    /// Some(true) se a condição de falha está presente, None se não tem dados para avaliar
+    fn check(&mut self, sample: &FaultSample, compressor: &CompressorState) -> Option<bool>;
+    /// Chamado quando há um buraco nas telemetrias, para não misturar dados de antes e depois
+    fn reset(&mut self);
+}
+
+/** Muitas partidas do compressor na última hora (mesma contagem do numDeparts do histórico) */
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ShortCyclingRule {
+    pub max_starts_per_hour: usize,
+    starts: VecDeque<i64>,
+    last_lcmp: Option<bool>,
+    // Início da observação, com menos de uma hora de dados só dá para confirmar a falha
+    observing_since: Option<i64>,
+}
+
+impl ShortCyclingRule {
+    pub fn new(max_starts_per_hour: usize) -> Self {
+        ShortCyclingRule {
+            max_starts_per_hour,
+            starts: VecDeque::new(),
+            last_lcmp: None,
+            observing_since: None,
+        }
+    }
+}
+
+impl DacFaultRule for ShortCyclingRule {
+    fn fault_id(&self) -> &str {
+        "short_cycling"
+    }
+    fn min_duration_s(&self) -> i64 {
+        0
+    }
+    fn check(&mut self, sample: &FaultSample, _compressor: &CompressorState) -> Option<bool> {
+        let lcmp = sample.Lcmp?;
+        let observing_since = *self.observing_since.get_or_insert(sample.ts);
+        if lcmp && self.last_lcmp == Some(false) {
+            self.starts.push_back(sample.ts);
+        }
+        self.last_lcmp = Some(lcmp);
+        while self
+            .starts
+            .front()
+            .is_some_and(|start| sample.ts - start > 3600)
+        {
+            self.starts.pop_front();
+        }
+        if self.starts.len() > self.max_starts_per_hour {
+            Some(true)
+        } else if sample.ts - observing_since < 3600 {
+            None
+        } else {
+            Some(false)
+        }
+    }
+    fn reset(&mut self) {
+        self.starts.clear();
+        self.last_lcmp = None;
+        self.observing_since = None;
+    }
+}
+
+/** Superaquecimento (Tsh) acima ou abaixo do limite com o compressor ligado e estável */
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SuperheatRule {
+    pub high: bool,
+    pub limit: f64,
+}
+
+impl DacFaultRule for SuperheatRule {
+    fn fault_id(&self) -> &str {
+        if self.high {
+            "high_superheat"
+        } else {
+            "low_superheat"
+        }
+    }
+    fn min_duration_s(&self) -> i64 {
+        if self.high {
+            900
+        } else {
+            600
+        }
+    }
+    fn check(&mut self, sample: &FaultSample, compressor: &CompressorState) -> Option<bool> {
+        if !compressor.is_stable_on(sample.ts) {
+            return None;
+        }
+        let tsh = sample.Tsh?;
+        Some(if self.high {
+            tsh > self.limit
+        } else {
+            tsh < self.limit
+        })
+    }
+    fn reset(&mut self) {}
+}
+
+/** Subresfriamento (Tsc) fora da faixa com o compressor ligado e estável */
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SubcoolingRule {
+    pub min: f64,
+    pub max: f64,
+}
+
+impl DacFaultRule for SubcoolingRule {
+    fn fault_id(&self) -> &str {
+        "subcooling_out_of_range"
+    }
+    fn min_duration_s(&self) -> i64 {
+        900
+    }
+    fn check(&mut self, sample: &FaultSample, compressor: &CompressorState) -> Option<bool> {
+        if !compressor.is_stable_on(sample.ts) {
+            return None;
+        }
+        let tsc = sample.Tsc?;
+        Some(tsc < self.min || tsc > self.max)
+    }
+    fn reset(&mut self) {}
+}
+
+/**
+ * Pressão de sucção anormal com o compressor ligado e estável: muito baixa (vazamento, sensor desconectado) ou,
+ * quando tem o Tsh, com a temperatura de evaporação (Tsuc - Tsh) fora da faixa esperada.
+ */
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SuctionPressureRule {
+    pub min_psuc: f64,
+    pub min_tevap: f64,
+    pub max_tevap: f64,
+}
+
+impl DacFaultRule for SuctionPressureRule {
+    fn fault_id(&self) -> &str {
+        "suction_pressure_anomaly"
+    }
+    fn min_duration_s(&self) -> i64 {
+        600
+    }
+    fn check(&mut self, sample: &FaultSample, compressor: &CompressorState) -> Option<bool> {
+        if !compressor.is_stable_on(sample.ts) {
+            return None;
+        }
+        let psuc = sample.Psuc?;
+        if psuc < self.min_psuc {
+            return Some(true);
+        }
+        match (sample.Tsuc, sample.Tsh) {
+            (Some(tsuc), Some(tsh)) => {
+                let tevap = tsuc - tsh;
+                Some(tevap < self.min_tevap || tevap > self.max_tevap)
+            }
+            _ => Some(false),
+        }
+    }
+    fn reset(&mut self) {}
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum FaultSensor {
+    Tamb,
+    Tsuc,
+    Tliq,
+    Psuc,
+    Pliq,
+}
+
+impl FaultSensor {
+    fn value(&self, sample: &FaultSample) -> Option<f64> {
+        match self {
+            FaultSensor::Tamb => sample.Tamb,
+            FaultSensor::Tsuc => sample.Tsuc,
+            FaultSensor::Tliq => sample.Tliq,
+            FaultSensor::Psuc => sample.Psuc,
+            FaultSensor::Pliq => sample.Pliq,
+        }
+    }
+}
+
+/**
+ * Sensor travado: o valor não muda por muito tempo mesmo com o compressor ligando e desligando, o que deveria
+ * mexer em todas as leituras.
+ */
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SensorStuckRule {
+    pub sensor: FaultSensor,
+    pub min_stuck_s: i64,
+    fault_id: String,
+    value: Option<f64>,
+    since: i64,
+}
+
+impl SensorStuckRule {
+    pub fn new(sensor: FaultSensor, min_stuck_s: i64) -> Self {
+        SensorStuckRule {
+            sensor,
+            min_stuck_s,
+            fault_id: format!("sensor_stuck_{:?}", sensor),
+            value: None,
+            since: 0,
+        }
+    }
+}
+
+impl DacFaultRule for SensorStuckRule {
+    fn fault_id(&self) -> &str {
+        &self.fault_id
+    }
+    fn min_duration_s(&self) -> i64 {
+        0
+    }
+    fn check(&mut self, sample: &FaultSample, compressor: &CompressorState) -> Option<bool> {
+        let value = self.sensor.value(sample)?;
+        let Some(last) = self.value else {
+            // Primeira leitura, ainda não dá para saber se mudou
+            self.value = Some(value);
+            self.since = sample.ts;
+            return None;
+        };
+        if (last - value).abs() > 0.001 {
+            self.value = Some(value);
+            self.since = sample.ts;
+            return Some(false);
+        }
+        let stuck_for = sample.ts - self.since;
+        let compressor_changed = compressor
+            .last_change
+            .is_some_and(|last_change| last_change > self.since);
+        if stuck_for >= self.min_stuck_s && compressor_changed {
+            Some(true)
+        } else {
+            None
+        }
+    }
+    fn reset(&mut self) {
+        self.value = None;
+    }
+}
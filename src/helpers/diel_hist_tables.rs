@@ -109,6 +109,7 @@ fn add_table_item(tables: &mut Vec<TopicRule>, topic: &str, prop: &str, prefix:
     };
 }
 
+#[derive(Clone)]
 pub enum BigQueryHistoryTable {
     None,                // Do not save to BigQuery
     SingleTable(String), // Save all telemetries to one table
@@ -1,3 +1,4 @@
+use super::schema::{self, ColumnDef, TypedRowBQ};
 use crate::GlobalVars;
 use gcp_bigquery_client::error::{BQError, NestedResponseError};
 use gcp_bigquery_client::google::cloud::bigquery::storage::v1::append_rows_response;
@@ -7,14 +8,16 @@ use gcp_bigquery_client::model::query_response::ResultSet;
 use gcp_bigquery_client::model::table::Table;
 use gcp_bigquery_client::model::table_data_insert_all_request::TableDataInsertAllRequest;
 use gcp_bigquery_client::model::table_data_insert_all_response::TableDataInsertAllResponse;
-use gcp_bigquery_client::model::table_field_schema::TableFieldSchema;
 use gcp_bigquery_client::model::table_schema::TableSchema;
 use gcp_bigquery_client::model::time_partitioning::TimePartitioning;
-use gcp_bigquery_client::storage::{ColumnType, FieldDescriptor, StreamName, TableDescriptor};
+use gcp_bigquery_client::storage::StreamName;
+use gcp_bigquery_client::table::ListOptions;
 use prost_derive::Message;
 use serde::Serialize;
+use std::collections::HashMap;
 use std::sync::atomic::Ordering;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tokio_stream::StreamExt;
 
 // type OnInserted = dyn Fn(&Arc<GlobalVars>) + Send + Sync;
@@ -30,11 +33,21 @@ pub struct BigQueryClient {
     pub client: gcp_bigquery_client::Client,
     pub project_id: String,
     pub dataset_id: String,
-    pub table_descriptor: TableDescriptor,
     // pub on_inserted: Option<&'static OnInserted>,
     pub on_table_not_found: Option<&'static OnTableNotFound>,
+    schemas: Arc<Mutex<HashMap<String, CachedSchema>>>, // Compartilhado entre os clones
 }
 
+// Colunas tipadas conhecidas de cada tabela
+#[derive(Clone)]
+struct CachedSchema {
+    columns: Vec<(ColumnDef, Instant)>, // Coluna e a partir de quando pode ser usada na Storage Write API
+    retry_add_after: Option<Instant>, // Falhou ao acrescentar colunas, espera antes de tentar de novo
+}
+
+// Depois de acrescentar uma coluna a Storage Write API demora alguns minutos para aceitá-la
+const SCHEMA_PROPAGATION: Duration = Duration::from_secs(10 * 60);
+
 impl BigQueryClient {
     pub async fn new(
         config: &GCPConfig,
@@ -46,54 +59,24 @@ impl BigQueryClient {
                 .await
                 .map_err(|err| format!("[54] {err}"))?;
 
-        let field_descriptors = vec![
-            FieldDescriptor {
-                name: "timestamp".to_string(),
-                number: 1,
-                typ: ColumnType::Timestamp,
-            },
-            FieldDescriptor {
-                name: "dev_id".to_string(),
-                number: 2,
-                typ: ColumnType::String,
-            },
-            FieldDescriptor {
-                name: "payload".to_string(),
-                number: 3,
-                typ: ColumnType::Json,
-            },
-        ];
-
-        let table_descriptor = TableDescriptor { field_descriptors };
-
         Ok(BigQueryClient {
             client,
             project_id: config.project_id.to_owned(),
             dataset_id: config.dataset_id.to_owned(),
-            table_descriptor,
             // on_inserted: None,
             on_table_not_found,
+            schemas: Arc::new(Mutex::new(HashMap::new())),
         })
     }
 
     pub fn clone(&self) -> BigQueryClient {
-        let field_descriptors = self
-            .table_descriptor
-            .field_descriptors
-            .iter()
-            .map(|x| FieldDescriptor {
-                name: x.name.to_owned(),
-                number: x.number,
-                typ: x.typ,
-            })
-            .collect();
         BigQueryClient {
             client: self.client.clone(),
             project_id: self.project_id.clone(),
             dataset_id: self.dataset_id.clone(),
-            table_descriptor: TableDescriptor { field_descriptors },
             // on_inserted: self.on_inserted.clone(),
             on_table_not_found: self.on_table_not_found.clone(),
+            schemas: self.schemas.clone(),
         }
     }
 
@@ -105,7 +88,17 @@ impl BigQueryClient {
         &self.dataset_id
     }
 
+    /** Cria a tabela com as colunas tipadas da família dela (pelo nome) */
     pub async fn create_new_table(&self, table_name: &str) -> Result<Table, String> {
+        self.create_table_with_columns(table_name, &schema::columns_for_table(table_name))
+            .await
+    }
+
+    pub async fn create_table_with_columns(
+        &self,
+        table_name: &str,
+        columns: &[ColumnDef],
+    ) -> Result<Table, String> {
         // Get dataset
         let dataset = self
             .client
@@ -114,24 +107,16 @@ impl BigQueryClient {
             .await
             .map_err(|err| format!("[122] {err}"))?;
 
-        let mut field_timestamp = TableFieldSchema::timestamp("timestamp");
-        let mut field_dev_id = TableFieldSchema::string("dev_id");
-        let mut field_payload = TableFieldSchema::json("payload");
-        field_timestamp.mode = Some("REQUIRED".to_owned());
-        field_dev_id.mode = Some("REQUIRED".to_owned());
-        field_payload.mode = Some("REQUIRED".to_owned());
+        let mut fields = schema::base_field_schemas();
+        fields.extend(columns.iter().map(schema::to_field_schema));
         dataset
             .create_table(
                 &self.client,
-                Table::from_dataset(
-                    &dataset,
-                    table_name,
-                    TableSchema::new(vec![field_timestamp, field_dev_id, field_payload]),
-                )
-                .time_partitioning(TimePartitioning::per_day().field("timestamp"))
-                .clustering(Clustering {
-                    fields: Some(vec!["dev_id".to_owned()]),
-                }),
+                Table::from_dataset(&dataset, table_name, TableSchema::new(fields))
+                    .time_partitioning(TimePartitioning::per_day().field("timestamp"))
+                    .clustering(Clustering {
+                        fields: Some(vec!["dev_id".to_owned()]),
+                    }),
             )
             .await
             .map_err(|err| format!("[144] {err}"))
@@ -139,6 +124,149 @@ impl BigQueryClient {
         // println!("BigQuery table created -> {:?}", resp);
     }
 
+    /** None se a tabela não existe */
+    pub async fn get_table(&self, table_name: &str) -> Result<Option<Table>, String> {
+        let result = self
+            .client
+            .table()
+            .get(&self.project_id, &self.dataset_id, table_name, None)
+            .await;
+        match result {
+            Ok(table) => Ok(Some(table)),
+            Err(BQError::ResponseError { error }) if error.error.code == 404 => Ok(None),
+            Err(err) => Err(format!("[69] {table_name} {err}")),
+        }
+    }
+
+    pub async fn list_tables(&self) -> Result<Vec<String>, String> {
+        let mut names = Vec::new();
+        let mut page_token: Option<String> = None;
+        loop {
+            let mut options = ListOptions::default().max_results(1000);
+            if let Some(page_token) = page_token {
+                options = options.page_token(page_token);
+            }
+            let page = self
+                .client
+                .table()
+                .list(&self.project_id, &self.dataset_id, options)
+                .await
+                .map_err(|err| format!("[70] {err}"))?;
+            for table in page.tables.unwrap_or_default() {
+                names.push(table.table_reference.table_id);
+            }
+            page_token = page.next_page_token;
+            if page_token.is_none() {
+                return Ok(names);
+            }
+        }
+    }
+
+    /** Acrescenta à tabela as colunas que faltam. Retorna as que foram acrescentadas. */
+    pub async fn add_columns(
+        &self,
+        table_name: &str,
+        columns: &[ColumnDef],
+    ) -> Result<Vec<ColumnDef>, String> {
+        let mut table = self
+            .get_table(table_name)
+            .await?
+            .ok_or_else(|| format!("[71] Table not found: {table_name}"))?;
+        let mut fields = table.schema.fields.take().unwrap_or_default();
+        let existing: Vec<&str> = fields.iter().map(|f| f.name.as_str()).collect();
+        let missing = schema::missing_columns(&existing, columns);
+        if missing.is_empty() {
+            return Ok(missing);
+        }
+        fields.extend(missing.iter().map(schema::to_field_schema));
+        table.schema.fields = Some(fields);
+        self.client
+            .table()
+            .patch(&self.project_id, &self.dataset_id, table_name, table)
+            .await
+            .map_err(|err| format!("[72] {table_name} {err}"))?;
+        Ok(missing)
+    }
+
+    /**
+     * Colunas tipadas que podem ser enviadas agora para a tabela. Cria a tabela se não existir e acrescenta as
+     * colunas que faltarem (que só passam a ser usadas depois de SCHEMA_PROPAGATION).
+     */
+    async fn prepare_columns(
+        &self,
+        table_name: &str,
+        needed: &[ColumnDef],
+    ) -> Result<Vec<ColumnDef>, String> {
+        let now = Instant::now();
+        let cached = self.schemas.lock().unwrap().get(table_name).cloned();
+        let mut cached = match cached {
+            Some(cached) => cached,
+            None => {
+                let columns = match self.get_table(table_name).await? {
+                    Some(table) => {
+                        schema::from_field_schemas(&table.schema.fields.unwrap_or_default())
+                    }
+                    None => {
+                        let mut columns = schema::columns_for_table(table_name);
+                        schema::merge_columns(&mut columns, needed.iter().cloned());
+                        columns.truncate(schema::MAX_TYPED_COLUMNS);
+                        self.create_table_with_columns(table_name, &columns).await?;
+                        crate::LOG.append_log_tag_msg(
+                            "INFO",
+                            &format!("BigQuery table created: {table_name}"),
+                        );
+                        columns
+                    }
+                };
+                CachedSchema {
+                    columns: columns.into_iter().map(|c| (c, now)).collect(),
+                    retry_add_after: None,
+                }
+            }
+        };
+
+        let existing: Vec<&str> = cached
+            .columns
+            .iter()
+            .map(|(c, _)| c.name.as_str())
+            .collect();
+        let mut missing = schema::missing_columns(&existing, needed);
+        missing.truncate(schema::MAX_TYPED_COLUMNS.saturating_sub(existing.len()));
+        let can_add = cached.retry_add_after.map_or(true, |ts| ts <= now);
+        if !missing.is_empty() && can_add {
+            match self.add_columns(table_name, &missing).await {
+                Ok(added) => {
+                    let names: Vec<&str> = added.iter().map(|c| c.name.as_str()).collect();
+                    crate::LOG.append_log_tag_msg(
+                        "INFO",
+                        &format!("BigQuery columns added to {table_name}: {names:?}"),
+                    );
+                    // As que já existiam na tabela (outro processo acrescentou) também entram com espera
+                    cached
+                        .columns
+                        .extend(missing.into_iter().map(|c| (c, now + SCHEMA_PROPAGATION)));
+                    cached.retry_add_after = None;
+                }
+                Err(err) => {
+                    crate::LOG.append_log_tag_msg("ERROR", &err);
+                    cached.retry_add_after = Some(now + SCHEMA_PROPAGATION);
+                }
+            }
+        }
+
+        let usable = cached
+            .columns
+            .iter()
+            .filter(|(_, usable_from)| *usable_from <= now)
+            .map(|(c, _)| c.clone())
+            .collect();
+        self.schemas
+            .lock()
+            .unwrap()
+            .insert(table_name.to_owned(), cached);
+        Ok(usable)
+    }
+
     pub async fn insert_telemetry_by_stream(
         &self,
         row: RowBQ,
@@ -210,10 +338,29 @@ impl BigQueryClient {
         );
         let trace_id = "D".to_string();
 
+        // Colunas tipadas: se não der para preparar, grava só as colunas fixas
+        let parsed: Vec<(&RowBQ, serde_json::Value)> = rows
+            .iter()
+            .map(|row| (row, serde_json::from_str(&row.payload).unwrap_or_default()))
+            .collect();
+        let needed = schema::columns_for_rows(&parsed);
+        let columns = match self.prepare_columns(table_name, &needed).await {
+            Ok(columns) => columns,
+            Err(err) => {
+                crate::LOG.append_log_tag_msg("ERROR", &err);
+                vec![]
+            }
+        };
+        let table_descriptor = schema::table_descriptor(&columns);
+        let typed_rows: Vec<TypedRowBQ> = parsed
+            .iter()
+            .map(|(row, payload)| TypedRowBQ::new(row, payload, &columns))
+            .collect();
+
         let result = self
             .client
             .storage_mut()
-            .append_rows(&stream_name, &self.table_descriptor, rows, trace_id)
+            .append_rows(&stream_name, &table_descriptor, &typed_rows, trace_id)
             .await
             .map_err(|err| format!("[242] {table_name} {err}"));
         let mut streaming = match result {
@@ -258,6 +405,8 @@ impl BigQueryClient {
                             on_table_not_found(globs, table_name);
                         }
                     }
+                    // Tabela apagada ou esquema alterado por fora: lê de novo na próxima vez
+                    self.schemas.lock().unwrap().remove(table_name);
                     return Err(format!("[264] {}", err.message));
                 }
             }
@@ -1,10 +1,23 @@
 use super::client::RowBQ;
+use crate::envvars_loader;
+use crate::lib_dead_letter::DeadLetterQueue;
+use crate::lib_metrics::{CounterDeltas, MetricsText};
 use crate::GlobalVars;
 use std::{
-    collections::HashMap,
-    sync::{atomic::Ordering, Arc},
+    collections::{HashMap, VecDeque},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
 };
-use tokio::{sync::mpsc, time::Instant};
+use tokio::{
+    sync::{mpsc, Semaphore, SemaphorePermit},
+    time::Instant,
+};
+
+// Máximo de linhas enviadas em cada chamada de append_rows
+const BATCH_ROWS: usize = 3000;
 
 pub enum SaveToBqEvent {
     PayloadToSave(String, RowBQ),
@@ -12,85 +25,406 @@ pub enum SaveToBqEvent {
     TimeTick,
 }
 
+/** O que fazer quando o buffer do saver atinge BQ_SAVER_MAX_BUFFER_MB */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum OverflowPolicy {
+    Block, // Para de ler a fila de entrada e quem envia as telemetrias espera
+    // Manda as linhas pendentes mais antigas para a fila de dead-letter em disco. Elas são gravadas depois,
+    // pelas novas tentativas da fila, então chegam ao BigQuery fora de ordem em relação às linhas mais novas.
+    Spill,
+    DropOldest, // Descarta as linhas pendentes mais antigas
+}
+
+#[derive(Clone)]
+pub struct BqSaverConfig {
+    pub max_inflight: usize,
+    pub max_buffered_bytes: usize,
+    pub overflow: OverflowPolicy,
+    pub max_attempts: u32,
+}
+
+pub fn load_config(dead_letters_enabled: bool) -> Result<BqSaverConfig, String> {
+    let max_inflight: Option<usize> =
+        envvars_loader::get_var_structure_optional("BQ_SAVER_MAX_INFLIGHT")?;
+    let max_buffer_mb: Option<usize> =
+        envvars_loader::get_var_structure_optional("BQ_SAVER_MAX_BUFFER_MB")?;
+    let max_attempts: Option<u32> =
+        envvars_loader::get_var_structure_optional("BQ_SAVER_MAX_ATTEMPTS")?;
+    let overflow = match envvars_loader::get_var_string_optional("BQ_SAVER_OVERFLOW").as_deref() {
+        None | Some("block") => OverflowPolicy::Block,
+        Some("spill") => OverflowPolicy::Spill,
+        Some("drop_oldest") => OverflowPolicy::DropOldest,
+        Some(other) => return Err(format!("[66] Invalid BQ_SAVER_OVERFLOW: {other}")),
+    };
+    if overflow == OverflowPolicy::Spill && !dead_letters_enabled {
+        return Err("[66] BQ_SAVER_OVERFLOW=spill requires DEAD_LETTER_MAX_MB > 0".to_owned());
+    }
+
+    let config = BqSaverConfig {
+        max_inflight: max_inflight.unwrap_or(4),
+        max_buffered_bytes: max_buffer_mb.unwrap_or(256) * 1024 * 1024,
+        overflow,
+        max_attempts: max_attempts.unwrap_or(5),
+    };
+    if config.max_inflight == 0 || config.max_buffered_bytes == 0 || config.max_attempts == 0 {
+        return Err(
+            "[66] BQ_SAVER_MAX_INFLIGHT, BQ_SAVER_MAX_BUFFER_MB and BQ_SAVER_MAX_ATTEMPTS must be > 0"
+                .to_owned(),
+        );
+    }
+    Ok(config)
+}
+
+/**
+ * Estado do saver visível para o resto do serviço: limite de envios simultâneos ao BigQuery e
+ * contadores que aparecem nas estatísticas e no /metrics.
+ */
+pub struct BqSaverHealth {
+    inflight_limit: Semaphore,
+    max_inflight: usize,
+    pub buffered_rows: AtomicUsize, // Inclui as linhas que estão sendo enviadas
+    pub buffered_bytes: AtomicUsize, // Idem
+    pub retries: AtomicUsize,
+    pub failed_batches: AtomicUsize,
+    pub dropped_rows: AtomicUsize,
+    pub spilled_rows: AtomicUsize,
+    pub blocked_ms: AtomicUsize,
+}
+
+impl BqSaverHealth {
+    pub fn new(config: &BqSaverConfig) -> BqSaverHealth {
+        BqSaverHealth {
+            inflight_limit: Semaphore::new(config.max_inflight),
+            max_inflight: config.max_inflight,
+            buffered_rows: AtomicUsize::new(0),
+            buffered_bytes: AtomicUsize::new(0),
+            retries: AtomicUsize::new(0),
+            failed_batches: AtomicUsize::new(0),
+            dropped_rows: AtomicUsize::new(0),
+            spilled_rows: AtomicUsize::new(0),
+            blocked_ms: AtomicUsize::new(0),
+        }
+    }
+
+    /** Vaga para uma chamada ao BigQuery. Usado também pelas novas tentativas da fila de dead-letter. */
+    pub async fn acquire(&self) -> SemaphorePermit<'_> {
+        self.inflight_limit
+            .acquire()
+            .await
+            .expect("BigQuery saver semaphore closed")
+    }
+
+    pub fn inflight(&self) -> usize {
+        self.max_inflight - self.inflight_limit.available_permits()
+    }
+
+    pub fn stats_json(&self, deltas: &mut CounterDeltas) -> serde_json::Value {
+        serde_json::json!({
+            "buffered_rows": self.buffered_rows.load(Ordering::Relaxed),
+            "buffered_bytes": self.buffered_bytes.load(Ordering::Relaxed),
+            "inflight": self.inflight(),
+            "retries": deltas.delta("bq_saver_retries", &self.retries),
+            "failed_batches": deltas.delta("bq_saver_failed_batches", &self.failed_batches),
+            "dropped_rows": deltas.delta("bq_saver_dropped_rows", &self.dropped_rows),
+            "spilled_rows": deltas.delta("bq_saver_spilled_rows", &self.spilled_rows),
+            "blocked_ms": deltas.delta("bq_saver_blocked_ms", &self.blocked_ms),
+        })
+    }
+
+    pub fn write_metrics(&self, out: &mut MetricsText) {
+        let counter = |x: &AtomicUsize| x.load(Ordering::Relaxed);
+        out.gauge(
+            "bq_saver_buffered_rows",
+            "Linhas no buffer do saver do BigQuery, incluindo as em envio",
+            counter(&self.buffered_rows) as f64,
+        );
+        out.gauge(
+            "bq_saver_buffered_bytes",
+            "Bytes no buffer do saver do BigQuery, incluindo os em envio",
+            counter(&self.buffered_bytes) as f64,
+        );
+        out.gauge(
+            "bq_saver_inflight",
+            "Chamadas ao BigQuery em andamento",
+            self.inflight() as f64,
+        );
+        out.counter(
+            "bq_saver_retries_total",
+            "Novas tentativas de envio de lotes ao BigQuery",
+            counter(&self.retries),
+        );
+        out.counter(
+            "bq_saver_failed_batches_total",
+            "Lotes que falharam em todas as tentativas",
+            counter(&self.failed_batches),
+        );
+        out.counter(
+            "bq_saver_dropped_rows_total",
+            "Linhas descartadas com o buffer cheio",
+            counter(&self.dropped_rows),
+        );
+        out.counter(
+            "bq_saver_spilled_rows_total",
+            "Linhas enviadas para o disco com o buffer cheio",
+            counter(&self.spilled_rows),
+        );
+        out.counter(
+            "bq_saver_blocked_ms_total",
+            "Tempo em que o saver parou de ler a fila de entrada por estar cheio",
+            counter(&self.blocked_ms),
+        );
+    }
+}
+
+struct TableQueue {
+    first_ts: Instant,
+    rows: VecDeque<RowBQ>,
+    busy: bool, // Tem um lote desta tabela em envio; o próximo só sai depois, para manter a ordem
+}
+
+// Lote terminado (gravado, descartado ou enviado para o disco): tabela, linhas e bytes
+type BatchDone = (String, usize, usize);
+
+fn row_bytes(row: &RowBQ) -> usize {
+    row.timestamp.len() + row.dev_id.len() + row.payload.len()
+}
+
+fn enqueue(
+    table_queues: &mut HashMap<String, TableQueue>,
+    health: &BqSaverHealth,
+    table_name: String,
+    rows: Vec<RowBQ>,
+) {
+    let queue = table_queues
+        .entry(table_name)
+        .or_insert_with(|| TableQueue {
+            first_ts: Instant::now(),
+            rows: VecDeque::with_capacity(1100),
+            busy: false,
+        });
+    if queue.rows.is_empty() {
+        queue.first_ts = Instant::now();
+    }
+    let bytes: usize = rows.iter().map(row_bytes).sum();
+    health
+        .buffered_rows
+        .fetch_add(rows.len(), Ordering::Relaxed);
+    health.buffered_bytes.fetch_add(bytes, Ordering::Relaxed);
+    queue.rows.extend(rows);
+}
+
 pub async fn task_save_to_bigquery(
     globs: Arc<GlobalVars>,
     mut receiver: mpsc::Receiver<SaveToBqEvent>,
     max_time_interval: u128, // 2800 ms
 ) {
-    let mut table_queues: HashMap<String, (Instant, Vec<RowBQ>)> = HashMap::new();
+    let config = globs.configfile.bq_saver_config.clone();
+    let health = &globs.bq_saver;
+    let mut table_queues: HashMap<String, TableQueue> = HashMap::new();
+    let (done_sender, mut done_receiver) = mpsc::unbounded_channel::<BatchDone>();
+    let mut tick = tokio::time::interval(Duration::from_millis(500));
+    let mut blocked_since: Option<Instant> = None;
 
     loop {
-        let event = receiver.recv().await.expect("Erro ao receber do mpsc");
-        match event {
-            SaveToBqEvent::PayloadToSave(table_name, row) => {
-                let mut queue = table_queues.get_mut(&table_name);
-                if queue.is_none() {
-                    // on_new_table_queue(&table_name, &globs);
-                    table_queues.insert(
-                        table_name.clone(),
-                        (Instant::now(), Vec::with_capacity(1100)),
-                    );
-                    queue = table_queues.get_mut(&table_name);
-                }
-                if let Some(queue) = queue {
-                    if queue.1.is_empty() {
-                        queue.0 = Instant::now();
-                    }
-                    queue.1.push(row);
-                }
+        let is_full = health.buffered_bytes.load(Ordering::Relaxed) > config.max_buffered_bytes;
+        let accept_input = !(is_full && config.overflow == OverflowPolicy::Block);
+        match (accept_input, blocked_since) {
+            (false, None) => {
+                crate::LOG.append_log_tag_msg("WARN", "[67] BigQuery saver buffer full, blocking");
+                blocked_since = Some(Instant::now());
             }
-            SaveToBqEvent::PayloadListToSave(table_name, mut rows) => {
-                let mut queue = table_queues.get_mut(&table_name);
-                if queue.is_none() {
-                    // on_new_table_queue(&table_name, &globs);
-                    table_queues.insert(
-                        table_name.clone(),
-                        (Instant::now(), Vec::with_capacity(1100)),
-                    );
-                    queue = table_queues.get_mut(&table_name);
-                }
-                if let Some(queue) = queue {
-                    if queue.1.is_empty() {
-                        queue.0 = Instant::now();
-                    }
+            (true, Some(since)) => {
+                let blocked_ms = since.elapsed().as_millis() as usize;
+                health.blocked_ms.fetch_add(blocked_ms, Ordering::Relaxed);
+                blocked_since = None;
+            }
+            _ => {}
+        }
 
-                    queue.1.append(&mut rows);
+        tokio::select! {
+            event = receiver.recv(), if accept_input => {
+                match event.expect("Erro ao receber do mpsc") {
+                    SaveToBqEvent::PayloadToSave(table_name, row) => {
+                        enqueue(&mut table_queues, health, table_name, vec![row]);
+                    }
+                    SaveToBqEvent::PayloadListToSave(table_name, rows) => {
+                        enqueue(&mut table_queues, health, table_name, rows);
+                    }
+                    SaveToBqEvent::TimeTick => {
+                        // Nothing to do here
+                    }
                 }
             }
-            SaveToBqEvent::TimeTick => {
-                // Nothing to do here
+            Some((table_name, rows, bytes)) = done_receiver.recv() => {
+                health.buffered_rows.fetch_sub(rows, Ordering::Relaxed);
+                health.buffered_bytes.fetch_sub(bytes, Ordering::Relaxed);
+                if let Some(queue) = table_queues.get_mut(&table_name) {
+                    queue.busy = false;
+                }
             }
+            _ = tick.tick() => {}
         }
 
+        relieve_overflow(
+            health,
+            &config,
+            globs.dead_letters.bigquery.as_ref(),
+            &mut table_queues,
+        );
+
+        // Com o buffer cheio não espera completar o lote nem o intervalo
+        let is_full = health.buffered_bytes.load(Ordering::Relaxed) > config.max_buffered_bytes;
         for (table_name, table_queue) in table_queues.iter_mut() {
-            if table_queue.1.is_empty() {
+            if table_queue.busy || table_queue.rows.is_empty() {
                 continue;
             }
-            let need_send = (table_queue.1.len() >= 3000)
-                || (table_queue.0.elapsed().as_millis() > max_time_interval);
+            let need_send = is_full
+                || (table_queue.rows.len() >= BATCH_ROWS)
+                || (table_queue.first_ts.elapsed().as_millis() > max_time_interval);
             if !need_send {
                 continue;
             }
-            let rows: Vec<RowBQ> = table_queue.1.drain(..).collect();
-            let globs = globs.clone();
-            let table_name = table_name.clone();
-            tokio::spawn(async move {
-                let mut client_bigquery = globs
-                    .client_bigquery
-                    .as_ref()
-                    .expect("BigQuery client is null")
-                    .clone();
-                let resp = client_bigquery
-                    .insert_telemetry_list_by_storage(&rows, &table_name, &globs)
-                    .await;
-                if let Err(err) = resp {
-                    crate::LOG.append_log_tag_msg("ERRO", &format!("[56] {:?}", err));
-                }
-            });
+            let count = table_queue.rows.len().min(BATCH_ROWS);
+            let rows: Vec<RowBQ> = table_queue.rows.drain(..count).collect();
+            table_queue.busy = true;
+            tokio::spawn(send_batch(
+                globs.clone(),
+                table_name.clone(),
+                rows,
+                config.max_attempts,
+                done_sender.clone(),
+            ));
+        }
+    }
+}
+
+/**
+ * Com o buffer cheio, tira as linhas pendentes da tabela mais antiga até voltar ao limite (exceto no Block).
+ * As linhas enviadas para o disco não respeitam a ordem garantida pelo `busy` da tabela.
+ */
+fn relieve_overflow(
+    health: &BqSaverHealth,
+    config: &BqSaverConfig,
+    spill_queue: Option<&DeadLetterQueue>,
+    table_queues: &mut HashMap<String, TableQueue>,
+) {
+    if config.overflow == OverflowPolicy::Block {
+        return;
+    }
+    while health.buffered_bytes.load(Ordering::Relaxed) > config.max_buffered_bytes {
+        // As linhas que já estão em envio não contam aqui
+        let oldest = table_queues
+            .iter_mut()
+            .filter(|(_, queue)| !queue.rows.is_empty())
+            .min_by_key(|(_, queue)| queue.first_ts);
+        let Some((table_name, queue)) = oldest else {
+            return;
+        };
+
+        let mut excess = health.buffered_bytes.load(Ordering::Relaxed) - config.max_buffered_bytes;
+        let mut rows = Vec::new();
+        let mut bytes = 0;
+        while excess > 0 {
+            let Some(row) = queue.rows.pop_front() else {
+                break;
+            };
+            let size = row_bytes(&row);
+            excess = excess.saturating_sub(size);
+            bytes += size;
+            rows.push(row);
+        }
+        health
+            .buffered_rows
+            .fetch_sub(rows.len(), Ordering::Relaxed);
+        health.buffered_bytes.fetch_sub(bytes, Ordering::Relaxed);
+
+        let spill_queue = match config.overflow {
+            OverflowPolicy::Spill => spill_queue,
+            _ => None,
+        };
+        if let Some(spill_queue) = spill_queue {
+            health.spilled_rows.fetch_add(rows.len(), Ordering::Relaxed);
+            let items = rows
+                .iter()
+                .filter_map(|row| serde_json::to_value(row).ok())
+                .collect();
+            spill_queue.push(table_name, items, "BigQuery saver buffer full");
+        } else {
+            health.dropped_rows.fetch_add(rows.len(), Ordering::Relaxed);
+            crate::LOG.append_log_tag_msg(
+                "WARN",
+                &format!(
+                    "[68] BigQuery saver buffer full, dropped {} rows of {}",
+                    rows.len(),
+                    table_name
+                ),
+            );
         }
     }
 }
 
+/** Espera antes da tentativa seguinte: 1s, 2s, 4s... até 30s, com até 50% a mais aleatório */
+fn retry_delay(attempt: u32) -> Duration {
+    let base_ms = (1000u64 << attempt.saturating_sub(1).min(5)).min(30_000);
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.subsec_nanos() as u64)
+        .unwrap_or(0);
+    Duration::from_millis(base_ms + nanos % (base_ms / 2 + 1))
+}
+
+async fn send_batch(
+    globs: Arc<GlobalVars>,
+    table_name: String,
+    rows: Vec<RowBQ>,
+    max_attempts: u32,
+    done: mpsc::UnboundedSender<BatchDone>,
+) {
+    let mut client_bigquery = globs
+        .client_bigquery
+        .as_ref()
+        .expect("BigQuery client is null")
+        .clone();
+
+    // As novas tentativas ficam dentro do lote, então a tabela não envia nada fora de ordem
+    let mut attempt = 1;
+    let resp = loop {
+        let resp = {
+            let _permit = globs.bq_saver.acquire().await;
+            client_bigquery
+                .insert_telemetry_list_by_storage(&rows, &table_name, &globs)
+                .await
+        };
+        match resp {
+            Err(_) if attempt < max_attempts => {
+                globs.bq_saver.retries.fetch_add(1, Ordering::Relaxed);
+                tokio::time::sleep(retry_delay(attempt)).await;
+                attempt += 1;
+            }
+            resp => break resp,
+        }
+    };
+
+    if let Err(err) = resp {
+        globs
+            .bq_saver
+            .failed_batches
+            .fetch_add(1, Ordering::Relaxed);
+        crate::LOG.append_log_tag_msg("ERRO", &format!("[56] {:?}", err));
+        // O lote vai para a fila em disco e é tentado de novo depois
+        if let Some(queue) = &globs.dead_letters.bigquery {
+            let items = rows
+                .iter()
+                .filter_map(|row| serde_json::to_value(row).ok())
+                .collect();
+            queue.push(&table_name, items, &err);
+        }
+    }
+
+    let bytes = rows.iter().map(row_bytes).sum();
+    let _ = done.send((table_name, rows.len(), bytes));
+}
+
 pub async fn task_force_save_to_bigquery(sender: mpsc::Sender<SaveToBqEvent>) {
     // Task to make sure stats are sent even if there are no important events
     loop {
@@ -124,3 +458,87 @@ pub async fn push_rows_to_storage(
         .await
         .map_err(|err| format!("{:?}", err))
 }
+
+#[test]
+fn test_retry_delay() {
+    for (attempt, base_ms) in [(1, 1000), (2, 2000), (3, 4000), (6, 30_000), (20, 30_000)] {
+        let delay = retry_delay(attempt).as_millis() as u64;
+        assert!(
+            delay >= base_ms && delay <= base_ms + base_ms / 2,
+            "{attempt} {delay}"
+        );
+    }
+}
+
+#[test]
+fn test_relieve_overflow() {
+    let row = |i: usize| RowBQ {
+        timestamp: format!("2024-01-01T00:00:{:02}", i),
+        dev_id: "DAC210191234".to_owned(),
+        payload: "x".repeat(81),
+    };
+    let row_size = row_bytes(&row(0));
+    let root_dir = std::env::temp_dir().join(format!(
+        "bq-saver-test-{}",
+        crate::lib_dead_letter::now_millis()
+    ));
+    let root_dir = root_dir.to_str().unwrap();
+    let spill_queue = DeadLetterQueue::open(root_dir, "bigquery", 1024 * 1024).unwrap();
+
+    for overflow in [
+        OverflowPolicy::Block,
+        OverflowPolicy::Spill,
+        OverflowPolicy::DropOldest,
+    ] {
+        let config = BqSaverConfig {
+            max_inflight: 1,
+            max_buffered_bytes: row_size * 5,
+            overflow,
+            max_attempts: 1,
+        };
+        let health = BqSaverHealth::new(&config);
+        let mut table_queues = HashMap::new();
+        // A tabela mais antiga perde as linhas primeiro
+        enqueue(
+            &mut table_queues,
+            &health,
+            "OLD".to_owned(),
+            (0..4).map(row).collect(),
+        );
+        table_queues.get_mut("OLD").unwrap().first_ts -= Duration::from_secs(10);
+        enqueue(
+            &mut table_queues,
+            &health,
+            "NEW".to_owned(),
+            (4..8).map(row).collect(),
+        );
+
+        relieve_overflow(&health, &config, Some(&spill_queue), &mut table_queues);
+        let remaining = |table: &str| table_queues[table].rows.len();
+        match overflow {
+            OverflowPolicy::Block => {
+                assert_eq!((remaining("OLD"), remaining("NEW")), (4, 4));
+                assert_eq!(health.buffered_bytes.load(Ordering::Relaxed), row_size * 8);
+            }
+            OverflowPolicy::Spill | OverflowPolicy::DropOldest => {
+                assert_eq!((remaining("OLD"), remaining("NEW")), (1, 4));
+                assert_eq!(table_queues["OLD"].rows[0].timestamp, "2024-01-01T00:00:03");
+                assert_eq!(health.buffered_rows.load(Ordering::Relaxed), 5);
+                assert_eq!(health.buffered_bytes.load(Ordering::Relaxed), row_size * 5);
+            }
+        }
+        let spilled = health.spilled_rows.load(Ordering::Relaxed);
+        let dropped = health.dropped_rows.load(Ordering::Relaxed);
+        match overflow {
+            OverflowPolicy::Block => assert_eq!((spilled, dropped), (0, 0)),
+            OverflowPolicy::Spill => assert_eq!((spilled, dropped), (3, 0)),
+            OverflowPolicy::DropOldest => assert_eq!((spilled, dropped), (0, 3)),
+        }
+    }
+
+    let (total, spilled) = spill_queue.list(0, 10);
+    assert_eq!(total, 1);
+    assert_eq!(spilled[0].table, "OLD");
+    assert_eq!(spill_queue.get(spilled[0].id).unwrap().items.len(), 3);
+    std::fs::remove_dir_all(root_dir).unwrap();
+}
@@ -0,0 +1,545 @@
+/*!
+Esquemas das tabelas de telemetria no BigQuery.
+
+Toda tabela tem as colunas fixas "timestamp", "dev_id" e "payload" (JSON com a telemetria inteira, que é o que o
+rusthist lê), particionamento por dia em "timestamp" e clustering por "dev_id". Além delas cada família de
+dispositivo tem colunas tipadas (DAC: Tamb, Tsuc, Psuc, L1...; DUT; medidores de energia; chillers do DRI), e as
+propriedades escalares que aparecerem nas telemetrias sem coluna ainda ganham uma nova coluna automaticamente.
+
+Tipos: números decimais vão em NUMERIC (enviados como texto para não perder precisão, já que a Storage Write API
+só aceita float de 32 bits pela descrição de colunas do gcp_bigquery_client), inteiros em INT64, booleanos em BOOL
+e textos em STRING. O tipo de uma coluna que já existe na tabela prevalece sobre o daqui.
+*/
+use super::client::RowBQ;
+use gcp_bigquery_client::model::field_type::FieldType;
+use gcp_bigquery_client::model::table_field_schema::TableFieldSchema;
+use gcp_bigquery_client::storage::{ColumnType, FieldDescriptor, TableDescriptor};
+use prost::bytes::{Buf, BufMut};
+use prost::encoding::{self, DecodeContext, WireType};
+use serde_json::Value;
+
+// Limite de colunas tipadas por tabela, para uma telemetria com lixo não criar colunas sem fim
+pub const MAX_TYPED_COLUMNS: usize = 1000;
+
+const BASE_COLUMNS: [&str; 3] = ["timestamp", "dev_id", "payload"];
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ColType {
+    Numeric,
+    Int,
+    Bool,
+    String,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct ColumnDef {
+    pub name: String,
+    pub typ: ColType,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum TableFamily {
+    Dac,
+    Dut,
+    Dme,
+    DriChiller,
+    Other,
+}
+
+use ColType::{Bool, Int, Numeric, String as Str};
+
+const DAC_COLUMNS: &[(&str, ColType)] = &[
+    ("L1", Bool),
+    ("T0", Numeric),
+    ("T1", Numeric),
+    ("T2", Numeric),
+    ("P0", Int),
+    ("P1", Int),
+    ("Lcmp", Int),
+    ("Lcut", Int),
+    ("Levp", Int),
+    ("Tamb", Numeric),
+    ("Tsuc", Numeric),
+    ("Tliq", Numeric),
+    ("Psuc", Numeric),
+    ("Pliq", Numeric),
+    ("Tsc", Numeric),
+    ("Tsh", Numeric),
+    ("State", Str),
+    ("Mode", Str),
+    ("samplingTime", Int),
+];
+
+const DUT_COLUMNS: &[(&str, ColType)] = &[
+    ("Temperature", Numeric),
+    ("Temperature_1", Numeric),
+    ("Tmp", Numeric),
+    ("Humidity", Numeric),
+    ("eCO2", Int),
+    ("raw_eCO2", Int),
+    ("TVOC", Int),
+    ("L1", Bool),
+    ("State", Str),
+    ("Mode", Str),
+    ("samplingTime", Int),
+];
+
+const DME_COLUMNS: &[(&str, ColType)] = &[
+    ("type", Str),
+    ("v_a", Numeric),
+    ("v_b", Numeric),
+    ("v_c", Numeric),
+    ("v_ab", Numeric),
+    ("v_bc", Numeric),
+    ("v_ca", Numeric),
+    ("i_a", Numeric),
+    ("i_b", Numeric),
+    ("i_c", Numeric),
+    ("pot_at_a", Numeric),
+    ("pot_at_b", Numeric),
+    ("pot_at_c", Numeric),
+    ("pot_ap_a", Numeric),
+    ("pot_ap_b", Numeric),
+    ("pot_ap_c", Numeric),
+    ("pot_re_a", Numeric),
+    ("pot_re_b", Numeric),
+    ("pot_re_c", Numeric),
+    ("v_tri_ln", Numeric),
+    ("v_tri_ll", Numeric),
+    ("pot_at_tri", Numeric),
+    ("pot_ap_tri", Numeric),
+    ("pot_re_tri", Numeric),
+    ("en_at_tri", Numeric),
+    ("en_re_tri", Numeric),
+    ("en_ap_tri", Numeric),
+    ("fp_a", Numeric),
+    ("fp_b", Numeric),
+    ("fp_c", Numeric),
+    ("fp", Numeric),
+    ("freq", Numeric),
+    ("demanda", Numeric),
+    ("demanda_at", Numeric),
+    ("demanda_ap", Numeric),
+    ("demanda_med_at", Numeric),
+    ("erro", Numeric),
+];
+
+const DRI_CHILLER_COLUMNS: &[(&str, ColType)] = &[
+    ("type", Str),
+    ("CHIL_S_S", Numeric),
+    ("CHIL_OCC", Numeric),
+    ("STATUS", Numeric),
+    ("ALM", Numeric),
+    ("alarm_1", Numeric),
+    ("alarm_2", Numeric),
+    ("alarm_3", Numeric),
+    ("alarm_4", Numeric),
+    ("alarm_5", Numeric),
+    ("CAP_T", Numeric),
+    ("DEM_LIM", Numeric),
+    ("LAG_LIM", Numeric),
+    ("SP", Numeric),
+    ("CTRL_PNT", Numeric),
+    ("EMSTOP", Numeric),
+    ("CAPA_T", Numeric),
+    ("CAPB_T", Numeric),
+    ("DP_A", Numeric),
+    ("SP_A", Numeric),
+    ("SCT_A", Numeric),
+    ("SST_A", Numeric),
+    ("DP_B", Numeric),
+    ("SP_B", Numeric),
+    ("SCT_B", Numeric),
+    ("SST_B", Numeric),
+    ("COND_LWT", Numeric),
+    ("COND_EWT", Numeric),
+    ("COOL_LWT", Numeric),
+    ("COOL_EWT", Numeric),
+    ("COND_SP", Numeric),
+];
+
+pub fn family_columns(family: TableFamily) -> Vec<ColumnDef> {
+    let list = match family {
+        TableFamily::Dac => DAC_COLUMNS,
+        TableFamily::Dut => DUT_COLUMNS,
+        TableFamily::Dme => DME_COLUMNS,
+        TableFamily::DriChiller => DRI_CHILLER_COLUMNS,
+        TableFamily::Other => &[],
+    };
+    list.iter()
+        .map(|(name, typ)| ColumnDef {
+            name: (*name).to_owned(),
+            typ: *typ,
+        })
+        .collect()
+}
+
+/** Famílias que podem ir para a tabela, pelo nome dela (dac_telemetry, DAC21019_telemetry, DAC210191234...) */
+pub fn families_for_table(table_name: &str) -> Vec<TableFamily> {
+    let prefix = table_name.get(..3).unwrap_or_default().to_uppercase();
+    match prefix.as_str() {
+        "DAC" => vec![TableFamily::Dac],
+        "DUT" => vec![TableFamily::Dut],
+        "DRI" => vec![TableFamily::Dme, TableFamily::DriChiller],
+        _ => vec![TableFamily::Other],
+    }
+}
+
+/** Colunas tipadas de uma tabela nova, antes de receber qualquer telemetria */
+pub fn columns_for_table(table_name: &str) -> Vec<ColumnDef> {
+    let mut columns = Vec::new();
+    for family in families_for_table(table_name) {
+        merge_columns(&mut columns, family_columns(family));
+    }
+    columns
+}
+
+/** Colunas de todas as famílias, para quando todos os dispositivos vão para uma tabela só */
+pub fn all_family_columns() -> Vec<ColumnDef> {
+    let mut columns = Vec::new();
+    for family in [
+        TableFamily::Dac,
+        TableFamily::Dut,
+        TableFamily::Dme,
+        TableFamily::DriChiller,
+    ] {
+        merge_columns(&mut columns, family_columns(family));
+    }
+    columns
+}
+
+pub fn row_family(dev_id: &str, payload: &Value) -> TableFamily {
+    if dev_id.starts_with("DAC") {
+        TableFamily::Dac
+    } else if dev_id.starts_with("DUT") {
+        TableFamily::Dut
+    } else if dev_id.starts_with("DRI") {
+        let is_chiller = payload.get("CHIL_S_S").is_some()
+            || payload["type"]
+                .as_str()
+                .is_some_and(|t| t.starts_with("CHILLER"));
+        if is_chiller {
+            TableFamily::DriChiller
+        } else if payload.get("v_a").is_some() || payload.get("en_at_tri").is_some() {
+            TableFamily::Dme
+        } else {
+            TableFamily::Other
+        }
+    } else {
+        TableFamily::Other
+    }
+}
+
+/** Colunas que as linhas precisam: as da família e as das propriedades escalares */
+pub fn columns_for_rows(rows: &[(&RowBQ, Value)]) -> Vec<ColumnDef> {
+    let mut columns = Vec::new();
+    for (row, payload) in rows {
+        merge_columns(
+            &mut columns,
+            family_columns(row_family(&row.dev_id, payload)),
+        );
+        let Some(props) = payload.as_object() else {
+            continue;
+        };
+        let inferred = props.iter().filter_map(|(name, value)| {
+            let typ = match value {
+                Value::Bool(_) => Bool,
+                Value::Number(_) => Numeric,
+                Value::String(_) => Str,
+                _ => return None, // Listas e objetos ficam só no payload
+            };
+            Some(ColumnDef {
+                name: name.to_owned(),
+                typ,
+            })
+        });
+        merge_columns(&mut columns, inferred);
+    }
+    columns
+}
+
+/** Acrescenta as colunas que ainda não estão na lista. No BigQuery os nomes não diferenciam maiúsculas. */
+pub fn merge_columns(columns: &mut Vec<ColumnDef>, new: impl IntoIterator<Item = ColumnDef>) {
+    for column in new {
+        if !is_valid_column_name(&column.name) {
+            continue;
+        }
+        let exists = columns
+            .iter()
+            .any(|c| c.name.eq_ignore_ascii_case(&column.name));
+        if !exists {
+            columns.push(column);
+        }
+    }
+}
+
+/** Colunas de "needed" que não estão entre os nomes de "existing" */
+pub fn missing_columns(existing: &[&str], needed: &[ColumnDef]) -> Vec<ColumnDef> {
+    needed
+        .iter()
+        .filter(|column| {
+            !existing
+                .iter()
+                .any(|c| c.eq_ignore_ascii_case(&column.name))
+        })
+        .cloned()
+        .collect()
+}
+
+fn is_valid_column_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    let Some(first) = chars.next() else {
+        return false;
+    };
+    let upper = name.to_uppercase();
+    let reserved = [
+        "_TABLE_",
+        "_FILE_",
+        "_PARTITION",
+        "_ROW_TIMESTAMP",
+        "__ROOT__",
+        "_COLIDENTIFIER",
+    ];
+    (first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && name.len() <= 300
+        && !BASE_COLUMNS.iter().any(|b| b.eq_ignore_ascii_case(name))
+        && !reserved.iter().any(|r| upper.starts_with(r))
+}
+
+pub fn base_field_schemas() -> Vec<TableFieldSchema> {
+    let mut field_timestamp = TableFieldSchema::timestamp("timestamp");
+    let mut field_dev_id = TableFieldSchema::string("dev_id");
+    let mut field_payload = TableFieldSchema::json("payload");
+    field_timestamp.mode = Some("REQUIRED".to_owned());
+    field_dev_id.mode = Some("REQUIRED".to_owned());
+    field_payload.mode = Some("REQUIRED".to_owned());
+    vec![field_timestamp, field_dev_id, field_payload]
+}
+
+pub fn to_field_schema(column: &ColumnDef) -> TableFieldSchema {
+    match column.typ {
+        Numeric => TableFieldSchema::numeric(&column.name),
+        Int => TableFieldSchema::integer(&column.name),
+        Bool => TableFieldSchema::bool(&column.name),
+        Str => TableFieldSchema::string(&column.name),
+    }
+}
+
+/** Colunas tipadas de uma tabela existente. As de tipos que não gravamos (ex.: FLOAT64 criada à mão) ficam de fora. */
+pub fn from_field_schemas(fields: &[TableFieldSchema]) -> Vec<ColumnDef> {
+    fields
+        .iter()
+        .filter(|field| !BASE_COLUMNS.contains(&field.name.as_str()))
+        .filter_map(|field| {
+            let typ = match field.r#type {
+                FieldType::Numeric => Numeric,
+                FieldType::Integer | FieldType::Int64 => Int,
+                FieldType::Boolean | FieldType::Bool => Bool,
+                FieldType::String => Str,
+                _ => return None,
+            };
+            Some(ColumnDef {
+                name: field.name.to_owned(),
+                typ,
+            })
+        })
+        .collect()
+}
+
+/** Descrição das colunas para a Storage Write API: as fixas com os números 1 a 3 e as tipadas a partir do 4 */
+pub fn table_descriptor(columns: &[ColumnDef]) -> TableDescriptor {
+    let mut field_descriptors = vec![
+        FieldDescriptor {
+            name: "timestamp".to_string(),
+            number: 1,
+            typ: ColumnType::Timestamp,
+        },
+        FieldDescriptor {
+            name: "dev_id".to_string(),
+            number: 2,
+            typ: ColumnType::String,
+        },
+        FieldDescriptor {
+            name: "payload".to_string(),
+            number: 3,
+            typ: ColumnType::Json,
+        },
+    ];
+    for (index, column) in columns.iter().enumerate() {
+        field_descriptors.push(FieldDescriptor {
+            name: column.name.to_owned(),
+            number: 4 + index as u32,
+            typ: match column.typ {
+                Numeric | Str => ColumnType::String,
+                Int => ColumnType::Int64,
+                Bool => ColumnType::Bool,
+            },
+        });
+    }
+    TableDescriptor { field_descriptors }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TypedValue {
+    Text(String),
+    Int(i64),
+    Bool(bool),
+}
+
+/** Linha com as colunas tipadas, codificada em protobuf de acordo com table_descriptor() */
+#[derive(Debug, Default)]
+pub struct TypedRowBQ {
+    values: Vec<(u32, TypedValue)>,
+}
+
+impl TypedRowBQ {
+    pub fn new(row: &RowBQ, payload: &Value, columns: &[ColumnDef]) -> TypedRowBQ {
+        let mut values = vec![
+            (1, TypedValue::Text(row.timestamp.to_owned())),
+            (2, TypedValue::Text(row.dev_id.to_owned())),
+            (3, TypedValue::Text(row.payload.to_owned())),
+        ];
+        for (index, column) in columns.iter().enumerate() {
+            // Valor com tipo diferente da coluna fica nulo, mas continua no payload
+            if let Some(value) = typed_value(payload.get(&column.name), column.typ) {
+                values.push((4 + index as u32, value));
+            }
+        }
+        TypedRowBQ { values }
+    }
+}
+
+fn typed_value(value: Option<&Value>, typ: ColType) -> Option<TypedValue> {
+    let value = value?;
+    match typ {
+        Numeric => {
+            let number = value.as_f64().filter(|x| x.is_finite() && x.abs() < 1e29)?;
+            if value.is_i64() || value.is_u64() {
+                return Some(TypedValue::Text(value.to_string()));
+            }
+            // NUMERIC tem 9 casas decimais
+            let text = format!("{:.9}", number);
+            let text = text.trim_end_matches('0').trim_end_matches('.');
+            Some(TypedValue::Text(text.to_owned()))
+        }
+        Int => match value {
+            Value::Number(n) => n
+                .as_i64()
+                .or_else(|| n.as_f64().filter(|x| x.fract() == 0.0).map(|x| x as i64))
+                .map(TypedValue::Int),
+            Value::Bool(b) => Some(TypedValue::Int(*b as i64)),
+            _ => None,
+        },
+        Bool => match value {
+            Value::Bool(b) => Some(TypedValue::Bool(*b)),
+            Value::Number(n) => match n.as_f64() {
+                Some(x) if x == 0.0 => Some(TypedValue::Bool(false)),
+                Some(x) if x == 1.0 => Some(TypedValue::Bool(true)),
+                _ => None,
+            },
+            _ => None,
+        },
+        Str => match value {
+            Value::String(s) => Some(TypedValue::Text(s.to_owned())),
+            Value::Number(_) | Value::Bool(_) => Some(TypedValue::Text(value.to_string())),
+            _ => None,
+        },
+    }
+}
+
+impl prost::Message for TypedRowBQ {
+    fn encode_raw(&self, buf: &mut impl BufMut) {
+        for (tag, value) in &self.values {
+            match value {
+                TypedValue::Text(v) => encoding::string::encode(*tag, v, buf),
+                TypedValue::Int(v) => encoding::int64::encode(*tag, v, buf),
+                TypedValue::Bool(v) => encoding::bool::encode(*tag, v, buf),
+            }
+        }
+    }
+
+    fn merge_field(
+        &mut self,
+        tag: u32,
+        wire_type: WireType,
+        buf: &mut impl Buf,
+        ctx: DecodeContext,
+    ) -> Result<(), prost::DecodeError> {
+        // Só é usado para enviar, nunca é lido de volta
+        encoding::skip_field(wire_type, tag, buf, ctx)
+    }
+
+    fn encoded_len(&self) -> usize {
+        self.values
+            .iter()
+            .map(|(tag, value)| match value {
+                TypedValue::Text(v) => encoding::string::encoded_len(*tag, v),
+                TypedValue::Int(v) => encoding::int64::encoded_len(*tag, v),
+                TypedValue::Bool(v) => encoding::bool::encoded_len(*tag, v),
+            })
+            .sum()
+    }
+
+    fn clear(&mut self) {
+        self.values.clear();
+    }
+}
+
+#[test]
+fn test_typed_rows() {
+    let payload = serde_json::json!({
+        "timestamp": "2024-05-02T10:11:12",
+        "L1": 1,
+        "Tamb": 25.5,
+        "P0": 120,
+        "State": "Enabled",
+        "newProp": 3,
+        "bad-name": 1,
+        "list": [1, 2],
+    });
+    let row = RowBQ {
+        timestamp: "2024-05-02T13:11:12".to_owned(),
+        dev_id: "DAC210191234".to_owned(),
+        payload: payload.to_string(),
+    };
+
+    let columns = columns_for_rows(&[(&row, payload.clone())]);
+    assert_eq!(columns.len(), DAC_COLUMNS.len() + 1);
+    let new_prop = columns.iter().find(|c| c.name == "newProp").unwrap();
+    assert_eq!(new_prop.typ, Numeric);
+    assert!(!columns
+        .iter()
+        .any(|c| c.name == "bad-name" || c.name == "list"));
+
+    // A tabela já tinha "p0" (em minúsculas); essa não falta
+    let missing = missing_columns(&["timestamp", "p0"], &columns);
+    assert!(!missing.iter().any(|c| c.name == "P0"));
+    assert!(missing.iter().any(|c| c.name == "newProp"));
+
+    let typed = TypedRowBQ::new(&row, &payload, &columns);
+    let index = |name: &str| 4 + columns.iter().position(|c| c.name == name).unwrap() as u32;
+    let value = |name: &str| {
+        typed
+            .values
+            .iter()
+            .find(|(tag, _)| *tag == index(name))
+            .map(|(_, v)| v.clone())
+    };
+    assert_eq!(value("L1"), Some(TypedValue::Bool(true)));
+    assert_eq!(value("Tamb"), Some(TypedValue::Text("25.5".to_owned())));
+    assert_eq!(value("P0"), Some(TypedValue::Int(120)));
+    assert_eq!(value("Tsuc"), None);
+
+    let encoded = prost::Message::encode_to_vec(&typed);
+    assert_eq!(encoded.len(), prost::Message::encoded_len(&typed));
+
+    assert_eq!(
+        row_family(
+            "DRI000000001",
+            &serde_json::json!({"type": "CHILLER-CARRIER-30HXE"})
+        ),
+        TableFamily::DriChiller
+    );
+    assert_eq!(families_for_table("dri_telemetry").len(), 2);
+}
@@ -0,0 +1,429 @@
+use crate::envvars_loader;
+use crate::lib_metrics::REGISTRY;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/*
+Fila em disco (dead-letter) com o que não foi possível gravar em um destino (DynamoDB, BigQuery).
+Cada item fica em um arquivo <pasta>/<destino>/<id>.json e é tentado de novo com backoff exponencial
+(30s, 1min, 2min... até 1h entre tentativas). Se o total passar do limite, os mais antigos são descartados.
+*/
+
+const BACKOFF_BASE_S: u64 = 30;
+const BACKOFF_MAX_S: u64 = 3600;
+
+#[derive(Clone)]
+pub struct DeadLetterConfig {
+    pub root_dir: String,
+    pub max_bytes: u64, // Por destino
+}
+
+// Retorna None se a fila estiver desativada (DEAD_LETTER_MAX_MB=0)
+pub fn load_config() -> Result<Option<DeadLetterConfig>, String> {
+    let root_dir = envvars_loader::get_var_string_optional("DEAD_LETTER_DIR")
+        .unwrap_or_else(|| "./dead_letters".to_owned());
+    let max_mb: Option<u64> = envvars_loader::get_var_structure_optional("DEAD_LETTER_MAX_MB")?;
+    let max_mb = max_mb.unwrap_or(1024);
+    if max_mb == 0 {
+        return Ok(None);
+    }
+    Ok(Some(DeadLetterConfig {
+        root_dir,
+        max_bytes: max_mb * 1024 * 1024,
+    }))
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct DeadLetter {
+    pub id: u64,
+    pub table: String,
+    pub items: Vec<serde_json::Value>, // DynamoDB: um payload; BigQuery: as linhas do lote
+    pub error: String,
+    #[serde(rename = "tsFailed")]
+    pub ts_failed: u64,
+    pub attempts: u32,
+    #[serde(rename = "nextRetryTs")]
+    pub next_retry_ts: u64,
+}
+
+// Resumo usado na listagem, sem os itens
+#[derive(Serialize, Clone)]
+pub struct DeadLetterInfo {
+    pub id: u64,
+    pub table: String,
+    #[serde(rename = "itemsCount")]
+    pub items_count: usize,
+    pub bytes: u64,
+    pub error: String,
+    #[serde(rename = "tsFailed")]
+    pub ts_failed: u64,
+    pub attempts: u32,
+    #[serde(rename = "nextRetryTs")]
+    pub next_retry_ts: u64,
+}
+
+struct QueueState {
+    next_id: u64,
+    total_bytes: u64,
+    entries: BTreeMap<u64, DeadLetterInfo>,
+}
+
+pub struct DeadLetterQueue {
+    pub name: &'static str,
+    dir: PathBuf,
+    max_bytes: u64,
+    state: Mutex<QueueState>,
+}
+
+// Filas por destino, no GlobalVars do broker2db e do telemetry_service
+pub struct DeadLetters {
+    pub dynamodb: Option<DeadLetterQueue>,
+    pub bigquery: Option<DeadLetterQueue>,
+}
+
+impl DeadLetters {
+    pub fn open(config: &Option<DeadLetterConfig>, dynamodb: bool, bigquery: bool) -> DeadLetters {
+        let open_queue = |enabled: bool, name: &'static str| {
+            let config = config.as_ref().filter(|_| enabled)?;
+            match DeadLetterQueue::open(&config.root_dir, name, config.max_bytes) {
+                Ok(queue) => Some(queue),
+                Err(err) => {
+                    crate::LOG.append_log_tag_msg("ERROR", &format!("[41] {}", err));
+                    None
+                }
+            }
+        };
+        DeadLetters {
+            dynamodb: open_queue(dynamodb, "dynamodb"),
+            bigquery: open_queue(bigquery, "bigquery"),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&DeadLetterQueue> {
+        match name {
+            "dynamodb" => self.dynamodb.as_ref(),
+            "bigquery" => self.bigquery.as_ref(),
+            _ => None,
+        }
+    }
+}
+
+pub fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_millis()
+        .try_into()
+        .expect("timestamp too large")
+}
+
+// Tempo de espera antes da próxima tentativa, depois de `attempts` tentativas com falha
+pub fn backoff_ms(attempts: u32) -> u64 {
+    let exp = attempts.saturating_sub(1).min(16);
+    (BACKOFF_BASE_S << exp).min(BACKOFF_MAX_S) * 1000
+}
+
+impl DeadLetterQueue {
+    pub fn open(root_dir: &str, name: &'static str, max_bytes: u64) -> Result<Self, String> {
+        let dir = Path::new(root_dir).join(name);
+        std::fs::create_dir_all(&dir).map_err(|err| format!("{} {}", dir.display(), err))?;
+
+        // Os itens de execuções anteriores continuam na fila
+        let mut entries = BTreeMap::new();
+        let mut total_bytes = 0;
+        let files = std::fs::read_dir(&dir).map_err(|err| format!("{} {}", dir.display(), err))?;
+        for file in files.flatten() {
+            let path = file.path();
+            if path.extension().and_then(|x| x.to_str()) != Some("json") {
+                continue;
+            }
+            match read_entry(&path) {
+                Ok((entry, bytes)) => {
+                    total_bytes += bytes;
+                    entries.insert(entry.id, entry_info(&entry, bytes));
+                }
+                Err(err) => {
+                    crate::LOG.append_log_tag_msg("ERROR", &format!("[42] {}", err));
+                }
+            }
+        }
+        let next_id = entries.keys().next_back().map(|id| id + 1).unwrap_or(1);
+
+        let queue = DeadLetterQueue {
+            name,
+            dir,
+            max_bytes,
+            state: Mutex::new(QueueState {
+                next_id,
+                total_bytes,
+                entries,
+            }),
+        };
+        queue.update_gauges(&queue.state.lock().unwrap());
+        Ok(queue)
+    }
+
+    fn entry_path(&self, id: u64) -> PathBuf {
+        self.dir.join(format!("{:012}.json", id))
+    }
+
+    fn write_entry(&self, entry: &DeadLetter) -> Result<u64, String> {
+        let path = self.entry_path(entry.id);
+        let tmp_path = path.with_extension("tmp");
+        let content = serde_json::to_vec(entry).map_err(|err| err.to_string())?;
+        std::fs::write(&tmp_path, &content)
+            .and_then(|_| std::fs::rename(&tmp_path, &path))
+            .map_err(|err| format!("{} {}", path.display(), err))?;
+        Ok(content.len() as u64)
+    }
+
+    fn delete_entry(&self, state: &mut QueueState, id: u64) -> bool {
+        let Some(info) = state.entries.remove(&id) else {
+            return false;
+        };
+        state.total_bytes -= info.bytes;
+        if let Err(err) = std::fs::remove_file(self.entry_path(id)) {
+            crate::LOG.append_log_tag_msg("ERROR", &format!("[43] {} {}", self.name, err));
+        }
+        true
+    }
+
+    fn update_gauges(&self, state: &QueueState) {
+        REGISTRY
+            .gauge(
+                "dead_letter_entries",
+                "Itens na fila de dead-letter, por destino",
+                &[("queue", self.name)],
+            )
+            .set(state.entries.len() as f64);
+        REGISTRY
+            .gauge(
+                "dead_letter_bytes",
+                "Tamanho em disco da fila de dead-letter, por destino",
+                &[("queue", self.name)],
+            )
+            .set(state.total_bytes as f64);
+    }
+
+    pub fn push(&self, table: &str, items: Vec<serde_json::Value>, error: &str) {
+        let mut state = self.state.lock().unwrap();
+        let now = now_millis();
+        let entry = DeadLetter {
+            id: state.next_id,
+            table: table.to_owned(),
+            items,
+            error: error.to_owned(),
+            ts_failed: now,
+            attempts: 1,
+            next_retry_ts: now + backoff_ms(1),
+        };
+        state.next_id += 1;
+        let bytes = match self.write_entry(&entry) {
+            Ok(v) => v,
+            Err(err) => {
+                crate::LOG.append_log_tag_msg("ERROR", &format!("[44] {} {}", self.name, err));
+                return;
+            }
+        };
+        state.total_bytes += bytes;
+        state.entries.insert(entry.id, entry_info(&entry, bytes));
+        REGISTRY
+            .counter(
+                "dead_letter_pushed_total",
+                "Itens colocados na fila de dead-letter, por destino",
+                &[("queue", self.name)],
+            )
+            .inc();
+
+        // Limite de espaço: descarta os mais antigos
+        while state.total_bytes > self.max_bytes && state.entries.len() > 1 {
+            let Some(oldest) = state.entries.keys().next().copied() else {
+                break;
+            };
+            self.delete_entry(&mut state, oldest);
+            REGISTRY
+                .counter(
+                    "dead_letter_dropped_total",
+                    "Itens descartados da fila de dead-letter por falta de espaço, por destino",
+                    &[("queue", self.name)],
+                )
+                .inc();
+        }
+        self.update_gauges(&state);
+    }
+
+    // Itens com a próxima tentativa vencida, dos mais antigos para os mais novos
+    pub fn due(&self, now: u64, limit: usize) -> Vec<DeadLetter> {
+        let state = self.state.lock().unwrap();
+        let ids: Vec<u64> = state
+            .entries
+            .values()
+            .filter(|x| x.next_retry_ts <= now)
+            .take(limit)
+            .map(|x| x.id)
+            .collect();
+        ids.into_iter()
+            .filter_map(|id| self.get_locked(id))
+            .collect()
+    }
+
+    fn get_locked(&self, id: u64) -> Option<DeadLetter> {
+        match read_entry(&self.entry_path(id)) {
+            Ok((entry, _bytes)) => Some(entry),
+            Err(err) => {
+                crate::LOG.append_log_tag_msg("ERROR", &format!("[45] {}", err));
+                None
+            }
+        }
+    }
+
+    pub fn get(&self, id: u64) -> Option<DeadLetter> {
+        let state = self.state.lock().unwrap();
+        if !state.entries.contains_key(&id) {
+            return None;
+        }
+        self.get_locked(id)
+    }
+
+    // Gravado no destino, sai da fila
+    pub fn on_retry_success(&self, id: u64) {
+        let mut state = self.state.lock().unwrap();
+        if self.delete_entry(&mut state, id) {
+            REGISTRY
+                .counter(
+                    "dead_letter_recovered_total",
+                    "Itens da fila de dead-letter gravados com sucesso, por destino",
+                    &[("queue", self.name)],
+                )
+                .inc();
+        }
+        self.update_gauges(&state);
+    }
+
+    pub fn on_retry_failed(&self, mut entry: DeadLetter, error: &str) {
+        let mut state = self.state.lock().unwrap();
+        if !state.entries.contains_key(&entry.id) {
+            // Removido pelo endpoint enquanto era tentado
+            return;
+        }
+        entry.attempts += 1;
+        entry.error = error.to_owned();
+        entry.next_retry_ts = now_millis() + backoff_ms(entry.attempts);
+        match self.write_entry(&entry) {
+            Ok(bytes) => {
+                if let Some(old) = state.entries.insert(entry.id, entry_info(&entry, bytes)) {
+                    state.total_bytes -= old.bytes;
+                }
+                state.total_bytes += bytes;
+            }
+            Err(err) => {
+                crate::LOG.append_log_tag_msg("ERROR", &format!("[46] {} {}", self.name, err));
+            }
+        }
+        self.update_gauges(&state);
+    }
+
+    pub fn list(&self, offset: usize, limit: usize) -> (usize, Vec<DeadLetterInfo>) {
+        let state = self.state.lock().unwrap();
+        let list = state
+            .entries
+            .values()
+            .skip(offset)
+            .take(limit)
+            .cloned()
+            .collect();
+        (state.entries.len(), list)
+    }
+
+    pub fn total_bytes(&self) -> u64 {
+        self.state.lock().unwrap().total_bytes
+    }
+
+    // Antecipa a próxima tentativa para agora. Sem ids, vale para a fila toda.
+    pub fn replay_now(&self, ids: Option<&[u64]>) -> usize {
+        let mut state = self.state.lock().unwrap();
+        let mut count = 0;
+        for info in state.entries.values_mut() {
+            if ids.map_or(true, |ids| ids.contains(&info.id)) {
+                info.next_retry_ts = 0;
+                count += 1;
+            }
+        }
+        count
+    }
+
+    // Remove da fila sem gravar no destino. Sem ids, esvazia a fila.
+    pub fn purge(&self, ids: Option<&[u64]>) -> usize {
+        let mut state = self.state.lock().unwrap();
+        let ids: Vec<u64> = match ids {
+            Some(ids) => ids.to_vec(),
+            None => state.entries.keys().copied().collect(),
+        };
+        let count = ids
+            .into_iter()
+            .filter(|id| self.delete_entry(&mut state, *id))
+            .count();
+        self.update_gauges(&state);
+        count
+    }
+}
+
+fn entry_info(entry: &DeadLetter, bytes: u64) -> DeadLetterInfo {
+    DeadLetterInfo {
+        id: entry.id,
+        table: entry.table.to_owned(),
+        items_count: entry.items.len(),
+        bytes,
+        error: entry.error.to_owned(),
+        ts_failed: entry.ts_failed,
+        attempts: entry.attempts,
+        next_retry_ts: entry.next_retry_ts,
+    }
+}
+
+fn read_entry(path: &Path) -> Result<(DeadLetter, u64), String> {
+    let content = std::fs::read(path).map_err(|err| format!("{} {}", path.display(), err))?;
+    let entry = serde_json::from_slice::<DeadLetter>(&content)
+        .map_err(|err| format!("{} {}", path.display(), err))?;
+    Ok((entry, content.len() as u64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dead_letter_queue() {
+        assert_eq!(backoff_ms(1), 30_000);
+        assert_eq!(backoff_ms(3), 120_000);
+        assert_eq!(backoff_ms(20), 3_600_000);
+
+        let root_dir = std::env::temp_dir().join(format!("dlq-test-{}", now_millis()));
+        let root_dir = root_dir.to_str().unwrap();
+        let queue = DeadLetterQueue::open(root_dir, "dynamodb", 400).unwrap();
+        let payload =
+            serde_json::json!({ "dev_id": "DAC210191234", "timestamp": "2024-01-01T00:00:00" });
+        queue.push("DAC21019XXXX_RAW", vec![payload.clone()], "timeout");
+        queue.push("DAC21019XXXX_RAW", vec![payload.clone()], "timeout");
+        assert!(queue.due(now_millis(), 10).is_empty());
+        assert_eq!(queue.replay_now(Some(&[2])), 1);
+        let due = queue.due(now_millis(), 10);
+        assert_eq!(due.len(), 1);
+        queue.on_retry_failed(due[0].clone(), "timeout again");
+        assert_eq!(queue.get(2).unwrap().attempts, 2);
+
+        // O limite de espaço descarta os mais antigos; o que está em disco é lido de volta
+        queue.push("DAC21019XXXX_RAW", vec![payload], "timeout");
+        let (total, list) = queue.list(0, 10);
+        assert!(total < 3);
+        assert_eq!(list.last().unwrap().id, 3);
+        let reopened = DeadLetterQueue::open(root_dir, "dynamodb", 400).unwrap();
+        assert_eq!(reopened.list(0, 10).0, total);
+        assert_eq!(reopened.purge(None), total);
+        assert_eq!(reopened.total_bytes(), 0);
+        std::fs::remove_dir_all(root_dir).unwrap();
+    }
+}
@@ -9,6 +9,7 @@ use std::sync::Arc;
 
 type OnTableNotFound = dyn Fn(&Arc<GlobalVars>, &str) + Send + Sync;
 
+#[derive(Clone)]
 pub struct AWSConfig {
     pub access_key_id: String,
     pub secret_access_key: String,
@@ -1,4 +1,5 @@
 use super::client::{AWSConfig, DynamoDBClientDiel};
+use crate::lib_metrics::{BUCKETS_DB_PAGE, REGISTRY};
 use rusoto_core::RusotoError;
 use rusoto_dynamodb::{AttributeValue, DynamoDb, DynamoDbClient, QueryError, QueryInput};
 use serde_dynamo::from_items;
@@ -61,7 +62,17 @@ impl QuerierDevIdTimestamp {
     ) -> Result<rusoto_dynamodb::QueryOutput, String> {
         let mut retries = 0;
         loop {
-            match client.query(query_input.clone()).await {
+            let ts_start = std::time::Instant::now();
+            let result = client.query(query_input.clone()).await;
+            REGISTRY
+                .histogram(
+                    "dynamodb_page_seconds",
+                    "Latência de cada página de query no DynamoDB",
+                    &[],
+                    BUCKETS_DB_PAGE,
+                )
+                .observe_since(ts_start);
+            match result {
                 Ok(result_page) => {
                     if result_page.items.is_none() {
                         return Err("Query returned no items".to_owned());
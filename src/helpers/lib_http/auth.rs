@@ -0,0 +1,133 @@
+use super::types::HttpRequest;
+use serde::Deserialize;
+
+/*
+Tokens de acesso para requisições HTTP. Cada token tem um nome (para aparecer nos logs) e uma lista de escopos.
+O escopo "*" libera todas as rotas.
+Exemplo de configuração:
+  [ { "name": "integracao-x", "token": "abc123", "scopes": ["hist"] } ]
+O token pode vir no header "Authorization: Bearer <token>" ou na propriedade "token" do corpo JSON.
+*/
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct ApiToken {
+    pub name: String,
+    pub token: String,
+    pub scopes: Vec<String>,
+}
+
+impl ApiToken {
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes.iter().any(|s| s == "*" || s == scope)
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct ApiTokens {
+    pub tokens: Vec<ApiToken>,
+}
+
+pub enum AuthError {
+    MissingToken,
+    InvalidToken,
+    MissingScope(String),
+}
+
+impl ApiTokens {
+    pub fn new(tokens: Vec<ApiToken>) -> ApiTokens {
+        ApiTokens { tokens }
+    }
+
+    // Retorna o nome do token que autorizou a requisição
+    pub fn authorize(
+        &self,
+        req: &HttpRequest,
+        json_body: &serde_json::Value,
+        scope: &str,
+    ) -> Result<String, AuthError> {
+        let provided = match token_from_header(req).or_else(|| json_body["token"].as_str()) {
+            Some(x) => x,
+            None => {
+                return Err(AuthError::MissingToken);
+            }
+        };
+        let api_token = match self.tokens.iter().find(|t| t.token == provided) {
+            Some(x) => x,
+            None => {
+                return Err(AuthError::InvalidToken);
+            }
+        };
+        if !api_token.has_scope(scope) {
+            return Err(AuthError::MissingScope(api_token.name.to_owned()));
+        }
+        Ok(api_token.name.to_owned())
+    }
+}
+
+fn token_from_header(req: &HttpRequest) -> Option<&str> {
+    // Os nomes dos headers já chegam em minúsculas
+    let value = req.headers.get("authorization")?;
+    let token = value
+        .strip_prefix("Bearer ")
+        .or_else(|| value.strip_prefix("bearer "))?
+        .trim();
+    if token.is_empty() {
+        None
+    } else {
+        Some(token)
+    }
+}
+
+#[test]
+fn test_api_tokens() {
+    let tokens = ApiTokens::new(vec![
+        ApiToken {
+            name: "integracao".to_owned(),
+            token: "abc123".to_owned(),
+            scopes: vec!["hist".to_owned()],
+        },
+        ApiToken {
+            name: "admin".to_owned(),
+            token: "def456".to_owned(),
+            scopes: vec!["*".to_owned()],
+        },
+    ]);
+    let no_body = serde_json::Value::Null;
+    let with_header = |value: &str| {
+        let mut req = HttpRequest::new_get("/x");
+        req.headers
+            .insert("authorization".to_owned(), value.to_owned());
+        req
+    };
+
+    let result = tokens.authorize(&with_header("Bearer abc123"), &no_body, "hist");
+    assert_eq!(result.ok().as_deref(), Some("integracao"));
+    let result = tokens.authorize(&with_header("bearer  abc123 "), &no_body, "hist");
+    assert_eq!(result.ok().as_deref(), Some("integracao"));
+    let body = serde_json::json!({ "token": "abc123" });
+    let result = tokens.authorize(&HttpRequest::new_get("/x"), &body, "hist");
+    assert_eq!(result.ok().as_deref(), Some("integracao"));
+    let result = tokens.authorize(&with_header("Bearer def456"), &no_body, "qualquer");
+    assert_eq!(result.ok().as_deref(), Some("admin"));
+
+    assert!(matches!(
+        tokens.authorize(&HttpRequest::new_get("/x"), &no_body, "hist"),
+        Err(AuthError::MissingToken)
+    ));
+    assert!(matches!(
+        tokens.authorize(&with_header("Bearer "), &no_body, "hist"),
+        Err(AuthError::MissingToken)
+    ));
+    assert!(matches!(
+        tokens.authorize(&with_header("Basic abc123"), &no_body, "hist"),
+        Err(AuthError::MissingToken)
+    ));
+    assert!(matches!(
+        tokens.authorize(&with_header("Bearer xyz"), &body, "hist"),
+        Err(AuthError::InvalidToken)
+    ));
+    assert!(matches!(
+        tokens.authorize(&with_header("Bearer abc123"), &no_body, "admin"),
+        Err(AuthError::MissingScope(name)) if name == "integracao"
+    ));
+}
@@ -0,0 +1,397 @@
+use super::auth::{ApiTokens, AuthError};
+use super::response::{respond_http_plain_text, send_response};
+use super::types::{HttpRequest, HttpResponse};
+use crate::GlobalVars;
+use futures::future::BoxFuture;
+use std::convert::Infallible;
+use std::sync::Arc;
+use tokio::net::TcpStream;
+
+/*
+Tabela de rotas compartilhada pelos serviços HTTP. Cada rota declara método, visibilidade, política de
+autenticação e como o corpo deve ser interpretado. Antes de chamar o handler a requisição passa pela
+cadeia de middlewares: visibilidade -> método -> corpo -> autenticação -> middlewares do serviço.
+Se algum middleware retornar uma resposta, ela é enviada e o handler não é chamado.
+*/
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Method {
+    Any,
+    Get,
+    Post,
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Visibility {
+    // Só aceita requisições de 127.0.0.1
+    Internal,
+    // Aceita requisições de qualquer origem
+    Public,
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum AuthPolicy {
+    None,
+    // Token com o escopo informado, exigido só nas requisições externas
+    ExternalToken(&'static str),
+    // Token com o escopo informado, exigido sempre
+    Token(&'static str),
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum BodyParser {
+    Raw,
+    Json,
+    Text,
+}
+
+pub struct RouteSpec {
+    pub method: Method,
+    pub path: &'static str,
+    pub visibility: Visibility,
+    pub auth: AuthPolicy,
+    pub body: BodyParser,
+}
+
+pub enum RequestBody {
+    Raw,
+    Json(serde_json::Value),
+    Text(String),
+}
+
+pub struct RouteRequest {
+    pub req: HttpRequest,
+    pub is_internal: bool,
+    pub body: RequestBody,
+    // Nome do token que autorizou a requisição, se a rota exigiu token
+    pub token_name: Option<String>,
+}
+
+static JSON_NULL: serde_json::Value = serde_json::Value::Null;
+
+impl RouteRequest {
+    pub fn json(&self) -> &serde_json::Value {
+        match &self.body {
+            RequestBody::Json(body) => body,
+            _ => &JSON_NULL,
+        }
+    }
+    pub fn json_mut(&mut self) -> Option<&mut serde_json::Value> {
+        match &mut self.body {
+            RequestBody::Json(body) => Some(body),
+            _ => None,
+        }
+    }
+    pub fn text(&self) -> &str {
+        match &self.body {
+            RequestBody::Text(body) => body,
+            _ => "",
+        }
+    }
+}
+
+pub enum RouteResult<T> {
+    // Resposta pronta para ser enviada
+    Respond(HttpResponse),
+    // O serviço vai cuidar da resposta depois (ex.: rusthist coloca a requisição na fila de compilação)
+    Defer(T),
+}
+
+pub type SyncHandler<T> =
+    fn(&RouteRequest, &Arc<GlobalVars>) -> Result<RouteResult<T>, HttpResponse>;
+pub type AsyncHandler<T> = for<'a> fn(
+    &'a RouteRequest,
+    &'a Arc<GlobalVars>,
+) -> BoxFuture<'a, Result<RouteResult<T>, HttpResponse>>;
+
+pub enum Handler<T> {
+    Sync(SyncHandler<T>),
+    Async(AsyncHandler<T>),
+}
+
+pub struct Route<T> {
+    pub spec: RouteSpec,
+    pub handler: Handler<T>,
+}
+
+impl<T> Route<T> {
+    pub fn new(
+        method: Method,
+        path: &'static str,
+        visibility: Visibility,
+        auth: AuthPolicy,
+        body: BodyParser,
+        handler: Handler<T>,
+    ) -> Route<T> {
+        Route {
+            spec: RouteSpec {
+                method,
+                path,
+                visibility,
+                auth,
+                body,
+            },
+            handler,
+        }
+    }
+}
+
+pub type Middleware =
+    Box<dyn Fn(&RouteSpec, &mut RouteRequest) -> Result<(), HttpResponse> + Send + Sync>;
+
+pub struct Router<T> {
+    routes: Vec<Route<T>>,
+    middlewares: Vec<Middleware>,
+}
+
+impl<T> Router<T> {
+    pub fn new(tokens: ApiTokens, routes: Vec<Route<T>>) -> Router<T> {
+        Router {
+            routes,
+            middlewares: vec![
+                Box::new(check_visibility),
+                Box::new(check_method),
+                Box::new(parse_body),
+                Box::new(move |spec, rreq| check_auth(&tokens, spec, rreq)),
+            ],
+        }
+    }
+
+    // Acrescenta um middleware do serviço, executado depois dos middlewares padrão
+    pub fn with_middleware(mut self, middleware: Middleware) -> Router<T> {
+        self.middlewares.push(middleware);
+        self
+    }
+
+    pub async fn dispatch(
+        &self,
+        req: HttpRequest,
+        is_internal: bool,
+        globs: &Arc<GlobalVars>,
+    ) -> RouteResult<T> {
+        let (route, rreq) = match self.route_request(req, is_internal) {
+            Ok(x) => x,
+            Err(response) => {
+                return RouteResult::Respond(response);
+            }
+        };
+
+        let result = match &route.handler {
+            Handler::Sync(handler) => handler(&rreq, globs),
+            Handler::Async(handler) => handler(&rreq, globs).await,
+        };
+        match result {
+            Ok(x) => x,
+            Err(response) => RouteResult::Respond(response),
+        }
+    }
+
+    // Encontra a rota e passa a requisição pelos middlewares, sem chamar o handler
+    fn route_request(
+        &self,
+        req: HttpRequest,
+        is_internal: bool,
+    ) -> Result<(&Route<T>, RouteRequest), HttpResponse> {
+        let route = match self.routes.iter().find(|r| r.spec.path == req.path) {
+            Some(x) => x,
+            None => {
+                crate::LOG.append_log_tag_msg(
+                    "ERROR",
+                    &format!("Invalid request: {} {}", req.method, req.path),
+                );
+                return Err(respond_not_found(&req.path));
+            }
+        };
+
+        let mut rreq = RouteRequest {
+            req,
+            is_internal,
+            body: RequestBody::Raw,
+            token_name: None,
+        };
+        for middleware in &self.middlewares {
+            middleware(&route.spec, &mut rreq)?;
+        }
+        Ok((route, rreq))
+    }
+}
+
+impl Router<Infallible> {
+    // Para os serviços em que todas as rotas respondem na hora
+    pub async fn handle(
+        &self,
+        req: HttpRequest,
+        is_internal: bool,
+        mut socket: TcpStream,
+        globs: &Arc<GlobalVars>,
+    ) {
+        let response = match self.dispatch(req, is_internal, globs).await {
+            RouteResult::Respond(response) => response,
+            RouteResult::Defer(never) => match never {},
+        };
+        respond_and_log(&mut socket, &response).await;
+    }
+}
+
+pub async fn respond_and_log(socket: &mut TcpStream, response: &HttpResponse) {
+    if let Err(err) = send_response(socket, response).await {
+        crate::LOG.append_log_tag_msg("ERROR[208]", &err.to_string());
+    }
+}
+
+fn respond_not_found(path: &str) -> HttpResponse {
+    respond_http_plain_text(404, &format!("Not found: {}", path))
+}
+
+fn check_visibility(spec: &RouteSpec, rreq: &mut RouteRequest) -> Result<(), HttpResponse> {
+    if spec.visibility == Visibility::Internal && !rreq.is_internal {
+        crate::LOG.append_log_tag_msg(
+            "ERROR",
+            &format!(
+                "Invalid external request: {} {}",
+                rreq.req.method, rreq.req.path
+            ),
+        );
+        return Err(respond_not_found(&rreq.req.path));
+    }
+    Ok(())
+}
+
+fn check_method(spec: &RouteSpec, rreq: &mut RouteRequest) -> Result<(), HttpResponse> {
+    let expected = match spec.method {
+        Method::Any => {
+            return Ok(());
+        }
+        Method::Get => "GET",
+        Method::Post => "POST",
+    };
+    if !rreq.req.method.eq_ignore_ascii_case(expected) {
+        return Err(respond_http_plain_text(
+            405,
+            &format!("Method not allowed: {} {}", rreq.req.method, rreq.req.path),
+        ));
+    }
+    Ok(())
+}
+
+fn parse_body(spec: &RouteSpec, rreq: &mut RouteRequest) -> Result<(), HttpResponse> {
+    rreq.body = match spec.body {
+        BodyParser::Raw => RequestBody::Raw,
+        BodyParser::Text => {
+            let body_str = std::str::from_utf8(&rreq.req.content)
+                .map_err(|e| respond_http_plain_text(400, &format!("ERROR258: {}", e)))?;
+            RequestBody::Text(body_str.to_owned())
+        }
+        // Corpo vazio vira null, para as rotas em que os parâmetros são opcionais
+        BodyParser::Json if rreq.req.content.is_empty() => {
+            RequestBody::Json(serde_json::Value::Null)
+        }
+        BodyParser::Json => {
+            let body_str = std::str::from_utf8(&rreq.req.content)
+                .map_err(|e| respond_http_plain_text(400, &format!("ERROR263: {}", e)))?;
+            let json_body = serde_json::from_str(body_str)
+                .map_err(|e| respond_http_plain_text(400, &format!("ERROR265: {}", e)))?;
+            RequestBody::Json(json_body)
+        }
+    };
+    Ok(())
+}
+
+fn check_auth(
+    tokens: &ApiTokens,
+    spec: &RouteSpec,
+    rreq: &mut RouteRequest,
+) -> Result<(), HttpResponse> {
+    let scope = match spec.auth {
+        AuthPolicy::None => {
+            return Ok(());
+        }
+        AuthPolicy::ExternalToken(_) if rreq.is_internal => {
+            return Ok(());
+        }
+        AuthPolicy::ExternalToken(scope) => scope,
+        AuthPolicy::Token(scope) => scope,
+    };
+    match tokens.authorize(&rreq.req, rreq.json(), scope) {
+        Ok(token_name) => {
+            rreq.token_name = Some(token_name);
+            Ok(())
+        }
+        Err(AuthError::MissingToken) => Err(respond_http_plain_text(403, "Token não fornecido")),
+        Err(AuthError::InvalidToken) => Err(respond_http_plain_text(403, "Token inválido")),
+        Err(AuthError::MissingScope(token_name)) => {
+            crate::LOG.append_log_tag_msg(
+                "WARN",
+                &format!(
+                    "Token '{}' sem o escopo '{}': {}",
+                    token_name, scope, rreq.req.path
+                ),
+            );
+            Err(respond_http_plain_text(
+                403,
+                "Token sem permissão para esta rota",
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+fn test_handler(
+    _rreq: &RouteRequest,
+    _globs: &Arc<GlobalVars>,
+) -> Result<RouteResult<Infallible>, HttpResponse> {
+    Ok(RouteResult::Respond(respond_http_plain_text(200, "OK")))
+}
+
+#[test]
+fn test_router_middlewares() {
+    use super::auth::ApiToken;
+
+    let tokens = ApiTokens::new(vec![ApiToken {
+        name: "integracao".to_owned(),
+        token: "abc123".to_owned(),
+        scopes: vec!["hist".to_owned()],
+    }]);
+    #[rustfmt::skip]
+    let routes = vec![
+        Route::new(Method::Any,  "/any",      Visibility::Public,   AuthPolicy::None,                  BodyParser::Raw,  Handler::Sync(test_handler)),
+        Route::new(Method::Post, "/post",     Visibility::Public,   AuthPolicy::None,                  BodyParser::Json, Handler::Sync(test_handler)),
+        Route::new(Method::Any,  "/internal", Visibility::Internal, AuthPolicy::None,                  BodyParser::Raw,  Handler::Sync(test_handler)),
+        Route::new(Method::Post, "/hist",     Visibility::Public,   AuthPolicy::ExternalToken("hist"), BodyParser::Json, Handler::Sync(test_handler)),
+        Route::new(Method::Post, "/admin",    Visibility::Public,   AuthPolicy::Token("admin"),        BodyParser::Json, Handler::Sync(test_handler)),
+    ];
+    let router = Router::new(tokens, routes);
+    let status = |req: HttpRequest, is_internal: bool| match router.route_request(req, is_internal)
+    {
+        Ok(_) => 200,
+        Err(response) => response.status_code,
+    };
+    let post = |path: &str, body: &str| HttpRequest::new_post(path, body.as_bytes().to_vec());
+
+    // Rota e método
+    assert_eq!(status(HttpRequest::new_get("/any"), false), 200);
+    assert_eq!(status(post("/any", ""), false), 200);
+    assert_eq!(status(HttpRequest::new_get("/unknown"), true), 404);
+    assert_eq!(status(HttpRequest::new_get("/post"), false), 405);
+    assert_eq!(status(post("/post", "{}"), false), 200);
+    assert_eq!(status(post("/post", "{"), false), 400);
+    let (_, rreq) = router.route_request(post("/post", ""), false).ok().unwrap();
+    assert!(rreq.json().is_null());
+
+    // Rota interna não existe para quem vem de fora
+    assert_eq!(status(HttpRequest::new_get("/internal"), true), 200);
+    assert_eq!(status(HttpRequest::new_get("/internal"), false), 404);
+
+    // Token só de fora na ExternalToken, sempre na Token
+    assert_eq!(status(post("/hist", "{}"), true), 200);
+    assert_eq!(status(post("/hist", "{}"), false), 403);
+    assert_eq!(status(post("/hist", r#"{"token":"xyz"}"#), false), 403);
+    let (_, rreq) = router
+        .route_request(post("/hist", r#"{"token":"abc123"}"#), false)
+        .ok()
+        .unwrap();
+    assert_eq!(rreq.token_name.as_deref(), Some("integracao"));
+    assert_eq!(rreq.json()["token"], "abc123");
+    assert_eq!(status(post("/admin", "{}"), true), 403);
+    assert_eq!(status(post("/admin", r#"{"token":"abc123"}"#), true), 403);
+}
@@ -0,0 +1,74 @@
+use std::io::{BufRead, Read};
+use std::path::{Path, PathBuf};
+
+/*
+Layout dos arquivos de telemetria em disco, compartilhado entre quem grava (broker2db, telemetry_service)
+e quem lê (rusthist):
+  <root>/<table_name>/<dev_id>/<YYYY-MM-DD>.jsonl
+  <root>/<table_name>/<dev_id>/<YYYY-MM-DD>.jsonl.zst
+Uma telemetria por linha, no mesmo formato do item salvo no DynamoDB. O dia é o do "timestamp" do payload.
+*/
+
+#[derive(Clone)]
+pub struct LocalStoreConfig {
+    pub root_dir: String,
+    pub compress: bool,
+}
+
+pub fn table_dir(root_dir: &str, table_name: &str) -> PathBuf {
+    Path::new(root_dir).join(table_name)
+}
+
+pub fn day_file_path(
+    root_dir: &str,
+    table_name: &str,
+    dev_id: &str,
+    day: &str,
+    compressed: bool,
+) -> PathBuf {
+    let file_name = if compressed {
+        format!("{}.jsonl.zst", day)
+    } else {
+        format!("{}.jsonl", day)
+    };
+    table_dir(root_dir, table_name).join(dev_id).join(file_name)
+}
+
+// Nomes de tabela e dev_id viram nomes de pasta, então não podem ter separadores de caminho
+pub fn is_valid_path_part(name: &str) -> bool {
+    !name.is_empty()
+        && name != "."
+        && name != ".."
+        && !name.contains(|c: char| c == '/' || c == '\\' || c == '\0')
+}
+
+// Lê as linhas de um arquivo diário, compactado ou não. Retorna vazio se o arquivo não existir.
+pub fn read_day_file_lines(path: &Path) -> Result<Vec<String>, String> {
+    let file = match std::fs::File::open(path) {
+        Ok(x) => x,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(Vec::new());
+        }
+        Err(err) => {
+            return Err(format!("[58] {} {}", path.display(), err));
+        }
+    };
+    let reader: Box<dyn Read> = if path.extension().map_or(false, |ext| ext == "zst") {
+        // Cada gravação acrescenta um frame zstd ao final do arquivo, o decoder lê todos em sequência
+        Box::new(
+            zstd::stream::read::Decoder::new(file)
+                .map_err(|err| format!("[65] {} {}", path.display(), err))?,
+        )
+    } else {
+        Box::new(file)
+    };
+    let mut lines = Vec::new();
+    for line in std::io::BufReader::new(reader).lines() {
+        let line = line.map_err(|err| format!("[72] {} {}", path.display(), err))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        lines.push(line);
+    }
+    Ok(lines)
+}
@@ -0,0 +1,124 @@
+use super::layout::{day_file_path, is_valid_path_part, LocalStoreConfig};
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::PathBuf;
+use tokio::{sync::mpsc, time::Instant};
+
+pub enum SaveToLocalEvent {
+    // (table_name, dev_id, day "YYYY-MM-DD", linha JSON)
+    LineToSave(String, String, String, String),
+    TimeTick,
+}
+
+pub async fn task_save_to_local_store(
+    config: LocalStoreConfig,
+    mut receiver: mpsc::Receiver<SaveToLocalEvent>,
+    max_time_interval: u128, // 2800 ms
+) {
+    // As linhas são agrupadas por arquivo e gravadas de uma vez, para não abrir o arquivo a cada payload
+    // e para que cada frame zstd tenha um tamanho razoável.
+    let mut file_queues: HashMap<PathBuf, (Instant, Vec<String>)> = HashMap::new();
+
+    loop {
+        let event = receiver.recv().await.expect("Erro ao receber do mpsc");
+        match event {
+            SaveToLocalEvent::LineToSave(table_name, dev_id, day, line) => {
+                if !(is_valid_path_part(&table_name)
+                    && is_valid_path_part(&dev_id)
+                    && is_valid_path_part(&day))
+                {
+                    crate::LOG.append_log_tag_msg(
+                        "ERROR",
+                        &format!("[29] Invalid local store path: {table_name} {dev_id} {day}"),
+                    );
+                    continue;
+                }
+                let path = day_file_path(
+                    &config.root_dir,
+                    &table_name,
+                    &dev_id,
+                    &day,
+                    config.compress,
+                );
+                let queue = file_queues
+                    .entry(path)
+                    .or_insert_with(|| (Instant::now(), Vec::new()));
+                if queue.1.is_empty() {
+                    queue.0 = Instant::now();
+                }
+                queue.1.push(line);
+            }
+            SaveToLocalEvent::TimeTick => {
+                // Nothing to do here
+            }
+        }
+
+        for (path, file_queue) in file_queues.iter_mut() {
+            if file_queue.1.is_empty() {
+                continue;
+            }
+            let need_send = (file_queue.1.len() >= 3000)
+                || (file_queue.0.elapsed().as_millis() > max_time_interval);
+            if !need_send {
+                continue;
+            }
+            let lines: Vec<String> = file_queue.1.drain(..).collect();
+            if let Err(err) = append_lines(path, &lines, config.compress) {
+                crate::LOG.append_log_tag_msg("ERROR", &format!("[61] {:?}", err));
+            }
+        }
+        // Arquivos de dias anteriores param de receber linhas, não precisam continuar no mapa
+        file_queues.retain(|_, queue| !queue.1.is_empty() || queue.0.elapsed().as_secs() < 3600);
+    }
+}
+
+fn append_lines(path: &PathBuf, lines: &[String], compress: bool) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|err| format!("{} {}", dir.display(), err))?;
+    }
+    let mut content = String::with_capacity(lines.iter().map(|x| x.len() + 1).sum());
+    for line in lines {
+        content.push_str(line);
+        content.push('\n');
+    }
+    let content = if compress {
+        zstd::stream::encode_all(content.as_bytes(), 3)
+            .map_err(|err| format!("{} {}", path.display(), err))?
+    } else {
+        content.into_bytes()
+    };
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| file.write_all(&content))
+        .map_err(|err| format!("{} {}", path.display(), err))
+}
+
+pub async fn task_force_save_to_local_store(sender: mpsc::Sender<SaveToLocalEvent>) {
+    // Garante que as linhas pendentes sejam gravadas mesmo sem chegar novos payloads
+    loop {
+        tokio::time::sleep(std::time::Duration::from_millis(3000)).await;
+        sender.send(SaveToLocalEvent::TimeTick).await.unwrap();
+    }
+}
+
+pub fn create_channel() -> (
+    mpsc::Sender<SaveToLocalEvent>,
+    mpsc::Receiver<SaveToLocalEvent>,
+) {
+    mpsc::channel::<SaveToLocalEvent>(20000)
+}
+
+pub async fn push_line_to_storage(
+    sender: &mpsc::Sender<SaveToLocalEvent>,
+    table_name: String,
+    dev_id: String,
+    day: String,
+    line: String,
+) -> Result<(), String> {
+    sender
+        .send(SaveToLocalEvent::LineToSave(table_name, dev_id, day, line))
+        .await
+        .map_err(|err| format!("{:?}", err))
+}
@@ -0,0 +1,382 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+/*
+Registro de métricas compartilhado pelos serviços, exposto no formato texto do Prometheus em /metrics.
+Counters só crescem (o Prometheus calcula as taxas), gauges guardam o valor atual e histogramas
+acumulam as observações em buckets.
+Métricas que não pertencem a nenhum GlobalVars (ex.: latência do DynamoDB) ficam no REGISTRY global;
+os contadores de estatística de cada serviço são escritos direto no MetricsText pelo handler de /metrics.
+*/
+
+pub static REGISTRY: MetricsRegistry = MetricsRegistry {
+    entries: Mutex::new(Vec::new()),
+};
+
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+// Buckets em segundos
+pub const BUCKETS_DB_PAGE: &[f64] = &[0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+pub const BUCKETS_COMPILATION: &[f64] = &[0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0];
+
+pub struct Counter {
+    value: AtomicU64,
+}
+impl Counter {
+    pub fn inc(&self) {
+        self.value.fetch_add(1, Ordering::Relaxed);
+    }
+    pub fn add(&self, n: u64) {
+        self.value.fetch_add(n, Ordering::Relaxed);
+    }
+    pub fn get(&self) -> u64 {
+        self.value.load(Ordering::Relaxed)
+    }
+}
+
+pub struct Gauge {
+    bits: AtomicU64,
+}
+impl Gauge {
+    pub fn set(&self, value: f64) {
+        self.bits.store(value.to_bits(), Ordering::Relaxed);
+    }
+    pub fn get(&self) -> f64 {
+        f64::from_bits(self.bits.load(Ordering::Relaxed))
+    }
+}
+
+pub struct Histogram {
+    bounds: Vec<f64>,
+    buckets: Vec<AtomicU64>,
+    count: AtomicU64,
+    sum_bits: AtomicU64,
+}
+impl Histogram {
+    fn new(bounds: &[f64]) -> Histogram {
+        Histogram {
+            bounds: bounds.to_vec(),
+            buckets: bounds.iter().map(|_| AtomicU64::new(0)).collect(),
+            count: AtomicU64::new(0),
+            sum_bits: AtomicU64::new(0f64.to_bits()),
+        }
+    }
+
+    pub fn observe(&self, value: f64) {
+        for (bound, bucket) in self.bounds.iter().zip(self.buckets.iter()) {
+            if value <= *bound {
+                bucket.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        self.count.fetch_add(1, Ordering::Relaxed);
+        let _ = self
+            .sum_bits
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| {
+                Some((f64::from_bits(bits) + value).to_bits())
+            });
+    }
+
+    pub fn observe_since(&self, start: std::time::Instant) {
+        self.observe(start.elapsed().as_secs_f64());
+    }
+}
+
+enum Metric {
+    Counter(Arc<Counter>),
+    Gauge(Arc<Gauge>),
+    Histogram(Arc<Histogram>),
+}
+
+struct Entry {
+    name: &'static str,
+    help: &'static str,
+    labels: Vec<(&'static str, String)>,
+    metric: Metric,
+}
+
+pub struct MetricsRegistry {
+    entries: Mutex<Vec<Entry>>,
+}
+
+impl MetricsRegistry {
+    // As funções abaixo retornam a métrica já registrada com o mesmo nome e labels, ou registram uma nova
+    pub fn counter(
+        &self,
+        name: &'static str,
+        help: &'static str,
+        labels: &[(&'static str, &str)],
+    ) -> Arc<Counter> {
+        let metric = self.get_or_insert(name, help, labels, || {
+            Metric::Counter(Arc::new(Counter {
+                value: AtomicU64::new(0),
+            }))
+        });
+        match metric {
+            Metric::Counter(x) => x,
+            _ => panic!("Métrica {name} já registrada com outro tipo"),
+        }
+    }
+
+    pub fn gauge(
+        &self,
+        name: &'static str,
+        help: &'static str,
+        labels: &[(&'static str, &str)],
+    ) -> Arc<Gauge> {
+        let metric = self.get_or_insert(name, help, labels, || {
+            Metric::Gauge(Arc::new(Gauge {
+                bits: AtomicU64::new(0f64.to_bits()),
+            }))
+        });
+        match metric {
+            Metric::Gauge(x) => x,
+            _ => panic!("Métrica {name} já registrada com outro tipo"),
+        }
+    }
+
+    pub fn histogram(
+        &self,
+        name: &'static str,
+        help: &'static str,
+        labels: &[(&'static str, &str)],
+        bounds: &[f64],
+    ) -> Arc<Histogram> {
+        let metric = self.get_or_insert(name, help, labels, || {
+            Metric::Histogram(Arc::new(Histogram::new(bounds)))
+        });
+        match metric {
+            Metric::Histogram(x) => x,
+            _ => panic!("Métrica {name} já registrada com outro tipo"),
+        }
+    }
+
+    fn get_or_insert(
+        &self,
+        name: &'static str,
+        help: &'static str,
+        labels: &[(&'static str, &str)],
+        create: impl FnOnce() -> Metric,
+    ) -> Metric {
+        let mut entries = self.entries.lock().unwrap();
+        let existing = entries.iter().position(|e| {
+            e.name == name
+                && e.labels.len() == labels.len()
+                && e.labels
+                    .iter()
+                    .zip(labels.iter())
+                    .all(|(a, b)| a.0 == b.0 && a.1 == b.1)
+        });
+        let index = match existing {
+            Some(index) => index,
+            None => {
+                entries.push(Entry {
+                    name,
+                    help,
+                    labels: labels.iter().map(|(k, v)| (*k, v.to_string())).collect(),
+                    metric: create(),
+                });
+                entries.len() - 1
+            }
+        };
+        let metric = &entries[index].metric;
+        match metric {
+            Metric::Counter(x) => Metric::Counter(x.clone()),
+            Metric::Gauge(x) => Metric::Gauge(x.clone()),
+            Metric::Histogram(x) => Metric::Histogram(x.clone()),
+        }
+    }
+
+    pub fn render(&self, out: &mut MetricsText) {
+        let entries = self.entries.lock().unwrap();
+        // Séries com o mesmo nome precisam ficar juntas, embaixo de um único HELP/TYPE
+        let mut names: Vec<&'static str> = Vec::new();
+        for entry in entries.iter() {
+            if !names.contains(&entry.name) {
+                names.push(entry.name);
+            }
+        }
+        for name in names {
+            let mut header_written = false;
+            for entry in entries.iter().filter(|e| e.name == name) {
+                let labels: Vec<(&str, &str)> =
+                    entry.labels.iter().map(|(k, v)| (*k, v.as_str())).collect();
+                if !header_written {
+                    let kind = match entry.metric {
+                        Metric::Counter(_) => "counter",
+                        Metric::Gauge(_) => "gauge",
+                        Metric::Histogram(_) => "histogram",
+                    };
+                    out.family(name, kind, entry.help);
+                    header_written = true;
+                }
+                match &entry.metric {
+                    Metric::Counter(x) => out.sample(name, &labels, x.get() as f64),
+                    Metric::Gauge(x) => out.sample(name, &labels, x.get()),
+                    Metric::Histogram(x) => out.histogram_samples(name, &labels, x),
+                }
+            }
+        }
+    }
+}
+
+// Texto no formato de exposição do Prometheus
+pub struct MetricsText {
+    out: String,
+}
+
+impl MetricsText {
+    pub fn new() -> MetricsText {
+        MetricsText { out: String::new() }
+    }
+
+    pub fn family(&mut self, name: &str, kind: &str, help: &str) {
+        let _ = writeln!(self.out, "# HELP {} {}", name, help.replace('\n', " "));
+        let _ = writeln!(self.out, "# TYPE {} {}", name, kind);
+    }
+
+    pub fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: f64) {
+        self.out.push_str(name);
+        write_labels(&mut self.out, labels, None);
+        let _ = writeln!(self.out, " {}", format_value(value));
+    }
+
+    pub fn counter(&mut self, name: &str, help: &str, value: usize) {
+        self.family(name, "counter", help);
+        self.sample(name, &[], value as f64);
+    }
+
+    pub fn gauge(&mut self, name: &str, help: &str, value: f64) {
+        self.family(name, "gauge", help);
+        self.sample(name, &[], value);
+    }
+
+    fn histogram_samples(&mut self, name: &str, labels: &[(&str, &str)], histogram: &Histogram) {
+        for (bound, bucket) in histogram.bounds.iter().zip(histogram.buckets.iter()) {
+            let _ = write!(self.out, "{}_bucket", name);
+            write_labels(&mut self.out, labels, Some(&format_value(*bound)));
+            let _ = writeln!(self.out, " {}", bucket.load(Ordering::Relaxed));
+        }
+        let count = histogram.count.load(Ordering::Relaxed);
+        let _ = write!(self.out, "{}_bucket", name);
+        write_labels(&mut self.out, labels, Some("+Inf"));
+        let _ = writeln!(self.out, " {}", count);
+        let _ = write!(self.out, "{}_sum", name);
+        write_labels(&mut self.out, labels, None);
+        let sum = f64::from_bits(histogram.sum_bits.load(Ordering::Relaxed));
+        let _ = writeln!(self.out, " {}", format_value(sum));
+        let _ = write!(self.out, "{}_count", name);
+        write_labels(&mut self.out, labels, None);
+        let _ = writeln!(self.out, " {}", count);
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.out.into_bytes()
+    }
+}
+
+fn write_labels(out: &mut String, labels: &[(&str, &str)], le: Option<&str>) {
+    if labels.is_empty() && le.is_none() {
+        return;
+    }
+    out.push('{');
+    let mut first = true;
+    for (key, value) in labels.iter().copied().chain(le.map(|le| ("le", le))) {
+        if !first {
+            out.push(',');
+        }
+        first = false;
+        let value = value
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('\n', "\\n");
+        let _ = write!(out, "{}=\"{}\"", key, value);
+    }
+    out.push('}');
+}
+
+fn format_value(value: f64) -> String {
+    if value.is_infinite() {
+        if value > 0.0 {
+            "+Inf".to_owned()
+        } else {
+            "-Inf".to_owned()
+        }
+    } else {
+        value.to_string()
+    }
+}
+
+// Quantidade de itens esperando em um canal mpsc
+pub fn queue_depth<T>(sender: &tokio::sync::mpsc::Sender<T>) -> f64 {
+    (sender.max_capacity() - sender.capacity()) as f64
+}
+
+/*
+Os contadores de estatística não são mais zerados a cada intervalo, para que o /metrics veja valores
+monotônicos. O arquivo diário de estatísticas continua recebendo o que aconteceu no intervalo,
+calculado pela diferença em relação à leitura anterior.
+*/
+#[derive(Default)]
+pub struct CounterDeltas {
+    last: HashMap<String, usize>,
+}
+
+impl CounterDeltas {
+    pub fn delta(&mut self, key: &str, counter: &AtomicUsize) -> usize {
+        let value = counter.load(Ordering::Relaxed);
+        let last = self.last.insert(key.to_owned(), value).unwrap_or(0);
+        value.wrapping_sub(last)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MetricsRegistry, MetricsText};
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_render_prometheus_text() {
+        let registry = MetricsRegistry {
+            entries: Mutex::new(Vec::new()),
+        };
+        registry
+            .counter("reqs_total", "Requisições", &[("kind", "dac")])
+            .add(3);
+        registry
+            .counter("reqs_total", "Requisições", &[("kind", "dut")])
+            .inc();
+        registry
+            .counter("reqs_total", "Requisições", &[("kind", "dac")])
+            .inc();
+        let histogram = registry.histogram("page_seconds", "Latência", &[], &[0.1, 1.0]);
+        histogram.observe(0.05);
+        histogram.observe(0.5);
+        histogram.observe(2.0);
+
+        let mut out = MetricsText::new();
+        out.gauge("queue_depth", "Fila", 7.0);
+        registry.render(&mut out);
+        let text = String::from_utf8(out.into_bytes()).unwrap();
+        assert_eq!(
+            text,
+            concat!(
+                "# HELP queue_depth Fila\n",
+                "# TYPE queue_depth gauge\n",
+                "queue_depth 7\n",
+                "# HELP reqs_total Requisições\n",
+                "# TYPE reqs_total counter\n",
+                "reqs_total{kind=\"dac\"} 4\n",
+                "reqs_total{kind=\"dut\"} 1\n",
+                "# HELP page_seconds Latência\n",
+                "# TYPE page_seconds histogram\n",
+                "page_seconds_bucket{le=\"0.1\"} 1\n",
+                "page_seconds_bucket{le=\"1\"} 2\n",
+                "page_seconds_bucket{le=\"+Inf\"} 3\n",
+                "page_seconds_sum 2.55\n",
+                "page_seconds_count 3\n",
+            )
+        );
+    }
+}
@@ -0,0 +1,116 @@
+use super::memory::MemoryStateStore;
+use super::store::DeviceStateStore;
+use crate::lib_metrics::REGISTRY;
+use futures::future::BoxFuture;
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+/*
+Store principal (Redis) com o store em memória como reserva. Todo estado salvo também fica em memória,
+então se o principal falhar as telemetrias continuam sendo processadas com o último estado conhecido.
+Os dispositivos salvos só em memória durante a falha ficam em `pending` e continuam sendo lidos da memória
+até conseguirem ser gravados de novo no principal, para não voltar a um estado antigo quando ele retornar.
+*/
+
+pub struct FallbackStateStore {
+    primary: Box<dyn DeviceStateStore>,
+    memory: MemoryStateStore,
+    pending: Mutex<HashSet<String>>,
+    primary_down: AtomicBool,
+}
+
+impl FallbackStateStore {
+    pub fn new(primary: Box<dyn DeviceStateStore>, memory: MemoryStateStore) -> Self {
+        Self {
+            primary,
+            memory,
+            pending: Mutex::new(HashSet::new()),
+            primary_down: AtomicBool::new(false),
+        }
+    }
+
+    fn on_primary_ok(&self) {
+        if self.primary_down.swap(false, Ordering::Relaxed) {
+            crate::LOG.append_log_tag_msg(
+                "INFO",
+                &format!("{} state store is back", self.primary.store_name()),
+            );
+        }
+    }
+
+    fn on_primary_error(&self, err: &str) {
+        REGISTRY
+            .counter(
+                "state_store_fallback_total",
+                "Operações de estado atendidas pela memória por falha no store principal",
+                &[("store", self.primary.store_name())],
+            )
+            .inc();
+        if !self.primary_down.swap(true, Ordering::Relaxed) {
+            crate::LOG.append_log_tag_msg(
+                "ERROR",
+                &format!(
+                    "{} state store unavailable, using memory ({} devices): {}",
+                    self.primary.store_name(),
+                    self.memory.len(),
+                    err
+                ),
+            );
+        }
+    }
+}
+
+impl DeviceStateStore for FallbackStateStore {
+    fn store_name(&self) -> &'static str {
+        self.primary.store_name()
+    }
+
+    fn get_state<'a>(&'a self, dev_id: &'a str) -> BoxFuture<'a, Result<Option<Vec<u8>>, String>> {
+        Box::pin(async move {
+            if self.pending.lock().unwrap().contains(dev_id) {
+                return Ok(self.memory.get(dev_id));
+            }
+            match self.primary.get_state(dev_id).await {
+                Ok(state) => {
+                    self.on_primary_ok();
+                    Ok(state)
+                }
+                Err(err) => {
+                    self.on_primary_error(&err);
+                    Ok(self.memory.get(dev_id))
+                }
+            }
+        })
+    }
+
+    fn save_state<'a>(
+        &'a self,
+        dev_id: &'a str,
+        state: Vec<u8>,
+    ) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(async move {
+            self.memory.save(dev_id, state.clone());
+            match self.primary.save_state(dev_id, state).await {
+                Ok(()) => {
+                    self.pending.lock().unwrap().remove(dev_id);
+                    self.on_primary_ok();
+                }
+                Err(err) => {
+                    self.pending.lock().unwrap().insert(dev_id.to_owned());
+                    self.on_primary_error(&err);
+                }
+            }
+            Ok(())
+        })
+    }
+
+    fn run_maintenance<'a>(&'a self) -> BoxFuture<'a, ()> {
+        Box::pin(async move {
+            futures::join!(
+                self.primary.run_maintenance(),
+                self.memory.run_maintenance()
+            );
+        })
+    }
+}
@@ -0,0 +1,71 @@
+use super::store::DeviceStateStore;
+use futures::future::BoxFuture;
+use std::path::PathBuf;
+
+/*
+Um arquivo por dispositivo: <pasta>/<dev_id>.cbor, com os mesmos bytes que seriam salvos no Redis.
+Pensado para instalações pequenas, sem Redis, em que o estado precisa sobreviver a reinícios.
+*/
+
+pub struct FileStateStore {
+    dir: PathBuf,
+}
+
+impl FileStateStore {
+    pub fn new(dir: String) -> Self {
+        if let Err(err) = std::fs::create_dir_all(&dir) {
+            crate::LOG.append_log_tag_msg(
+                "ERROR",
+                &format!("Could not create state dir {}: {}", dir, err),
+            );
+        }
+        Self {
+            dir: PathBuf::from(dir),
+        }
+    }
+
+    fn file_path(&self, dev_id: &str) -> Result<PathBuf, String> {
+        // O dev_id vira nome de arquivo, então não pode ter separadores de caminho
+        if dev_id.is_empty() || dev_id.contains(['/', '\\', '.']) {
+            return Err(format!("Invalid dev_id for state file: {}", dev_id));
+        }
+        Ok(self.dir.join(format!("{}.cbor", dev_id)))
+    }
+}
+
+impl DeviceStateStore for FileStateStore {
+    fn store_name(&self) -> &'static str {
+        "file"
+    }
+
+    fn get_state<'a>(&'a self, dev_id: &'a str) -> BoxFuture<'a, Result<Option<Vec<u8>>, String>> {
+        Box::pin(async move {
+            let path = self.file_path(dev_id)?;
+            match std::fs::read(&path) {
+                Ok(v) => Ok(Some(v)),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+                Err(err) => Err(format!("{}: {}", path.display(), err)),
+            }
+        })
+    }
+
+    fn save_state<'a>(
+        &'a self,
+        dev_id: &'a str,
+        state: Vec<u8>,
+    ) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(async move {
+            let path = self.file_path(dev_id)?;
+            let tmp_path = path.with_extension("cbor.tmp");
+            std::fs::write(&tmp_path, state)
+                .map_err(|err| format!("{}: {}", tmp_path.display(), err))?;
+            std::fs::rename(&tmp_path, &path)
+                .map_err(|err| format!("{}: {}", path.display(), err))?;
+            Ok(())
+        })
+    }
+
+    fn run_maintenance<'a>(&'a self) -> BoxFuture<'a, ()> {
+        Box::pin(futures::future::pending())
+    }
+}
@@ -0,0 +1,200 @@
+use super::store::{DeviceStateStore, MemoryStoreConfig};
+use futures::future::BoxFuture;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/*
+Estado dos dispositivos em memória, limitado a `max_devices` (descarta os usados há mais tempo).
+Se tiver `snapshot_file`, o conteúdo é gravado em disco periodicamente (CBOR, dev_id => bytes) e
+carregado de volta quando o serviço inicia, para o L1 virtual não recomeçar do zero a cada reinício.
+*/
+
+pub struct MemoryStateStore {
+    config: MemoryStoreConfig,
+    entries: Mutex<MemoryEntries>,
+}
+
+struct MemoryEntries {
+    states: HashMap<String, (Vec<u8>, u64)>, // (estado, último uso)
+    clock: u64,
+    changed: bool,
+}
+
+impl MemoryStateStore {
+    pub fn new(config: MemoryStoreConfig) -> Self {
+        let states = match &config.snapshot_file {
+            Some(path) => load_snapshot(path),
+            None => HashMap::new(),
+        };
+        let clock = states.len() as u64;
+        Self {
+            config,
+            entries: Mutex::new(MemoryEntries {
+                states,
+                clock,
+                changed: false,
+            }),
+        }
+    }
+
+    pub fn get(&self, dev_id: &str) -> Option<Vec<u8>> {
+        let mut entries = self.entries.lock().unwrap();
+        entries.clock += 1;
+        let clock = entries.clock;
+        let (state, last_use) = entries.states.get_mut(dev_id)?;
+        *last_use = clock;
+        Some(state.clone())
+    }
+
+    pub fn save(&self, dev_id: &str, state: Vec<u8>) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.clock += 1;
+        let clock = entries.clock;
+        entries.states.insert(dev_id.to_owned(), (state, clock));
+        entries.changed = true;
+        if entries.states.len() > self.config.max_devices {
+            evict_least_recently_used(&mut entries.states, self.config.max_devices);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().states.len()
+    }
+
+    fn write_snapshot(&self, path: &str) -> Result<(), String> {
+        let snapshot = {
+            let mut entries = self.entries.lock().unwrap();
+            if !entries.changed {
+                return Ok(());
+            }
+            entries.changed = false;
+            entries
+                .states
+                .iter()
+                .map(|(dev_id, (state, _))| {
+                    (dev_id.to_owned(), serde_cbor::Value::Bytes(state.clone()))
+                })
+                .collect::<HashMap<String, serde_cbor::Value>>()
+        };
+        let bytes = serde_cbor::to_vec(&snapshot).map_err(|err| err.to_string())?;
+        // Grava em um arquivo temporário e renomeia, para nunca deixar um snapshot pela metade
+        let tmp_path = format!("{}.tmp", path);
+        std::fs::write(&tmp_path, bytes).map_err(|err| format!("{}: {}", tmp_path, err))?;
+        std::fs::rename(&tmp_path, path).map_err(|err| format!("{}: {}", path, err))?;
+        Ok(())
+    }
+}
+
+impl DeviceStateStore for MemoryStateStore {
+    fn store_name(&self) -> &'static str {
+        "memory"
+    }
+
+    fn get_state<'a>(&'a self, dev_id: &'a str) -> BoxFuture<'a, Result<Option<Vec<u8>>, String>> {
+        Box::pin(async move { Ok(self.get(dev_id)) })
+    }
+
+    fn save_state<'a>(
+        &'a self,
+        dev_id: &'a str,
+        state: Vec<u8>,
+    ) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(async move {
+            self.save(dev_id, state);
+            Ok(())
+        })
+    }
+
+    fn run_maintenance<'a>(&'a self) -> BoxFuture<'a, ()> {
+        Box::pin(async move {
+            let path = match &self.config.snapshot_file {
+                Some(path) => path,
+                None => return futures::future::pending().await,
+            };
+            loop {
+                tokio::time::sleep(self.config.snapshot_interval).await;
+                if let Err(err) = self.write_snapshot(path) {
+                    crate::LOG.append_log_tag_msg(
+                        "ERROR",
+                        &format!("Could not write state snapshot: {}", err),
+                    );
+                }
+            }
+        })
+    }
+}
+
+// Descarta de uma vez 1% a mais do que o necessário, para não ter que ordenar a cada inserção
+fn evict_least_recently_used(states: &mut HashMap<String, (Vec<u8>, u64)>, max_devices: usize) {
+    let target = max_devices - (max_devices / 100);
+    let mut by_use = states
+        .iter()
+        .map(|(dev_id, (_, last_use))| (*last_use, dev_id.to_owned()))
+        .collect::<Vec<_>>();
+    by_use.sort_unstable();
+    let excess = states.len().saturating_sub(target);
+    for (_, dev_id) in by_use.into_iter().take(excess) {
+        states.remove(&dev_id);
+    }
+}
+
+fn load_snapshot(path: &str) -> HashMap<String, (Vec<u8>, u64)> {
+    let bytes = match std::fs::read(path) {
+        Ok(v) => v,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return HashMap::new(),
+        Err(err) => {
+            crate::LOG.append_log_tag_msg(
+                "ERROR",
+                &format!("Could not read state snapshot {}: {}", path, err),
+            );
+            return HashMap::new();
+        }
+    };
+    let snapshot = match serde_cbor::from_slice::<HashMap<String, serde_cbor::Value>>(&bytes) {
+        Ok(v) => v,
+        Err(err) => {
+            crate::LOG.append_log_tag_msg(
+                "ERROR",
+                &format!("Could not parse state snapshot {}: {}", path, err),
+            );
+            return HashMap::new();
+        }
+    };
+    let mut states = HashMap::new();
+    for (dev_id, state) in snapshot {
+        if let serde_cbor::Value::Bytes(state) = state {
+            let last_use = states.len() as u64;
+            states.insert(dev_id, (state, last_use));
+        }
+    }
+    crate::LOG.append_log_tag_msg(
+        "INFO",
+        &format!("Loaded {} device states from {}", states.len(), path),
+    );
+    states
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MemoryStateStore;
+    use crate::lib_state_store::store::MemoryStoreConfig;
+
+    #[test]
+    fn test_memory_store_lru() {
+        let store = MemoryStateStore::new(MemoryStoreConfig {
+            max_devices: 3,
+            snapshot_file: None,
+            snapshot_interval: std::time::Duration::from_secs(60),
+        });
+        store.save("DAC1", vec![1]);
+        store.save("DAC2", vec![2]);
+        store.save("DAC3", vec![3]);
+        // DAC1 foi usado por último, então DAC2 é o descartado
+        assert_eq!(store.get("DAC1"), Some(vec![1]));
+        store.save("DAC4", vec![4]);
+        assert_eq!(store.len(), 3);
+        assert_eq!(store.get("DAC2"), None);
+        assert_eq!(store.get("DAC1"), Some(vec![1]));
+        assert_eq!(store.get("DAC4"), Some(vec![4]));
+    }
+}
@@ -0,0 +1,97 @@
+use super::store::DeviceStateStore;
+use futures::future::BoxFuture;
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+// Sem resposta nesse tempo o comando é considerado falho, para não travar o processamento das telemetrias
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(3);
+
+pub struct RedisStateStore {
+    url: String,
+    prefix: String,
+    client: Mutex<Option<redis::aio::ConnectionManager>>,
+}
+
+impl RedisStateStore {
+    pub fn new(url: String, prefix: String) -> Self {
+        Self {
+            url,
+            prefix,
+            client: Mutex::new(None),
+        }
+    }
+
+    async fn send_command(&self, cmd: redis::Cmd) -> Result<redis::Value, String> {
+        let mut db_client = match self.client.lock().await.clone() {
+            None => return Err("redis_client not available".to_owned()),
+            Some(x) => x,
+        };
+        match tokio::time::timeout(COMMAND_TIMEOUT, db_client.send_packed_command(&cmd)).await {
+            Ok(result) => result.map_err(|err| err.to_string()),
+            Err(_) => Err("redis command timed out".to_owned()),
+        }
+    }
+
+    async fn connect(&self) -> Result<(), String> {
+        // https://docs.rs/redis/latest/redis/
+        let client = redis::Client::open(self.url.to_owned()).map_err(|err| err.to_string())?;
+        let con = client
+            .get_connection_manager()
+            .await
+            .map_err(|err| err.to_string())?;
+        *self.client.lock().await = Some(con);
+        crate::LOG.append_log_tag_msg("INFO", "redis connected");
+        Ok(())
+    }
+}
+
+impl DeviceStateStore for RedisStateStore {
+    fn store_name(&self) -> &'static str {
+        "redis"
+    }
+
+    fn get_state<'a>(&'a self, dev_id: &'a str) -> BoxFuture<'a, Result<Option<Vec<u8>>, String>> {
+        Box::pin(async move {
+            let mut cmd = redis::Cmd::new();
+            cmd.arg("GET").arg(format!("{}{}", self.prefix, dev_id));
+            match self.send_command(cmd).await? {
+                redis::Value::BulkString(dev_state_bytes) => Ok(Some(dev_state_bytes)),
+                redis::Value::Nil => Ok(None),
+                x => Err(format!("Invalid response from redis: {:?}", x)),
+            }
+        })
+    }
+
+    fn save_state<'a>(
+        &'a self,
+        dev_id: &'a str,
+        state: Vec<u8>,
+    ) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(async move {
+            let mut cmd = redis::Cmd::new();
+            cmd.arg("SET")
+                .arg(format!("{}{}", self.prefix, dev_id))
+                .arg(state);
+            self.send_command(cmd).await?;
+            Ok(())
+        })
+    }
+
+    fn run_maintenance<'a>(&'a self) -> BoxFuture<'a, ()> {
+        Box::pin(async move {
+            // Depois de criado, o ConnectionManager reconecta sozinho quando a conexão cai
+            loop {
+                let client_is_none = { self.client.lock().await.is_none() };
+                if client_is_none {
+                    if let Err(err) = self.connect().await {
+                        crate::LOG.append_log_tag_msg(
+                            "ERROR",
+                            &format!("Could not connect to redis: {}", err),
+                        );
+                    }
+                }
+                tokio::time::sleep(Duration::from_secs(10)).await;
+            }
+        })
+    }
+}
@@ -0,0 +1,69 @@
+use super::fallback::FallbackStateStore;
+use super::file::FileStateStore;
+use super::memory::MemoryStateStore;
+use super::redis::RedisStateStore;
+use futures::future::BoxFuture;
+use std::sync::Arc;
+
+/*
+Abstração de "onde fica o estado de cada dispositivo" usado nas conversões do iotrelay/telemetry_service
+(ex.: DacDbState e DutDbState serializados em CBOR). O conteúdo é opaco para o store, só bytes por dev_id.
+Implementações: Redis (padrão), memória (LRU com snapshot periódico em disco) e arquivos (um por dispositivo).
+O Redis pode ter o store em memória como reserva para quando estiver inacessível.
+*/
+
+#[derive(Clone)]
+pub enum StateStoreConfig {
+    Redis {
+        url: String,
+        prefix: String,
+        fallback: Option<MemoryStoreConfig>,
+    },
+    Memory(MemoryStoreConfig),
+    File(String), // Pasta onde ficam os arquivos de estado
+}
+
+#[derive(Clone)]
+pub struct MemoryStoreConfig {
+    // Quantidade máxima de dispositivos mantidos, os usados há mais tempo são descartados
+    pub max_devices: usize,
+    pub snapshot_file: Option<String>,
+    pub snapshot_interval: std::time::Duration,
+}
+
+pub trait DeviceStateStore: Send + Sync {
+    fn store_name(&self) -> &'static str;
+
+    fn get_state<'a>(&'a self, dev_id: &'a str) -> BoxFuture<'a, Result<Option<Vec<u8>>, String>>;
+
+    fn save_state<'a>(
+        &'a self,
+        dev_id: &'a str,
+        state: Vec<u8>,
+    ) -> BoxFuture<'a, Result<(), String>>;
+
+    // Tarefa contínua de manutenção (conexão com o Redis, snapshot em disco). Não deve retornar.
+    fn run_maintenance<'a>(&'a self) -> BoxFuture<'a, ()>;
+}
+
+pub fn create_state_store(config: &StateStoreConfig) -> Arc<dyn DeviceStateStore> {
+    match config {
+        StateStoreConfig::Redis {
+            url,
+            prefix,
+            fallback: None,
+        } => Arc::new(RedisStateStore::new(url.to_owned(), prefix.to_owned())),
+        StateStoreConfig::Redis {
+            url,
+            prefix,
+            fallback: Some(memory_config),
+        } => Arc::new(FallbackStateStore::new(
+            Box::new(RedisStateStore::new(url.to_owned(), prefix.to_owned())),
+            MemoryStateStore::new(memory_config.clone()),
+        )),
+        StateStoreConfig::Memory(memory_config) => {
+            Arc::new(MemoryStateStore::new(memory_config.clone()))
+        }
+        StateStoreConfig::File(dir) => Arc::new(FileStateStore::new(dir.to_owned())),
+    }
+}
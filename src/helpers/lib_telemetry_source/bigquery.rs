@@ -0,0 +1,202 @@
+use super::source::{PageCallback, SourceQuery, TelemetrySource};
+use crate::diel_hist_tables::BigQueryHistoryTable;
+use chrono::{Duration, NaiveDate};
+use futures::future::BoxFuture;
+use gcp_bigquery_client::error::BQError;
+use gcp_bigquery_client::model::job_configuration_query::JobConfigurationQuery;
+use gcp_bigquery_client::model::query_parameter::QueryParameter;
+use gcp_bigquery_client::model::query_parameter_type::QueryParameterType;
+use gcp_bigquery_client::model::query_parameter_value::QueryParameterValue;
+use tokio_stream::StreamExt;
+
+/*
+Lê as tabelas "*_telemetry" que o broker2db grava no BigQuery (colunas timestamp, dev_id e payload).
+O broker2db divide os pacotes de DAC e DUT em uma linha por amostra, então aqui cada amostra volta a ser
+um "pacote" de tamanho 1 para que os compiladores continuem recebendo vetores.
+*/
+
+// Propriedades que o broker2db separa em uma linha por amostra (ver dividir_telemetria_dac e dividir_telemetria_dut)
+const DAC_SPLIT_PROPS: [&str; 16] = [
+    "L1", "T0", "T1", "T2", "P0", "P1", "Lcmp", "Lcut", "Levp", "Tamb", "Tsuc", "Tliq", "Psuc",
+    "Pliq", "Tsc", "Tsh",
+];
+const DUT_SPLIT_PROPS: [&str; 8] = [
+    "Temperature",
+    "Temperature_1",
+    "Tmp",
+    "Humidity",
+    "eCO2",
+    "raw_eCO2",
+    "TVOC",
+    "L1",
+];
+
+#[derive(Clone)]
+pub struct BigQuerySourceConfig {
+    pub credentials_file: String,
+    pub project_id: String,
+    pub dataset_id: String,
+    pub dest_table: BigQueryHistoryTable,
+}
+
+pub struct BigQuerySource {
+    pub config: BigQuerySourceConfig,
+}
+
+impl BigQuerySource {
+    fn find_table_name(&self, dev_id: &str) -> Option<String> {
+        if dev_id.len() < 8 {
+            return None;
+        }
+        match &self.config.dest_table {
+            BigQueryHistoryTable::None => None,
+            BigQueryHistoryTable::SingleTable(table_id) => Some(table_id.to_owned()),
+            BigQueryHistoryTable::DevType => {
+                Some(format!("{}_telemetry", dev_id[..3].to_lowercase()))
+            }
+            BigQueryHistoryTable::DevGeneration => {
+                Some(format!("{}_telemetry", dev_id[..8].to_uppercase()))
+            }
+            BigQueryHistoryTable::DevId => Some(dev_id.to_uppercase()),
+        }
+    }
+}
+
+impl TelemetrySource for BigQuerySource {
+    fn source_name(&self) -> &'static str {
+        "bigquery"
+    }
+
+    fn query_pages<'a>(
+        &'a self,
+        query: &'a SourceQuery,
+        ts_ini: &'a str,
+        ts_end: &'a str,
+        proc_page: &'a mut PageCallback<'_>,
+    ) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(async move {
+            if ts_ini >= ts_end {
+                return Ok(());
+            }
+            let Some(table_name) = self.find_table_name(&query.dev_id) else {
+                return Err(format!(
+                    "ResourceNotFound: no BigQuery table for {}",
+                    query.dev_id
+                ));
+            };
+
+            // A coluna "timestamp" está em UTC e o "timestamp" do payload está no fuso do dispositivo,
+            // então a partição é filtrada com folga de um dia para cada lado.
+            let part_ini = parse_day(ts_ini)? - Duration::days(1);
+            let part_end = parse_day(ts_end)? + Duration::days(2);
+
+            let sql = format!(
+                "SELECT TO_JSON_STRING(payload) AS payload FROM `{}.{}.{}` \
+                 WHERE dev_id = @dev_id \
+                 AND timestamp >= TIMESTAMP(@part_ini) AND timestamp < TIMESTAMP(@part_end) \
+                 AND JSON_VALUE(payload, '$.timestamp') BETWEEN @ts_ini AND @ts_end \
+                 ORDER BY JSON_VALUE(payload, '$.timestamp')",
+                self.config.project_id, self.config.dataset_id, table_name
+            );
+            let job_query = JobConfigurationQuery {
+                query: sql,
+                use_legacy_sql: Some(false),
+                parameter_mode: Some("NAMED".to_owned()),
+                query_parameters: Some(vec![
+                    string_parameter("dev_id", &query.dev_id),
+                    string_parameter("part_ini", &part_ini.format("%Y-%m-%d").to_string()),
+                    string_parameter("part_end", &part_end.format("%Y-%m-%d").to_string()),
+                    string_parameter("ts_ini", ts_ini),
+                    string_parameter("ts_end", ts_end),
+                ]),
+                ..Default::default()
+            };
+
+            crate::LOG.append_log_tag_msg(
+                "INFO",
+                &format!("bigqueryQuery: {} {} {}", &table_name, ts_ini, ts_end),
+            );
+
+            // O client é criado a cada consulta porque cada compilação roda no seu próprio runtime do tokio.
+            let client = gcp_bigquery_client::Client::from_service_account_key_file(
+                &self.config.credentials_file,
+            )
+            .await
+            .map_err(|err| format!("[118] {err}"))?;
+
+            let rejoin_props: &[&str] = if query.dev_id.starts_with("DAC") {
+                &DAC_SPLIT_PROPS
+            } else if query.dev_id.starts_with("DUT") {
+                &DUT_SPLIT_PROPS
+            } else {
+                &[]
+            };
+
+            let job_api = client.job();
+            let mut pages =
+                Box::pin(job_api.query_all(&self.config.project_id, job_query, Some(5000)));
+            while let Some(page) = pages.next().await {
+                let rows = page.map_err(|err| match &err {
+                    BQError::ResponseError { error } if error.error.code == 404 => {
+                        format!("ResourceNotFound: {}", error.error.message)
+                    }
+                    _ => format!("[140] {err}"),
+                })?;
+                let mut items = Vec::with_capacity(rows.len());
+                for row in rows {
+                    let payload = row
+                        .columns
+                        .and_then(|mut columns| columns.pop())
+                        .and_then(|cell| cell.value);
+                    let Some(serde_json::Value::String(payload)) = payload else {
+                        continue;
+                    };
+                    let mut item: serde_json::Value = match serde_json::from_str(&payload) {
+                        Ok(x) => x,
+                        Err(_) => continue,
+                    };
+                    rejoin_split_sample(&mut item, rejoin_props);
+                    items.push(item);
+                }
+                if !items.is_empty() {
+                    proc_page(items)?;
+                }
+            }
+            Ok(())
+        })
+    }
+}
+
+fn string_parameter(name: &str, value: &str) -> QueryParameter {
+    QueryParameter {
+        name: Some(name.to_owned()),
+        parameter_type: Some(QueryParameterType {
+            r#type: "STRING".to_owned(),
+            ..Default::default()
+        }),
+        parameter_value: Some(QueryParameterValue {
+            value: Some(value.to_owned()),
+            ..Default::default()
+        }),
+    }
+}
+
+fn rejoin_split_sample(item: &mut serde_json::Value, props: &[&str]) {
+    for prop in props {
+        let Some(value) = item.get_mut(*prop) else {
+            continue;
+        };
+        if value.is_array() || value.is_string() {
+            continue;
+        }
+        *value = serde_json::Value::Array(vec![value.take()]);
+    }
+}
+
+fn parse_day(ts: &str) -> Result<NaiveDate, String> {
+    let day = ts
+        .get(0..10)
+        .ok_or_else(|| format!("Invalid timestamp: {}", ts))?;
+    NaiveDate::parse_from_str(day, "%Y-%m-%d")
+        .map_err(|err| format!("Invalid timestamp: {} {}", ts, err))
+}
@@ -0,0 +1,38 @@
+use super::source::{PageCallback, SourceQuery, TelemetrySource};
+use crate::lib_dynamodb::client::AWSConfig;
+use crate::lib_dynamodb::query::QuerierDevIdTimestamp;
+use futures::future::BoxFuture;
+
+pub struct DynamoDBSource {
+    pub aws_config: AWSConfig,
+}
+
+impl TelemetrySource for DynamoDBSource {
+    fn source_name(&self) -> &'static str {
+        "dynamodb"
+    }
+
+    fn query_pages<'a>(
+        &'a self,
+        query: &'a SourceQuery,
+        ts_ini: &'a str,
+        ts_end: &'a str,
+        proc_page: &'a mut PageCallback<'_>,
+    ) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(async move {
+            // O client é criado a cada consulta porque cada compilação roda no seu próprio runtime do tokio.
+            let querier = QuerierDevIdTimestamp::new_custom(
+                query.table_name.to_owned(),
+                query.key_var_name.to_owned(),
+                "timestamp".to_owned(),
+                query.dev_id.to_owned(),
+                &self.aws_config,
+            );
+            querier
+                .run(ts_ini, ts_end, &mut |items: Vec<serde_json::Value>| {
+                    proc_page(items)
+                })
+                .await
+        })
+    }
+}
@@ -0,0 +1,227 @@
+use super::source::{PageCallback, SourceQuery, TelemetrySource};
+use crate::lib_local_store::layout::{
+    day_file_path, is_valid_path_part, read_day_file_lines, table_dir,
+};
+use chrono::NaiveDate;
+use futures::future::BoxFuture;
+
+/*
+Lê telemetrias gravadas pelo broker2db/telemetry_service no layout de lib_local_store:
+  <root>/<table_name>/<dev_id>/<YYYY-MM-DD>.jsonl(.zst)
+Cada linha do arquivo é um item no mesmo formato que seria salvo no DynamoDB (com "timestamp" e "dev_id").
+Cada arquivo diário é entregue como uma página.
+*/
+
+pub struct LocalFilesSource {
+    pub root_dir: String,
+}
+
+impl LocalFilesSource {
+    fn read_day(
+        &self,
+        query: &SourceQuery,
+        day: &str,
+        ts_ini: &str,
+        ts_end: &str,
+    ) -> Result<Vec<serde_json::Value>, String> {
+        let mut items = Vec::new();
+        for compressed in [false, true] {
+            let path = day_file_path(
+                &self.root_dir,
+                &query.table_name,
+                &query.dev_id,
+                day,
+                compressed,
+            );
+            for line in read_day_file_lines(&path)? {
+                let item: serde_json::Value = match serde_json::from_str(&line) {
+                    Ok(x) => x,
+                    Err(err) => {
+                        crate::LOG.append_log_tag_msg(
+                            "WARN",
+                            &format!("Ignoring invalid line in {}: {}", path.display(), err),
+                        );
+                        continue;
+                    }
+                };
+                // Mesmo critério da query do DynamoDB: "key = :key and #ts between :ts_begin and :ts_end"
+                if let Some(item_dev_id) = item[&query.key_var_name].as_str() {
+                    if item_dev_id != query.dev_id {
+                        continue;
+                    }
+                }
+                match item["timestamp"].as_str() {
+                    Some(ts) if (ts >= ts_ini) && (ts <= ts_end) => {}
+                    _ => continue,
+                };
+                items.push(item);
+            }
+        }
+        items.sort_by(|a, b| a["timestamp"].as_str().cmp(&b["timestamp"].as_str()));
+        Ok(items)
+    }
+}
+
+impl TelemetrySource for LocalFilesSource {
+    fn source_name(&self) -> &'static str {
+        "local_files"
+    }
+
+    fn query_pages<'a>(
+        &'a self,
+        query: &'a SourceQuery,
+        ts_ini: &'a str,
+        ts_end: &'a str,
+        proc_page: &'a mut PageCallback<'_>,
+    ) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(async move {
+            if ts_ini >= ts_end {
+                return Ok(());
+            }
+            // table_name e dev_id vêm da requisição e viram partes do caminho
+            if !(is_valid_path_part(&query.table_name) && is_valid_path_part(&query.dev_id)) {
+                return Err(format!(
+                    "Invalid local store path: {} {}",
+                    query.table_name, query.dev_id
+                ));
+            }
+            let table_dir = table_dir(&self.root_dir, &query.table_name);
+            if !table_dir.is_dir() {
+                return Err(format!(
+                    "ResourceNotFound: {} does not exist",
+                    table_dir.display()
+                ));
+            }
+            let day_ini = parse_day(ts_ini)?;
+            let day_end = parse_day(ts_end)?;
+
+            let mut day = day_ini;
+            while day <= day_end {
+                let items =
+                    self.read_day(query, &day.format("%Y-%m-%d").to_string(), ts_ini, ts_end)?;
+                if !items.is_empty() {
+                    proc_page(items)?;
+                }
+                day = match day.succ_opt() {
+                    Some(x) => x,
+                    None => break,
+                };
+            }
+            Ok(())
+        })
+    }
+}
+
+fn parse_day(ts: &str) -> Result<NaiveDate, String> {
+    // Aceita "2023-03-21T00:00:00" e também "2023-03-21T24:00:00", por isso só a data é interpretada.
+    let day = ts
+        .get(0..10)
+        .ok_or_else(|| format!("Invalid timestamp: {}", ts))?;
+    NaiveDate::parse_from_str(day, "%Y-%m-%d")
+        .map_err(|err| format!("Invalid timestamp: {} {}", ts, err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LocalFilesSource;
+    use crate::lib_telemetry_source::source::{run_query, SourceQuery};
+
+    #[test]
+    fn test_local_files_source() {
+        let root_dir = std::env::temp_dir().join(format!("telsrc_test_{}", std::process::id()));
+        let dev_dir = root_dir.join("DUT00122XXXX_RAW").join("DUT001220001");
+        std::fs::create_dir_all(&dev_dir).unwrap();
+        std::fs::write(
+            dev_dir.join("2023-03-21.jsonl"),
+            concat!(
+                r#"{"dev_id":"DUT001220001","timestamp":"2023-03-21T23:59:50","Temp":[1]}"#,
+                "\n",
+                r#"{"dev_id":"DUT001220001","timestamp":"2023-03-21T10:00:00","Temp":[2]}"#,
+                "\n",
+            ),
+        )
+        .unwrap();
+        // Arquivo compactado com dois frames zstd, como o saver grava
+        let mut compressed = zstd::stream::encode_all(
+            concat!(
+                r#"{"dev_id":"DUT001220001","timestamp":"2023-03-22T00:01:00","Temp":[3]}"#,
+                "\n"
+            )
+            .as_bytes(),
+            3,
+        )
+        .unwrap();
+        compressed.extend(
+            zstd::stream::encode_all(
+                concat!(
+                    r#"{"dev_id":"DUT001220001","timestamp":"2023-03-22T00:03:00","Temp":[4]}"#,
+                    "\n"
+                )
+                .as_bytes(),
+                3,
+            )
+            .unwrap(),
+        );
+        std::fs::write(dev_dir.join("2023-03-22.jsonl.zst"), compressed).unwrap();
+
+        let source = LocalFilesSource {
+            root_dir: root_dir.to_str().unwrap().to_owned(),
+        };
+        let query =
+            SourceQuery::new_diel_dev("DUT00122XXXX_RAW".to_owned(), "DUT001220001".to_owned());
+        let mut timestamps = Vec::new();
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        rt.block_on(run_query(
+            &source,
+            &query,
+            "2023-03-21T00:00:00",
+            "2023-03-22T00:02:00",
+            &mut |items: Vec<serde_json::Value>| {
+                for item in items {
+                    timestamps.push(item["timestamp"].as_str().unwrap().to_owned());
+                }
+                Ok(())
+            },
+        ))
+        .unwrap();
+        assert_eq!(
+            timestamps,
+            [
+                "2023-03-21T10:00:00",
+                "2023-03-21T23:59:50",
+                "2023-03-22T00:01:00"
+            ]
+        );
+
+        let missing = SourceQuery::new_diel_dev("DAC00122XXXX_RAW".to_owned(), "X".to_owned());
+        let result = rt.block_on(run_query(
+            &source,
+            &missing,
+            "2023-03-21T00:00:00",
+            "2023-03-22T00:00:00",
+            &mut |_items: Vec<serde_json::Value>| Ok(()),
+        ));
+        assert!(result.unwrap_err().starts_with("ResourceNotFound:"));
+
+        // Não pode sair da raiz do store
+        for (table_name, dev_id) in [
+            ("DUT00122XXXX_RAW", ".."),
+            ("..", "DUT001220001"),
+            ("DUT00122XXXX_RAW", "../x"),
+        ] {
+            let traversal = SourceQuery::new_diel_dev(table_name.to_owned(), dev_id.to_owned());
+            let result = rt.block_on(run_query(
+                &source,
+                &traversal,
+                "2023-03-21T00:00:00",
+                "2023-03-22T00:00:00",
+                &mut |_items: Vec<serde_json::Value>| Ok(()),
+            ));
+            assert!(result.unwrap_err().starts_with("Invalid local store path:"));
+        }
+
+        std::fs::remove_dir_all(&root_dir).unwrap();
+    }
+}
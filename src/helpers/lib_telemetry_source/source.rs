@@ -0,0 +1,72 @@
+use futures::future::BoxFuture;
+use serde::de::DeserializeOwned;
+
+/*
+Abstração de "onde estão as telemetrias". Os compiladores do rusthist só precisam receber, em páginas,
+os itens brutos de um dev_id dentro do intervalo [ts_ini, ts_end]. Cada implementação (DynamoDB, BigQuery,
+arquivos locais) decide como buscar esses itens.
+*/
+
+pub type PageCallback<'a> = dyn FnMut(Vec<serde_json::Value>) -> Result<(), String> + Send + 'a;
+
+pub struct SourceQuery {
+    pub table_name: String, // Tabela no padrão do DynamoDB, ex.: "DAC21019XXXX_RAW"
+    pub key_var_name: String, // Nome da propriedade com o ID do dispositivo, normalmente "dev_id"
+    pub dev_id: String,
+}
+
+impl SourceQuery {
+    pub fn new_diel_dev(table_name: String, dev_id: String) -> Self {
+        Self {
+            table_name,
+            key_var_name: "dev_id".to_owned(),
+            dev_id,
+        }
+    }
+
+    pub fn new_custom(table_name: String, key_var_name: String, dev_id: String) -> Self {
+        Self {
+            table_name,
+            key_var_name,
+            dev_id,
+        }
+    }
+}
+
+pub trait TelemetrySource: Send + Sync {
+    fn source_name(&self) -> &'static str;
+
+    // Os erros seguem o padrão do DynamoDB: "ResourceNotFound: ..." quando a tabela não existe e
+    // "ProvisionedThroughputExceeded: ..." quando a busca foi interrompida por limite de leitura.
+    fn query_pages<'a>(
+        &'a self,
+        query: &'a SourceQuery,
+        ts_ini: &'a str,
+        ts_end: &'a str,
+        proc_page: &'a mut PageCallback<'_>,
+    ) -> BoxFuture<'a, Result<(), String>>;
+}
+
+pub async fn run_query<T, F>(
+    source: &dyn TelemetrySource,
+    query: &SourceQuery,
+    ts_ini: &str,
+    ts_end: &str,
+    proc_items: &mut F,
+) -> Result<(), String>
+where
+    T: DeserializeOwned,
+    F: FnMut(Vec<T>) -> Result<(), String>,
+    F: Send,
+{
+    source
+        .query_pages(query, ts_ini, ts_end, &mut |items| {
+            let items = items
+                .into_iter()
+                .map(serde_json::from_value::<T>)
+                .collect::<Result<Vec<T>, _>>()
+                .map_err(|err| err.to_string())?;
+            proc_items(items)
+        })
+        .await
+}
@@ -0,0 +1,303 @@
+/*
+Tabelas de saturação dos fluidos refrigerantes, usadas para calcular o superaquecimento (Tsh) e o subresfriamento
+(Tsc) do DAC e pela rota de consulta do rusthist.
+Cada fluido fica num arquivo JSON em "data/", embutido no binário. Fluidos puros e azeotrópicos só precisam da curva
+de orvalho ("dew"); os zeotrópicos (série 400) têm também a curva de bolha ("bubble"), usada no lado do líquido.
+As pressões são em bar manométrico, como as do DAC.
+Arquivos extras podem ser colocados no diretório da variável REFRIGERANTS_DIR, e substituem os embutidos com o mesmo
+nome de fluido. Todas as tabelas são validadas no carregamento: uma tabela inválida é descartada com log de erro.
+*/
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock};
+
+// Versão do formato dos arquivos de fluido, para poder mudar o formato no futuro sem ler errado arquivos antigos
+const DATA_FORMAT: u32 = 1;
+
+const BUILTIN_FILES: [(&str, &str); 11] = [
+    ("r22.json", include_str!("data/r22.json")),
+    ("r32.json", include_str!("data/r32.json")),
+    ("r134a.json", include_str!("data/r134a.json")),
+    ("r290.json", include_str!("data/r290.json")),
+    ("r404a.json", include_str!("data/r404a.json")),
+    ("r407c.json", include_str!("data/r407c.json")),
+    ("r410a.json", include_str!("data/r410a.json")),
+    ("r507a.json", include_str!("data/r507a.json")),
+    ("r717.json", include_str!("data/r717.json")),
+    ("r1234yf.json", include_str!("data/r1234yf.json")),
+    ("idealecosafe.json", include_str!("data/idealecosafe.json")),
+];
+
+static REGISTRY: OnceLock<RefrigerantRegistry> = OnceLock::new();
+
+#[derive(Deserialize)]
+struct RefrigerantFile {
+    format: u32,
+    fluid: String,
+    revision: u32,
+    #[serde(default)]
+    aliases: Vec<String>,
+    source: String,
+    pressure_unit: String,
+    dew: Vec<[f64; 2]>,
+    bubble: Option<Vec<[f64; 2]>>,
+}
+
+/** Curva de saturação: temperatura (°C) em função da pressão (bar manométrico), interpolada linearmente */
+#[derive(Debug, Clone)]
+pub struct SaturationCurve {
+    pressure: Vec<f64>,
+    temperature: Vec<f64>,
+}
+
+impl SaturationCurve {
+    fn from_points(points: &[[f64; 2]], pressure_offset: f64) -> Result<Self, String> {
+        if points.len() < 2 {
+            return Err("precisa de pelo menos 2 pontos".to_owned());
+        }
+        for (i, [p, t]) in points.iter().enumerate() {
+            if !p.is_finite() || !t.is_finite() {
+                return Err(format!("ponto {} não é numérico", i));
+            }
+            if i > 0 {
+                let [p_prev, t_prev] = points[i - 1];
+                if *p <= p_prev || *t <= t_prev {
+                    return Err(format!(
+                        "ponto {} ({} bar, {} °C) não é crescente em relação ao anterior ({} bar, {} °C)",
+                        i, p, t, p_prev, t_prev
+                    ));
+                }
+            }
+        }
+        Ok(SaturationCurve {
+            pressure: points.iter().map(|[p, _]| p + pressure_offset).collect(),
+            temperature: points.iter().map(|[_, t]| *t).collect(),
+        })
+    }
+
+    /** None se a pressão estiver fora da faixa da tabela */
+    pub fn temperature_at(&self, pressure: f64) -> Option<f64> {
+        let last = self.pressure.len() - 1;
+        if !(pressure >= self.pressure[0] && pressure <= self.pressure[last]) {
+            return None;
+        }
+        let i = self
+            .pressure
+            .partition_point(|p| *p < pressure)
+            .clamp(1, last);
+        let (p0, p1) = (self.pressure[i - 1], self.pressure[i]);
+        let (t0, t1) = (self.temperature[i - 1], self.temperature[i]);
+        Some(t0 + (pressure - p0) / (p1 - p0) * (t1 - t0))
+    }
+
+    pub fn pressure_range(&self) -> (f64, f64) {
+        (self.pressure[0], self.pressure[self.pressure.len() - 1])
+    }
+}
+
+#[derive(Debug)]
+pub struct Refrigerant {
+    pub fluid: String,
+    pub revision: u32,
+    pub aliases: Vec<String>,
+    pub source: String,
+    pub dew: SaturationCurve,
+    // Igual à de orvalho nos fluidos sem glide
+    pub bubble: SaturationCurve,
+    pub zeotropic: bool,
+}
+
+impl Refrigerant {
+    fn parse(contents: &str) -> Result<Refrigerant, String> {
+        let file: RefrigerantFile =
+            serde_json::from_str(contents).map_err(|err| err.to_string())?;
+        if file.format != DATA_FORMAT {
+            return Err(format!("formato {} não suportado", file.format));
+        }
+        let pressure_offset = match file.pressure_unit.as_str() {
+            "bar_g" => 0.0,
+            "bar_a" => -1.013,
+            unit => return Err(format!("unidade de pressão desconhecida: {}", unit)),
+        };
+        let dew = SaturationCurve::from_points(&file.dew, pressure_offset)
+            .map_err(|err| format!("curva de orvalho: {}", err))?;
+        let bubble = match &file.bubble {
+            Some(points) => SaturationCurve::from_points(points, pressure_offset)
+                .map_err(|err| format!("curva de bolha: {}", err))?,
+            None => dew.clone(),
+        };
+        Ok(Refrigerant {
+            fluid: file.fluid,
+            revision: file.revision,
+            aliases: file.aliases,
+            source: file.source,
+            dew,
+            bubble,
+            zeotropic: file.bubble.is_some(),
+        })
+    }
+}
+
+#[derive(Serialize)]
+pub struct RefrigerantInfo<'a> {
+    pub fluid: &'a str,
+    pub revision: u32,
+    pub aliases: &'a [String],
+    pub source: &'a str,
+    pub zeotropic: bool,
+    pub pressure_range: (f64, f64),
+}
+
+pub struct RefrigerantRegistry {
+    fluids: HashMap<String, Arc<Refrigerant>>,
+    aliases: HashMap<String, String>,
+}
+
+// "R-410A", "r410a" e "R 410a" são o mesmo fluido
+fn normalize_name(name: &str) -> String {
+    name.chars()
+        .filter(|c| !matches!(c, '-' | ' ' | '_'))
+        .flat_map(|c| c.to_lowercase())
+        .collect()
+}
+
+impl RefrigerantRegistry {
+    fn load(extra_dir: Option<&str>) -> Self {
+        let mut registry = RefrigerantRegistry {
+            fluids: HashMap::new(),
+            aliases: HashMap::new(),
+        };
+        for (file_name, contents) in BUILTIN_FILES {
+            registry.add_file(file_name, contents);
+        }
+        if let Some(extra_dir) = extra_dir {
+            if let Err(err) = registry.load_dir(extra_dir) {
+                crate::LOG.append_log_tag_msg(
+                    "ERROR",
+                    &format!("[75] Erro lendo REFRIGERANTS_DIR {}: {}", extra_dir, err),
+                );
+            }
+        }
+        registry
+    }
+
+    fn load_dir(&mut self, dir: &str) -> Result<(), String> {
+        let mut paths: Vec<_> = std::fs::read_dir(dir)
+            .map_err(|err| err.to_string())?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .collect();
+        paths.sort();
+        for path in paths {
+            match std::fs::read_to_string(&path) {
+                Ok(contents) => self.add_file(&path.to_string_lossy(), &contents),
+                Err(err) => crate::LOG.append_log_tag_msg(
+                    "ERROR",
+                    &format!("[75] Erro lendo {}: {}", path.to_string_lossy(), err),
+                ),
+            }
+        }
+        Ok(())
+    }
+
+    fn add_file(&mut self, file_name: &str, contents: &str) {
+        let refrigerant = match Refrigerant::parse(contents) {
+            Ok(x) => x,
+            Err(err) => {
+                crate::LOG.append_log_tag_msg(
+                    "ERROR",
+                    &format!(
+                        "[77] Tabela de refrigerante inválida em {}: {}",
+                        file_name, err
+                    ),
+                );
+                return;
+            }
+        };
+        let key = normalize_name(&refrigerant.fluid);
+        for alias in &refrigerant.aliases {
+            self.aliases.insert(normalize_name(alias), key.clone());
+        }
+        self.fluids.insert(key, Arc::new(refrigerant));
+    }
+
+    pub fn get(&self, fluid: &str) -> Option<Arc<Refrigerant>> {
+        let name = normalize_name(fluid);
+        let key = self.aliases.get(&name).unwrap_or(&name);
+        self.fluids.get(key).cloned()
+    }
+
+    pub fn list(&self) -> Vec<RefrigerantInfo<'_>> {
+        let mut list: Vec<RefrigerantInfo> = self
+            .fluids
+            .values()
+            .map(|r| RefrigerantInfo {
+                fluid: &r.fluid,
+                revision: r.revision,
+                aliases: &r.aliases,
+                source: &r.source,
+                zeotropic: r.zeotropic,
+                pressure_range: r.dew.pressure_range(),
+            })
+            .collect();
+        list.sort_by(|a, b| a.fluid.cmp(b.fluid));
+        list
+    }
+}
+
+/** Carregado na primeira consulta, com os fluidos embutidos mais os de REFRIGERANTS_DIR */
+pub fn registry() -> &'static RefrigerantRegistry {
+    REGISTRY.get_or_init(|| {
+        let extra_dir = crate::envvars_loader::get_var_string_optional("REFRIGERANTS_DIR");
+        RefrigerantRegistry::load(extra_dir.as_deref())
+    })
+}
+
+#[test]
+fn test_refrigerant_tables() {
+    for (file_name, contents) in BUILTIN_FILES {
+        if let Err(err) = Refrigerant::parse(contents) {
+            panic!("{}: {}", file_name, err);
+        }
+    }
+
+    let registry = RefrigerantRegistry::load(None);
+    let r410a = registry.get("R-410A").unwrap();
+    assert!(r410a.zeotropic);
+    assert_eq!(registry.get("propane").unwrap().fluid, "r290");
+    assert_eq!(registry.get("idealEcoSafe").unwrap().fluid, "idealEcoSafe");
+    assert!(registry.get("r999").is_none());
+
+    let r134a = registry.get("r134a").unwrap();
+    assert_eq!(r134a.dew.temperature_at(0.0), Some(-26.1));
+    let t = r134a.dew.temperature_at(39.4).unwrap();
+    assert!(t > 100.7 && t < 101.0);
+    assert_eq!(r134a.dew.temperature_at(50.0), None);
+
+    // Na mesma pressão, a temperatura de orvalho é maior que a de bolha
+    let r407c = registry.get("r407c").unwrap();
+    assert!(r407c.dew.temperature_at(10.0).unwrap() > r407c.bubble.temperature_at(10.0).unwrap());
+
+    // Pontos de saturação conhecidos das equações de estado de referência (NIST), em bar absoluto
+    let known_points = [
+        ("r32", 1.774, -40.0),
+        ("r32", 24.783, 40.0),
+        ("r290", 4.7446, 0.0),
+        ("r290", 13.694, 40.0),
+        ("r1234yf", 1.01325, -29.49),
+        ("r1234yf", 6.826, 25.0),
+    ];
+    for (fluid, pressure_abs, temperature) in known_points {
+        let t = registry
+            .get(fluid)
+            .unwrap()
+            .dew
+            .temperature_at(pressure_abs - 1.01325)
+            .unwrap();
+        assert!((t - temperature).abs() < 0.1, "{}: {} °C", fluid, t);
+    }
+
+    let bad = r#"{ "format": 1, "fluid": "x", "revision": 1, "source": "", "pressure_unit": "bar_g",
+        "dew": [[0.0, -10.0], [1.0, -12.0]] }"#;
+    assert!(Refrigerant::parse(bad).is_err());
+}
@@ -50,10 +50,14 @@ pub struct HwInfoDAC {
     pub DAC_TYPE: Option<String>,
 }
 
+// Amostras a partir de ts_next não passam pelo cálculo do L1, assim o estado do L1 no fim da compilação
+// é o do fim do intervalo e pode ser usado no intervalo seguinte. Nesse caso prev_query_end é o fim
+// da consulta do intervalo anterior: as amostras antes de ts_ini dos pacotes até ele já passaram pelo L1.
 pub fn split_pack(
     payload: &TelemetryPackDAC_v2,
     ts_ini: i64,
     ts_next: i64,
+    prev_query_end: i64,
     dev: &HwInfoDAC,
     dac_state: &mut dyn DacL1Calculator,
     itemCallback: &mut dyn FnMut(&mut TelemetryDAC_v3, Option<bool>, Option<bool>, isize),
@@ -97,6 +101,11 @@ pub fn split_pack(
         Ok(date) => date.timestamp(),
     };
     let sampling_time: i64 = payload.samplingTime; // de quantos em quantos segundos o firmware lê os sensores e insere nos vetores.
+    let l1_ts_ini = if pack_ts <= prev_query_end {
+        ts_ini
+    } else {
+        i64::MIN
+    };
 
     let mut telemetry = TelemetryDAC_v3 {
         Lcmp: None,
@@ -116,6 +125,10 @@ pub fn split_pack(
     let mut remainingSteps = payload.L1.len();
     for _i in 0..payload.L1.len() {
         let telm_ts = pack_ts - ((remainingSteps as i64 - 1) * sampling_time);
+        if telm_ts < l1_ts_ini || telm_ts >= ts_next {
+            remainingSteps -= 1;
+            continue;
+        }
         remainingSteps = checkSetTelemetryValues(
             dev,
             payload,
@@ -130,9 +143,6 @@ pub fn split_pack(
         if telm_ts < ts_ini {
             continue;
         }
-        if telm_ts >= ts_next {
-            continue;
-        }
         itemCallback(
             &mut telemetry,
             L1,
@@ -899,3 +909,116 @@ pub fn parse_dac_payload(json_payload: &serde_json::Value) -> Result<TelemetryPa
 
     return Ok(payload);
 }
+
+// Calculador de L1 que só registra o instante de cada amostra processada
+#[cfg(test)]
+struct L1Timestamps(Vec<i64>);
+
+#[cfg(test)]
+impl DacL1Calculator for L1Timestamps {
+    fn calc_l1(
+        &mut self,
+        _building_tel: &TelemetryDAC_v3,
+        full_tel: &TelemetryDACv2,
+        _cfg: &HwInfoDAC,
+    ) -> Result<Option<bool>, String> {
+        self.0.push(full_tel.timestamp.timestamp());
+        Ok(Some(true))
+    }
+}
+
+#[test]
+fn test_split_pack_l1_window() {
+    let dev: HwInfoDAC = serde_json::from_value(serde_json::json!({
+        "isVrf": false, "hasAutomation": false,
+        "P0Psuc": false, "P1Psuc": false, "P0Pliq": false, "P1Pliq": false,
+        "P0multQuad": 0.0, "P1multQuad": 0.0, "P0mult": 1.0, "P1mult": 1.0, "P0ofst": 0.0, "P1ofst": 0.0,
+        "simulateL1": false, "l1_psuc_offset": 0.0,
+    }))
+    .unwrap();
+    let day = 24 * 60 * 60;
+    let day1 = NaiveDateTime::parse_from_str("2023-11-14T00:00:00", "%Y-%m-%dT%H:%M:%S")
+        .unwrap()
+        .timestamp();
+    // Pacotes de 5 minutos que atravessam a meia-noite
+    let packs: Vec<TelemetryPackDAC_v2> = (0..(2 * day / 300 + 10))
+        .map(|k| TelemetryPackDAC_v2 {
+            timestamp: NaiveDateTime::from_timestamp_opt(day1 - 20 * 60 + 150 + k * 300, 0)
+                .unwrap()
+                .format("%Y-%m-%dT%H:%M:%S")
+                .to_string(),
+            samplingTime: 5,
+            L1: vec![Some(true); 60],
+            T0: vec![Some(20.0); 60],
+            T1: vec![Some(10.0); 60],
+            T2: vec![Some(30.0); 60],
+            P0: vec![None; 60],
+            P1: vec![None; 60],
+            State: None,
+            Mode: None,
+            GMT: Some(-3),
+            saved_data: None,
+        })
+        .collect();
+    // Mesmas janelas de consulta do dac_hist: 15 minutos de preparação sem o estado do L1 e 60s depois do fim
+    let compile = |query_ini: i64,
+                   ts_ini: i64,
+                   ts_next: i64,
+                   prev_query_end: i64,
+                   state: &mut L1Timestamps| {
+        let mut points = Vec::new();
+        for payload in &packs {
+            let pack_ts = NaiveDateTime::parse_from_str(&payload.timestamp, "%Y-%m-%dT%H:%M:%S")
+                .unwrap()
+                .timestamp();
+            if pack_ts < query_ini || pack_ts > ts_next + 60 {
+                continue;
+            }
+            split_pack(
+                payload,
+                ts_ini,
+                ts_next,
+                prev_query_end,
+                &dev,
+                state,
+                &mut |_, _, _, index| {
+                    points.push(ts_ini + index as i64);
+                },
+            )
+            .unwrap();
+        }
+        points
+    };
+    let day2 = day1 + day;
+    let day3 = day2 + day;
+
+    // Compilação de vários dias, passando o estado do L1 de um dia para o outro
+    let mut carried = L1Timestamps(Vec::new());
+    let mut points = compile(day1 - 15 * 60, day1, day2, i64::MIN, &mut carried);
+    points.extend(compile(day2, day2, day3, day2 + 60, &mut carried));
+
+    // Os pontos são os mesmos das compilações diárias
+    let mut daily_points = compile(
+        day1 - 15 * 60,
+        day1,
+        day2,
+        i64::MIN,
+        &mut L1Timestamps(Vec::new()),
+    );
+    daily_points.extend(compile(
+        day2 - 15 * 60,
+        day2,
+        day3,
+        i64::MIN,
+        &mut L1Timestamps(Vec::new()),
+    ));
+    assert_eq!(points, daily_points);
+
+    // E o L1 recebe cada amostra uma única vez, em ordem, como numa consulta só
+    let mut continuous = L1Timestamps(Vec::new());
+    compile(day1 - 15 * 60, day1, day3, i64::MIN, &mut continuous);
+    assert_eq!(carried.0, continuous.0);
+    assert!(carried.0.windows(2).all(|w| w[1] - w[0] == 5));
+    // Inclusive as do fim do dia que vêm em pacotes depois da consulta do dia
+    assert!(carried.0.contains(&(day2 - 5)));
+}
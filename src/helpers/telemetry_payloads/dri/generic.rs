@@ -0,0 +1,224 @@
+use super::super::dri_telemetry::ChillerParametersChangesHist;
+use super::profile::DriProfile;
+use crate::telemetry_payloads::energy::padronized::calculateFormulas;
+use chrono::{NaiveDateTime, Timelike};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+
+/** Telemetria de DRI convertida por um perfil: um valor para cada campo do perfil, na mesma ordem */
+#[derive(Debug, Clone)]
+pub struct DriProfileTelemetry {
+    pub timestamp: NaiveDateTime,
+    pub values: Vec<Option<f64>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DriProfileCompiledPeriod {
+    params_grouped: Vec<Map<String, Value>>,
+    params_changed: Vec<ChillerParametersChangesHist>,
+}
+
+/**
+ * Converte o payload com os campos do perfil. As fórmulas vêm da propriedade "formulas" do payload (que o chamador
+ * preenche com as do dispositivo) e, para os campos sem fórmula, da fórmula padrão do perfil.
+ * O valor -1 é o que o DRI envia quando não conseguiu ler o registrador.
+ */
+pub fn decode_payload(
+    profile: &DriProfile,
+    mut payload: Value,
+) -> Result<DriProfileTelemetry, String> {
+    let timestamp = payload["timestamp"]
+        .as_str()
+        .ok_or_else(|| "Telemetry does not have \"timestamp\" field".to_owned())
+        .and_then(|ts| {
+            NaiveDateTime::parse_from_str(ts, "%Y-%m-%dT%H:%M:%S").map_err(|e| e.to_string())
+        })?;
+
+    let mut formulas = match payload.get("formulas") {
+        Some(Value::Object(formulas)) => formulas.clone(),
+        _ => Map::new(),
+    };
+    for field in &profile.fields {
+        if let Some(formula) = &field.formula {
+            formulas
+                .entry(formula_key(field))
+                .or_insert_with(|| formula.as_str().into());
+        }
+    }
+    payload["formulas"] = Value::Object(formulas);
+
+    let values = profile
+        .fields
+        .iter()
+        .map(|field| {
+            let raw = match field.index {
+                Some(index) => payload["values"].get(index),
+                None => payload.get(field.source()),
+            };
+            match raw.and_then(|raw| raw.as_f64()) {
+                None => None,
+                Some(raw) if raw == -1.0 => None,
+                Some(raw) => Some(calculateFormulas(&formula_key(field), raw, &payload, false)),
+            }
+        })
+        .collect();
+
+    Ok(DriProfileTelemetry { timestamp, values })
+}
+
+// Campos lidos do vetor "values" não têm nome no payload, a fórmula fica com o nome do campo
+fn formula_key(field: &super::profile::DriProfileField) -> String {
+    match field.index {
+        Some(_) => field.name.to_owned(),
+        None => field.source().to_owned(),
+    }
+}
+
+impl DriProfileTelemetry {
+    /** Formato da telemetria convertida enviada pelo iotrelay: os campos do perfil, null quando não tem valor */
+    pub fn to_json(&self, profile: &DriProfile) -> Value {
+        let mut obj = Map::new();
+        obj.insert(
+            "timestamp".to_owned(),
+            self.timestamp
+                .format("%Y-%m-%dT%H:%M:%S")
+                .to_string()
+                .into(),
+        );
+        for (field, value) in profile.fields.iter().zip(&self.values) {
+            obj.insert(field.name.to_owned(), (*value).into());
+        }
+        obj.insert("record_date".to_owned(), Value::Null);
+        Value::Object(obj)
+    }
+}
+
+/**
+ * Histórico do dia: médias dos campos "average" em grupos de 10 minutos (ou 1 hora com hour_graphic) e as mudanças
+ * dos campos "track_changes". A primeira e a última telemetria sempre entram no histórico de mudanças.
+ */
+pub fn compile_hist(
+    profile: &DriProfile,
+    device_code: &str,
+    telemetries: &[DriProfileTelemetry],
+    hour_graphic: bool,
+) -> DriProfileCompiledPeriod {
+    let interval = if hour_graphic { 60 } else { 10 };
+    let mut groups: BTreeMap<NaiveDateTime, Vec<(f64, usize)>> = BTreeMap::new();
+    let mut params_changed = Vec::new();
+    let mut last_values: Option<&[Option<f64>]> = None;
+
+    for (index, telemetry) in telemetries.iter().enumerate() {
+        let timestamp = telemetry.timestamp;
+        let rounded_minute = (timestamp.minute() / interval) * interval;
+        let rounded_timestamp = timestamp
+            .date()
+            .and_hms(timestamp.hour(), rounded_minute, 0);
+        let totals = groups
+            .entry(rounded_timestamp)
+            .or_insert_with(|| vec![(0.0, 0); profile.fields.len()]);
+
+        let save_all = index == 0 || index == telemetries.len() - 1;
+        for (i, field) in profile.fields.iter().enumerate() {
+            let value = telemetry.values[i];
+            if field.average {
+                if let Some(value) = value {
+                    totals[i].0 += value;
+                    totals[i].1 += 1;
+                }
+            }
+            if !field.track_changes {
+                continue;
+            }
+            let Some(value) = value else {
+                continue;
+            };
+            let changed = last_values.map_or(true, |last| last[i] != Some(value));
+            if save_all || changed {
+                params_changed.push(ChillerParametersChangesHist {
+                    device_code: device_code.to_owned(),
+                    parameter_name: field.name.to_owned(),
+                    record_date: timestamp,
+                    parameter_value: value.round() as i32,
+                });
+            }
+        }
+        last_values = Some(&telemetry.values);
+    }
+
+    let params_grouped = groups
+        .into_iter()
+        .map(|(time_interval, totals)| {
+            let mut obj = Map::new();
+            obj.insert("timestamp".to_owned(), time_interval.to_string().into());
+            for (field, (total, count)) in profile.fields.iter().zip(totals) {
+                let avg = (count > 0).then(|| (total / count as f64 * 100.0).round() / 100.0);
+                obj.insert(field.name.to_owned(), avg.into());
+            }
+            obj.insert(
+                "record_date".to_owned(),
+                time_interval.format("%Y-%m-%dT%H:%M:%S").to_string().into(),
+            );
+            obj
+        })
+        .collect();
+
+    DriProfileCompiledPeriod {
+        params_grouped,
+        params_changed,
+    }
+}
+
+#[test]
+fn test_dri_generic_driver() {
+    let profile: DriProfile = serde_json::from_str(
+        r#"{ "format": 1, "name": "teste", "hist_type": "TESTE", "fields": [
+            { "name": "STATUS", "track_changes": true },
+            { "name": "COOL_LWT", "formula": "/10", "average": true },
+            { "name": "CAP_T", "index": 1, "average": true }
+        ] }"#,
+    )
+    .unwrap();
+
+    let payload = |ts: &str, status: i64, lwt: i64| {
+        serde_json::json!({
+            "timestamp": ts, "type": "TESTE", "STATUS": status, "COOL_LWT": lwt, "values": [0, 40],
+            "formulas": { "CAP_T": "/2" },
+        })
+    };
+    let tels: Vec<DriProfileTelemetry> = [
+        payload("2024-01-01T10:00:00", 1, 70),
+        payload("2024-01-01T10:05:00", 1, 80),
+        payload("2024-01-01T10:12:00", 2, -1),
+        payload("2024-01-01T10:14:00", 2, 90),
+    ]
+    .into_iter()
+    .map(|p| decode_payload(&profile, p).unwrap())
+    .collect();
+    assert_eq!(tels[0].values, vec![Some(1.0), Some(7.0), Some(20.0)]);
+    assert_eq!(tels[2].values[1], None);
+    assert_eq!(
+        tels[0].to_json(&profile)["timestamp"],
+        "2024-01-01T10:00:00"
+    );
+
+    let hist = compile_hist(&profile, "DRI000000001", &tels, false);
+    assert_eq!(hist.params_grouped.len(), 2);
+    assert_eq!(hist.params_grouped[0]["COOL_LWT"], 7.5);
+    assert_eq!(hist.params_grouped[0]["STATUS"], Value::Null);
+    assert_eq!(hist.params_grouped[1]["record_date"], "2024-01-01T10:10:00");
+    let changes: Vec<(String, i32)> = hist
+        .params_changed
+        .iter()
+        .map(|c| (c.record_date.format("%M").to_string(), c.parameter_value))
+        .collect();
+    assert_eq!(
+        changes,
+        vec![
+            ("00".to_owned(), 1),
+            ("12".to_owned(), 2),
+            ("14".to_owned(), 2)
+        ]
+    );
+}
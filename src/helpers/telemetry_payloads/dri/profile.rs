@@ -0,0 +1,269 @@
+/*
+Perfis declarativos de DRI: descrevem os campos que um modelo (chiller, VRF...) envia, para que a conversão no
+iotrelay/telemetry_service, o histórico do rusthist e o merge dos valores calculados sejam feitos pelo driver
+genérico (generic.rs) em vez de um módulo escrito à mão para cada modelo.
+Os perfis embutidos ficam em "profiles/". Outros podem ser colocados no diretório da variável DRI_PROFILES_DIR e
+substituem os embutidos com o mesmo "name". Um perfil inválido é descartado com log de erro.
+
+Cada campo tem:
+ - name: nome do campo na telemetria convertida e no histórico
+ - source: propriedade do payload de onde vem o valor bruto (padrão: o próprio name)
+ - index: posição no vetor "values" do payload, no lugar de source
+ - formula: fórmula padrão aplicada ao valor bruto (ex.: "/10"), a do dispositivo (formulas) tem prioridade
+ - unit: unidade, só informativa
+ - average: entra na média dos grupos de 10 minutos (ou 1 hora) do histórico
+ - track_changes: entra no histórico de mudanças de parâmetro (params_changed)
+*/
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, OnceLock};
+
+const PROFILE_FORMAT: u32 = 1;
+
+const BUILTIN_PROFILES: [(&str, &str); 3] = [
+    (
+        "chiller_carrier_hx.json",
+        include_str!("profiles/chiller_carrier_hx.json"),
+    ),
+    (
+        "chiller_carrier_xa.json",
+        include_str!("profiles/chiller_carrier_xa.json"),
+    ),
+    (
+        "chiller_carrier_xa_hvar.json",
+        include_str!("profiles/chiller_carrier_xa_hvar.json"),
+    ),
+];
+
+static REGISTRY: OnceLock<DriProfileRegistry> = OnceLock::new();
+
+#[derive(Debug, Deserialize)]
+pub struct DriProfileField {
+    pub name: String,
+    source: Option<String>,
+    pub index: Option<usize>,
+    pub formula: Option<String>,
+    pub unit: Option<String>,
+    #[serde(default)]
+    pub average: bool,
+    #[serde(default)]
+    pub track_changes: bool,
+}
+
+impl DriProfileField {
+    /** Propriedade do payload com o valor bruto, também usada como chave da fórmula */
+    pub fn source(&self) -> &str {
+        self.source.as_deref().unwrap_or(&self.name)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DriProfile {
+    format: u32,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    // Valor de "dri_type" nas requisições de histórico do rusthist
+    pub hist_type: Option<String>,
+    // Valores de "type" do payload, na ordem de prioridade: igual, começa com, tem a propriedade
+    #[serde(default)]
+    payload_types: Vec<String>,
+    #[serde(default)]
+    payload_type_prefixes: Vec<String>,
+    #[serde(default)]
+    payload_has_field: Vec<String>,
+    pub fields: Vec<DriProfileField>,
+}
+
+impl DriProfile {
+    fn parse(contents: &str) -> Result<DriProfile, String> {
+        let profile: DriProfile = serde_json::from_str(contents).map_err(|err| err.to_string())?;
+        if profile.format != PROFILE_FORMAT {
+            return Err(format!("formato {} não suportado", profile.format));
+        }
+        if profile.name.is_empty() {
+            return Err("perfil sem nome".to_owned());
+        }
+        if profile.payload_types.is_empty()
+            && profile.payload_type_prefixes.is_empty()
+            && profile.payload_has_field.is_empty()
+            && profile.hist_type.is_none()
+        {
+            return Err(
+                "perfil não é usado por nenhum tipo de payload nem de histórico".to_owned(),
+            );
+        }
+        if profile.fields.is_empty() {
+            return Err("perfil sem campos".to_owned());
+        }
+        let mut names = HashSet::new();
+        for field in &profile.fields {
+            if field.name.is_empty() || matches!(field.name.as_str(), "timestamp" | "record_date") {
+                return Err(format!("nome de campo inválido: \"{}\"", field.name));
+            }
+            if !names.insert(field.name.as_str()) {
+                return Err(format!("campo repetido: {}", field.name));
+            }
+            if field.index.is_some() && field.source.is_some() {
+                return Err(format!("campo {} com index e source", field.name));
+            }
+        }
+        Ok(profile)
+    }
+}
+
+pub struct DriProfileRegistry {
+    profiles: HashMap<String, Arc<DriProfile>>,
+}
+
+impl DriProfileRegistry {
+    fn load(extra_dir: Option<&str>) -> Self {
+        let mut registry = DriProfileRegistry {
+            profiles: HashMap::new(),
+        };
+        for (file_name, contents) in BUILTIN_PROFILES {
+            registry.add_file(file_name, contents);
+        }
+        if let Some(extra_dir) = extra_dir {
+            if let Err(err) = registry.load_dir(extra_dir) {
+                crate::LOG.append_log_tag_msg(
+                    "ERROR",
+                    &format!("[79] Erro lendo DRI_PROFILES_DIR {}: {}", extra_dir, err),
+                );
+            }
+        }
+        registry
+    }
+
+    fn load_dir(&mut self, dir: &str) -> Result<(), String> {
+        let mut paths: Vec<_> = std::fs::read_dir(dir)
+            .map_err(|err| err.to_string())?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .collect();
+        paths.sort();
+        for path in paths {
+            match std::fs::read_to_string(&path) {
+                Ok(contents) => self.add_file(&path.to_string_lossy(), &contents),
+                Err(err) => crate::LOG.append_log_tag_msg(
+                    "ERROR",
+                    &format!("[79] Erro lendo {}: {}", path.to_string_lossy(), err),
+                ),
+            }
+        }
+        Ok(())
+    }
+
+    fn add_file(&mut self, file_name: &str, contents: &str) {
+        match DriProfile::parse(contents) {
+            Ok(profile) => {
+                self.profiles
+                    .insert(profile.name.clone(), Arc::new(profile));
+            }
+            Err(err) => crate::LOG.append_log_tag_msg(
+                "ERROR",
+                &format!("[80] Perfil de DRI inválido em {}: {}", file_name, err),
+            ),
+        };
+    }
+
+    /** Perfil que converte o payload, pelo "type" e, por último, pelas propriedades que ele tem */
+    pub fn for_payload(
+        &self,
+        payload_type: &str,
+        payload_json: &serde_json::Value,
+    ) -> Option<Arc<DriProfile>> {
+        if let Some(profile) = self
+            .profiles
+            .values()
+            .find(|p| p.payload_types.iter().any(|t| t == payload_type))
+        {
+            return Some(profile.clone());
+        }
+        // O prefixo mais longo ganha, para um modelo mais específico poder ter o próprio perfil
+        let by_prefix = self
+            .profiles
+            .values()
+            .filter_map(|p| {
+                p.payload_type_prefixes
+                    .iter()
+                    .filter(|prefix| payload_type.starts_with(prefix.as_str()))
+                    .map(|prefix| prefix.len())
+                    .max()
+                    .map(|len| (len, p))
+            })
+            .max_by_key(|(len, _)| *len);
+        if let Some((_, profile)) = by_prefix {
+            return Some(profile.clone());
+        }
+        self.profiles
+            .values()
+            .find(|p| {
+                p.payload_has_field
+                    .iter()
+                    .any(|field| payload_json.get(field).is_some())
+            })
+            .cloned()
+    }
+
+    pub fn for_hist_type(&self, dri_type: &str) -> Option<Arc<DriProfile>> {
+        self.profiles
+            .values()
+            .find(|p| p.hist_type.as_deref() == Some(dri_type))
+            .cloned()
+    }
+
+    /** Todos os campos de todos os perfis, usados no merge dos valores calculados do telemetry_service */
+    pub fn all_field_names(&self) -> HashSet<&str> {
+        self.profiles
+            .values()
+            .flat_map(|p| p.fields.iter().map(|f| f.name.as_str()))
+            .collect()
+    }
+}
+
+/** Carregado na primeira consulta, com os perfis embutidos mais os de DRI_PROFILES_DIR */
+pub fn registry() -> &'static DriProfileRegistry {
+    REGISTRY.get_or_init(|| {
+        let extra_dir = crate::envvars_loader::get_var_string_optional("DRI_PROFILES_DIR");
+        DriProfileRegistry::load(extra_dir.as_deref())
+    })
+}
+
+#[test]
+fn test_dri_profiles() {
+    for (file_name, contents) in BUILTIN_PROFILES {
+        if let Err(err) = DriProfile::parse(contents) {
+            panic!("{}: {}", file_name, err);
+        }
+    }
+
+    let registry = DriProfileRegistry::load(None);
+    let payload = serde_json::json!({ "CHIL_S_S": 1 });
+    let name_for = |payload_type: &str| {
+        registry
+            .for_payload(payload_type, &payload)
+            .map(|p| p.name.clone())
+    };
+    assert_eq!(
+        name_for("CHILLER-CARRIER-30XAB-HVAR").as_deref(),
+        Some("chiller_carrier_xa_hvar")
+    );
+    assert_eq!(
+        name_for("CHILLER-CARRIER-30XAB").as_deref(),
+        Some("chiller_carrier_xa")
+    );
+    assert_eq!(
+        name_for("CHILLER-CARRIER-30HXF").as_deref(),
+        Some("chiller_carrier_hx")
+    );
+    assert_eq!(name_for("OUTRO").as_deref(), Some("chiller_carrier_hx"));
+    assert!(registry
+        .for_payload("OUTRO", &serde_json::json!({}))
+        .is_none());
+    assert!(registry.for_hist_type("CHILLER_CARRIER_XA").is_some());
+
+    let repeated = r#"{ "format": 1, "name": "x", "hist_type": "X",
+        "fields": [{ "name": "A" }, { "name": "A" }] }"#;
+    assert!(DriProfile::parse(repeated).is_err());
+}
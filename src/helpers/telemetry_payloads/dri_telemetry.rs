@@ -34,37 +34,6 @@ pub struct HwInfoDRI {
     pub formulas: Option<HashMap<String, String>>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct DriChillerCarrierChangeParams {
-    pub timestamp: NaiveDateTime,
-    pub CHIL_S_S: Option<f64>,
-    pub ALM: Option<f64>,
-    pub EMSTOP: Option<f64>,
-    pub STATUS: Option<f64>,
-    pub CP_A1: Option<f64>,
-    pub CP_A2: Option<f64>,
-    pub CP_B1: Option<f64>,
-    pub CP_B2: Option<f64>,
-    pub CHIL_OCC: Option<f64>,
-}
-
-impl DriChillerCarrierChangeParams {
-    pub fn new(timestamp: NaiveDateTime) -> Self {
-        Self {
-            timestamp,
-            CHIL_S_S: None,
-            ALM: None,
-            EMSTOP: None,
-            STATUS: None,
-            CP_A1: None,
-            CP_A2: None,
-            CP_B1: None,
-            CP_B2: None,
-            CHIL_OCC: None,
-        }
-    }
-}
-
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ChillerParametersChangesHist {
     pub device_code: String,
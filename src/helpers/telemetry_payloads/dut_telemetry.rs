@@ -11,10 +11,14 @@ pub struct HwInfoDUT {
     pub temperature_offset: f64,
 }
 
+// Amostras a partir de ts_next não passam pelo cálculo do L1, assim o estado do L1 no fim da compilação
+// é o do fim do intervalo e pode ser usado no intervalo seguinte. Nesse caso prev_query_end é o fim
+// da consulta do intervalo anterior: as amostras antes de ts_ini dos pacotes até ele já passaram pelo L1.
 pub fn split_pack(
     payload: &TelemetryPackDUT_v2,
     ts_ini: i64,
     ts_next: i64,
+    prev_query_end: i64,
     dut_state: &mut L1Calculator,
     itemCallback: &mut dyn FnMut(&TelemetryDUT_v3, isize),
     dev: &HwInfoDUT,
@@ -79,23 +83,25 @@ pub fn split_pack(
             itemCallback(&telemetry, isize::try_from(pack_ts - ts_ini).unwrap());
         }
     } else {
+        let l1_ts_ini = if pack_ts <= prev_query_end {
+            ts_ini
+        } else {
+            i64::MIN
+        };
         let mut remainingSteps = pack_length;
         let mut telm_ts;
         for _i in 0..pack_length {
-            telemetry.timestamp = NaiveDateTime::from_timestamp_opt(
-                pack_ts - ((remainingSteps as i64 - 1) * sampling_time),
-                0,
-            )
-            .unwrap();
+            telm_ts = pack_ts - ((remainingSteps as i64 - 1) * sampling_time);
+            if telm_ts < l1_ts_ini || telm_ts >= ts_next {
+                remainingSteps -= 1;
+                continue;
+            }
+            telemetry.timestamp = NaiveDateTime::from_timestamp_opt(telm_ts, 0).unwrap();
             remainingSteps =
                 checkSetTelemetryValues(payload, &mut telemetry, dut_state, remainingSteps, dev);
-            telm_ts = pack_ts - ((remainingSteps as i64) * sampling_time);
             if telm_ts < ts_ini {
                 continue;
             }
-            if telm_ts >= ts_next {
-                continue;
-            }
             telemetry.timestamp = NaiveDateTime::from_timestamp_opt(telm_ts, 0).unwrap();
             itemCallback(&telemetry, isize::try_from(telm_ts - ts_ini).unwrap());
         }
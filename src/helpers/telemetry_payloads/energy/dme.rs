@@ -103,20 +103,7 @@ pub struct TelemetryDME<'a> {
     pub formulas: Option<HashMap<String, String>>,
 }
 
-pub const DME_METERS: [&str; 12] = [
-    "CG-ET330",
-    "ABB-NEXUS-II",
-    "ABB-ETE-30",
-    "ABB-ETE-50",
-    "CG-EM210",
-    "KRON-MULT-K",
-    "KRON-IKRON-03",
-    "SCHNEIDER-ELETRIC-PM2100",
-    "SCHNEIDER-ELECTRIC-PM210",
-    "SCHNEIDER-ELECTRIC-PM9C",
-    "KRON-MULT-K 05",
-    "KRON-MULT-K 120",
-];
+pub const DME_MANUFACTURER: &str = "Diel Energia";
 
 pub fn convert_dme_payload<'a>(
     mut payload: TelemetryDME<'a>,
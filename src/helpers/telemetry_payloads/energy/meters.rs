@@ -0,0 +1,559 @@
+/*
+Definições declarativas dos medidores de energia: para cada medidor, o mapa de registradores com a decodificação, a escala
+e a unidade de cada valor, usado para converter o payload em PadronizedEnergyTelemetry.
+O medidor é escolhido pelo "type" do payload (iotrelay/telemetry_service e histórico do DME) ou pelo fabricante e modelo
+do cadastro (histórico do rusthist). Payloads de energia do DRI sem definição para o "type" usam a do DME.
+As definições embutidas ficam em "meters/". Outras podem ser colocadas no diretório da variável ENERGY_METERS_DIR e
+substituem as embutidas com o mesmo "name", por exemplo para informar o "type" com que o DRI de uma instalação envia um
+medidor ou os registradores CMN onde ele grava cada valor. Uma definição inválida é descartada com log de erro.
+
+Cada campo tem:
+ - name: campo do PadronizedEnergyTelemetry (v_a, i_b, pot_at_tri, en_at_tri...)
+ - source: propriedade do payload com o valor bruto (padrão: o próprio name)
+ - register: registrador Modbus do manual do fabricante, só informativo
+ - decode: como o valor bruto é lido (padrão: o "decode" da definição):
+   "number" (como veio), "float32" (bits do IEEE 754), "int16" e "int32" (inteiro com sinal enviado sem sinal)
+ - scale: multiplicador da resolução do registrador (padrão 1)
+ - unit: unidade do valor já multiplicado, convertida para a do PadronizedEnergyTelemetry (V, A, kW, kVA, kvar, kWh,
+   kvarh, kVAh, Hz). Sem unit o valor já está nessa unidade.
+ - pf_format: só nos fatores de potência: "signed" (-1 a 1, padrão) ou "four_quadrant" (0 a 2, como o PM2100)
+ - invalid_values: valores brutos que indicam leitura indisponível (padrão: os da definição)
+A fórmula do dispositivo (formulas) é aplicada ao valor decodificado, antes da escala. Como no calculateFormulas,
+ela não é aplicada aos valores float32.
+*/
+use super::padronized::{calculateFormulas, convert_4Q_FP_PF, PadronizedEnergyTelemetry};
+use chrono::NaiveDateTime;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, OnceLock};
+
+const DEFINITION_FORMAT: u32 = 1;
+
+// Usada nos payloads de energia do DRI sem definição para o "type"
+const DME_DEFINITION: &str = "dme";
+
+const BUILTIN_METERS: [(&str, &str); 12] = [
+    ("abb_ete.json", include_str!("meters/abb_ete.json")),
+    ("abb_m4m.json", include_str!("meters/abb_m4m.json")),
+    (
+        "carlo_gavazzi.json",
+        include_str!("meters/carlo_gavazzi.json"),
+    ),
+    ("dme.json", include_str!("meters/dme.json")),
+    (
+        "eastron_sdm120.json",
+        include_str!("meters/eastron_sdm120.json"),
+    ),
+    (
+        "eastron_sdm630.json",
+        include_str!("meters/eastron_sdm630.json"),
+    ),
+    ("kron_ikron.json", include_str!("meters/kron_ikron.json")),
+    ("kron_mult_k.json", include_str!("meters/kron_mult_k.json")),
+    (
+        "schneider_iem3000.json",
+        include_str!("meters/schneider_iem3000.json"),
+    ),
+    (
+        "schneider_pm210.json",
+        include_str!("meters/schneider_pm210.json"),
+    ),
+    (
+        "schneider_pm2100.json",
+        include_str!("meters/schneider_pm2100.json"),
+    ),
+    (
+        "schneider_pm9c.json",
+        include_str!("meters/schneider_pm9c.json"),
+    ),
+];
+
+// Valores que o DRI envia quando não tem a leitura
+fn default_invalid_values() -> Vec<f64> {
+    vec![-1.0, 65535.0, 1845494299.0, 2147483647.0]
+}
+
+static REGISTRY: OnceLock<MeterRegistry> = OnceLock::new();
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Quantity {
+    Voltage,
+    Current,
+    ActivePower,
+    ApparentPower,
+    ReactivePower,
+    ActiveEnergy,
+    ReactiveEnergy,
+    ApparentEnergy,
+    PowerFactor,
+    Frequency,
+    Error,
+}
+
+impl Quantity {
+    fn of_field(name: &str) -> Option<Quantity> {
+        Some(match name {
+            "v_a" | "v_b" | "v_c" | "v_ab" | "v_bc" | "v_ca" | "v_tri_ln" | "v_tri_ll" => {
+                Quantity::Voltage
+            }
+            "i_a" | "i_b" | "i_c" => Quantity::Current,
+            "pot_at_a" | "pot_at_b" | "pot_at_c" | "pot_at_tri" | "demanda" | "demanda_at"
+            | "demanda_med_at" => Quantity::ActivePower,
+            "pot_ap_a" | "pot_ap_b" | "pot_ap_c" | "pot_ap_tri" | "demanda_ap" => {
+                Quantity::ApparentPower
+            }
+            "pot_re_a" | "pot_re_b" | "pot_re_c" | "pot_re_tri" => Quantity::ReactivePower,
+            "en_at_tri" => Quantity::ActiveEnergy,
+            "en_re_tri" => Quantity::ReactiveEnergy,
+            "en_ap_tri" => Quantity::ApparentEnergy,
+            "fp_a" | "fp_b" | "fp_c" | "fp" => Quantity::PowerFactor,
+            "freq" => Quantity::Frequency,
+            "erro" => Quantity::Error,
+            _ => return None,
+        })
+    }
+
+    // Fator para a unidade do PadronizedEnergyTelemetry, None se a unidade não for dessa grandeza
+    fn unit_factor(self, unit: &str) -> Option<f64> {
+        let units: &[(&str, f64)] = match self {
+            Quantity::Voltage => &[("V", 1.0), ("kV", 1000.0)],
+            Quantity::Current => &[("A", 1.0), ("mA", 0.001)],
+            Quantity::ActivePower => &[("kW", 1.0), ("W", 0.001), ("MW", 1000.0)],
+            Quantity::ApparentPower => &[("kVA", 1.0), ("VA", 0.001), ("MVA", 1000.0)],
+            Quantity::ReactivePower => &[("kvar", 1.0), ("var", 0.001), ("Mvar", 1000.0)],
+            Quantity::ActiveEnergy => &[("kWh", 1.0), ("Wh", 0.001), ("MWh", 1000.0)],
+            Quantity::ReactiveEnergy => &[("kvarh", 1.0), ("varh", 0.001), ("Mvarh", 1000.0)],
+            Quantity::ApparentEnergy => &[("kVAh", 1.0), ("VAh", 0.001), ("MVAh", 1000.0)],
+            Quantity::Frequency => &[("Hz", 1.0)],
+            Quantity::PowerFactor | Quantity::Error => &[],
+        };
+        units
+            .iter()
+            .find(|(name, _)| *name == unit)
+            .map(|(_, factor)| *factor)
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum Decode {
+    Number,
+    Float32,
+    Int16,
+    Int32,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum PowerFactorFormat {
+    Signed,
+    FourQuadrant,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MeterField {
+    pub name: String,
+    source: Option<String>,
+    pub register: Option<String>,
+    decode: Option<Decode>,
+    #[serde(default = "default_scale")]
+    scale: f64,
+    pub unit: Option<String>,
+    pf_format: Option<PowerFactorFormat>,
+    invalid_values: Option<Vec<f64>>,
+    // scale já multiplicado pelo fator da unidade, calculado no carregamento
+    #[serde(skip)]
+    factor: f64,
+}
+
+fn default_scale() -> f64 {
+    1.0
+}
+
+impl MeterField {
+    /** Propriedade do payload com o valor bruto */
+    pub fn source(&self) -> &str {
+        self.source.as_deref().unwrap_or(&self.name)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MeterDefinition {
+    format: u32,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    manufacturer: String,
+    // Modelos do cadastro, comparados sem maiúsculas, espaços e pontuação
+    models: Vec<String>,
+    // Valores de "type" com que o DRI envia o medidor
+    #[serde(default)]
+    payload_types: Vec<String>,
+    #[serde(default = "default_decode")]
+    decode: Decode,
+    #[serde(default = "default_invalid_values")]
+    invalid_values: Vec<f64>,
+    // Monofásico: os totais que faltam são os valores da fase A
+    #[serde(default)]
+    single_phase: bool,
+    pub fields: Vec<MeterField>,
+}
+
+fn default_decode() -> Decode {
+    Decode::Number
+}
+
+impl MeterDefinition {
+    fn parse(contents: &str) -> Result<MeterDefinition, String> {
+        let mut definition: MeterDefinition =
+            serde_json::from_str(contents).map_err(|err| err.to_string())?;
+        if definition.format != DEFINITION_FORMAT {
+            return Err(format!("formato {} não suportado", definition.format));
+        }
+        if definition.name.is_empty() || definition.manufacturer.is_empty() {
+            return Err("definição sem nome ou sem fabricante".to_owned());
+        }
+        if definition
+            .models
+            .iter()
+            .all(|m| normalize_name(m).is_empty())
+        {
+            return Err("definição sem modelos".to_owned());
+        }
+        if definition.fields.is_empty() {
+            return Err("definição sem campos".to_owned());
+        }
+        let mut names = HashSet::new();
+        for field in &mut definition.fields {
+            let quantity = Quantity::of_field(&field.name)
+                .ok_or_else(|| format!("campo desconhecido: \"{}\"", field.name))?;
+            if !names.insert(field.name.clone()) {
+                return Err(format!("campo repetido: {}", field.name));
+            }
+            if !field.scale.is_finite() || field.scale == 0.0 {
+                return Err(format!("scale inválido no campo {}", field.name));
+            }
+            let unit_factor = match &field.unit {
+                Some(unit) => quantity
+                    .unit_factor(unit)
+                    .ok_or_else(|| format!("unidade {} inválida no campo {}", unit, field.name))?,
+                None => 1.0,
+            };
+            if field.pf_format.is_some() && quantity != Quantity::PowerFactor {
+                return Err(format!("pf_format no campo {}", field.name));
+            }
+            field.factor = field.scale * unit_factor;
+        }
+        Ok(definition)
+    }
+
+    /** Converte o payload (TelemetryDME ou o JSON gravado pelo DRI) com o mapa de registradores do medidor */
+    pub fn decode(&self, payload: &Value) -> Result<PadronizedEnergyTelemetry, String> {
+        let timestamp = payload["timestamp"]
+            .as_str()
+            .ok_or_else(|| "Telemetry does not have \"timestamp\" field".to_owned())
+            .and_then(|ts| {
+                NaiveDateTime::parse_from_str(ts, "%Y-%m-%dT%H:%M:%S").map_err(|e| e.to_string())
+            })?;
+
+        let mut values = Map::new();
+        for field in &self.fields {
+            // Os contadores de energia podem vir como string, como no DME
+            let raw = match payload.get(field.source()) {
+                Some(Value::Number(n)) => n.as_f64(),
+                Some(Value::String(s)) => s.parse::<f64>().ok(),
+                _ => None,
+            };
+            let Some(raw) = raw else {
+                continue;
+            };
+            let invalid_values = field
+                .invalid_values
+                .as_ref()
+                .unwrap_or(&self.invalid_values);
+            if invalid_values.contains(&raw) {
+                continue;
+            }
+            let value = match field.decode.unwrap_or(self.decode) {
+                Decode::Number => calculateFormulas(&field.name, raw, payload, false),
+                Decode::Float32 => calculateFormulas(&field.name, raw, payload, true),
+                Decode::Int16 => {
+                    calculateFormulas(&field.name, raw as i64 as u16 as i16 as f64, payload, false)
+                }
+                Decode::Int32 => {
+                    calculateFormulas(&field.name, raw as i64 as u32 as i32 as f64, payload, false)
+                }
+            };
+            let mut value = value * field.factor;
+            if field.pf_format == Some(PowerFactorFormat::FourQuadrant) {
+                value = convert_4Q_FP_PF(value);
+            }
+            // NaN é o valor indisponível dos registradores float32
+            if value.is_finite() {
+                values.insert(field.name.to_owned(), value.into());
+            }
+        }
+        if self.single_phase {
+            let totals = [
+                ("v_tri_ln", "v_a"),
+                ("pot_at_tri", "pot_at_a"),
+                ("pot_ap_tri", "pot_ap_a"),
+                ("pot_re_tri", "pot_re_a"),
+                ("fp", "fp_a"),
+            ];
+            for (total, phase) in totals {
+                if let (false, Some(value)) = (values.contains_key(total), values.get(phase)) {
+                    values.insert(total.to_owned(), value.clone());
+                }
+            }
+        }
+
+        values.insert(
+            "timestamp".to_owned(),
+            timestamp.format("%Y-%m-%dT%H:%M:%S").to_string().into(),
+        );
+        serde_json::from_value(Value::Object(values)).map_err(|err| err.to_string())
+    }
+}
+
+// "Schneider Electric" e "schneider-electric", "Mult-K 05" e "MULT-K05" são iguais
+fn normalize_name(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(|c| c.to_lowercase())
+        .collect()
+}
+
+pub struct MeterRegistry {
+    definitions: HashMap<String, Arc<MeterDefinition>>,
+}
+
+impl MeterRegistry {
+    fn load(extra_dir: Option<&str>) -> Self {
+        let mut registry = MeterRegistry {
+            definitions: HashMap::new(),
+        };
+        for (file_name, contents) in BUILTIN_METERS {
+            registry.add_file(file_name, contents);
+        }
+        if let Some(extra_dir) = extra_dir {
+            if let Err(err) = registry.load_dir(extra_dir) {
+                crate::LOG.append_log_tag_msg(
+                    "ERROR",
+                    &format!("[81] Erro lendo ENERGY_METERS_DIR {}: {}", extra_dir, err),
+                );
+            }
+        }
+        registry
+    }
+
+    fn load_dir(&mut self, dir: &str) -> Result<(), String> {
+        let mut paths: Vec<_> = std::fs::read_dir(dir)
+            .map_err(|err| err.to_string())?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .collect();
+        paths.sort();
+        for path in paths {
+            match std::fs::read_to_string(&path) {
+                Ok(contents) => self.add_file(&path.to_string_lossy(), &contents),
+                Err(err) => crate::LOG.append_log_tag_msg(
+                    "ERROR",
+                    &format!("[81] Erro lendo {}: {}", path.to_string_lossy(), err),
+                ),
+            }
+        }
+        Ok(())
+    }
+
+    fn add_file(&mut self, file_name: &str, contents: &str) {
+        match MeterDefinition::parse(contents) {
+            Ok(definition) => {
+                self.definitions
+                    .insert(definition.name.clone(), Arc::new(definition));
+            }
+            Err(err) => crate::LOG.append_log_tag_msg(
+                "ERROR",
+                &format!(
+                    "[82] Definição de medidor de energia inválida em {}: {}",
+                    file_name, err
+                ),
+            ),
+        };
+    }
+
+    /** Medidor com o "type" do payload */
+    pub fn for_payload_type(&self, payload_type: &str) -> Option<Arc<MeterDefinition>> {
+        self.definitions
+            .values()
+            .find(|d| d.payload_types.iter().any(|t| t == payload_type))
+            .cloned()
+    }
+
+    /** Medidor com o "type" do payload, ou o DME */
+    pub fn for_payload(&self, payload_type: Option<&str>) -> Option<Arc<MeterDefinition>> {
+        payload_type
+            .and_then(|t| self.for_payload_type(t))
+            .or_else(|| self.definitions.get(DME_DEFINITION).cloned())
+    }
+
+    /** Medidor com o fabricante e o modelo do cadastro */
+    pub fn for_meter(&self, manufacturer: &str, model: &str) -> Option<Arc<MeterDefinition>> {
+        let (manufacturer, model) = (normalize_name(manufacturer), normalize_name(model));
+        self.definitions
+            .values()
+            .find(|d| {
+                normalize_name(&d.manufacturer) == manufacturer
+                    && d.models.iter().any(|m| normalize_name(m) == model)
+            })
+            .cloned()
+    }
+}
+
+/** Carregado na primeira consulta, com as definições embutidas mais as de ENERGY_METERS_DIR */
+pub fn registry() -> &'static MeterRegistry {
+    REGISTRY.get_or_init(|| {
+        let extra_dir = crate::envvars_loader::get_var_string_optional("ENERGY_METERS_DIR");
+        MeterRegistry::load(extra_dir.as_deref())
+    })
+}
+
+#[test]
+fn test_energy_meter_definitions() {
+    for (file_name, contents) in BUILTIN_METERS {
+        if let Err(err) = MeterDefinition::parse(contents) {
+            panic!("{}: {}", file_name, err);
+        }
+    }
+
+    let registry = MeterRegistry::load(None);
+    let by_type =
+        |payload_type: Option<&str>| registry.for_payload(payload_type).unwrap().name.clone();
+    assert_eq!(
+        by_type(Some("SCHNEIDER-ELETRIC-PM2100")),
+        "schneider_pm2100"
+    );
+    assert_eq!(by_type(Some("KRON-MULT-K 05")), "kron_mult_k");
+    assert_eq!(by_type(Some("OUTRO")), "dme");
+    assert_eq!(by_type(None), "dme");
+    let by_model = |manufacturer: &str, model: &str| {
+        registry
+            .for_meter(manufacturer, model)
+            .map(|d| d.name.clone())
+    };
+    assert_eq!(
+        by_model("Eastron", "SDM630-Modbus").as_deref(),
+        Some("eastron_sdm630")
+    );
+    assert_eq!(
+        by_model("schneider electric", "IEM3255").as_deref(),
+        Some("schneider_iem3000")
+    );
+    assert_eq!(by_model("KRON", "MULT-K05").as_deref(), Some("kron_mult_k"));
+    assert_eq!(by_model("ABB", "PM2100"), None);
+
+    let bad_unit = r#"{ "format": 1, "name": "x", "manufacturer": "X", "models": ["X"],
+        "fields": [{ "name": "pot_at_tri", "unit": "kWh" }] }"#;
+    assert!(MeterDefinition::parse(bad_unit).is_err());
+    let bad_field = r#"{ "format": 1, "name": "x", "manufacturer": "X", "models": ["X"],
+        "fields": [{ "name": "potencia" }] }"#;
+    assert!(MeterDefinition::parse(bad_field).is_err());
+}
+
+#[test]
+fn test_energy_meter_samples() {
+    use super::dme::TelemetryDME;
+    use std::convert::TryFrom;
+
+    // Amostras montadas com a codificação dos registradores de cada medidor: float32 chega com os bits do IEEE 754 e
+    // inteiros com sinal chegam sem sinal, como o DRI lê do Modbus
+    let bits = |value: f32| value.to_bits() as f64;
+    let approx = |value: Option<f64>, expected: f64| (value.unwrap() - expected).abs() < 1e-4;
+    let from_dri = |payload: Value| {
+        let tel: TelemetryDME = serde_json::from_value(payload).unwrap();
+        PadronizedEnergyTelemetry::try_from(tel).unwrap()
+    };
+
+    let pm2100 = from_dri(serde_json::json!({
+        "dev_id": "DRI000000001", "timestamp": "2024-01-01T10:00:00", "type": "SCHNEIDER-ELETRIC-PM2100",
+        "v_a": bits(220.5), "v_b": -1, "en_at_tri": "123456", "fp": bits(1.25),
+    }));
+    assert_eq!(pm2100.v_a, Some(220.5));
+    assert_eq!(pm2100.v_b, None);
+    assert_eq!(pm2100.en_at_tri, Some(123456.0));
+    assert_eq!(pm2100.fp, Some(0.75));
+
+    // Valores já convertidos pelo DRI, com a fórmula do dispositivo
+    let mult_k = from_dri(serde_json::json!({
+        "dev_id": "DRI000000001", "timestamp": "2024-01-01T10:00:00", "type": "KRON-MULT-K",
+        "pot_at_tri": 15.5, "i_a": 65535, "erro": 65535, "formulas": { "pot_at_tri": "*2" },
+    }));
+    assert_eq!(mult_k.pot_at_tri, Some(31.0));
+    assert_eq!(mult_k.i_a, None);
+    assert_eq!(mult_k.erro, Some(65535.0));
+
+    let registry = MeterRegistry::load(None);
+    let decode = |manufacturer: &str, model: &str, payload: Value| {
+        let mut payload = payload;
+        payload["timestamp"] = "2024-01-01T10:00:00".into();
+        registry
+            .for_meter(manufacturer, model)
+            .unwrap()
+            .decode(&payload)
+            .unwrap()
+    };
+
+    // Potências em W e NaN no registrador sem leitura
+    let sdm630 = decode(
+        "Eastron",
+        "SDM630",
+        serde_json::json!({
+            "v_a": bits(229.8), "v_b": bits(f32::NAN), "pot_at_tri": bits(-1520.0), "fp": bits(-0.95),
+            "en_at_tri": bits(1234.5), "v_tri_ll": bits(398.0),
+        }),
+    );
+    assert!(approx(sdm630.v_a, 229.8));
+    assert_eq!(sdm630.v_b, None);
+    assert!(approx(sdm630.pot_at_tri, -1.52));
+    assert!(approx(sdm630.fp, -0.95));
+    assert_eq!(sdm630.en_at_tri, Some(1234.5));
+    assert_eq!(sdm630.v_tri_ll, Some(398.0));
+
+    // Monofásico: os totais são os da fase
+    let sdm120 = decode(
+        "Eastron",
+        "SDM120",
+        serde_json::json!({ "v_a": bits(230.0), "pot_at_a": bits(1500.0), "fp_a": bits(0.98) }),
+    );
+    assert_eq!(sdm120.v_tri_ln, Some(230.0));
+    assert_eq!(sdm120.pot_at_tri, Some(1.5));
+    assert!(approx(sdm120.fp, 0.98));
+
+    // Energia INT64 em Wh, com a fórmula do dispositivo antes da conversão para kWh
+    let iem3255 = decode(
+        "Schneider Electric",
+        "iEM3255",
+        serde_json::json!({
+            "pot_at_tri": bits(12.5), "en_at_tri": 123456789, "formulas": { "en_at_tri": "*2" },
+        }),
+    );
+    assert_eq!(iem3255.pot_at_tri, Some(12.5));
+    assert!(approx(iem3255.en_at_tri, 246913.578));
+
+    // Inteiros com resolução fixa, INT32/INT16 com sinal e 0xFFFFFFFF sem leitura
+    let m4m = decode(
+        "ABB",
+        "M4M 30",
+        serde_json::json!({
+            "v_a": 2301, "i_a": 4294967295u32, "pot_at_tri": (-150000i32) as u32, "fp": (-950i16) as u16,
+            "freq": 6001, "en_at_tri": 12345678,
+        }),
+    );
+    assert!(approx(m4m.v_a, 230.1));
+    assert_eq!(m4m.i_a, None);
+    assert!(approx(m4m.pot_at_tri, -1.5));
+    assert!(approx(m4m.fp, -0.95));
+    assert!(approx(m4m.freq, 60.01));
+    assert!(approx(m4m.en_at_tri, 123456.78));
+}
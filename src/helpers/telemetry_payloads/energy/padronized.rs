@@ -4,6 +4,7 @@ use serde_json::{json, Value};
 use std::convert::TryFrom;
 
 use crate::telemetry_payloads::energy::dme::TelemetryDME;
+use crate::telemetry_payloads::energy::meters;
 
 pub fn calculateFormulas(param: &str, value: f64, tel: &Value, is_ieee754_fp: bool) -> f64 {
     let computed_value = {
@@ -174,7 +175,7 @@ pub struct PadronizedEnergyTelemetry {
     pub timestamp: NaiveDateTime,
 }
 
-fn convert_4Q_FP_PF(value: f64) -> f64 {
+pub(super) fn convert_4Q_FP_PF(value: f64) -> f64 {
     if value > 1.0 {
         return 2.0 - value;
     } else if value < -1.0 {
@@ -188,380 +189,10 @@ fn convert_4Q_FP_PF(value: f64) -> f64 {
 impl<'a> TryFrom<TelemetryDME<'a>> for PadronizedEnergyTelemetry {
     type Error = String;
     fn try_from(value: TelemetryDME) -> Result<PadronizedEnergyTelemetry, String> {
-        let timestamp =
-            NaiveDateTime::parse_from_str(value.timestamp.as_ref(), "%Y-%m-%dT%H:%M:%S")
-                .map_err(|e| e.to_string())?;
-        let tel = json!(value);
-
-        let is_schneider_pm2100 = match value.dev_type.as_ref() {
-            Some(v) => v.as_str() == "SCHNEIDER-ELETRIC-PM2100",
-            _ => false,
-        };
-
-        let is_schneider_pm210 = match value.dev_type.as_ref() {
-            Some(v) => v.as_str() == "SCHNEIDER-ELECTRIC-PM210",
-            _ => false,
-        };
-
-        let is_ieee754_fp = match value.dev_type.as_ref() {
-            Some(v) => v.as_str() == "KRON-IKRON-03" || is_schneider_pm2100 || is_schneider_pm210,
-            _ => false,
-        };
-
-        let result = PadronizedEnergyTelemetry {
-            timestamp,
-            v_a: match value.v_a {
-                None | Some(-1.0) | Some(65535.0) | Some(1845494299.0) | Some(2147483647.0) => None,
-                _ => Some(calculateFormulas(
-                    "v_a",
-                    value.v_a.unwrap(),
-                    &tel,
-                    is_ieee754_fp,
-                )),
-            },
-            v_b: match value.v_b {
-                None | Some(-1.0) | Some(65535.0) | Some(1845494299.0) | Some(2147483647.0) => None,
-                _ => Some(calculateFormulas(
-                    "v_b",
-                    value.v_b.unwrap(),
-                    &tel,
-                    is_ieee754_fp,
-                )),
-            },
-            v_c: match value.v_c {
-                None | Some(-1.0) | Some(65535.0) | Some(1845494299.0) | Some(2147483647.0) => None,
-                _ => Some(calculateFormulas(
-                    "v_c",
-                    value.v_c.unwrap(),
-                    &tel,
-                    is_ieee754_fp,
-                )),
-            },
-            v_ab: match value.v_ab {
-                None | Some(-1.0) | Some(65535.0) | Some(1845494299.0) | Some(2147483647.0) => None,
-                _ => Some(calculateFormulas(
-                    "v_ab",
-                    value.v_ab.unwrap(),
-                    &tel,
-                    is_ieee754_fp,
-                )),
-            },
-            v_bc: match value.v_bc {
-                None | Some(-1.0) | Some(65535.0) | Some(1845494299.0) | Some(2147483647.0) => None,
-                _ => Some(calculateFormulas(
-                    "v_bc",
-                    value.v_bc.unwrap(),
-                    &tel,
-                    is_ieee754_fp,
-                )),
-            },
-            v_ca: match value.v_ca {
-                None | Some(-1.0) | Some(65535.0) | Some(1845494299.0) | Some(2147483647.0) => None,
-                _ => Some(calculateFormulas(
-                    "v_ca",
-                    value.v_ca.unwrap(),
-                    &tel,
-                    is_ieee754_fp,
-                )),
-            },
-            i_a: match value.i_a {
-                None | Some(-1.0) | Some(65535.0) | Some(1845494299.0) | Some(2147483647.0) => None,
-                _ => Some(calculateFormulas(
-                    "i_a",
-                    value.i_a.unwrap(),
-                    &tel,
-                    is_ieee754_fp,
-                )),
-            },
-            i_b: match value.i_b {
-                None | Some(-1.0) | Some(65535.0) | Some(1845494299.0) | Some(2147483647.0) => None,
-                _ => Some(calculateFormulas(
-                    "i_b",
-                    value.i_b.unwrap(),
-                    &tel,
-                    is_ieee754_fp,
-                )),
-            },
-            i_c: match value.i_c {
-                None | Some(-1.0) | Some(65535.0) | Some(1845494299.0) | Some(2147483647.0) => None,
-                _ => Some(calculateFormulas(
-                    "i_c",
-                    value.i_c.unwrap(),
-                    &tel,
-                    is_ieee754_fp,
-                )),
-            },
-            pot_at_a: match value.pot_at_a {
-                None | Some(-1.0) | Some(65535.0) | Some(1845494299.0) | Some(2147483647.0) => None,
-                _ => Some(calculateFormulas(
-                    "pot_at_a",
-                    value.pot_at_a.unwrap(),
-                    &tel,
-                    is_ieee754_fp,
-                )),
-            },
-            pot_at_b: match value.pot_at_b {
-                None | Some(-1.0) | Some(65535.0) | Some(1845494299.0) | Some(2147483647.0) => None,
-                _ => Some(calculateFormulas(
-                    "pot_at_b",
-                    value.pot_at_b.unwrap(),
-                    &tel,
-                    is_ieee754_fp,
-                )),
-            },
-            pot_at_c: match value.pot_at_c {
-                None | Some(-1.0) | Some(65535.0) | Some(1845494299.0) | Some(2147483647.0) => None,
-                _ => Some(calculateFormulas(
-                    "pot_at_c",
-                    value.pot_at_c.unwrap(),
-                    &tel,
-                    is_ieee754_fp,
-                )),
-            },
-            pot_ap_a: match value.pot_ap_a {
-                None | Some(-1.0) | Some(65535.0) | Some(1845494299.0) | Some(2147483647.0) => None,
-                _ => Some(calculateFormulas(
-                    "pot_ap_a",
-                    value.pot_ap_a.unwrap(),
-                    &tel,
-                    is_ieee754_fp,
-                )),
-            },
-            pot_ap_b: match value.pot_ap_b {
-                None | Some(-1.0) | Some(65535.0) | Some(1845494299.0) | Some(2147483647.0) => None,
-                _ => Some(calculateFormulas(
-                    "pot_ap_b",
-                    value.pot_ap_b.unwrap(),
-                    &tel,
-                    is_ieee754_fp,
-                )),
-            },
-            pot_ap_c: match value.pot_ap_c {
-                None | Some(-1.0) | Some(65535.0) | Some(1845494299.0) | Some(2147483647.0) => None,
-                _ => Some(calculateFormulas(
-                    "pot_ap_c",
-                    value.pot_ap_c.unwrap(),
-                    &tel,
-                    is_ieee754_fp,
-                )),
-            },
-            pot_re_a: match value.pot_re_a {
-                None | Some(-1.0) | Some(65535.0) | Some(1845494299.0) | Some(2147483647.0) => None,
-                _ => Some(calculateFormulas(
-                    "pot_re_a",
-                    value.pot_re_a.unwrap(),
-                    &tel,
-                    is_ieee754_fp,
-                )),
-            },
-            pot_re_b: match value.pot_re_b {
-                None | Some(-1.0) | Some(65535.0) | Some(1845494299.0) | Some(2147483647.0) => None,
-                _ => Some(calculateFormulas(
-                    "pot_re_b",
-                    value.pot_re_b.unwrap(),
-                    &tel,
-                    is_ieee754_fp,
-                )),
-            },
-            pot_re_c: match value.pot_re_c {
-                None | Some(-1.0) | Some(65535.0) | Some(1845494299.0) | Some(2147483647.0) => None,
-                _ => Some(calculateFormulas(
-                    "pot_re_c",
-                    value.pot_re_c.unwrap(),
-                    &tel,
-                    is_ieee754_fp,
-                )),
-            },
-            v_tri_ln: match value.v_tri_ln {
-                None | Some(-1.0) | Some(65535.0) | Some(1845494299.0) | Some(2147483647.0) => None,
-                _ => Some(calculateFormulas(
-                    "v_tri_ln",
-                    value.v_tri_ln.unwrap(),
-                    &tel,
-                    is_ieee754_fp,
-                )),
-            },
-            v_tri_ll: match value.v_tri_ll {
-                None | Some(-1.0) | Some(65535.0) | Some(1845494299.0) | Some(2147483647.0) => None,
-                _ => Some(calculateFormulas(
-                    "v_tri_ll",
-                    value.v_tri_ll.unwrap(),
-                    &tel,
-                    is_ieee754_fp,
-                )),
-            },
-            pot_at_tri: match value.pot_at_tri {
-                None | Some(-1.0) | Some(65535.0) | Some(1845494299.0) | Some(2147483647.0) => None,
-                _ => Some(calculateFormulas(
-                    "pot_at_tri",
-                    value.pot_at_tri.unwrap(),
-                    &tel,
-                    is_ieee754_fp,
-                )),
-            },
-            pot_ap_tri: match value.pot_ap_tri {
-                None | Some(-1.0) | Some(65535.0) | Some(1845494299.0) | Some(2147483647.0) => None,
-                _ => Some(calculateFormulas(
-                    "pot_ap_tri",
-                    value.pot_ap_tri.unwrap(),
-                    &tel,
-                    is_ieee754_fp,
-                )),
-            },
-            pot_re_tri: match value.pot_re_tri {
-                None | Some(-1.0) | Some(65535.0) | Some(1845494299.0) | Some(2147483647.0) => None,
-                _ => Some(calculateFormulas(
-                    "pot_re_tri",
-                    value.pot_re_tri.unwrap(),
-                    &tel,
-                    is_ieee754_fp,
-                )),
-            },
-            en_at_tri: match value.en_at_tri {
-                None | Some(-1.0) | Some(65535.0) | Some(1845494299.0) | Some(2147483647.0) => None,
-                _ => {
-                    if is_schneider_pm2100 {
-                        Some(calculateFormulas(
-                            "en_at_tri",
-                            value.en_at_tri.unwrap(),
-                            &tel,
-                            false,
-                        ))
-                    } else {
-                        Some(calculateFormulas(
-                            "en_at_tri",
-                            value.en_at_tri.unwrap(),
-                            &tel,
-                            is_ieee754_fp,
-                        ))
-                    }
-                }
-            },
-            en_re_tri: match value.en_re_tri {
-                None | Some(-1.0) | Some(65535.0) | Some(1845494299.0) | Some(2147483647.0) => None,
-                _ => {
-                    if is_schneider_pm2100 {
-                        Some(calculateFormulas(
-                            "en_re_tri",
-                            value.en_re_tri.unwrap(),
-                            &tel,
-                            false,
-                        ))
-                    } else {
-                        Some(calculateFormulas(
-                            "en_re_tri",
-                            value.en_re_tri.unwrap(),
-                            &tel,
-                            is_ieee754_fp,
-                        ))
-                    }
-                }
-            },
-            en_ap_tri: match value.en_ap_tri {
-                None | Some(-1.0) | Some(65535.0) | Some(1845494299.0) | Some(2147483647.0) => None,
-                _ => Some(calculateFormulas(
-                    "en_ap_tri",
-                    value.en_ap_tri.unwrap(),
-                    &tel,
-                    is_ieee754_fp,
-                )),
-            },
-            fp_a: match value.fp_a {
-                None | Some(-1.0) | Some(65535.0) | Some(1845494299.0) | Some(2147483647.0) => None,
-                _ => {
-                    let value = calculateFormulas("fp_a", value.fp_a.unwrap(), &tel, is_ieee754_fp);
-                    if is_schneider_pm2100 {
-                        Some(convert_4Q_FP_PF(value))
-                    } else {
-                        Some(value)
-                    }
-                }
-            },
-            fp_b: match value.fp_b {
-                None | Some(-1.0) | Some(65535.0) | Some(1845494299.0) | Some(2147483647.0) => None,
-                _ => {
-                    let value = calculateFormulas("fp_b", value.fp_b.unwrap(), &tel, is_ieee754_fp);
-                    if is_schneider_pm2100 {
-                        Some(convert_4Q_FP_PF(value))
-                    } else {
-                        Some(value)
-                    }
-                }
-            },
-            fp_c: match value.fp_c {
-                None | Some(-1.0) | Some(65535.0) | Some(1845494299.0) | Some(2147483647.0) => None,
-                _ => {
-                    let value = calculateFormulas("fp_c", value.fp_c.unwrap(), &tel, is_ieee754_fp);
-                    if is_schneider_pm2100 {
-                        Some(convert_4Q_FP_PF(value))
-                    } else {
-                        Some(value)
-                    }
-                }
-            },
-            fp: match value.fp {
-                None | Some(-1.0) | Some(65535.0) | Some(1845494299.0) | Some(2147483647.0) => None,
-                _ => {
-                    let value = calculateFormulas("fp", value.fp.unwrap(), &tel, is_ieee754_fp);
-                    if is_schneider_pm2100 {
-                        Some(convert_4Q_FP_PF(value))
-                    } else {
-                        Some(value)
-                    }
-                }
-            },
-            freq: match value.freq {
-                None | Some(-1.0) | Some(65535.0) | Some(1845494299.0) | Some(2147483647.0) => None,
-                _ => Some(calculateFormulas(
-                    "freq",
-                    value.freq.unwrap(),
-                    &tel,
-                    is_ieee754_fp,
-                )),
-            },
-            demanda_at: match value.demanda_at {
-                None | Some(-1.0) | Some(65535.0) | Some(1845494299.0) | Some(2147483647.0) => None,
-                _ => Some(calculateFormulas(
-                    "demanda_at",
-                    value.demanda_at.unwrap(),
-                    &tel,
-                    is_ieee754_fp,
-                )),
-            },
-            demanda_ap: match value.demanda_ap {
-                None | Some(-1.0) | Some(65535.0) | Some(1845494299.0) | Some(2147483647.0) => None,
-                _ => Some(calculateFormulas(
-                    "demanda_ap",
-                    value.demanda_ap.unwrap(),
-                    &tel,
-                    is_ieee754_fp,
-                )),
-            },
-            demanda_med_at: match value.demanda_med_at {
-                None | Some(-1.0) | Some(65535.0) | Some(1845494299.0) | Some(2147483647.0) => None,
-                _ => Some(calculateFormulas(
-                    "demanda_med_at",
-                    value.demanda_med_at.unwrap(),
-                    &tel,
-                    is_ieee754_fp,
-                )),
-            },
-            demanda: match value.demanda {
-                None | Some(-1.0) | Some(65535.0) | Some(1845494299.0) | Some(2147483647.0) => None,
-                _ => Some(calculateFormulas(
-                    "demanda",
-                    value.demanda.unwrap(),
-                    &tel,
-                    is_ieee754_fp,
-                )),
-            },
-            erro: match value.erro {
-                None => None,
-                Some(-1.0) => None,
-                _ => Some(calculateFormulas("erro", value.erro.unwrap(), &tel, false)),
-            },
-        };
-        Ok(result)
+        let meter = meters::registry()
+            .for_payload(value.dev_type.as_ref().map(|t| t.as_str()))
+            .ok_or_else(|| "Energy meter definition not found".to_owned())?;
+        meter.decode(&json!(value))
     }
 }
 
@@ -5,15 +5,22 @@ mod helpers {
     pub mod lib_bigquery {
         pub mod client;
         pub mod saver;
+        pub mod schema;
     }
     pub mod lib_http {
+        pub mod auth;
         pub mod buffer;
         pub mod protocol;
         pub mod request;
         pub mod response;
+        pub mod router;
         pub mod service;
         pub mod types;
     }
+    pub mod lib_local_store {
+        pub mod layout;
+        pub mod saver;
+    }
     pub mod lib_essential_thread;
     // pub mod lib_pahomqtt;
     pub mod lib_rumqtt;
@@ -22,22 +29,28 @@ mod helpers {
     }
     pub mod diel_hist_tables;
     pub mod envvars_loader;
+    pub mod lib_dead_letter;
     pub mod lib_log;
+    pub mod lib_metrics;
     pub mod tls_socket_rustls;
 }
 
 mod app_br2db {
     pub mod api;
     pub mod configs;
+    pub mod dead_letters;
     pub mod global_vars;
     pub mod log;
+    pub mod migrate_bigquery;
     pub mod mqtt_task;
     pub mod on_control_message;
     pub mod on_data_message;
     pub mod on_mqtt_message;
     pub mod on_table_not_found;
+    pub mod reingest_errdyndb;
     pub mod save_to_bigquery;
     pub mod save_to_dynamodb;
+    pub mod save_to_local_files;
     pub mod socket_protocol;
     pub mod statistics;
 }
@@ -62,6 +75,32 @@ fn main() {
         }
     }
 
+    // Ferramentas de linha de comando, que rodam e encerram:
+    // --reingest-errdyndb regrava no DynamoDB as telemetrias das linhas ERRDYNDB de arquivos de log
+    // --migrate-bigquery-schema cria ou atualiza as tabelas de telemetria do BigQuery
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let command = args.first().map(String::as_str);
+    if matches!(
+        command,
+        Some("--reingest-errdyndb" | "--migrate-bigquery-schema")
+    ) {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("Error creating tokio runtime");
+        let result = rt.block_on(async {
+            match command {
+                Some("--reingest-errdyndb") => reingest_errdyndb::run(&args[1..]).await,
+                _ => migrate_bigquery::run(&args[1..]).await,
+            }
+        });
+        if let Err(err) = result {
+            println!("{}", err);
+            std::process::exit(1);
+        }
+        std::process::exit(0);
+    }
+
     crate::LOG.append_log_tag_msg("INIT", "Serviço iniciado");
 
     let rt = tokio::runtime::Builder::new_current_thread()
@@ -74,7 +113,7 @@ fn main() {
 
 async fn main2() {
     let configfile = ConfigFile::from_env().expect("configfile inválido");
-    let (globs, receiver_bigquery) = GlobalVars::new(configfile).await;
+    let (globs, receiver_bigquery, receiver_local_store) = GlobalVars::new(configfile).await;
     let globs = Arc::new(globs);
 
     lib_essential_thread::run_thread_async(
@@ -87,6 +126,11 @@ async fn main2() {
         lib_http::service::run_service_result(addr, globs, &app_br2db::api::on_http_req)
     });
 
+    lib_essential_thread::run_thread_async(
+        "dead_letters".to_owned(),
+        dead_letters::run_retry_task(globs.clone()),
+    );
+
     lib_essential_thread::run_thread_async(
         "save_to_bigquery".to_owned(),
         lib_bigquery::saver::task_save_to_bigquery(globs.clone(), receiver_bigquery, 2800),
@@ -98,6 +142,25 @@ async fn main2() {
         |globs| lib_bigquery::saver::task_force_save_to_bigquery(globs.to_bigquery.clone()),
     );
 
+    if let Some(local_store_config) = globs.configfile.local_store_config.clone() {
+        lib_essential_thread::run_thread_async(
+            "save_to_local_store".to_owned(),
+            lib_local_store::saver::task_save_to_local_store(
+                local_store_config,
+                receiver_local_store,
+                2800,
+            ),
+        );
+
+        lib_essential_thread::run_thread_async_loop_pars(
+            "force_save_to_local_store".to_owned(),
+            globs.clone(),
+            |globs| {
+                lib_local_store::saver::task_force_save_to_local_store(globs.to_local_store.clone())
+            },
+        );
+    }
+
     lib_essential_thread::run_thread_async_loop_pars(
         "task_mqtt_client_broker".to_owned(),
         globs.clone(),
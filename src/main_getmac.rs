@@ -2,15 +2,18 @@ mod helpers {
     pub mod envvars_loader;
     pub mod lib_essential_thread;
     pub mod lib_log;
+    pub mod lib_metrics;
     pub mod lib_dynamodb {
         pub mod client;
         pub mod query;
     }
     pub mod lib_http {
+        pub mod auth;
         pub mod buffer;
         pub mod protocol;
         pub mod request;
         pub mod response;
+        pub mod router;
         pub mod service;
         pub mod types;
     }
@@ -1,9 +1,11 @@
 mod helpers {
     pub mod lib_http {
+        pub mod auth;
         pub mod buffer;
         pub mod protocol;
         pub mod request;
         pub mod response;
+        pub mod router;
         pub mod service;
         pub mod types;
     }
@@ -11,10 +13,21 @@ mod helpers {
         pub mod client;
         pub mod query;
     }
+    pub mod lib_local_store {
+        pub mod layout;
+    }
+    pub mod lib_telemetry_source {
+        pub mod bigquery;
+        pub mod dynamodb;
+        pub mod local_files;
+        pub mod source;
+    }
+    pub mod dac_faults;
     pub mod l1_virtual {
         pub mod dac_l1;
         pub mod dut_l1;
     }
+    pub mod refrigerants;
     pub mod telemetry_payloads {
         pub mod dac_payload_json;
         pub mod dac_telemetry;
@@ -35,13 +48,13 @@ mod helpers {
         pub mod temprt_value_checker;
         pub mod energy {
             pub mod dme;
+            pub mod meters;
             pub mod padronized;
         }
         pub mod dri {
             pub mod ccn;
-            pub mod chiller_carrier_hx;
-            pub mod chiller_carrier_xa;
-            pub mod chiller_carrier_xa_hvar;
+            pub mod generic;
+            pub mod profile;
             pub mod vav_fancoil;
         }
         pub mod circ_buffer;
@@ -49,6 +62,7 @@ mod helpers {
     pub mod compression {
         pub mod common_func;
         pub mod compiler_DAC;
+        pub mod compiler_DAC_faults;
         pub mod compiler_DAL;
         pub mod compiler_DAM;
         pub mod compiler_DMA;
@@ -56,11 +70,13 @@ mod helpers {
         pub mod compiler_DRI;
         pub mod compiler_DUT;
         pub mod compiler_common;
+        pub mod downsample;
     }
     pub mod diel_hist_tables;
     pub mod envvars_loader;
     pub mod lib_essential_thread;
     pub mod lib_log;
+    pub mod lib_metrics;
 }
 mod app_history {
     pub mod cache_files;
@@ -74,10 +90,13 @@ mod app_history {
     pub mod dmt_hist;
     pub mod dri_hist;
     pub mod dut_hist;
+    pub mod energy_consumption;
     pub mod energy_hist;
     pub mod energy_stats;
     pub mod global_vars;
     pub mod http_router;
+    pub mod range_hist;
+    pub mod refrigerant_sat;
 }
 
 use app_history::*;
@@ -1,8 +1,10 @@
 mod helpers {
+    pub mod dac_faults;
     pub mod l1_virtual {
         pub mod dac_l1;
         pub mod dut_l1;
     }
+    pub mod refrigerants;
     pub mod telemetry_payloads {
         pub mod dac_payload_json;
         pub mod dac_telemetry;
@@ -19,41 +21,52 @@ mod helpers {
         pub mod temprt_value_checker;
         pub mod energy {
             pub mod dme;
+            pub mod meters;
             pub mod padronized;
         }
         pub mod dri {
             pub mod ccn;
-            pub mod chiller_carrier_hx;
-            pub mod chiller_carrier_xa;
-            pub mod chiller_carrier_xa_hvar;
+            pub mod generic;
+            pub mod profile;
             pub mod vav_fancoil;
         }
         pub mod circ_buffer;
     }
     pub mod lib_http {
+        pub mod auth;
         pub mod buffer;
         pub mod protocol;
         pub mod request;
         pub mod response;
+        pub mod router;
         pub mod service;
         pub mod types;
     }
     pub mod envvars_loader;
     pub mod lib_essential_thread;
     pub mod lib_log;
+    pub mod lib_metrics;
     pub mod lib_rumqtt;
+    pub mod lib_state_store {
+        pub mod fallback;
+        pub mod file;
+        pub mod memory;
+        pub mod redis;
+        pub mod store;
+    }
     pub mod tls_socket_rustls;
 }
 mod app_relay {
     pub mod commands_sender;
     pub mod configs;
     pub mod dash_update;
+    pub mod device_commands;
     pub mod global_vars;
+    pub mod hw_config;
     pub mod http_router;
     pub mod mqtt_task;
     pub mod on_mqtt_message;
     pub mod payload_conversions;
-    pub mod redis_connection;
     pub mod state_persistence;
     pub mod statistics;
 }
@@ -95,10 +108,12 @@ fn main() {
         lib_http::service::run_service_result(addr, globs, &http_router::on_http_req)
     });
 
-    lib_essential_thread::run_thread_async(
-        format!("mqtt_client_broker"),
-        mqtt_task::task_mqtt_client_broker(globs.clone()),
-    );
+    for (broker_index, broker) in globs.configfile.brokers.iter().enumerate() {
+        lib_essential_thread::run_thread_async(
+            format!("mqtt_client_broker_{}", broker.name),
+            mqtt_task::task_mqtt_client_broker(globs.clone(), broker_index),
+        );
+    }
 
     // Quando inicia o serviço (e também de tempo em tempo) tem que solicitar as configs de hardware do API-Server
     lib_essential_thread::run_thread_async_loop_pars(
@@ -109,11 +124,12 @@ fn main() {
         },
     );
 
+    // Conexão com o Redis e snapshots do estado dos dispositivos, dependendo do store configurado
     lib_essential_thread::run_thread_async_loop_pars(
-        "manter-conexao-redis".to_owned(),
+        "state-store".to_owned(),
         globs.clone(),
         |globs| async move {
-            redis_connection::manter_conexao_redis(globs).await;
+            globs.state_store.run_maintenance().await;
         },
     );
 
@@ -1,10 +1,13 @@
 mod helpers {
     pub mod lib_log;
+    pub mod lib_metrics;
     pub mod lib_http {
+        pub mod auth;
         pub mod buffer;
         pub mod protocol;
         pub mod request;
         pub mod response;
+        pub mod router;
         pub mod service;
         pub mod types;
     }
@@ -16,6 +19,7 @@ mod helpers {
         pub mod dut_l1;
     }
 
+    pub mod refrigerants;
     pub mod telemetry_payloads {
         pub mod dac_payload_json;
         pub mod dac_telemetry;
@@ -36,13 +40,13 @@ mod helpers {
         pub mod temprt_value_checker;
         pub mod energy {
             pub mod dme;
+            pub mod meters;
             pub mod padronized;
         }
         pub mod dri {
             pub mod ccn;
-            pub mod chiller_carrier_hx;
-            pub mod chiller_carrier_xa;
-            pub mod chiller_carrier_xa_hvar;
+            pub mod generic;
+            pub mod profile;
             pub mod vav_fancoil;
         }
         pub mod circ_buffer;
@@ -54,13 +58,18 @@ mod helpers {
 mod app_realtime {
     pub mod configs;
     pub mod devs_cache;
+    pub mod devs_status;
     pub mod global_vars;
     pub mod http_router;
+    pub mod live_stream;
     pub mod mqtt_task;
     pub mod on_mqtt_message;
+    pub mod recent_telemetries;
     pub mod endpoints {
         pub mod get_devices_last_telemetries;
         pub mod get_devices_last_ts;
+        pub mod get_devices_recent_telemetries;
+        pub mod get_devices_status;
     }
 }
 
@@ -108,7 +117,7 @@ fn main() {
 
 async fn main2() {
     let configfile = ConfigFile::from_env().expect("configfile inválido");
-    let globs = GlobalVars::new(configfile).await;
+    let (globs, status_receiver) = GlobalVars::new(configfile).await;
     let globs = Arc::new(globs);
 
     // Inicia e aguarda as threads principais
@@ -124,5 +133,13 @@ async fn main2() {
         result = tokio::spawn(
             devs_cache::run_service(globs.clone())
         ) => { panic!("Some thread stopped: {:?}", result.unwrap()); },
+
+        result = tokio::spawn(
+            devs_status::run_sweeper(globs.clone())
+        ) => { panic!("Some thread stopped: {:?}", result.unwrap()); },
+
+        result = tokio::spawn(
+            devs_status::run_notifier(globs.clone(), status_receiver)
+        ) => { panic!("Some thread stopped: {:?}", result.unwrap()); },
     }
 }
@@ -0,0 +1,99 @@
+mod helpers {
+    pub mod dac_faults;
+    pub mod l1_virtual {
+        pub mod dac_l1;
+        pub mod dut_l1;
+    }
+    pub mod refrigerants;
+    pub mod telemetry_payloads {
+        pub mod dac_payload_json;
+        pub mod dac_telemetry;
+        pub mod dac_tsh_tsc;
+        pub mod dal_payload_json;
+        pub mod dal_telemetry;
+        pub mod dmt_payload_json;
+        pub mod dmt_telemetry;
+        pub mod dri_telemetry;
+        pub mod dut_payload_json;
+        pub mod dut_telemetry;
+        pub mod parse_json_props;
+        pub mod telemetry_formats;
+        pub mod temprt_value_checker;
+        pub mod energy {
+            pub mod dme;
+            pub mod meters;
+            pub mod padronized;
+        }
+        pub mod dri {
+            pub mod ccn;
+            pub mod generic;
+            pub mod profile;
+            pub mod vav_fancoil;
+        }
+        pub mod circ_buffer;
+    }
+    pub mod envvars_loader;
+    pub mod lib_log;
+    pub mod lib_metrics;
+    pub mod lib_rumqtt;
+    pub mod lib_state_store {
+        pub mod fallback;
+        pub mod file;
+        pub mod memory;
+        pub mod redis;
+        pub mod store;
+    }
+    pub mod tls_socket_rustls;
+}
+
+mod app_relay {
+    pub mod commands_sender;
+    pub mod configs;
+    pub mod dash_update;
+    pub mod device_commands;
+    pub mod global_vars;
+    pub mod hw_config;
+    pub mod on_mqtt_message;
+    pub mod payload_conversions;
+    pub mod replay;
+    pub mod state_persistence;
+    pub mod statistics;
+}
+
+use app_relay::*;
+use configs::ConfigFile;
+use global_vars::GlobalVars;
+use helpers::*;
+
+/*
+Ferramenta para reproduzir offline as conversões do iotrelay a partir de telemetrias gravadas.
+Serve para investigar problemas de clientes e comparar mudanças nos algoritmos de L1 com dados reais.
+  replay <telemetrias.jsonl> <hwcfg.json> <saida.jsonl>
+*/
+
+static LOG: lib_log::AppLog = lib_log::AppLog { app_name: "replay" };
+
+fn main() {
+    lib_log::create_log_dir().expect("Não foi possível criar a pasta de logs");
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.len() != 3 {
+        eprintln!("Uso: replay <telemetrias.jsonl> <hwcfg.json> <saida.jsonl>");
+        std::process::exit(2);
+    }
+
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("Error creating tokio runtime");
+
+    match rt.block_on(replay::run_replay(&args[0], &args[1], &args[2])) {
+        Ok(summary) => {
+            println!("[REPLAY] {:?}", summary);
+        }
+        Err(err) => {
+            eprintln!("[REPLAY] {}", err);
+            std::process::exit(1);
+        }
+    }
+}
@@ -3,28 +3,46 @@ mod helpers {
         pub mod client;
         pub mod query;
     }
+    pub mod lib_dead_letter;
     pub mod lib_log;
+    pub mod lib_metrics;
     pub mod lib_bigquery {
         pub mod client;
         pub mod saver;
+        pub mod schema;
     }
     pub mod lib_http {
+        pub mod auth;
         pub mod buffer;
         pub mod protocol;
         pub mod request;
         pub mod response;
+        pub mod router;
         pub mod service;
         pub mod types;
     }
+    pub mod lib_local_store {
+        pub mod layout;
+        pub mod saver;
+    }
     pub mod lib_essential_thread;
     // pub mod lib_pahomqtt;
     pub mod lib_rumqtt;
+    pub mod lib_state_store {
+        pub mod fallback;
+        pub mod file;
+        pub mod memory;
+        pub mod redis;
+        pub mod store;
+    }
 
+    pub mod dac_faults;
     pub mod l1_virtual {
         pub mod dac_l1;
         pub mod dut_l1;
     }
 
+    pub mod refrigerants;
     pub mod telemetry_payloads {
         pub mod dac_payload_json;
         pub mod dac_telemetry;
@@ -45,13 +63,13 @@ mod helpers {
         pub mod temprt_value_checker;
         pub mod energy {
             pub mod dme;
+            pub mod meters;
             pub mod padronized;
         }
         pub mod dri {
             pub mod ccn;
-            pub mod chiller_carrier_hx;
-            pub mod chiller_carrier_xa;
-            pub mod chiller_carrier_xa_hvar;
+            pub mod generic;
+            pub mod profile;
             pub mod vav_fancoil;
         }
         pub mod circ_buffer;
@@ -66,36 +84,40 @@ mod app_relay {
     pub mod commands_sender;
     pub mod configs;
     pub mod dash_update;
+    pub mod device_commands;
     pub mod global_vars;
+    pub mod hw_config;
     pub mod http_router;
     pub mod on_mqtt_message;
     pub mod payload_conversions;
-    pub mod redis_connection;
     pub mod state_persistence;
     pub mod statistics;
 }
 mod app_br2db {
     pub mod api;
     pub mod configs;
+    pub mod dead_letters;
     pub mod global_vars;
     pub mod log;
     pub mod on_table_not_found;
     pub mod save_to_bigquery;
     pub mod save_to_dynamodb;
+    pub mod save_to_local_files;
     pub mod socket_protocol;
     pub mod statistics;
 }
 mod app_telserv {
     pub mod configs;
     pub mod global_vars;
+    pub mod http_router;
     pub mod merge_calculated_values;
     pub mod mqtt_task;
     pub mod on_mqtt_message;
     pub mod statistics;
 }
 
-use app_br2db::{log, save_to_bigquery, save_to_dynamodb};
-use app_relay::{commands_sender, dash_update, redis_connection};
+use app_br2db::{log, save_to_bigquery, save_to_dynamodb, save_to_local_files};
+use app_relay::{commands_sender, dash_update};
 use app_telserv::global_vars::GlobalVars;
 use app_telserv::*;
 use configs::ConfigFile;
@@ -133,9 +155,27 @@ fn main() {
 
 async fn main2() {
     let configfile = ConfigFile::from_env().expect("configfile inválido");
-    let (globs, receiver_fila, receiver_bigquery) = GlobalVars::new(configfile).await;
+    let (globs, receiver_fila, receiver_bigquery, receiver_local_store) =
+        GlobalVars::new(configfile).await;
     let globs = Arc::new(globs);
     let globs_clone1 = globs.clone();
+    let globs_clone2 = globs.clone();
+
+    // A gravação em disco roda em outra thread para não travar o runtime com IO bloqueante
+    if let Some(local_store_config) = globs.configfile.local_store_config.clone() {
+        lib_essential_thread::run_thread_async(
+            "save_to_local_store".to_owned(),
+            lib_local_store::saver::task_save_to_local_store(
+                local_store_config,
+                receiver_local_store,
+                2800,
+            ),
+        );
+        lib_essential_thread::run_thread_async(
+            "force_save_to_local_store".to_owned(),
+            lib_local_store::saver::task_force_save_to_local_store(globs.to_local_store.clone()),
+        );
+    }
 
     // Inicia e aguarda as threads principais
     tokio::select! {
@@ -144,7 +184,7 @@ async fn main2() {
         ) => { panic!("Some thread stopped: {:?}", result.unwrap()); },
 
         result = tokio::spawn(
-            lib_http::service::run_service_result(globs.configfile.listen_http_api.to_owned(), globs.clone(), &app_relay::http_router::on_http_req)
+            lib_http::service::run_service_result(globs.configfile.listen_http_api.to_owned(), globs.clone(), &app_telserv::http_router::on_http_req)
         ) => { panic!("Some thread stopped: {:?}", result.unwrap()); },
 
         result = tokio::spawn(
@@ -156,9 +196,9 @@ async fn main2() {
             dash_update::run_task(&globs_clone1, &globs_clone1.conv_vars).await;
         }) => { panic!("Some thread stopped: {:?}", result.unwrap()); },
 
-        result = tokio::spawn(
-            redis_connection::manter_conexao_redis(globs.clone())
-        ) => { panic!("Some thread stopped: {:?}", result.unwrap()); },
+        result = tokio::spawn(async move {
+            globs_clone2.state_store.run_maintenance().await;
+        }) => { panic!("Some thread stopped: {:?}", result.unwrap()); },
 
         result = tokio::spawn(
             lib_bigquery::saver::task_save_to_bigquery(globs.clone(), receiver_bigquery, 2800),
@@ -171,5 +211,9 @@ async fn main2() {
         result = tokio::spawn(
             commands_sender::task_mqtt_broker_writer(receiver_fila, globs.clone()),
         ) => { panic!("Some thread stopped: {:?}", result.unwrap()); },
+
+        result = tokio::spawn(
+            app_br2db::dead_letters::run_retry_task(globs.clone()),
+        ) => { panic!("Some thread stopped: {:?}", result.unwrap()); },
     }
 }